@@ -1,3 +1,7 @@
+use std::collections::HashSet;
+
+use crate::codegen::config;
+use crate::deadcode::{locals, mentioned_names};
 use crate::kw;
 use crate::stm::*;
 
@@ -44,10 +48,16 @@ impl ToTokens for Program {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let name = &self.name.to_token_stream();
         let var_defs = if let Some(var_defs) = &self.var_defs {
-            let var_defs = TokenStream::from_iter(var_defs.iter().map(|x| x.to_token_stream()));
-            quote! {
-                #var_defs
+            // a procedure may be expanded before main assigns the globals
+            // it uses, so those stay initialized
+            let captured = self.captured();
+            let mut var_defs_tokens = quote! {};
+            for var_def in var_defs {
+                var_def.declare(&mut var_defs_tokens, |x| {
+                    !config().strict_init || captured.contains(&x.to_string())
+                });
             }
+            var_defs_tokens
         } else {
             quote! {}
         };
@@ -63,7 +73,9 @@ impl ToTokens for Program {
         let body = TokenStream::from_iter(self.body.iter().map(
             |x| {let mut res = x.to_token_stream(); res.extend(quote!{;}); res}));
 
+        // rustc lints the procedures where they expand, which is here
         tokens.extend(quote! {
+            #[allow(redundant_semicolons, unused_assignments)]
             let mut #name = || {
                 #var_defs
                 #procedure_defs
@@ -74,6 +86,22 @@ impl ToTokens for Program {
     }
 }
 
+impl Program {
+    /// Globals the procedures mention, which their closures capture.
+    fn captured(&self) -> HashSet<String> {
+        let mut captured = HashSet::new();
+        for proc in self.procedure_defs.iter().flatten() {
+            let locals = locals(proc);
+            captured.extend(
+                mentioned_names(&proc.body)
+                    .into_iter()
+                    .filter(|x| !locals.contains(x)),
+            );
+        }
+        captured
+    }
+}
+
 fn parse_procdef_within(input: ParseStream) -> syn::Result<Vec<ProcedureDef>> {
     let mut procs = Vec::new();
     loop {
@@ -126,6 +154,16 @@ pub enum VarDef {
     IntTyVar(IntTyVarDef),
 }
 
+impl VarDef {
+    /// Declares the names, with their zero value when `init` says so.
+    fn declare(&self, tokens: &mut TokenStream, init: impl Fn(&syn::Ident) -> bool) {
+        match self {
+            VarDef::CharTyVar(var_def) => var_def.declare(tokens, init),
+            VarDef::IntTyVar(var_def) => var_def.declare(tokens, init),
+        }
+    }
+}
+
 impl ToTokens for VarDef {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         match self {
@@ -180,14 +218,26 @@ pub struct CharTyVarDef {
     pub names: Vec<syn::Ident>,
 }
 
+impl CharTyVarDef {
+    fn declare(&self, tokens: &mut TokenStream, init: impl Fn(&syn::Ident) -> bool) {
+        for name in &self.names {
+            if init(name) {
+                tokens.extend(quote! {
+                    let mut #name = '\0';
+                })
+            } else {
+                tokens.extend(quote! {
+                    let mut #name: char;
+                })
+            }
+        }
+    }
+}
+
 impl ToTokens for CharTyVarDef {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let _ty_kw = &self.ty_kw;
-        for name in &self.names {
-            tokens.extend(quote! {
-                let mut #name = '\0';
-            })
-        }
+        self.declare(tokens, |_| !config().strict_init);
     }
 }
 
@@ -206,14 +256,27 @@ pub struct IntTyVarDef {
     pub names: Vec<syn::Ident>,
 }
 
+impl IntTyVarDef {
+    fn declare(&self, tokens: &mut TokenStream, init: impl Fn(&syn::Ident) -> bool) {
+        let ty = config().int_width.rust_type();
+        for name in &self.names {
+            if init(name) {
+                tokens.extend(quote! {
+                    let mut #name: #ty = 0;
+                })
+            } else {
+                tokens.extend(quote! {
+                    let mut #name: #ty;
+                })
+            }
+        }
+    }
+}
+
 impl ToTokens for IntTyVarDef {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let _ty_kw = &self.ty_kw;
-        for name in &self.names {
-            tokens.extend(quote! {
-                let mut #name = 0;
-            })
-        }
+        self.declare(tokens, |_| !config().strict_init);
     }
 }
 
@@ -233,6 +296,15 @@ pub enum ParamDecl {
     IntTyParam(IntTyParamDecl),
 }
 
+impl ParamDecl {
+    pub fn name(&self) -> &syn::Ident {
+        match self {
+            ParamDecl::CharTyParam(x) => &x.name,
+            ParamDecl::IntTyParam(x) => &x.name,
+        }
+    }
+}
+
 impl ToTokens for ParamDecl {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         match self {
@@ -292,8 +364,9 @@ impl ToTokens for IntTyParamDecl {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let _ty_kw = &self.ty_kw;
         let name = &self.name;
+        let ty = config().int_width.rust_type();
         tokens.extend(quote! {
-            #name: i32
+            #name: #ty
         })
     }
 }
@@ -319,14 +392,21 @@ pub struct ProcedureDef {
 impl ToTokens for ProcedureDef {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let name = &self.name.to_token_stream();
+        // a procedure is a macro expanded at each call, since closures would
+        // keep the globals they assign borrowed; sema rules out recursion
+        let mut matchers = quote!{};
         let mut params = quote!{};
-        // generate params and append a comma, exclude last param
-        let params_len = self.params.len();
         for (i, param) in self.params.iter().enumerate() {
-            param.to_tokens(&mut params);
-            if i != params_len - 1{
-                params.extend(quote!{,});
+            let arg = quote::format_ident!("__{}", i);
+            if i != 0 {
+                matchers.extend(quote!{,});
             }
+            matchers.extend(quote!{$#arg:expr});
+            // SNL procedures may assign their parameters, or ignore them
+            params.extend(quote!{
+                #[allow(unused_mut, unused_variables)]
+                let mut #param = $#arg;
+            });
         }
         let var_defs = match &self.var_defs {
             Some(var_defs) => {
@@ -344,10 +424,14 @@ impl ToTokens for ProcedureDef {
             body.extend(quote!{;});
         }
         tokens.extend(quote! {
-            let mut #name = |#params| {
-                #var_defs
-                #body
-            };
+            #[allow(unused_macros)]
+            macro_rules! #name {
+                (#matchers) => {{
+                    #params
+                    #var_defs
+                    #body
+                }};
+            }
         })
     }
 }
@@ -369,7 +453,8 @@ impl Parse for ProcedureDef {
         //begin
         input.parse::<kw::begin>()?;
         let body = parse_stm_list(input)?;
-        //end    r#array [1..20] r#of r#integer a;
+        //end
+        input.parse::<kw::end>()?;
 
         Ok(ProcedureDef {
             name,
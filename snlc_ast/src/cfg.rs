@@ -0,0 +1,159 @@
+//! Control-flow graphs over `Stmatment` lists.
+
+use crate::ast::Program;
+use crate::expr::Expr;
+use crate::stm::Stmatment;
+
+pub type BlockId = usize;
+
+#[derive(Debug)]
+pub enum Terminator<'a> {
+    Goto(BlockId),
+    Branch {
+        condition: &'a Expr,
+        then_block: BlockId,
+        else_block: BlockId,
+    },
+    Return,
+}
+
+/// A straight-line run of `Expr`, `Read` and `Write` statements.
+#[derive(Debug)]
+pub struct BasicBlock<'a> {
+    pub id: BlockId,
+    pub stms: Vec<&'a Stmatment>,
+    pub terminator: Terminator<'a>,
+}
+
+impl BasicBlock<'_> {
+    /// Successors of this block, each with the label of its edge.
+    pub fn successors(&self) -> Vec<(BlockId, &'static str)> {
+        match self.terminator {
+            Terminator::Goto(target) => vec![(target, "")],
+            Terminator::Branch {
+                then_block,
+                else_block,
+                ..
+            } => vec![(then_block, "true"), (else_block, "false")],
+            Terminator::Return => Vec::new(),
+        }
+    }
+}
+
+/// Control-flow graph of a single procedure (or the program body).
+#[derive(Debug)]
+pub struct Cfg<'a> {
+    pub name: String,
+    pub entry: BlockId,
+    pub exit: BlockId,
+    pub blocks: Vec<BasicBlock<'a>>,
+    /// Every statement, nested ones included, with the block it starts in.
+    pub stm_blocks: Vec<(&'a Stmatment, BlockId)>,
+}
+
+impl<'a> Cfg<'a> {
+    pub fn build(name: String, body: &'a [Stmatment]) -> Cfg<'a> {
+        let mut builder = CfgBuilder {
+            blocks: Vec::new(),
+            stm_blocks: Vec::new(),
+        };
+        let entry = builder.new_block();
+        let last = builder.lower_stm_list(entry, body);
+        let exit = builder.new_block();
+        builder.blocks[last].terminator = Terminator::Goto(exit);
+
+        Cfg {
+            name,
+            entry,
+            exit,
+            blocks: builder.blocks,
+            stm_blocks: builder.stm_blocks,
+        }
+    }
+
+    /// Predecessors of every block, indexed by `BlockId`.
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![Vec::new(); self.blocks.len()];
+        for block in &self.blocks {
+            for (succ, _) in block.successors() {
+                preds[succ].push(block.id);
+            }
+        }
+        preds
+    }
+}
+
+/// Builds one CFG for the program body followed by one per `ProcedureDef`.
+pub fn build_program_cfgs(prog: &Program) -> Vec<Cfg<'_>> {
+    let mut cfgs = vec![Cfg::build(prog.name.to_string(), &prog.body)];
+    if let Some(procedure_defs) = &prog.procedure_defs {
+        for proc in procedure_defs {
+            cfgs.push(Cfg::build(proc.name.to_string(), &proc.body));
+        }
+    }
+    cfgs
+}
+
+struct CfgBuilder<'a> {
+    blocks: Vec<BasicBlock<'a>>,
+    stm_blocks: Vec<(&'a Stmatment, BlockId)>,
+}
+
+impl<'a> CfgBuilder<'a> {
+    fn new_block(&mut self) -> BlockId {
+        let id = self.blocks.len();
+        self.blocks.push(BasicBlock {
+            id,
+            stms: Vec::new(),
+            terminator: Terminator::Return,
+        });
+        id
+    }
+
+    /// Appends `stms` starting at block `curr`, returns the block control
+    /// falls out of.
+    fn lower_stm_list(&mut self, mut curr: BlockId, stms: &'a [Stmatment]) -> BlockId {
+        for stm in stms {
+            self.stm_blocks.push((stm, curr));
+            curr = match stm {
+                Stmatment::If(if_stm) => {
+                    let then_block = self.new_block();
+                    let else_block = self.new_block();
+                    self.blocks[curr].terminator = Terminator::Branch {
+                        condition: &if_stm.condition,
+                        then_block,
+                        else_block,
+                    };
+                    let then_end = self.lower_stm_list(then_block, &if_stm.body);
+                    let else_end = match &if_stm.else_body {
+                        Some(else_body) => self.lower_stm_list(else_block, else_body),
+                        None => else_block,
+                    };
+                    let join = self.new_block();
+                    self.blocks[then_end].terminator = Terminator::Goto(join);
+                    self.blocks[else_end].terminator = Terminator::Goto(join);
+                    join
+                }
+                Stmatment::While(while_stm) => {
+                    let header = self.new_block();
+                    let body = self.new_block();
+                    let after = self.new_block();
+                    self.blocks[curr].terminator = Terminator::Goto(header);
+                    self.blocks[header].terminator = Terminator::Branch {
+                        condition: &while_stm.condition,
+                        then_block: body,
+                        else_block: after,
+                    };
+                    let body_end = self.lower_stm_list(body, &while_stm.body);
+                    self.blocks[body_end].terminator = Terminator::Goto(header);
+                    after
+                }
+                _ => {
+                    self.blocks[curr].stms.push(stm);
+                    curr
+                }
+            };
+        }
+        curr
+    }
+}
@@ -0,0 +1,216 @@
+//! Settings that shape the generated Rust code.
+//!
+//! `ToTokens` has no room for extra arguments, so the settings for one
+//! expansion live in a thread local installed by `with_config`. They come
+//! from an optional `#![snlc(int_width = 32, overflow = "trap")]` attribute
+//! in front of the program, `opt_level = 1` also runs `opt::optimize` and
+//! `strict_init = true` leaves variables without an initial value.
+
+use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+
+/// Width of the SNL `integer` type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntWidth {
+    I16,
+    #[default]
+    I32,
+    I64,
+}
+
+impl IntWidth {
+    pub fn bits(&self) -> u32 {
+        match self {
+            IntWidth::I16 => 16,
+            IntWidth::I32 => 32,
+            IntWidth::I64 => 64,
+        }
+    }
+
+    /// Largest value an `integer` can hold.
+    pub fn max(&self) -> i64 {
+        match self {
+            IntWidth::I16 => i16::MAX as i64,
+            IntWidth::I32 => i32::MAX as i64,
+            IntWidth::I64 => i64::MAX,
+        }
+    }
+
+    pub fn min(&self) -> i64 {
+        match self {
+            IntWidth::I16 => i16::MIN as i64,
+            IntWidth::I32 => i32::MIN as i64,
+            IntWidth::I64 => i64::MIN,
+        }
+    }
+
+    /// The Rust type used for `integer`.
+    pub fn rust_type(&self) -> TokenStream {
+        match self {
+            IntWidth::I16 => quote!(i16),
+            IntWidth::I32 => quote!(i32),
+            IntWidth::I64 => quote!(i64),
+        }
+    }
+}
+
+impl FromStr for IntWidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "16" => Ok(IntWidth::I16),
+            "32" => Ok(IntWidth::I32),
+            "64" => Ok(IntWidth::I64),
+            _ => Err(format!("unsupported integer width `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for IntWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.bits())
+    }
+}
+
+/// What happens when integer arithmetic leaves the range of `IntWidth`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Two's complement wrap around.
+    Wrap,
+    /// Stop with a runtime error naming the SNL line.
+    #[default]
+    Trap,
+    /// Clamp to the smallest or largest value.
+    Saturate,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrap" => Ok(OverflowPolicy::Wrap),
+            "trap" => Ok(OverflowPolicy::Trap),
+            "saturate" => Ok(OverflowPolicy::Saturate),
+            _ => Err(format!("unknown overflow policy `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for OverflowPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OverflowPolicy::Wrap => write!(f, "wrap"),
+            OverflowPolicy::Trap => write!(f, "trap"),
+            OverflowPolicy::Saturate => write!(f, "saturate"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Config {
+    pub int_width: IntWidth,
+    pub overflow: OverflowPolicy,
+    /// 0 generates code for the program as written, 1 folds constants.
+    pub opt_level: u8,
+    /// Variables are not zero-initialized, `init` has checked that no read
+    /// comes before an assignment.
+    pub strict_init: bool,
+    /// Line of the `#![snlc(..)]` attribute, SNL line 1 follows it.
+    pub line_base: usize,
+}
+
+impl Config {
+    /// Reads the settings from `#![snlc(..)]` attributes.
+    pub fn from_attrs(attrs: &[syn::Attribute]) -> syn::Result<Config> {
+        let mut config = Config::default();
+        for attr in attrs {
+            if !attr.path().is_ident("snlc") {
+                return Err(syn::Error::new_spanned(attr, "unknown attribute"));
+            }
+            config.line_base = line_of(attr.pound_token.span);
+            attr.parse_nested_meta(|meta| {
+                let value = meta.value()?;
+                if meta.path.is_ident("int_width") {
+                    let lit: syn::LitInt = value.parse()?;
+                    config.int_width = lit
+                        .base10_digits()
+                        .parse()
+                        .map_err(|err| syn::Error::new(lit.span(), err))?;
+                } else if meta.path.is_ident("overflow") {
+                    let lit: syn::LitStr = value.parse()?;
+                    config.overflow = lit
+                        .value()
+                        .parse()
+                        .map_err(|err| syn::Error::new(lit.span(), err))?;
+                } else if meta.path.is_ident("opt_level") {
+                    let lit: syn::LitInt = value.parse()?;
+                    config.opt_level = match lit.base10_digits() {
+                        "0" => 0,
+                        "1" => 1,
+                        _ => return Err(syn::Error::new(lit.span(), "opt_level must be 0 or 1")),
+                    };
+                } else if meta.path.is_ident("strict_init") {
+                    let lit: syn::LitBool = value.parse()?;
+                    config.strict_init = lit.value;
+                } else {
+                    return Err(meta.error("unknown snlc setting"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(config)
+    }
+
+    /// The attribute `from_attrs` reads back.
+    pub fn to_attr(&self) -> String {
+        let opt_level = match self.opt_level {
+            0 => String::new(),
+            level => format!(", opt_level = {}", level),
+        };
+        let strict_init = if self.strict_init {
+            ", strict_init = true"
+        } else {
+            ""
+        };
+        format!(
+            "#![snlc(int_width = {}, overflow = \"{}\"{}{})]",
+            self.int_width, self.overflow, opt_level, strict_init
+        )
+    }
+
+    /// SNL line number of `span`, 0 when positions are unavailable.
+    pub fn snl_line(&self, span: Span) -> usize {
+        line_of(span).saturating_sub(self.line_base)
+    }
+}
+
+fn line_of(span: Span) -> usize {
+    if proc_macro::is_available() {
+        span.unwrap().line()
+    } else {
+        0
+    }
+}
+
+thread_local! {
+    static CONFIG: Cell<Config> = Cell::new(Config::default());
+}
+
+/// The settings of the current expansion.
+pub fn config() -> Config {
+    CONFIG.with(Cell::get)
+}
+
+/// Runs `f` with `config` installed.
+pub fn with_config<R>(config: Config, f: impl FnOnce() -> R) -> R {
+    let prev = CONFIG.with(|x| x.replace(config));
+    let res = f();
+    CONFIG.with(|x| x.set(prev));
+    res
+}
@@ -0,0 +1,491 @@
+//! Dead code found by reachability and liveness over the CFG of every
+//! body: variables never read, assignments whose value is never read,
+//! procedures never called and statements control never reaches.
+//!
+//! A call reads every global the procedure reads, directly or through its
+//! own calls, and every global read somewhere is live when a procedure
+//! returns.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use proc_macro2::Span;
+
+use crate::ast::{ProcedureDef, Program, VarDef};
+use crate::cfg::{BlockId, Cfg, Terminator};
+use crate::expr::Expr;
+use crate::opt::condition;
+use crate::stm::Stmatment;
+
+#[derive(Debug, Clone, Copy)]
+pub enum DeadCode<'a> {
+    /// A declared variable whose value is never read.
+    UnusedVar(&'a syn::Ident),
+    /// An assignment to a variable that is not read before it is assigned
+    /// again or goes out of scope.
+    DeadStore(&'a Stmatment, &'a syn::Ident),
+    /// A procedure the program body never calls, directly or not.
+    UnusedProcedure(&'a ProcedureDef),
+    /// The first of a run of statements that can't be reached, every
+    /// statement after it in the same list can't be reached either.
+    Unreachable(&'a Stmatment),
+}
+
+impl DeadCode<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            DeadCode::UnusedVar(name) => name.span(),
+            DeadCode::DeadStore(_, name) => name.span(),
+            DeadCode::UnusedProcedure(proc) => proc.name.span(),
+            DeadCode::Unreachable(stm) => stm.span(),
+        }
+    }
+}
+
+impl fmt::Display for DeadCode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeadCode::UnusedVar(name) => write!(f, "variable `{}` is never read", name),
+            DeadCode::DeadStore(_, name) => {
+                write!(f, "value assigned to `{}` is never read", name)
+            }
+            DeadCode::UnusedProcedure(proc) => {
+                write!(f, "procedure `{}` is never called", proc.name)
+            }
+            DeadCode::Unreachable(_) => write!(f, "unreachable statement"),
+        }
+    }
+}
+
+/// Names declared by `var_defs`.
+pub fn var_names(var_defs: &Option<Vec<VarDef>>) -> Vec<&syn::Ident> {
+    var_defs
+        .iter()
+        .flatten()
+        .flat_map(|x| match x {
+            VarDef::CharTyVar(def) => &def.names,
+            VarDef::IntTyVar(def) => &def.names,
+        })
+        .collect()
+}
+
+/// Names of the parameters and variables of `proc`.
+pub fn locals(proc: &ProcedureDef) -> HashSet<String> {
+    proc.params
+        .iter()
+        .map(|x| x.name())
+        .chain(var_names(&proc.var_defs))
+        .map(|x| x.to_string())
+        .collect()
+}
+
+/// Every piece of dead code in `prog`, in no particular order.
+pub fn find_dead_code(prog: &Program) -> Vec<DeadCode<'_>> {
+    let procs = prog.procedure_defs.iter().flatten().collect::<Vec<_>>();
+    let globals = var_names(&prog.var_defs);
+    let mut analysis = Analysis {
+        procs: procs.iter().map(|x| (x.name.to_string(), *x)).collect(),
+        globals: globals.iter().map(|x| x.to_string()).collect(),
+        globals_read: HashMap::new(),
+        read: HashSet::new(),
+        res: Vec::new(),
+    };
+
+    analysis.read_names(&prog.body, &HashSet::new());
+    for proc in &procs {
+        let read = analysis.read_names(&proc.body, &locals(proc));
+        for name in var_names(&proc.var_defs) {
+            if !read.contains(&name.to_string()) {
+                analysis.res.push(DeadCode::UnusedVar(name));
+            }
+        }
+    }
+    for name in globals {
+        if !analysis.read.contains(&name.to_string()) {
+            analysis.res.push(DeadCode::UnusedVar(name));
+        }
+    }
+
+    analysis.body(&Body {
+        locals: HashSet::new(),
+        stms: &prog.body,
+        exit_live: HashSet::new(),
+    });
+    let mut called = HashSet::new();
+    analysis.calls(&prog.body, &mut called);
+    for proc in procs {
+        let locals = locals(proc);
+        // the caller may read any global that is read somewhere
+        let exit_live = analysis
+            .read
+            .iter()
+            .filter(|x| !locals.contains(*x))
+            .cloned()
+            .collect();
+        analysis.body(&Body {
+            locals,
+            stms: &proc.body,
+            exit_live,
+        });
+        if !called.contains(&proc.name.to_string()) {
+            analysis.res.push(DeadCode::UnusedProcedure(proc));
+        }
+    }
+    analysis.res
+}
+
+struct Body<'a> {
+    locals: HashSet<String>,
+    stms: &'a [Stmatment],
+    /// Variables the code after the body may read.
+    exit_live: HashSet<String>,
+}
+
+struct Analysis<'a> {
+    procs: HashMap<String, &'a ProcedureDef>,
+    globals: HashSet<String>,
+    /// Globals each procedure reads, through its calls too.
+    globals_read: HashMap<String, HashSet<String>>,
+    /// Globals read anywhere.
+    read: HashSet<String>,
+    res: Vec<DeadCode<'a>>,
+}
+
+impl<'a> Analysis<'a> {
+    /// Adds the procedures `stms` calls, directly or not, to `called`.
+    fn calls(&self, stms: &[Stmatment], called: &mut HashSet<String>) {
+        for stm in stms {
+            for_each_stm(stm, &mut |stm| {
+                if let Stmatment::Expr(Expr::Call(call)) = stm {
+                    let name = call.ident.to_string();
+                    if let Some(proc) = self.procs.get(&name) {
+                        if called.insert(name) {
+                            self.calls(&proc.body, called);
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    /// Globals `name` reads when called.
+    fn proc_reads(&mut self, name: &str) -> HashSet<String> {
+        if let Some(read) = self.globals_read.get(name) {
+            return read.clone();
+        }
+        let Some(proc) = self.procs.get(name).copied() else {
+            return HashSet::new();
+        };
+        // a call back into `name` adds nothing new
+        self.globals_read.insert(name.to_string(), HashSet::new());
+        let locals = locals(proc);
+        let read = self.read_names(&proc.body, &locals);
+        let read = read
+            .into_iter()
+            .filter(|x| !locals.contains(x))
+            .collect::<HashSet<_>>();
+        self.globals_read.insert(name.to_string(), read.clone());
+        read
+    }
+
+    /// Names `stms` reads, a call reads the globals of the procedure.
+    fn read_names(&mut self, stms: &[Stmatment], locals: &HashSet<String>) -> HashSet<String> {
+        let mut read = HashSet::new();
+        let mut called = Vec::new();
+        for stm in stms {
+            for_each_stm(stm, &mut |stm| {
+                let (used, _) = use_def(stm);
+                read.extend(used);
+                if let Some(cond) = condition_of(stm) {
+                    expr_uses(cond, &mut read);
+                }
+                if let Stmatment::Expr(Expr::Call(call)) = stm {
+                    called.push(call.ident.to_string());
+                }
+            });
+        }
+        for name in called {
+            read.extend(
+                self.proc_reads(&name)
+                    .into_iter()
+                    .filter(|x| !locals.contains(x)),
+            );
+        }
+        self.read.extend(
+            read.iter()
+                .filter(|x| !locals.contains(*x) && self.globals.contains(*x))
+                .cloned(),
+        );
+        read
+    }
+
+    /// Reports unreachable statements and dead stores of one body.
+    fn body(&mut self, body: &Body<'a>) {
+        let cfg = Cfg::build(String::new(), body.stms);
+        let reachable = reachable(&cfg);
+        let start = cfg
+            .stm_blocks
+            .iter()
+            .map(|(stm, block)| (*stm as *const Stmatment, *block))
+            .collect::<HashMap<_, _>>();
+        let is_reachable = |stm: &Stmatment| reachable[start[&(stm as *const _)]];
+        unreachable(body.stms, true, &is_reachable, &mut self.res);
+
+        // per block: the names read before being assigned, and assigned
+        let mut summaries = Vec::new();
+        for block in &cfg.blocks {
+            let mut live = HashSet::new();
+            if let Terminator::Branch { condition, .. } = block.terminator {
+                expr_uses(condition, &mut live);
+            }
+            let mut defs = HashSet::new();
+            for stm in block.stms.iter().rev() {
+                let (used, def) = self.stm_use_def(stm, &body.locals);
+                for name in def {
+                    live.remove(&name);
+                    defs.insert(name);
+                }
+                live.extend(used);
+            }
+            summaries.push((live, defs));
+        }
+
+        let mut live_in: Vec<HashSet<String>> = vec![HashSet::new(); cfg.blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in cfg.blocks.iter().rev() {
+                let out = self.live_out(&cfg, block.id, &live_in, body);
+                let (used, defs) = &summaries[block.id];
+                let mut new = used.clone();
+                new.extend(out.into_iter().filter(|x| !defs.contains(x)));
+                if new != live_in[block.id] {
+                    live_in[block.id] = new;
+                    changed = true;
+                }
+            }
+        }
+
+        for block in &cfg.blocks {
+            if !reachable[block.id] {
+                continue;
+            }
+            let mut live = self.live_out(&cfg, block.id, &live_in, body);
+            if let Terminator::Branch { condition, .. } = block.terminator {
+                expr_uses(condition, &mut live);
+            }
+            for stm in block.stms.iter().rev() {
+                if let Some(target) = assign_target(stm) {
+                    let name = target.to_string();
+                    let declared = body.locals.contains(&name) || self.globals.contains(&name);
+                    if declared && !live.contains(&name) {
+                        self.res.push(DeadCode::DeadStore(stm, target));
+                    }
+                }
+                let (used, def) = self.stm_use_def(stm, &body.locals);
+                for name in def {
+                    live.remove(&name);
+                }
+                live.extend(used);
+            }
+        }
+    }
+
+    fn live_out(
+        &self,
+        cfg: &Cfg,
+        block: BlockId,
+        live_in: &[HashSet<String>],
+        body: &Body,
+    ) -> HashSet<String> {
+        let mut out = HashSet::new();
+        if block == cfg.exit {
+            out.extend(body.exit_live.iter().cloned());
+        }
+        for succ in successors(cfg, block) {
+            out.extend(live_in[succ].iter().cloned());
+        }
+        out
+    }
+
+    /// `use_def` with calls reading the globals of the procedure.
+    fn stm_use_def(
+        &mut self,
+        stm: &Stmatment,
+        locals: &HashSet<String>,
+    ) -> (HashSet<String>, Vec<String>) {
+        let (mut used, def) = use_def(stm);
+        if let Stmatment::Expr(Expr::Call(call)) = stm {
+            let read = self.proc_reads(&call.ident.to_string());
+            used.extend(read.into_iter().filter(|x| !locals.contains(x)));
+        }
+        (used, def)
+    }
+}
+
+/// Successors of `block` that can be taken, a branch on a constant
+/// comparison only goes one way.
+fn successors(cfg: &Cfg, block: BlockId) -> Vec<BlockId> {
+    let block = &cfg.blocks[block];
+    match block.terminator {
+        Terminator::Branch {
+            condition: cond,
+            then_block,
+            else_block,
+        } => match condition(cond) {
+            Some(true) => vec![then_block],
+            Some(false) => vec![else_block],
+            None => vec![then_block, else_block],
+        },
+        _ => block.successors().into_iter().map(|x| x.0).collect(),
+    }
+}
+
+fn reachable(cfg: &Cfg) -> Vec<bool> {
+    let mut reachable = vec![false; cfg.blocks.len()];
+    let mut stack = vec![cfg.entry];
+    while let Some(block) = stack.pop() {
+        if !std::mem::replace(&mut reachable[block], true) {
+            stack.extend(successors(cfg, block));
+        }
+    }
+    reachable
+}
+
+/// Reports the first statement of every unreachable run in `stms`.
+fn unreachable<'a>(
+    stms: &'a [Stmatment],
+    mut prev_reachable: bool,
+    is_reachable: &impl Fn(&Stmatment) -> bool,
+    res: &mut Vec<DeadCode<'a>>,
+) {
+    for stm in stms {
+        let reached = is_reachable(stm);
+        if !reached && prev_reachable {
+            res.push(DeadCode::Unreachable(stm));
+        }
+        match stm {
+            Stmatment::If(if_stm) => {
+                unreachable(&if_stm.body, reached, is_reachable, res);
+                if let Some(else_body) = &if_stm.else_body {
+                    unreachable(else_body, reached, is_reachable, res);
+                }
+            }
+            Stmatment::While(while_stm) => unreachable(&while_stm.body, reached, is_reachable, res),
+            _ => {}
+        }
+        prev_reachable = reached;
+    }
+}
+
+/// Calls `f` on `stm` and every statement nested in it.
+pub fn for_each_stm<'a>(stm: &'a Stmatment, f: &mut impl FnMut(&'a Stmatment)) {
+    f(stm);
+    let lists: Vec<&'a [Stmatment]> = match stm {
+        Stmatment::If(if_stm) => {
+            let mut lists = vec![if_stm.body.as_slice()];
+            lists.extend(if_stm.else_body.as_deref());
+            lists
+        }
+        Stmatment::While(while_stm) => vec![while_stm.body.as_slice()],
+        _ => Vec::new(),
+    };
+    for stm in lists.into_iter().flatten() {
+        for_each_stm(stm, f);
+    }
+}
+
+fn condition_of(stm: &Stmatment) -> Option<&Expr> {
+    match stm {
+        Stmatment::If(if_stm) => Some(&if_stm.condition),
+        Stmatment::While(while_stm) => Some(&while_stm.condition),
+        _ => None,
+    }
+}
+
+/// The variable `stm` assigns, when it is a plain assignment.
+pub fn assign_target(stm: &Stmatment) -> Option<&syn::Ident> {
+    match stm {
+        Stmatment::Expr(Expr::Assign(assign)) => match &*assign.target {
+            Expr::Var(var) => Some(&var.ident),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Every variable `stms` reads or assigns.
+pub fn mentioned_names(stms: &[Stmatment]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for stm in stms {
+        for_each_stm(stm, &mut |stm| {
+            let (used, def) = use_def(stm);
+            names.extend(used);
+            names.extend(def);
+            if let Some(cond) = condition_of(stm) {
+                expr_uses(cond, &mut names);
+            }
+        });
+    }
+    names
+}
+
+/// Names a simple statement reads and assigns, conditions and the
+/// statements nested in `if` and `while` are not included.
+fn use_def(stm: &Stmatment) -> (HashSet<String>, Vec<String>) {
+    let mut used = HashSet::new();
+    let mut def = Vec::new();
+    match stm {
+        Stmatment::Expr(Expr::Assign(assign)) => {
+            match &*assign.target {
+                Expr::Var(var) => def.push(var.ident.to_string()),
+                target => expr_uses(target, &mut used),
+            }
+            expr_uses(&assign.from, &mut used);
+        }
+        Stmatment::Expr(expr) => expr_uses(expr, &mut used),
+        Stmatment::Write(write_stm) => {
+            for arg in &write_stm.args {
+                expr_uses(arg, &mut used);
+            }
+        }
+        Stmatment::Read(read_stm) => {
+            for param in &read_stm.params {
+                match param {
+                    Expr::Var(var) => def.push(var.ident.to_string()),
+                    param => expr_uses(param, &mut used),
+                }
+            }
+        }
+        Stmatment::If(_) | Stmatment::While(_) => {}
+    }
+    (used, def)
+}
+
+/// Adds the variables `expr` reads to `used`.
+fn expr_uses(expr: &Expr, used: &mut HashSet<String>) {
+    match expr {
+        Expr::Assign(assign) => {
+            expr_uses(&assign.target, used);
+            expr_uses(&assign.from, used);
+        }
+        Expr::Binary(binary) => {
+            expr_uses(&binary.lhs, used);
+            expr_uses(&binary.rhs, used);
+        }
+        Expr::Var(var) => {
+            used.insert(var.ident.to_string());
+        }
+        Expr::Lit(_) => {}
+        Expr::Index(index) => {
+            used.insert(index.ident.to_string());
+            expr_uses(&index.index, used);
+        }
+        Expr::Call(call) => {
+            for arg in &call.args {
+                expr_uses(arg, used);
+            }
+        }
+        Expr::Paren(paren) => expr_uses(&paren.expr, used),
+    }
+}
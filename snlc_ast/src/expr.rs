@@ -1,8 +1,15 @@
-use proc_macro2::{Punct, Spacing, TokenStream};
+use std::fmt;
+
+use proc_macro2::{Punct, Spacing, Span, TokenStream};
 use quote::{quote, ToTokens, TokenStreamExt};
+use syn::ext::IdentExt;
 use syn::{bracketed, parenthesized, Token};
 use syn::{parse::Parse, parse::ParseStream};
 
+use snlc_lexer::unescape::{escape_char, escape_str};
+
+use crate::codegen::{config, OverflowPolicy};
+
 #[derive(Debug)]
 pub enum Expr {
     Assign(ExprAssign),
@@ -28,7 +35,53 @@ impl ToTokens for Expr {
     }
 }
 
-#[derive(Debug)]
+impl Expr {
+    /// Span of the first token of the expression.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Assign(expr) => expr.target.span(),
+            Expr::Binary(expr) => expr.lhs.span(),
+            Expr::Var(expr) => expr.ident.span(),
+            Expr::Lit(expr) => match &expr.lit {
+                Lit::Char(lit) => lit.span(),
+                Lit::Integer(lit) => lit.span(),
+                Lit::Str(lit) => lit.span(),
+            },
+            Expr::Index(expr) => expr.ident.span(),
+            Expr::Call(expr) => expr.ident.span(),
+            Expr::Paren(expr) => expr.paren_token.span.open(),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Assign(expr) => write!(f, "{} := {}", expr.target, expr.from),
+            Expr::Binary(expr) => write!(f, "{} {} {}", expr.lhs, expr.op, expr.rhs),
+            Expr::Var(expr) => write!(f, "{}", expr.ident),
+            Expr::Lit(expr) => match &expr.lit {
+                Lit::Char(lit) => write!(f, "{}", escape_char(lit.value())),
+                Lit::Integer(lit) => write!(f, "{}", lit.base10_digits()),
+                Lit::Str(lit) => write!(f, "{}", escape_str(&lit.value())),
+            },
+            Expr::Index(expr) => write!(f, "{}[{}]", expr.ident, expr.index),
+            Expr::Call(expr) => {
+                write!(f, "{}(", expr.ident)?;
+                for (i, arg) in expr.args.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Paren(expr) => write!(f, "({})", expr.expr),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BinOp {
     Lt,
     Le,
@@ -42,6 +95,18 @@ pub enum BinOp {
     Slash,
     Unknown,
 }
+impl BinOp {
+    /// Binding power of the operator, a higher value binds tighter.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinOp::Assign | BinOp::Unknown => 0,
+            BinOp::Lt | BinOp::Le | BinOp::Eq => 1,
+            BinOp::Plus | BinOp::Minus => 2,
+            BinOp::Star | BinOp::Slash => 3,
+        }
+    }
+}
+
 impl ToTokens for BinOp {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         match self {
@@ -71,6 +136,23 @@ impl ToTokens for BinOp {
         };
     }
 }
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Eq => "=",
+            BinOp::Assign => ":=",
+            BinOp::Plus => "+",
+            BinOp::Minus => "-",
+            BinOp::Star => "*",
+            BinOp::Slash => "/",
+            BinOp::Unknown => "?",
+        };
+        write!(f, "{}", op)
+    }
+}
+
 impl Parse for BinOp {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let ahead = input.fork();
@@ -120,6 +202,8 @@ impl Parse for BinOp {
 pub enum Lit {
     Char(syn::LitChar),
     Integer(syn::LitInt),
+    /// Only valid as an argument of `write`.
+    Str(syn::LitStr),
 }
 
 #[derive(Debug)]
@@ -149,10 +233,61 @@ pub struct ExprBinary {
 impl ToTokens for ExprBinary {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let lhs = self.lhs.to_token_stream();
-        let op = self.op.to_token_stream();
         let rhs = self.rhs.to_token_stream();
+        let method = match self.op {
+            BinOp::Plus => "add",
+            BinOp::Minus => "sub",
+            BinOp::Star => "mul",
+            BinOp::Slash => "div",
+            _ => {
+                let op = self.op.to_token_stream();
+                tokens.extend(quote! {
+                    #lhs #op #rhs
+                });
+                return;
+            }
+        };
+
+        // arithmetic goes through the integer methods of the overflow policy
+        let config = config();
+        let ty = config.int_width.rust_type();
+        let line = config.snl_line(self.lhs.span());
+        let value = match config.overflow {
+            OverflowPolicy::Wrap => {
+                let method = syn::Ident::new(&format!("wrapping_{}", method), Span::call_site());
+                quote!(lhs.#method(rhs))
+            }
+            OverflowPolicy::Saturate => {
+                let method = syn::Ident::new(&format!("saturating_{}", method), Span::call_site());
+                quote!(lhs.#method(rhs))
+            }
+            OverflowPolicy::Trap => {
+                let method = syn::Ident::new(&format!("checked_{}", method), Span::call_site());
+                quote! {
+                    match lhs.#method(rhs) {
+                        Some(value) => value,
+                        None => ::snlc::runtime::fail(::snlc::runtime::ErrorKind::Overflow, #line),
+                    }
+                }
+            }
+        };
+        // division by zero is an error whatever the policy
+        let check_zero = if self.op == BinOp::Slash {
+            quote! {
+                if rhs == 0 {
+                    ::snlc::runtime::fail(::snlc::runtime::ErrorKind::DivisionByZero, #line)
+                }
+            }
+        } else {
+            quote!()
+        };
+        // both operands are evaluated before the bindings shadow anything
         tokens.extend(quote! {
-            #lhs #op #rhs
+            {
+                let (lhs, rhs): (#ty, #ty) = (#lhs, #rhs);
+                #check_zero
+                #value
+            }
         });
     }
 }
@@ -180,6 +315,7 @@ impl ToTokens for ExprLit {
         let lit = match &self.lit {
             Lit::Char(lit) => lit.to_token_stream(),
             Lit::Integer(lit) => lit.to_token_stream(),
+            Lit::Str(lit) => lit.to_token_stream(),
         };
         tokens.extend(quote! {
             #lit
@@ -213,24 +349,20 @@ pub struct ExprCall {
 
 impl ToTokens for ExprCall {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        let ident = self.ident.to_token_stream();
+        let ident = &self.ident;
+        let name = self.ident.unraw().to_string();
+        let line = config().snl_line(self.ident.span());
         let args = &self.args;
-        let mut args_tokenstream = quote!{};
-        let args_len = args.len();
-        for (i, arg) in args.iter().enumerate() {
-            let arg_tokens = arg.to_token_stream();
-            if i == args_len - 1 {
-                args_tokenstream.extend(quote! {
-                    #arg_tokens
-                });
-            } else {
-                args_tokenstream.extend(quote! {
-                    #arg_tokens,
-                });
-            }
-        }
+        let index = (0..args.len()).map(syn::Index::from);
+        // arguments are evaluated before `enter`, so runtime errors in them
+        // are reported in the caller's frame
         tokens.extend(quote! {
-            #ident(#args_tokenstream)
+            {
+                let __args = (#(#args,)*);
+                ::snlc::runtime::enter(#name, #line);
+                #ident!(#(__args.#index),*);
+                ::snlc::runtime::leave();
+            }
         });
     }
 }
@@ -257,51 +389,93 @@ fn expr_tailer(input: ParseStream) -> syn::Result<(BinOp, Expr)> {
     };
 
     let op = input.parse()?;
-    let expr = input.parse()?;
+    let expr = parse_operand(input)?;
 
     Ok((op, expr))
 }
 
-impl Parse for Expr {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let ahead = input.fork();
-        let lookahead = input.lookahead1();
-        let lhs: Expr = if lookahead.peek(syn::Lit) {
-            Expr::Lit(ExprLit::parse(input)?)
-        } else if lookahead.peek(syn::Ident) {
-            ahead.parse::<syn::Ident>()?;
-            if ahead.peek(syn::token::Bracket) {
-                Expr::Index(ExprIndex::parse(input)?)
-            } else if ahead.peek(syn::token::Paren) {
-                Expr::Call(ExprCall::parse(input)?)
-            } else {
-                Expr::Var(ExprVar::parse(input)?)
-            }
-        } else if lookahead.peek(syn::token::Paren) {
-            Expr::Paren(ExprParen::parse(input)?)
+fn parse_operand(input: ParseStream) -> syn::Result<Expr> {
+    let ahead = input.fork();
+    let lookahead = input.lookahead1();
+    let res = if lookahead.peek(syn::Lit) {
+        Expr::Lit(ExprLit::parse(input)?)
+    } else if lookahead.peek(syn::Ident) {
+        ahead.parse::<syn::Ident>()?;
+        if ahead.peek(syn::token::Bracket) {
+            Expr::Index(ExprIndex::parse(input)?)
+        } else if ahead.peek(syn::token::Paren) {
+            Expr::Call(ExprCall::parse(input)?)
         } else {
-            return Err(lookahead.error());
-        };
+            Expr::Var(ExprVar::parse(input)?)
+        }
+    } else if lookahead.peek(syn::token::Paren) {
+        Expr::Paren(ExprParen::parse(input)?)
+    } else {
+        return Err(lookahead.error());
+    };
+
+    Ok(res)
+}
+
+/// Joins `lhs` and `rhs` with `op`, `:=` becomes an `ExprAssign`.
+pub fn make_binary(lhs: Expr, op: BinOp, rhs: Expr) -> Expr {
+    match op {
+        BinOp::Assign => Expr::Assign(ExprAssign {
+            target: Box::new(lhs),
+            from: Box::new(rhs),
+        }),
+        _ => Expr::Binary(ExprBinary {
+            lhs: Box::new(lhs),
+            op,
+            rhs: Box::new(rhs),
+        }),
+    }
+}
 
-        let (op, rhs) = match expr_tailer(input) {
-            Ok((op, rhs)) => (op, rhs),
-            Err(_) => {
-                return Ok(lhs);
+/// Folds `first op1 e1 op2 e2 ...` into a tree honoring operator precedence.
+/// Arithmetic and comparisons are left associative, `:=` is right associative.
+pub fn fold_binary(first: Expr, rest: Vec<(BinOp, Expr)>) -> Expr {
+    fn reduce(operands: &mut Vec<Expr>, ops: &mut Vec<BinOp>) {
+        let op = ops.pop().unwrap();
+        let rhs = operands.pop().unwrap();
+        let lhs = operands.pop().unwrap();
+        operands.push(make_binary(lhs, op, rhs));
+    }
+
+    let mut operands = vec![first];
+    let mut ops: Vec<BinOp> = Vec::new();
+    for (op, rhs) in rest {
+        while let Some(top) = ops.last() {
+            let should_reduce = if op == BinOp::Assign {
+                top.precedence() > op.precedence()
+            } else {
+                top.precedence() >= op.precedence()
+            };
+            if !should_reduce {
+                break;
             }
-        };
+            reduce(&mut operands, &mut ops);
+        }
+        ops.push(op);
+        operands.push(rhs);
+    }
+    while !ops.is_empty() {
+        reduce(&mut operands, &mut ops);
+    }
 
-        let res = match op {
-            BinOp::Assign => Expr::Assign(ExprAssign {
-                target: Box::new(lhs),
-                from: Box::new(rhs),
-            }),
-            _ => Expr::Binary(ExprBinary {
-                lhs: Box::new(lhs),
-                op,
-                rhs: Box::new(rhs),
-            }),
-        };
-        Ok(res)
+    operands.pop().unwrap()
+}
+
+impl Parse for Expr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let lhs = parse_operand(input)?;
+
+        let mut rest = Vec::new();
+        while input.fork().parse::<BinOp>().is_ok() {
+            rest.push(expr_tailer(input)?);
+        }
+
+        Ok(fold_binary(lhs, rest))
     }
 }
 
@@ -339,6 +513,8 @@ impl Parse for ExprLit {
             Lit::Char(input.parse::<syn::LitChar>()?)
         } else if lookahead.peek(syn::LitInt) {
             Lit::Integer(input.parse::<syn::LitInt>()?)
+        } else if lookahead.peek(syn::LitStr) {
+            Lit::Str(input.parse::<syn::LitStr>()?)
         } else {
             return Err(lookahead.error());
         };
@@ -0,0 +1,330 @@
+//! Definite assignment: reads of variables that may not have been assigned
+//! on some path through the CFG.
+//!
+//! Parameters are assigned by the caller, every other variable starts out
+//! unassigned. A procedure is summed up by the globals it may read before
+//! assigning them and the globals it assigns, so a call reads the former
+//! and assigns the latter.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use proc_macro2::Span;
+
+use crate::ast::{ProcedureDef, Program};
+use crate::cfg::{Cfg, Terminator};
+use crate::deadcode::{locals, var_names};
+use crate::expr::Expr;
+use crate::stm::Stmatment;
+
+/// A read of `var` that may come before any assignment to it.
+#[derive(Debug, Clone)]
+pub struct UninitRead<'a> {
+    /// The variable read, or the procedure called for a read by a call.
+    pub at: &'a syn::Ident,
+    pub var: String,
+    /// The procedure that reads `var`, when it's read by a call.
+    pub call: Option<String>,
+    /// No path assigns `var` before the read.
+    pub never_assigned: bool,
+}
+
+impl UninitRead<'_> {
+    pub fn span(&self) -> Span {
+        self.at.span()
+    }
+}
+
+impl fmt::Display for UninitRead<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let may = if self.never_assigned { "" } else { "may " };
+        match &self.call {
+            Some(proc) => write!(
+                f,
+                "call to `{}` {}read{} `{}` before it is assigned",
+                proc,
+                may,
+                if self.never_assigned { "s" } else { "" },
+                self.var
+            ),
+            None => write!(
+                f,
+                "variable `{}` {}{} read before it is assigned",
+                self.var,
+                may,
+                if self.never_assigned { "is" } else { "be" }
+            ),
+        }
+    }
+}
+
+/// Every read in `prog` that may see a variable no path has assigned yet,
+/// in no particular order. Reads of globals inside a procedure are
+/// reported at the calls to it.
+pub fn find_uninit_reads(prog: &Program) -> Vec<UninitRead<'_>> {
+    let globals = var_names(&prog.var_defs)
+        .into_iter()
+        .map(|x| x.to_string())
+        .collect::<HashSet<_>>();
+    let mut analysis = Analysis {
+        procs: prog
+            .procedure_defs
+            .iter()
+            .flatten()
+            .map(|x| (x.name.to_string(), x))
+            .collect(),
+        globals: globals.clone(),
+        summaries: HashMap::new(),
+        res: Vec::new(),
+    };
+    for proc in prog.procedure_defs.iter().flatten() {
+        analysis.summary(proc);
+    }
+    let (reads, _) = analysis.body(&prog.body, &globals, &HashSet::new());
+    analysis.res.extend(reads);
+    analysis.res
+}
+
+/// What a call to a procedure does to the globals.
+#[derive(Debug, Clone, Default)]
+struct Summary {
+    /// Globals read before the procedure assigns them, with whether no
+    /// path assigns them first.
+    reads: HashMap<String, bool>,
+    /// Globals assigned on every path.
+    must: HashSet<String>,
+    /// Globals assigned on some path.
+    may: HashSet<String>,
+}
+
+/// Variables assigned on every path and on some path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State {
+    must: HashSet<String>,
+    may: HashSet<String>,
+}
+
+impl State {
+    fn assign(&mut self, name: String) {
+        self.must.insert(name.clone());
+        self.may.insert(name);
+    }
+
+    fn join(&mut self, other: &State) {
+        self.must.retain(|x| other.must.contains(x));
+        self.may.extend(other.may.iter().cloned());
+    }
+}
+
+struct Analysis<'a> {
+    procs: HashMap<String, &'a ProcedureDef>,
+    globals: HashSet<String>,
+    summaries: HashMap<String, Summary>,
+    res: Vec<UninitRead<'a>>,
+}
+
+impl<'a> Analysis<'a> {
+    fn summary(&mut self, proc: &'a ProcedureDef) -> Summary {
+        let name = proc.name.to_string();
+        if let Some(summary) = self.summaries.get(&name) {
+            return summary.clone();
+        }
+        // a call back into `name` reads and assigns nothing
+        self.summaries.insert(name.clone(), Summary::default());
+        let vars = var_names(&proc.var_defs)
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect::<HashSet<_>>();
+        let locals = locals(proc);
+        let globals = self
+            .globals
+            .iter()
+            .filter(|x| !locals.contains(*x))
+            .cloned()
+            .collect::<HashSet<_>>();
+        let params = locals
+            .iter()
+            .filter(|x| !vars.contains(*x))
+            .cloned()
+            .collect();
+        let tracked = vars.union(&globals).cloned().collect();
+
+        let (reads, exit) = self.body(&proc.body, &tracked, &params);
+        let mut summary = Summary::default();
+        for read in reads {
+            if globals.contains(&read.var) {
+                *summary.reads.entry(read.var).or_insert(read.never_assigned) &=
+                    read.never_assigned;
+            } else {
+                self.res.push(read);
+            }
+        }
+        if let Some(exit) = exit {
+            summary.must = exit.must.intersection(&globals).cloned().collect();
+            summary.may = exit.may.intersection(&globals).cloned().collect();
+        } else {
+            // never returns, anything after the call is unreachable
+            summary.must = globals.clone();
+            summary.may = globals;
+        }
+        self.summaries.insert(name, summary.clone());
+        summary
+    }
+
+    /// Reads in `stms` of `tracked` variables that may be unassigned, with
+    /// `assigned` assigned on entry, and the state at the exit when it can
+    /// be reached.
+    fn body(
+        &mut self,
+        stms: &'a [Stmatment],
+        tracked: &HashSet<String>,
+        assigned: &HashSet<String>,
+    ) -> (Vec<UninitRead<'a>>, Option<State>) {
+        let cfg = Cfg::build(String::new(), stms);
+        let preds = cfg.predecessors();
+        let entry = State {
+            must: assigned.clone(),
+            may: assigned.clone(),
+        };
+
+        // `None` is a block no path reaches yet
+        let mut state_in: Vec<Option<State>> = vec![None; cfg.blocks.len()];
+        state_in[cfg.entry] = Some(entry.clone());
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in &cfg.blocks {
+                let mut new = if block.id == cfg.entry {
+                    Some(entry.clone())
+                } else {
+                    None
+                };
+                for &pred in &preds[block.id] {
+                    let Some(mut out) = state_in[pred].clone() else {
+                        continue;
+                    };
+                    for stm in &cfg.blocks[pred].stms {
+                        self.stm(stm, tracked, &mut out, &mut Vec::new());
+                    }
+                    match &mut new {
+                        Some(new) => new.join(&out),
+                        None => new = Some(out),
+                    }
+                }
+                if new != state_in[block.id] {
+                    state_in[block.id] = new;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut reads = Vec::new();
+        for block in &cfg.blocks {
+            let Some(mut state) = state_in[block.id].clone() else {
+                continue;
+            };
+            for stm in &block.stms {
+                self.stm(stm, tracked, &mut state, &mut reads);
+            }
+            if let Terminator::Branch { condition, .. } = block.terminator {
+                check_reads(condition, tracked, &state, &mut reads);
+            }
+        }
+        let exit = state_in[cfg.exit].clone();
+        (reads, exit)
+    }
+
+    /// Checks the reads of a simple statement and applies its assignments.
+    fn stm(
+        &mut self,
+        stm: &'a Stmatment,
+        tracked: &HashSet<String>,
+        state: &mut State,
+        reads: &mut Vec<UninitRead<'a>>,
+    ) {
+        match stm {
+            Stmatment::Expr(Expr::Assign(assign)) => {
+                check_reads(&assign.from, tracked, state, reads);
+                match &*assign.target {
+                    Expr::Var(var) => state.assign(var.ident.to_string()),
+                    target => check_reads(target, tracked, state, reads),
+                }
+            }
+            Stmatment::Expr(Expr::Call(call)) => {
+                for arg in &call.args {
+                    check_reads(arg, tracked, state, reads);
+                }
+                let Some(proc) = self.procs.get(&call.ident.to_string()).copied() else {
+                    return;
+                };
+                let summary = self.summary(proc);
+                for (var, never_assigned) in summary.reads {
+                    if tracked.contains(&var) && !state.must.contains(&var) {
+                        reads.push(UninitRead {
+                            at: &call.ident,
+                            never_assigned: never_assigned && !state.may.contains(&var),
+                            var,
+                            call: Some(proc.name.to_string()),
+                        });
+                    }
+                }
+                state.must.extend(summary.must);
+                state.may.extend(summary.may);
+            }
+            Stmatment::Expr(expr) => check_reads(expr, tracked, state, reads),
+            Stmatment::Write(write_stm) => {
+                for arg in &write_stm.args {
+                    check_reads(arg, tracked, state, reads);
+                }
+            }
+            Stmatment::Read(read_stm) => {
+                for param in &read_stm.params {
+                    match param {
+                        Expr::Var(var) => state.assign(var.ident.to_string()),
+                        param => check_reads(param, tracked, state, reads),
+                    }
+                }
+            }
+            Stmatment::If(_) | Stmatment::While(_) => {}
+        }
+    }
+}
+
+/// Adds the reads in `expr` of `tracked` variables `state` doesn't
+/// assign on every path.
+fn check_reads<'a>(
+    expr: &'a Expr,
+    tracked: &HashSet<String>,
+    state: &State,
+    reads: &mut Vec<UninitRead<'a>>,
+) {
+    match expr {
+        Expr::Var(var) => {
+            let name = var.ident.to_string();
+            if tracked.contains(&name) && !state.must.contains(&name) {
+                reads.push(UninitRead {
+                    at: &var.ident,
+                    never_assigned: !state.may.contains(&name),
+                    var: name,
+                    call: None,
+                });
+            }
+        }
+        Expr::Assign(assign) => {
+            check_reads(&assign.target, tracked, state, reads);
+            check_reads(&assign.from, tracked, state, reads);
+        }
+        Expr::Binary(binary) => {
+            check_reads(&binary.lhs, tracked, state, reads);
+            check_reads(&binary.rhs, tracked, state, reads);
+        }
+        Expr::Index(index) => check_reads(&index.index, tracked, state, reads),
+        Expr::Call(call) => {
+            for arg in &call.args {
+                check_reads(arg, tracked, state, reads);
+            }
+        }
+        Expr::Paren(paren) => check_reads(&paren.expr, tracked, state, reads),
+        Expr::Lit(_) => {}
+    }
+}
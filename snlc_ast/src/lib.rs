@@ -1,9 +1,18 @@
+extern crate proc_macro;
+
 use ast::Program;
+use codegen::{with_config, Config};
 use proc_macro2::{Span, TokenStream};
 use quote::ToTokens;
+use syn::parse::{ParseStream, Parser};
 
 pub mod ast;
+pub mod cfg;
+pub mod codegen;
+pub mod deadcode;
 pub mod expr;
+pub mod init;
+pub mod opt;
 pub mod stm;
 pub mod token;
 
@@ -34,6 +43,33 @@ pub mod kw {
 
     syn::custom_keyword!(r#read);
     syn::custom_keyword!(r#write);
+    syn::custom_keyword!(r#writeln);
+
+    /// Every keyword above, as written in SNL source.
+    pub const KEYWORDS: &[&str] = &[
+        "program",
+        "begin",
+        "end",
+        "procedure",
+        "return",
+        "type",
+        "var",
+        "if",
+        "then",
+        "else",
+        "fi",
+        "while",
+        "do",
+        "endwh",
+        "char",
+        "integer",
+        "record",
+        "array",
+        "of",
+        "read",
+        "write",
+        "writeln",
+    ];
 }
 #[derive(Default)]
 struct Errors {
@@ -57,21 +93,31 @@ pub fn snl(input: TokenStream) -> TokenStream {
 pub fn snl_with_erros(input: TokenStream) -> (TokenStream, Vec<syn::Error>) {
     let mut errors = Errors::default();
 
-    let prog = match syn::parse2(input) {
+    let parser = |input: ParseStream| {
+        let attrs = input.call(syn::Attribute::parse_inner)?;
+        let config = Config::from_attrs(&attrs)?;
+        let prog = input.parse::<Program>()?;
+        Ok((config, prog))
+    };
+    let (config, mut prog) = match parser.parse2(input) {
         Ok(input) => input,
         Err(e) => {
             // This allows us to display errors at the proper span, while minimizing
             // unrelated errors caused by bailing out (and not generating code).
             errors.list.push(e);
-            Program {
+            let prog = Program {
                 name: syn::Ident::new("yurzi", Span::call_site()),
                 var_defs: None,
                 procedure_defs: None,
                 body: Vec::new(),
-            }
+            };
+            (Config::default(), prog)
         }
     };
 
-    let output = prog.to_token_stream();
+    if config.opt_level >= 1 {
+        errors.list.extend(opt::optimize(&mut prog, &config));
+    }
+    let output = with_config(config, || prog.to_token_stream());
     (output, errors.list)
 }
@@ -0,0 +1,332 @@
+//! Constant folding and algebraic simplification, run at `opt_level = 1`.
+//!
+//! Arithmetic on constants folds the way the overflow policy evaluates it at
+//! runtime, under `trap` an overflowing expression is left for the program
+//! to report. An `if` whose condition is constant is replaced by the branch
+//! it takes and a `while` whose condition is false is dropped. Then the
+//! dead code `deadcode` finds is removed, assignments whose value could
+//! stop the program stay.
+
+use std::collections::HashSet;
+
+use proc_macro2::Span;
+
+use crate::ast::{Program, VarDef};
+use crate::codegen::{Config, OverflowPolicy};
+use crate::deadcode::{find_dead_code, locals, mentioned_names, DeadCode};
+use crate::expr::{BinOp, Expr, ExprBinary, ExprLit, Lit};
+use crate::stm::Stmatment;
+
+/// Value of a constant expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Const {
+    Int(i64),
+    Char(char),
+}
+
+impl Const {
+    /// The value of a literal, possibly in parentheses.
+    pub fn of(expr: &Expr) -> Option<Const> {
+        match expr {
+            Expr::Lit(expr) => match &expr.lit {
+                Lit::Integer(lit) => lit.base10_parse().ok().map(Const::Int),
+                Lit::Char(lit) => Some(Const::Char(lit.value())),
+                Lit::Str(_) => None,
+            },
+            Expr::Paren(expr) => Const::of(&expr.expr),
+            _ => None,
+        }
+    }
+}
+
+/// Simplifies every expression and statement of `prog`, dividing by a
+/// constant zero is an error.
+pub fn optimize(prog: &mut Program, config: &Config) -> Vec<syn::Error> {
+    let mut folder = Folder {
+        config,
+        errors: Vec::new(),
+    };
+    for proc in prog.procedure_defs.iter_mut().flatten() {
+        folder.stm_list(&mut proc.body);
+    }
+    folder.stm_list(&mut prog.body);
+    while remove_dead_code(prog, config.overflow) {}
+    folder.errors
+}
+
+/// Removes one round of dead code, true if anything was removed.
+fn remove_dead_code(prog: &mut Program, overflow: OverflowPolicy) -> bool {
+    let mut stores = HashSet::new();
+    let mut unreachable = HashSet::new();
+    let mut procs = HashSet::new();
+    for dead in find_dead_code(prog) {
+        match dead {
+            DeadCode::DeadStore(stm, _) => {
+                if let Stmatment::Expr(Expr::Assign(assign)) = stm {
+                    if !may_fail(&assign.from, overflow) {
+                        stores.insert(stm as *const Stmatment);
+                    }
+                }
+            }
+            DeadCode::Unreachable(stm) => {
+                unreachable.insert(stm as *const Stmatment);
+            }
+            DeadCode::UnusedProcedure(proc) => {
+                procs.insert(proc.name.to_string());
+            }
+            DeadCode::UnusedVar(_) => {}
+        }
+    }
+
+    let mut removed = !stores.is_empty() || !unreachable.is_empty() || !procs.is_empty();
+    let prune = |stms: &mut Vec<Stmatment>| prune(stms, &stores, &unreachable);
+    prune(&mut prog.body);
+    if let Some(procedure_defs) = &mut prog.procedure_defs {
+        procedure_defs.retain(|x| !procs.contains(&x.name.to_string()));
+        for proc in procedure_defs.iter_mut() {
+            prune(&mut proc.body);
+            let mentioned = mentioned_names(&proc.body);
+            removed |= retain_vars(&mut proc.var_defs, &mentioned);
+        }
+    }
+
+    // a global is mentioned by any body that doesn't declare the name
+    let mut mentioned = mentioned_names(&prog.body);
+    for proc in prog.procedure_defs.iter().flatten() {
+        let locals = locals(proc);
+        mentioned.extend(
+            mentioned_names(&proc.body)
+                .into_iter()
+                .filter(|x| !locals.contains(x)),
+        );
+    }
+    removed |= retain_vars(&mut prog.var_defs, &mentioned);
+    removed
+}
+
+/// Removes `stores` and everything from an `unreachable` statement on.
+fn prune(
+    stms: &mut Vec<Stmatment>,
+    stores: &HashSet<*const Stmatment>,
+    unreachable: &HashSet<*const Stmatment>,
+) {
+    if let Some(i) = stms
+        .iter()
+        .position(|x| unreachable.contains(&(x as *const _)))
+    {
+        stms.truncate(i);
+    }
+    stms.retain(|x| !stores.contains(&(x as *const _)));
+    for stm in stms {
+        match stm {
+            Stmatment::If(if_stm) => {
+                prune(&mut if_stm.body, stores, unreachable);
+                if let Some(else_body) = &mut if_stm.else_body {
+                    prune(else_body, stores, unreachable);
+                }
+            }
+            Stmatment::While(while_stm) => prune(&mut while_stm.body, stores, unreachable),
+            _ => {}
+        }
+    }
+}
+
+/// Drops the declarations of variables not in `mentioned`, true if any
+/// was dropped.
+fn retain_vars(var_defs: &mut Option<Vec<VarDef>>, mentioned: &HashSet<String>) -> bool {
+    let Some(var_defs) = var_defs else {
+        return false;
+    };
+    let mut removed = false;
+    for var_def in var_defs.iter_mut() {
+        let names = match var_def {
+            VarDef::CharTyVar(def) => &mut def.names,
+            VarDef::IntTyVar(def) => &mut def.names,
+        };
+        let len = names.len();
+        names.retain(|x| mentioned.contains(&x.to_string()));
+        removed |= names.len() != len;
+    }
+    var_defs.retain(|x| match x {
+        VarDef::CharTyVar(def) => !def.names.is_empty(),
+        VarDef::IntTyVar(def) => !def.names.is_empty(),
+    });
+    removed
+}
+
+struct Folder<'a> {
+    config: &'a Config,
+    errors: Vec<syn::Error>,
+}
+
+impl Folder<'_> {
+    fn stm_list(&mut self, stms: &mut Vec<Stmatment>) {
+        let mut res = Vec::with_capacity(stms.len());
+        for mut stm in std::mem::take(stms) {
+            match &mut stm {
+                Stmatment::Expr(expr) => self.expr(expr),
+                Stmatment::Write(write_stm) => {
+                    for arg in &mut write_stm.args {
+                        self.expr(arg);
+                    }
+                }
+                Stmatment::Read(_) => {}
+                Stmatment::If(if_stm) => {
+                    self.expr(&mut if_stm.condition);
+                    self.stm_list(&mut if_stm.body);
+                    if let Some(else_body) = &mut if_stm.else_body {
+                        self.stm_list(else_body);
+                    }
+                    if let Some(taken) = condition(&if_stm.condition) {
+                        let Stmatment::If(if_stm) = stm else {
+                            unreachable!()
+                        };
+                        if taken {
+                            res.extend(if_stm.body);
+                        } else {
+                            res.extend(if_stm.else_body.into_iter().flatten());
+                        }
+                        continue;
+                    }
+                }
+                Stmatment::While(while_stm) => {
+                    self.expr(&mut while_stm.condition);
+                    self.stm_list(&mut while_stm.body);
+                    if condition(&while_stm.condition) == Some(false) {
+                        continue;
+                    }
+                }
+            }
+            res.push(stm);
+        }
+        *stms = res;
+    }
+
+    fn expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Assign(assign) => self.expr(&mut assign.from),
+            Expr::Binary(binary) => {
+                self.expr(&mut binary.lhs);
+                self.expr(&mut binary.rhs);
+                if let Some(folded) = self.binary(binary) {
+                    *expr = folded;
+                }
+            }
+            Expr::Paren(paren) => {
+                self.expr(&mut paren.expr);
+                if matches!(*paren.expr, Expr::Lit(_) | Expr::Var(_)) {
+                    *expr = take(&mut paren.expr);
+                }
+            }
+            Expr::Index(index) => self.expr(&mut index.index),
+            Expr::Call(call) => {
+                for arg in &mut call.args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Var(_) | Expr::Lit(_) => {}
+        }
+    }
+
+    /// The simpler expression `binary` can be replaced with.
+    fn binary(&mut self, binary: &mut ExprBinary) -> Option<Expr> {
+        // the result keeps the span, and so the line, of the first operand
+        let span = binary.lhs.span();
+        let lhs = Const::of(&binary.lhs);
+        let rhs = Const::of(&binary.rhs);
+        match (binary.op, lhs, rhs) {
+            (BinOp::Slash, _, Some(Const::Int(0))) => {
+                self.errors
+                    .push(syn::Error::new(binary.rhs.span(), "division by zero"));
+                None
+            }
+            (op, Some(Const::Int(a)), Some(Const::Int(b))) => {
+                self.arith(op, a, b).map(|x| int_lit(x, span))
+            }
+            (BinOp::Plus, Some(Const::Int(0)), _) | (BinOp::Star, Some(Const::Int(1)), _) => {
+                Some(take(&mut binary.rhs))
+            }
+            (BinOp::Plus | BinOp::Minus, _, Some(Const::Int(0)))
+            | (BinOp::Star | BinOp::Slash, _, Some(Const::Int(1))) => Some(take(&mut binary.lhs)),
+            // the dropped operand must not have had a runtime error to report
+            (BinOp::Star, Some(Const::Int(0)), _)
+                if !may_fail(&binary.rhs, self.config.overflow) =>
+            {
+                Some(int_lit(0, span))
+            }
+            (BinOp::Star, _, Some(Const::Int(0)))
+                if !may_fail(&binary.lhs, self.config.overflow) =>
+            {
+                Some(int_lit(0, span))
+            }
+            _ => None,
+        }
+    }
+
+    /// `a op b` as the program would compute it, `None` when it would stop
+    /// with an overflow.
+    fn arith(&self, op: BinOp, a: i64, b: i64) -> Option<i64> {
+        let (a, b) = (a as i128, b as i128);
+        let value = match op {
+            BinOp::Plus => a + b,
+            BinOp::Minus => a - b,
+            BinOp::Star => a * b,
+            BinOp::Slash => a / b,
+            _ => return None,
+        };
+        let width = self.config.int_width;
+        let (min, max) = (width.min() as i128, width.max() as i128);
+        if (min..=max).contains(&value) {
+            return Some(value as i64);
+        }
+        match self.config.overflow {
+            OverflowPolicy::Trap => None,
+            OverflowPolicy::Saturate => Some(value.clamp(min, max) as i64),
+            OverflowPolicy::Wrap => {
+                let shift = 128 - width.bits();
+                Some(((value << shift) >> shift) as i64)
+            }
+        }
+    }
+}
+
+/// Whether evaluating `expr` can stop the program.
+fn may_fail(expr: &Expr, overflow: OverflowPolicy) -> bool {
+    match expr {
+        Expr::Binary(binary) => {
+            binary.op == BinOp::Slash
+                || (overflow == OverflowPolicy::Trap
+                    && matches!(binary.op, BinOp::Plus | BinOp::Minus | BinOp::Star))
+                || may_fail(&binary.lhs, overflow)
+                || may_fail(&binary.rhs, overflow)
+        }
+        Expr::Paren(paren) => may_fail(&paren.expr, overflow),
+        Expr::Var(_) | Expr::Lit(_) => false,
+        Expr::Assign(_) | Expr::Index(_) | Expr::Call(_) => true,
+    }
+}
+
+/// The value of a comparison of two constants.
+pub(crate) fn condition(expr: &Expr) -> Option<bool> {
+    let Expr::Binary(binary) = expr else {
+        return None;
+    };
+    let (lhs, rhs) = (Const::of(&binary.lhs)?, Const::of(&binary.rhs)?);
+    match binary.op {
+        BinOp::Lt => Some(lhs < rhs),
+        BinOp::Le => Some(lhs <= rhs),
+        BinOp::Eq => Some(lhs == rhs),
+        _ => None,
+    }
+}
+
+fn int_lit(value: i64, span: Span) -> Expr {
+    Expr::Lit(ExprLit {
+        lit: Lit::Integer(syn::LitInt::new(&value.to_string(), span)),
+    })
+}
+
+/// Moves `expr` out, leaving a placeholder that is about to be dropped.
+fn take(expr: &mut Expr) -> Expr {
+    std::mem::replace(expr, int_lit(0, Span::call_site()))
+}
@@ -1,33 +1,33 @@
+use std::fmt;
+
+use crate::codegen::config;
 use crate::expr::Expr;
 use crate::kw;
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
+use syn::punctuated::Punctuated;
 use syn::{parenthesized, Token};
 use syn::{parse::Parse, parse::ParseStream};
 
 pub fn parse_stm_list(input: ParseStream) -> syn::Result<Vec<Stmatment>> {
-    let res = if input.is_empty() {
-        Vec::new()
-    } else {
-
-        let stm = input.parse()?;
-
-        let mut res = Vec::new();
-        res.push(stm);
-        res.append(&mut parse_stm_more(input)?);
-        res
-    };
-
+    // a loop rather than parse_stm_more, so long lists need no stack
+    let mut res = Vec::new();
+    while !input.is_empty() {
+        res.push(input.parse()?);
+        if !input.peek(Token![;]) {
+            break;
+        }
+        let _semi = input.parse::<Token![;]>()?;
+    }
     Ok(res)
-
 }
 
 pub fn parse_stm_more(input: ParseStream) -> syn::Result<Vec<Stmatment>> {
     if input.peek(Token![;]) {
         let _semi = input.parse::<Token![;]>()?;
-        return parse_stm_list(input);
-    }else {
-        return Ok(Vec::new());
+        parse_stm_list(input)
+    } else {
+        Ok(Vec::new())
     }
 }
 
@@ -53,6 +53,75 @@ impl ToTokens for Stmatment {
     }
 }
 
+impl Stmatment {
+    /// Span of the first token of the statement.
+    pub fn span(&self) -> Span {
+        match self {
+            Stmatment::Expr(expr) => expr.span(),
+            Stmatment::If(if_stm) => if_stm.if_token.span,
+            Stmatment::While(while_stm) => while_stm.while_token.span,
+            Stmatment::Write(write_stm) => write_stm.span,
+            Stmatment::Read(read_stm) => read_stm.read_token.span,
+        }
+    }
+}
+
+impl fmt::Display for Stmatment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stmatment::Expr(expr) => write!(f, "{}", expr),
+            Stmatment::If(if_stm) => {
+                write!(f, "if {} then ", if_stm.condition)?;
+                fmt_stm_list(f, &if_stm.body)?;
+                if let Some(else_body) = &if_stm.else_body {
+                    write!(f, " else ")?;
+                    fmt_stm_list(f, else_body)?;
+                }
+                write!(f, " fi")
+            }
+            Stmatment::While(while_stm) => {
+                write!(f, "while {} do ", while_stm.condition)?;
+                fmt_stm_list(f, &while_stm.body)?;
+                write!(f, " endwh")
+            }
+            Stmatment::Write(write_stm) => {
+                write!(f, "{}", if write_stm.newline { "writeln" } else { "write" })?;
+                if !write_stm.args.is_empty() {
+                    write!(f, "(")?;
+                    for (i, arg) in write_stm.args.iter().enumerate() {
+                        if i != 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}", arg)?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Stmatment::Read(read_stm) => {
+                write!(f, "read(")?;
+                for (i, param) in read_stm.params.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn fmt_stm_list(f: &mut fmt::Formatter<'_>, stms: &[Stmatment]) -> fmt::Result {
+    for (i, stm) in stms.iter().enumerate() {
+        if i != 0 {
+            write!(f, "; ")?;
+        }
+        write!(f, "{}", stm)?;
+    }
+    Ok(())
+}
+
 pub fn is_peek_kw(input: ParseStream) -> bool {
     let mut res = false;
     res = res
@@ -75,7 +144,8 @@ pub fn is_peek_kw(input: ParseStream) -> bool {
         || input.peek(kw::array)
         || input.peek(kw::of)
         || input.peek(kw::read)
-        || input.peek(kw::write);
+        || input.peek(kw::write)
+        || input.peek(kw::writeln);
 
     res
 }
@@ -96,7 +166,7 @@ impl Parse for Stmatment {
 
         let res = if lookahead.peek(kw::read) {
             Stmatment::Read(ReadStm::parse(input)?)
-        } else if lookahead.peek(kw::write) {
+        } else if lookahead.peek(kw::write) || lookahead.peek(kw::writeln) {
             Stmatment::Write(WriteStm::parse(input)?)
         } else if lookahead.peek(kw::r#if) {
             Stmatment::If(IfStm::parse(input)?)
@@ -138,6 +208,7 @@ impl Parse for Stmatment {
 
 #[derive(Debug)]
 pub struct IfStm {
+    pub if_token: kw::r#if,
     pub condition: Expr,
     pub body: Vec<Stmatment>,
     pub else_body: Option<Vec<Stmatment>>,
@@ -171,7 +242,7 @@ impl ToTokens for IfStm {
 
 impl Parse for IfStm {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        input.parse::<kw::r#if>()?;
+        let if_token = input.parse::<kw::r#if>()?;
         let condition = input.parse()?;
         input.parse::<kw::then>()?;
         let body = parse_stm_list(input)?;
@@ -185,6 +256,7 @@ impl Parse for IfStm {
         input.parse::<kw::fi>()?;
 
         Ok(IfStm {
+            if_token,
             condition,
             body,
             else_body,
@@ -194,6 +266,7 @@ impl Parse for IfStm {
 
 #[derive(Debug)]
 pub struct WhileStm {
+    pub while_token: kw::r#while,
     pub condition: Expr,
     pub body: Vec<Stmatment>,
 }
@@ -213,63 +286,113 @@ impl ToTokens for WhileStm {
 
 impl Parse for WhileStm {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        input.parse::<kw::r#while>()?;
+        let while_token = input.parse::<kw::r#while>()?;
         let condition = input.parse()?;
         input.parse::<kw::r#do>()?;
         let body = parse_stm_list(input)?;
         input.parse::<kw::endwh>()?;
 
-        Ok(WhileStm { condition, body })
+        Ok(WhileStm {
+            while_token,
+            condition,
+            body,
+        })
     }
 }
 
+/// `write(args)` prints its arguments one after another, `writeln` also
+/// ends the line and may be written without arguments.
 #[derive(Debug)]
 pub struct WriteStm {
-    pub param: Expr,
+    /// Span of the `write` or `writeln` keyword.
+    pub span: Span,
+    pub args: Vec<Expr>,
+    pub newline: bool,
 }
 
 impl ToTokens for WriteStm {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        let param = self.param.to_token_stream();
-        tokens.extend(quote! {
-            println!("{:?}", #param);
-        });
+        let format = "{}".repeat(self.args.len());
+        let args = &self.args;
+        if self.newline {
+            tokens.extend(quote! {
+                println!(#format #(, #args)*);
+            });
+        } else {
+            // keep prompts visible before a following `read`
+            tokens.extend(quote! {
+                print!(#format #(, #args)*);
+                ::std::io::Write::flush(&mut ::std::io::stdout()).unwrap();
+            });
+        }
     }
 }
 
 impl Parse for WriteStm {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        input.parse::<kw::write>()?;
+        let span = input.span();
+        let newline = if input.peek(kw::writeln) {
+            input.parse::<kw::writeln>()?;
+            if !input.peek(syn::token::Paren) {
+                return Ok(WriteStm {
+                    span,
+                    args: Vec::new(),
+                    newline: true,
+                });
+            }
+            true
+        } else {
+            input.parse::<kw::write>()?;
+            false
+        };
         let content;
         parenthesized!(content in input);
-        let param = content.parse()?;
+        let args = Punctuated::<Expr, Token![,]>::parse_separated_nonempty(&content)?;
+        if !content.is_empty() {
+            return Err(content.error("expected `,` or `)`"));
+        }
 
-        Ok(WriteStm { param })
+        Ok(WriteStm {
+            span,
+            args: args.into_iter().collect(),
+            newline,
+        })
     }
 }
 
+/// `read(a, b)` reads whitespace separated values into its variables, the
+/// generated `read` reports bad input with the SNL line of the variable.
 #[derive(Debug)]
 pub struct ReadStm {
-    pub param: Expr,
+    pub read_token: kw::read,
+    pub params: Vec<Expr>,
 }
 
 impl ToTokens for ReadStm {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        let param = self.param.to_token_stream();
-        tokens.extend(quote! {
-            #param = read();
-        });
+        for param in &self.params {
+            let line = config().snl_line(param.span());
+            tokens.extend(quote! {
+                #param = ::snlc::runtime::read(#line);
+            });
+        }
     }
 }
 
 impl Parse for ReadStm {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        input.parse::<kw::read>()?;
+        let read_token = input.parse::<kw::read>()?;
         let content;
         parenthesized!(content in input);
-        let param = content.parse()?;
+        let params = Punctuated::<Expr, Token![,]>::parse_separated_nonempty(&content)?;
+        if !content.is_empty() {
+            return Err(content.error("expected `,` or `)`"));
+        }
 
-        Ok(ReadStm { param })
+        Ok(ReadStm {
+            read_token,
+            params: params.into_iter().collect(),
+        })
     }
 }
 
@@ -291,6 +414,5 @@ pub fn snlc_stm(input: TokenStream) -> TokenStream {
             return e.to_compile_error();
         }
     };
-    let res = stm.to_token_stream();
-    res
+    stm.to_token_stream()
 }
\ No newline at end of file
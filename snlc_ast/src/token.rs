@@ -36,7 +36,7 @@ impl Token {
         res
     }
 
-    pub fn from_str(src: &str) -> Vec<Self> {
+    pub fn tokenize(src: &str) -> Vec<Self> {
         let mut cursor = Cursor::new(src);
         let mut curr_pos: usize = 0;
         let mut tokens = Vec::new();
@@ -45,13 +45,17 @@ impl Token {
             if lex_token.kind == snlc_lexer::TokenKind::Eof {
                 break;
             }
-            // if is comment skip
-            match lex_token.kind {
-                snlc_lexer::TokenKind::Comment { terminated: _ } => {
-                    curr_pos += lex_token.len as usize;
-                    continue;
+            // if is comment, keep only its line breaks so positions in
+            // the generated code still match SNL lines
+            if let snlc_lexer::TokenKind::Comment { terminated: _ } = lex_token.kind {
+                let comment = &src[curr_pos..curr_pos + lex_token.len as usize];
+                let mut lexeme = "\n".repeat(comment.matches('\n').count());
+                if lexeme.is_empty() {
+                    lexeme.push(' ');
                 }
-                _ => {}
+                tokens.push(Token::new(TokenKind::Whitespace, curr_pos as u32, lexeme));
+                curr_pos += lex_token.len as usize;
+                continue;
             }
             // convert
             let lexeme = src[curr_pos..curr_pos + lex_token.len as usize].to_string();
@@ -67,8 +71,24 @@ impl Token {
         tokens
     }
 
+    /// Like `tokenize`, but keywords and identifiers are lowercased first so
+    /// `BEGIN`, `Begin` and `begin` all read as the keyword and `Count` and
+    /// `count` name the same thing. Only ASCII letters change case.
+    pub fn tokenize_ignore_case(src: &str) -> Vec<Self> {
+        let mut tokens = Token::tokenize(src);
+        for token in &mut tokens {
+            if token.kind == TokenKind::Ident || token.kind == TokenKind::Keyword {
+                token.lexeme.make_ascii_lowercase();
+                if is_keyword(&token.lexeme) {
+                    token.kind = TokenKind::Keyword;
+                }
+            }
+        }
+        tokens
+    }
+
     fn from_lex_tokenkind(lex_kind: snlc_lexer::TokenKind) -> TokenKind {
-        let res = match lex_kind {
+        match lex_kind {
             // if is a operator
             snlc_lexer::TokenKind::Plus => TokenKind::BinOp,
             snlc_lexer::TokenKind::Minus => TokenKind::BinOp,
@@ -95,35 +115,10 @@ impl Token {
             // if is a ident
             snlc_lexer::TokenKind::Ident => TokenKind::Ident,
             _ => TokenKind::Invisiable,
-        };
-        res
+        }
     }
 }
 
-fn is_keyword(lexeme: &str) -> bool {
-    let res = match lexeme {
-        "program" => true,
-        "begin" => true,
-        "end" => true,
-        "procedure" => true,
-        "return" => true,
-        "type" => true,
-        "var" => true,
-        "if" => true,
-        "then" => true,
-        "else" => true,
-        "fi" => true,
-        "while" => true,
-        "do" => true,
-        "endwh" => true,
-        "char" => true,
-        "integer" => true,
-        "record" => true,
-        "array" => true,
-        "of" => true,
-        "read" => true,
-        "write" => true,
-        _ => false,
-    };
-    res
-}
\ No newline at end of file
+pub fn is_keyword(lexeme: &str) -> bool {
+    crate::kw::KEYWORDS.contains(&lexeme)
+}
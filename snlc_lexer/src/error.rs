@@ -0,0 +1,119 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::unescape::{unescape_char, unescape_str, CharError};
+use crate::{tokensize, LiteralKind, Token, TokenKind};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LexErrorKind {
+    UnterminatedComment,
+    UnterminatedChar,
+    UnterminatedString,
+    UnexpectedChar(char),
+    InvalidChar(CharError),
+}
+
+/// A lexical error with the byte span it covers and the 1-based line and
+/// column it starts at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Range<usize>,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            LexErrorKind::UnterminatedComment => write!(
+                f,
+                "unterminated comment starting at {}:{}",
+                self.line, self.col
+            ),
+            LexErrorKind::UnterminatedChar => write!(
+                f,
+                "unterminated character literal starting at {}:{}",
+                self.line, self.col
+            ),
+            LexErrorKind::UnterminatedString => write!(
+                f,
+                "unterminated string literal starting at {}:{}",
+                self.line, self.col
+            ),
+            LexErrorKind::UnexpectedChar(c) => write!(f, "unexpected character {:?}", c),
+            LexErrorKind::InvalidChar(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// Collects every lexical error of `input`, in source order.
+pub fn lex_errors(input: &str) -> Vec<LexError> {
+    token_errors(input, &tokensize(input).collect::<Vec<_>>())
+}
+
+/// `lex_errors` for already lexed `tokens` of `input`.
+pub fn token_errors(input: &str, tokens: &[Token]) -> Vec<LexError> {
+    let mut errors = Vec::new();
+    let mut pos = 0;
+    let mut line = 1;
+    let mut col = 1;
+    for token in tokens {
+        let span = pos..pos + token.len as usize;
+        let mut error_span = span.clone();
+        let kind = match token.kind {
+            TokenKind::Comment { terminated: false } => Some(LexErrorKind::UnterminatedComment),
+            TokenKind::Literal {
+                kind: LiteralKind::Char { terminated: false },
+            } => Some(LexErrorKind::UnterminatedChar),
+            TokenKind::Literal {
+                kind: LiteralKind::Char { terminated: true },
+            } => match unescape_char(&input[span.clone()]) {
+                Ok(_) => None,
+                Err((err, range)) => {
+                    error_span = span.start + range.start..span.start + range.end;
+                    Some(LexErrorKind::InvalidChar(err))
+                }
+            },
+            TokenKind::Literal {
+                kind: LiteralKind::Str { terminated: false },
+            } => Some(LexErrorKind::UnterminatedString),
+            TokenKind::Literal {
+                kind: LiteralKind::Str { terminated: true },
+            } => match unescape_str(&input[span.clone()]) {
+                Ok(_) => None,
+                Err((err, range)) => {
+                    error_span = span.start + range.start..span.start + range.end;
+                    Some(LexErrorKind::InvalidChar(err))
+                }
+            },
+            TokenKind::Unknown => input[span.clone()]
+                .chars()
+                .next()
+                .map(LexErrorKind::UnexpectedChar),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            let col = col + input[span.start..error_span.start].chars().count();
+            errors.push(LexError {
+                kind,
+                span: error_span,
+                line,
+                col,
+            });
+        }
+
+        for c in input[span.clone()].chars() {
+            if c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+        pos = span.end;
+    }
+    errors
+}
@@ -0,0 +1,100 @@
+//! Re-lexing after an edit.
+//!
+//! Tokens carry no state between each other, the lexer restarts from
+//! scratch at every token boundary. So after an edit only the tokens around
+//! the damaged region change: lexing restarts a little before the edit and
+//! stops as soon as it reaches a boundary the old tokens share.
+
+use std::ops::Range;
+
+use crate::{Cursor, Token, TokenKind};
+
+/// How many characters past its end a token may look at while lexing (a
+/// closing quote peeks at the next one for a doubled quote).
+const LOOKAHEAD: usize = 1;
+
+/// Replacement of the bytes `range` of the old text with `text`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl Edit {
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> Edit {
+        Edit {
+            range,
+            text: text.into(),
+        }
+    }
+
+    /// The text after the edit.
+    pub fn apply(&self, old: &str) -> String {
+        let mut res = String::with_capacity(old.len() - self.range.len() + self.text.len());
+        res.push_str(&old[..self.range.start]);
+        res.push_str(&self.text);
+        res.push_str(&old[self.range.end..]);
+        res
+    }
+
+    /// Where `pos` of the old text ends up, `None` inside the replaced range.
+    pub fn map_offset(&self, pos: usize) -> Option<usize> {
+        if pos <= self.range.start {
+            Some(pos)
+        } else if pos >= self.range.end {
+            Some(pos - self.range.end + self.range.start + self.text.len())
+        } else {
+            None
+        }
+    }
+}
+
+/// Updates `tokens`, the tokens of the text before `edit`, to the tokens of
+/// `new_src`, the text after it. The result equals `tokensize(new_src)`.
+pub fn relex(tokens: &[Token], new_src: &str, edit: &Edit) -> Vec<Token> {
+    let mut starts = Vec::with_capacity(tokens.len() + 1);
+    let mut pos = 0;
+    for token in tokens {
+        starts.push(pos);
+        pos += token.len as usize;
+    }
+    starts.push(pos);
+
+    // the first token that touches the edit, backing up over tokens whose
+    // lookahead may have seen the edited text
+    let mut first = tokens
+        .iter()
+        .zip(&starts[1..])
+        .position(|(_, &end)| end >= edit.range.start)
+        .unwrap_or(tokens.len());
+    while first > 0 && new_src[starts[first]..edit.range.start].chars().count() < LOOKAHEAD {
+        first -= 1;
+    }
+
+    let restart = starts[first];
+    let damage_end = edit.range.start + edit.text.len();
+    let mut res = tokens[..first].to_vec();
+    let mut cursor = Cursor::new(&new_src[restart..]);
+    let mut pos = restart;
+    // index into the old tokens of the next boundary to sync with
+    let mut old = first;
+    loop {
+        if pos >= damage_end {
+            let old_pos = pos - damage_end + edit.range.end;
+            while old < tokens.len() && starts[old] < old_pos {
+                old += 1;
+            }
+            if old < tokens.len() && starts[old] == old_pos {
+                res.extend_from_slice(&tokens[old..]);
+                return res;
+            }
+        }
+
+        let token = cursor.advance_token();
+        if token.kind == TokenKind::Eof {
+            return res;
+        }
+        pos += token.len as usize;
+        res.push(token);
+    }
+}
@@ -1,14 +1,18 @@
 //! Low Level SNLC Lexer
 
 mod cursor;
+mod error;
+pub mod incremental;
+pub mod unescape;
 
 pub use crate::cursor::Cursor;
+pub use crate::error::{lex_errors, token_errors, LexError, LexErrorKind};
 
 use crate::cursor::EOF_CHAR;
 use crate::LiteralKind::*;
 use crate::TokenKind::*;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Token {
     pub kind: TokenKind,
     pub len: u32,
@@ -61,6 +65,7 @@ pub enum TokenKind {
 pub enum LiteralKind {
     Integer,
     Char { terminated: bool },
+    Str { terminated: bool },
 }
 
 pub fn tokensize(input: &str) -> impl Iterator<Item = Token> + '_ {
@@ -219,25 +224,42 @@ impl Cursor<'_> {
         Ident
     }
 
+    /// Eats a quoted literal up to its closing quote. One character
+    /// between the quotes makes a character literal, any other number a
+    /// string; the content is checked by `unescape`.
     fn char_or_string(&mut self) -> TokenKind {
-        match (self.first(), self.second()) {
-            (_, '\'') => {
-                // eat literal char
-                self.bump();
-                // eat single quoted
-                self.bump();
-                Literal {
-                    kind: Char { terminated: true },
+        let mut len = 0;
+        let terminated = loop {
+            match self.first() {
+                // a doubled quote, Pascal style
+                '\'' if self.second() == '\'' => {
+                    self.bump();
+                    self.bump();
                 }
-            }
-            (_, _) => {
-                // eat literal char
-                self.bump();
-                Literal {
-                    kind: Char { terminated: false },
+                '\'' => {
+                    self.bump();
+                    break true;
+                }
+                '\n' => break false,
+                _ if self.is_eof() => break false,
+                '\\' => {
+                    self.bump();
+                    if self.first() != '\n' {
+                        self.bump();
+                    }
+                }
+                _ => {
+                    self.bump();
                 }
             }
-        }
+            len += 1;
+        };
+        let kind = if len == 1 {
+            Char { terminated }
+        } else {
+            Str { terminated }
+        };
+        Literal { kind }
     }
 
     fn int_or_unknown(&mut self) -> TokenKind {
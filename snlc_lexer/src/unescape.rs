@@ -0,0 +1,106 @@
+//! Values of character and string literals.
+//!
+//! A literal holds its characters between single quotes, exactly one makes
+//! a character literal. A quote is written either doubled, Pascal style
+//! (`''''`, `'it''s'`), or escaped (`'\''`). The other escapes are `\n`,
+//! `\t`, `\r`, `\0` and `\\`.
+
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CharError {
+    /// `''` where a character is required.
+    Empty,
+    /// `'ab'` where a character is required.
+    MoreThanOneChar,
+    /// A backslash followed by something other than a known escape.
+    UnknownEscape(char),
+    /// A backslash right before the closing quote.
+    LoneSlash,
+}
+
+impl fmt::Display for CharError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CharError::Empty => write!(f, "empty character literal"),
+            CharError::MoreThanOneChar => {
+                write!(f, "character literal may only contain one character")
+            }
+            CharError::UnknownEscape(c) => {
+                write!(f, "unknown character escape `\\{}`", c.escape_default())
+            }
+            CharError::LoneSlash => write!(f, "escape at the end of a literal"),
+        }
+    }
+}
+
+/// Decodes the text of a terminated string literal, quotes included.
+/// Errors come with the range of the offending part within `lit`.
+pub fn unescape_str(lit: &str) -> Result<String, (CharError, Range<usize>)> {
+    let inner = &lit[1..lit.len() - 1];
+    let mut res = String::with_capacity(inner.len());
+    let mut chars = inner.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let c = match c {
+            '\'' => {
+                chars.next();
+                '\''
+            }
+            '\\' => match chars.next() {
+                None => return Err((CharError::LoneSlash, 1 + i..2 + i)),
+                Some((_, c)) => match c {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '0' => '\0',
+                    '\\' => '\\',
+                    '\'' => '\'',
+                    _ => {
+                        let range = 1 + i..2 + i + c.len_utf8();
+                        return Err((CharError::UnknownEscape(c), range));
+                    }
+                },
+            },
+            c => c,
+        };
+        res.push(c);
+    }
+    Ok(res)
+}
+
+/// Decodes the text of a terminated character literal, quotes included.
+/// Errors come with the range of the offending part within `lit`.
+pub fn unescape_char(lit: &str) -> Result<char, (CharError, Range<usize>)> {
+    let value = unescape_str(lit)?;
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        (None, _) => Err((CharError::Empty, 0..lit.len())),
+        (Some(_), Some(_)) => Err((CharError::MoreThanOneChar, 0..lit.len())),
+    }
+}
+
+/// Writes `c` as an SNL character literal, `unescape_char` reads it back.
+pub fn escape_char(c: char) -> String {
+    escape_str(c.encode_utf8(&mut [0; 4]))
+}
+
+/// Writes `s` as an SNL string literal, `unescape_str` reads it back.
+pub fn escape_str(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('\'');
+    for c in s.chars() {
+        match c {
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            '\r' => res.push_str("\\r"),
+            '\0' => res.push_str("\\0"),
+            '\\' => res.push_str("\\\\"),
+            '\'' => res.push_str("\\'"),
+            _ => res.push(c),
+        }
+    }
+    res.push('\'');
+    res
+}
@@ -0,0 +1,178 @@
+//! Compiling and running SNL from Rust, without the command line.
+//!
+//! `compile` checks a program once and returns an `Artifact` that runs in
+//! process on the interpreter or the VM, or becomes the source of a Rust
+//! binary. `run` compiles with the default options and interprets. Nothing
+//! here touches the file system or starts a process.
+
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use snlc_ast::codegen::{Config, IntWidth, OverflowPolicy};
+
+use crate::diagnostic::Diagnostic;
+use crate::frontend::{preprocess, preprocess_ignore_case};
+use crate::interp::{self, Limits, Program, RuntimeError};
+use crate::utils::gen_ir_file;
+use crate::vm::{self, exec::Stats, regalloc::Allocator};
+use crate::{ir, sema};
+
+/// The settings of `snlc` and `snlc run` on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Options {
+    pub int_width: IntWidth,
+    pub overflow: OverflowPolicy,
+    /// 1 folds constants in generated Rust code.
+    pub opt_level: u8,
+    pub case_insensitive: bool,
+    pub strict_init: bool,
+}
+
+impl Options {
+    pub fn sema(&self) -> sema::Options {
+        sema::Options {
+            int_width: self.int_width,
+            case_insensitive: self.case_insensitive,
+            strict_init: self.strict_init,
+        }
+    }
+
+    pub fn config(&self) -> Config {
+        Config {
+            int_width: self.int_width,
+            overflow: self.overflow,
+            opt_level: self.opt_level,
+            strict_init: self.strict_init,
+            ..Config::default()
+        }
+    }
+}
+
+/// Diagnostics about one source, which `render` points into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostics {
+    pub diagnostics: Vec<Diagnostic>,
+    source: String,
+}
+
+impl Diagnostics {
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    /// One `path:line:col: severity: message` line per diagnostic.
+    pub fn render(&self, path: &str) -> String {
+        self.diagnostics
+            .iter()
+            .map(|x| x.render(path, &self.source) + "\n")
+            .collect()
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render("<input>"))
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// A checked program.
+#[derive(Debug)]
+pub struct Artifact {
+    source: String,
+    options: Options,
+    program: Program,
+    /// Warnings of the compilation.
+    pub warnings: Diagnostics,
+}
+
+impl Artifact {
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Interprets the program.
+    pub fn run<R: BufRead, W: Write>(
+        &self,
+        stdin: R,
+        stdout: W,
+        limits: &Limits,
+    ) -> Result<(), RuntimeError> {
+        interp::run(&self.program, &self.options.config(), stdin, stdout, limits)
+    }
+
+    /// Runs the program on the VM, as `snlc test --backend=vm` does.
+    pub fn run_vm<R: BufRead, W: Write>(
+        &self,
+        stdin: R,
+        stdout: W,
+        limits: &Limits,
+    ) -> Result<Stats, RuntimeError> {
+        let mut code = vm::codegen::compile(&ir::lower(&self.program), Allocator::LinearScan);
+        vm::peephole::optimize(&mut code);
+        vm::exec::run(&code, &self.options.config(), stdin, stdout, limits)
+    }
+
+    /// The source of a Rust binary for the program, `path` names the SNL
+    /// file in its runtime errors.
+    pub fn rust_source(&self, path: &str) -> String {
+        let snl = if self.options.case_insensitive {
+            preprocess_ignore_case(&self.source)
+        } else {
+            preprocess(&self.source)
+        };
+        gen_ir_file(snl, path, &self.options.config())
+    }
+}
+
+/// Checks `source`, returning every error when there is one.
+pub fn compile(source: &str, options: &Options) -> Result<Artifact, Diagnostics> {
+    let diagnostics = |diagnostics| Diagnostics {
+        diagnostics,
+        source: source.to_string(),
+    };
+    let (program, warnings) = interp::compile(source, &options.sema()).map_err(diagnostics)?;
+    Ok(Artifact {
+        source: source.to_string(),
+        options: *options,
+        program,
+        warnings: diagnostics(warnings),
+    })
+}
+
+/// Why `run` failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Compile(Diagnostics),
+    Runtime(RuntimeError),
+}
+
+impl Error {
+    /// The error as the command line reports it for the file `path`.
+    pub fn render(&self, path: &str) -> String {
+        match self {
+            Error::Compile(diagnostics) => diagnostics.render(path),
+            Error::Runtime(err) => err.render(path) + "\n",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render("<input>"))
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Compiles `source` with the default options and interprets it.
+pub fn run<R: BufRead, W: Write>(
+    source: &str,
+    stdin: R,
+    stdout: W,
+    limits: &Limits,
+) -> Result<(), Error> {
+    let artifact = compile(source, &Options::default()).map_err(Error::Compile)?;
+    artifact.run(stdin, stdout, limits).map_err(Error::Runtime)
+}
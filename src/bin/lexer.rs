@@ -1,7 +1,7 @@
 use clap::{Arg, Command};
 use std::fs;
 
-use snlc_lexer::tokensize;
+use snlc_lexer::{lex_errors, tokensize};
 
 fn main() {
     let matches = Command::new("snlc lexer")
@@ -30,4 +30,15 @@ fn main() {
         .map(|token| format!("{:?}\n", token))
         .collect();
     println!("{res}");
+
+    let errors = lex_errors(&input_file);
+    for err in &errors {
+        eprintln!(
+            "Error: {}:{}:{}: {}",
+            input_file_path, err.line, err.col, err
+        );
+    }
+    if !errors.is_empty() {
+        std::process::exit(3);
+    }
 }
@@ -1,22 +1,3 @@
-use std::cell::RefCell;
-
-fn read<T>() -> T 
-where
-    T: std::str::FromStr,
-    T::Err: std::fmt::Debug,
-{
-    let mut buf = String::new();
-    std::io::stdin().read_line(&mut buf).unwrap();
-    let res = match buf.trim().parse() {
-        Ok(res) => res,
-        Err(e) => {
-            println!("{:?}: {:?}", "unmatch type", e);
-            std::process::exit(1);
-        }
-    };
-    res
-}
-
 fn main() {
 
     snlc_parse::snl!(
@@ -27,10 +8,10 @@ fn main() {
             r#begin
                 k:=1;
                 i:=num;
-                r#write(i);
+                r#writeln(i);
                 r#while k <= 10 r#do
                     k:=k+1;
-                    r#write(k)
+                    r#writeln(k)
                 r#endwh
             r#end
         r#begin
@@ -0,0 +1,11 @@
+use std::io;
+
+fn main() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let code = snlc::lsp::run(&mut stdin.lock(), &mut stdout.lock()).unwrap_or_else(|err| {
+        eprintln!("Error: {err}");
+        1
+    });
+    std::process::exit(code);
+}
@@ -0,0 +1,3 @@
+//! The CFG lives in `snlc_ast` so the `snl!` macro can optimize with it.
+
+pub use snlc_ast::cfg::*;
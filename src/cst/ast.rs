@@ -0,0 +1,394 @@
+//! Typed view over the concrete syntax tree.
+//!
+//! Each wrapper owns a `SyntaxNode` of a known kind and exposes its
+//! meaningful parts, skipping whitespace, comments and the helper
+//! nonterminals the LL(1) grammar needs for repetition (`IdMore`,
+//! `StmMore`, ...).
+
+use super::{SyntaxElement, SyntaxNode, SyntaxToken};
+
+fn nth(node: &SyntaxNode, i: usize) -> Option<SyntaxElement> {
+    node.significant_children().into_iter().nth(i)
+}
+
+fn nth_node(node: &SyntaxNode, i: usize) -> Option<SyntaxNode> {
+    match nth(node, i)? {
+        SyntaxElement::Node(node) => Some(node),
+        SyntaxElement::Token(_) => None,
+    }
+}
+
+fn nth_token(node: &SyntaxNode, i: usize) -> Option<SyntaxToken> {
+    match nth(node, i)? {
+        SyntaxElement::Token(token) => Some(token),
+        SyntaxElement::Node(_) => None,
+    }
+}
+
+/// Follows a right recursive list such as `ArgMore ::= "," Exp ArgMore | ε`,
+/// calling `f` on every non-empty link.
+fn walk_more(mut more: Option<SyntaxNode>, kind: &str, mut f: impl FnMut(&SyntaxNode)) {
+    while let Some(node) = more {
+        if nth(&node, 0).is_none() {
+            break;
+        }
+        f(&node);
+        more = node.child(kind);
+    }
+}
+
+macro_rules! ast_node {
+    ($name:ident, $kind:literal) => {
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct $name(SyntaxNode);
+
+        impl $name {
+            pub const KIND: &'static str = $kind;
+
+            pub fn cast(node: SyntaxNode) -> Option<$name> {
+                if node.kind() == $kind {
+                    Some($name(node))
+                } else {
+                    None
+                }
+            }
+
+            pub fn syntax(&self) -> &SyntaxNode {
+                &self.0
+            }
+        }
+    };
+}
+
+ast_node!(Program, "Program");
+ast_node!(VarPart, "VarPart");
+ast_node!(VarDec, "VarDec");
+ast_node!(ProcDec, "ProcDec");
+ast_node!(Param, "Param");
+ast_node!(StmList, "StmList");
+ast_node!(Stm, "Stm");
+ast_node!(Exp, "Exp");
+ast_node!(SimpleExp, "SimpleExp");
+ast_node!(Term, "Term");
+ast_node!(Factor, "Factor");
+
+impl Program {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        nth_token(&self.0, 1)
+    }
+
+    pub fn var_part(&self) -> Option<VarPart> {
+        self.0.child(VarPart::KIND).and_then(VarPart::cast)
+    }
+
+    pub fn procedures(&self) -> Vec<ProcDec> {
+        let mut res = Vec::new();
+        // ProcPart ::= ProcDec ProcPart | ε
+        walk_more(self.0.child("ProcPart"), "ProcPart", |node| {
+            res.extend(node.child(ProcDec::KIND).and_then(ProcDec::cast));
+        });
+        res
+    }
+
+    pub fn body(&self) -> Option<StmList> {
+        self.0.child(StmList::KIND).and_then(StmList::cast)
+    }
+}
+
+impl VarPart {
+    /// Whether the `var` section is actually present.
+    pub fn is_present(&self) -> bool {
+        nth(&self.0, 0).is_some()
+    }
+
+    pub fn var_decs(&self) -> Vec<VarDec> {
+        let mut res = Vec::new();
+        // VarDecList ::= VarDec ";" VarDecMore
+        // VarDecMore ::= VarDecList | ε
+        let mut list = self.0.child("VarDecList");
+        while let Some(node) = list {
+            res.extend(node.child(VarDec::KIND).and_then(VarDec::cast));
+            list = node.child("VarDecMore").and_then(|x| x.child("VarDecList"));
+        }
+        res
+    }
+}
+
+impl VarDec {
+    /// The `char` or `integer` keyword.
+    pub fn type_name(&self) -> Option<SyntaxToken> {
+        self.0.child("TypeName").and_then(|x| nth_token(&x, 0))
+    }
+
+    pub fn names(&self) -> Vec<SyntaxToken> {
+        ids(self.0.child("IdList"))
+    }
+}
+
+/// Names of an `IdList` node.
+fn ids(list: Option<SyntaxNode>) -> Vec<SyntaxToken> {
+    let Some(list) = list else {
+        return Vec::new();
+    };
+    let mut res = Vec::new();
+    res.extend(nth_token(&list, 0));
+    // IdMore ::= "," ID IdMore | ε
+    walk_more(list.child("IdMore"), "IdMore", |node| {
+        res.extend(nth_token(node, 1));
+    });
+    res
+}
+
+impl ProcDec {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        nth_token(&self.0, 1)
+    }
+
+    pub fn params(&self) -> Vec<Param> {
+        let Some(list) = self.0.child("ParamList") else {
+            return Vec::new();
+        };
+        let mut res = Vec::new();
+        res.extend(list.child(Param::KIND).and_then(Param::cast));
+        // ParamMore ::= "," Param ParamMore | ε
+        walk_more(list.child("ParamMore"), "ParamMore", |node| {
+            res.extend(node.child(Param::KIND).and_then(Param::cast));
+        });
+        res
+    }
+
+    pub fn var_part(&self) -> Option<VarPart> {
+        self.0.child(VarPart::KIND).and_then(VarPart::cast)
+    }
+
+    pub fn body(&self) -> Option<StmList> {
+        self.0.child(StmList::KIND).and_then(StmList::cast)
+    }
+}
+
+impl Param {
+    pub fn type_name(&self) -> Option<SyntaxToken> {
+        self.0.child("TypeName").and_then(|x| nth_token(&x, 0))
+    }
+
+    pub fn name(&self) -> Option<SyntaxToken> {
+        nth_token(&self.0, 1)
+    }
+}
+
+impl StmList {
+    pub fn stms(&self) -> Vec<Stm> {
+        let mut res = Vec::new();
+        // StmList ::= Stm StmMore
+        // StmMore ::= ";" StmList | ε
+        let mut list = Some(self.0.clone());
+        while let Some(node) = list {
+            res.extend(node.child(Stm::KIND).and_then(Stm::cast));
+            list = node.child("StmMore").and_then(|x| x.child(StmList::KIND));
+        }
+        res
+    }
+}
+
+/// The statement forms of `Stm`.
+pub enum StmKind {
+    If {
+        condition: Exp,
+        body: StmList,
+        else_body: Option<StmList>,
+    },
+    While {
+        condition: Exp,
+        body: StmList,
+    },
+    Read {
+        targets: Vec<SyntaxToken>,
+    },
+    /// `write`, or `writeln` with `newline`.
+    Write {
+        args: Vec<Exp>,
+        newline: bool,
+    },
+    Assign {
+        target: SyntaxToken,
+        index: Option<Exp>,
+        value: Exp,
+    },
+    Call {
+        name: SyntaxToken,
+        args: Vec<Exp>,
+    },
+}
+
+fn exp_at(node: &SyntaxNode, i: usize) -> Option<Exp> {
+    nth_node(node, i).and_then(Exp::cast)
+}
+
+fn stm_list_at(node: &SyntaxNode, i: usize) -> Option<StmList> {
+    nth_node(node, i).and_then(StmList::cast)
+}
+
+/// Arguments of an `ArgList` or `WriteList` node.
+fn args(list: Option<SyntaxNode>) -> Vec<Exp> {
+    let Some(list) = list else {
+        return Vec::new();
+    };
+    let mut res = Vec::new();
+    res.extend(exp_at(&list, 0));
+    // ArgMore ::= "," Exp ArgMore | ε
+    walk_more(list.child("ArgMore"), "ArgMore", |node| {
+        res.extend(exp_at(node, 1));
+    });
+    res
+}
+
+impl Stm {
+    /// The first token of the statement, the keyword or target name.
+    pub fn first_token(&self) -> Option<SyntaxToken> {
+        nth_token(&self.0, 0)
+    }
+
+    pub fn kind(&self) -> Option<StmKind> {
+        let first = nth_token(&self.0, 0)?;
+        let res = match first.text() {
+            "if" if first.is_keyword() => StmKind::If {
+                condition: exp_at(&self.0, 1)?,
+                body: stm_list_at(&self.0, 3)?,
+                // ElsePart ::= "else" StmList | ε
+                else_body: nth_node(&self.0, 4).and_then(|x| stm_list_at(&x, 1)),
+            },
+            "while" if first.is_keyword() => StmKind::While {
+                condition: exp_at(&self.0, 1)?,
+                body: stm_list_at(&self.0, 3)?,
+            },
+            "read" if first.is_keyword() => StmKind::Read {
+                targets: ids(nth_node(&self.0, 2)),
+            },
+            "write" if first.is_keyword() => StmKind::Write {
+                args: args(nth_node(&self.0, 2)),
+                newline: false,
+            },
+            // WriteArgs ::= "(" WriteList ")" | ε
+            "writeln" if first.is_keyword() => StmKind::Write {
+                args: args(self.0.child("WriteArgs").and_then(|x| nth_node(&x, 1))),
+                newline: true,
+            },
+            _ => {
+                // AssCall ::= ":=" Exp | "[" Exp "]" ":=" Exp | "(" ArgList ")"
+                let ass_call = self.0.child("AssCall")?;
+                match nth_token(&ass_call, 0)?.text() {
+                    ":=" => StmKind::Assign {
+                        target: first,
+                        index: None,
+                        value: exp_at(&ass_call, 1)?,
+                    },
+                    "[" => StmKind::Assign {
+                        target: first,
+                        index: exp_at(&ass_call, 1),
+                        value: exp_at(&ass_call, 4)?,
+                    },
+                    _ => StmKind::Call {
+                        name: first,
+                        args: args(ass_call.child("ArgList")),
+                    },
+                }
+            }
+        };
+        Some(res)
+    }
+}
+
+/// Operator token of a `CmpOp`, `AddOp` or `MultOp` node.
+fn op_token(node: Option<SyntaxNode>) -> Option<SyntaxToken> {
+    node.and_then(|x| nth_token(&x, 0))
+}
+
+impl Exp {
+    pub fn lhs(&self) -> Option<SimpleExp> {
+        self.0.child(SimpleExp::KIND).and_then(SimpleExp::cast)
+    }
+
+    /// The comparison operator and right hand side, if any.
+    pub fn cmp(&self) -> Option<(SyntaxToken, SimpleExp)> {
+        // CmpTail ::= CmpOp SimpleExp | ε
+        let tail = self.0.child("CmpTail")?;
+        let op = op_token(tail.child("CmpOp"))?;
+        let rhs = tail.child(SimpleExp::KIND).and_then(SimpleExp::cast)?;
+        Some((op, rhs))
+    }
+}
+
+impl SimpleExp {
+    pub fn first(&self) -> Option<Term> {
+        self.0.child(Term::KIND).and_then(Term::cast)
+    }
+
+    /// The `+`/`-` operators and their right operands, left to right.
+    pub fn rest(&self) -> Vec<(SyntaxToken, Term)> {
+        let mut res = Vec::new();
+        // ExpMore ::= AddOp Term ExpMore | ε
+        walk_more(self.0.child("ExpMore"), "ExpMore", |node| {
+            let op = op_token(node.child("AddOp"));
+            let term = node.child(Term::KIND).and_then(Term::cast);
+            if let (Some(op), Some(term)) = (op, term) {
+                res.push((op, term));
+            }
+        });
+        res
+    }
+}
+
+impl Term {
+    pub fn first(&self) -> Option<Factor> {
+        self.0.child(Factor::KIND).and_then(Factor::cast)
+    }
+
+    /// The `*`/`/` operators and their right operands, left to right.
+    pub fn rest(&self) -> Vec<(SyntaxToken, Factor)> {
+        let mut res = Vec::new();
+        // TermMore ::= MultOp Factor TermMore | ε
+        walk_more(self.0.child("TermMore"), "TermMore", |node| {
+            let op = op_token(node.child("MultOp"));
+            let factor = node.child(Factor::KIND).and_then(Factor::cast);
+            if let (Some(op), Some(factor)) = (op, factor) {
+                res.push((op, factor));
+            }
+        });
+        res
+    }
+}
+
+/// The operand forms of `Factor`.
+pub enum FactorKind {
+    Paren(Exp),
+    Int(SyntaxToken),
+    Char(SyntaxToken),
+    Str(SyntaxToken),
+    Var(SyntaxToken),
+    Index(SyntaxToken, Exp),
+    Call(SyntaxToken, Vec<Exp>),
+}
+
+impl Factor {
+    pub fn kind(&self) -> Option<FactorKind> {
+        let first = nth_token(&self.0, 0)?;
+        let res = match crate::ll1::terminal_of(first.kind(), first.text()).as_str() {
+            "(" => FactorKind::Paren(exp_at(&self.0, 1)?),
+            "INTC" => FactorKind::Int(first),
+            "CHARC" => FactorKind::Char(first),
+            "STRC" => FactorKind::Str(first),
+            _ => {
+                // VarRest ::= "[" Exp "]" | "(" ArgList ")" | ε
+                let rest = self.0.child("VarRest");
+                match rest.as_ref().and_then(|x| nth_token(x, 0)) {
+                    Some(open) if open.text() == "[" => {
+                        FactorKind::Index(first, exp_at(rest.as_ref()?, 1)?)
+                    }
+                    Some(_) => FactorKind::Call(first, args(rest?.child("ArgList"))),
+                    None => FactorKind::Var(first),
+                }
+            }
+        };
+        Some(res)
+    }
+}
@@ -0,0 +1,394 @@
+//! Lossless concrete syntax tree.
+//!
+//! The green tree is immutable and position independent, it stores every
+//! token of the source including whitespace and comments, so printing it
+//! gives back the input byte for byte. The red tree (`SyntaxNode`,
+//! `SyntaxToken`) wraps green nodes with parent pointers and absolute
+//! offsets. Typed accessors live in `ast`.
+//!
+//! Lists are right recursive in the grammar, so a tree is about as deep as
+//! its longest statement list is long. Everything here that goes through a
+//! whole tree, dropping it included, uses an explicit stack.
+
+pub mod ast;
+
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use snlc_ast::token::is_keyword;
+use snlc_lexer::{LiteralKind, TokenKind};
+
+use crate::ll1::{Ll1Error, Ll1Parser};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct GreenToken {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct GreenNode {
+    /// Name of the grammar nonterminal this node was derived from.
+    pub kind: Rc<str>,
+    pub len: usize,
+    pub children: Vec<GreenElement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl GreenNode {
+    pub fn new(kind: Rc<str>, children: Vec<GreenElement>) -> GreenNode {
+        let len = children.iter().map(GreenElement::len).sum();
+        GreenNode {
+            kind,
+            len,
+            children,
+        }
+    }
+}
+
+impl fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut stack = self.children.iter().rev().collect::<Vec<_>>();
+        while let Some(child) = stack.pop() {
+            match child {
+                GreenElement::Node(node) => stack.extend(node.children.iter().rev()),
+                GreenElement::Token(token) => write!(f, "{}", token.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for GreenNode {
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(child) = stack.pop() {
+            if let GreenElement::Node(node) = child {
+                // nodes shared with another tree are dropped with that one
+                if let Ok(mut node) = Rc::try_unwrap(node) {
+                    stack.append(&mut node.children);
+                }
+            }
+        }
+    }
+}
+
+/// Whitespace and comments, which the parser skips over.
+pub fn is_trivia(kind: TokenKind) -> bool {
+    matches!(kind, TokenKind::Whitespace | TokenKind::Comment { .. })
+}
+
+/// Short name of a token kind, used when dumping trees.
+pub fn token_kind_name(kind: TokenKind, text: &str) -> &'static str {
+    match kind {
+        TokenKind::Comment { .. } => "Comment",
+        TokenKind::Whitespace => "Whitespace",
+        TokenKind::Ident if is_keyword(text) => "Keyword",
+        TokenKind::Ident => "Ident",
+        TokenKind::Literal {
+            kind: LiteralKind::Integer,
+        } => "IntLit",
+        TokenKind::Literal {
+            kind: LiteralKind::Str { .. },
+        } => "StrLit",
+        TokenKind::Literal { .. } => "CharLit",
+        TokenKind::Unknown => "Unknown",
+        _ => "Punct",
+    }
+}
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    offset: usize,
+}
+
+impl Drop for NodeData {
+    fn drop(&mut self) {
+        let mut parent = self.parent.take();
+        while let Some(node) = parent {
+            match Rc::try_unwrap(node.0) {
+                Ok(mut data) => parent = data.parent.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// A green node positioned within the file.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+/// A green token positioned within the file.
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    parent: SyntaxNode,
+    offset: usize,
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Rc<GreenNode>) -> SyntaxNode {
+        SyntaxNode(Rc::new(NodeData {
+            green,
+            parent: None,
+            offset: 0,
+        }))
+    }
+
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn kind(&self) -> &str {
+        &self.0.green.kind
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.len
+    }
+
+    pub fn text(&self) -> String {
+        self.0.green.to_string()
+    }
+
+    /// Range of the node without its leading and trailing trivia.
+    pub fn trimmed_range(&self) -> Range<usize> {
+        let tokens = self.descendant_tokens();
+        let first = tokens.iter().find(|x| !x.is_trivia());
+        let last = tokens.iter().rev().find(|x| !x.is_trivia());
+        match (first, last) {
+            (Some(first), Some(last)) => first.text_range().start..last.text_range().end,
+            _ => self.text_range(),
+        }
+    }
+
+    pub fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        let mut res = Vec::with_capacity(self.0.green.children.len());
+        for child in &self.0.green.children {
+            res.push(match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                    green: green.clone(),
+                    parent: Some(self.clone()),
+                    offset,
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green: green.clone(),
+                    parent: self.clone(),
+                    offset,
+                }),
+            });
+            offset += child.len();
+        }
+        res
+    }
+
+    /// Children without whitespace and comments.
+    pub fn significant_children(&self) -> Vec<SyntaxElement> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter(|x| match x {
+                SyntaxElement::Token(token) => !token.is_trivia(),
+                SyntaxElement::Node(_) => true,
+            })
+            .collect()
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|x| match x {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+    }
+
+    /// First child node of the given kind.
+    pub fn child(&self, kind: &str) -> Option<SyntaxNode> {
+        self.children().find(|x| x.kind() == kind)
+    }
+
+    /// First significant child token with the given text.
+    pub fn child_token(&self, text: &str) -> Option<SyntaxToken> {
+        self.significant_children()
+            .into_iter()
+            .find_map(|x| match x {
+                SyntaxElement::Token(token) if token.text() == text => Some(token),
+                _ => None,
+            })
+    }
+
+    /// The node itself and every node below it, in preorder.
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut res = Vec::new();
+        let mut stack = vec![self.clone()];
+        while let Some(node) = stack.pop() {
+            let mut children = node.children().collect::<Vec<_>>();
+            children.reverse();
+            stack.extend(children);
+            res.push(node);
+        }
+        res
+    }
+
+    /// Every token below the node, in source order.
+    pub fn descendant_tokens(&self) -> Vec<SyntaxToken> {
+        let mut res = Vec::new();
+        let mut stack = vec![SyntaxElement::Node(self.clone())];
+        while let Some(element) = stack.pop() {
+            match element {
+                SyntaxElement::Node(node) => {
+                    stack.extend(node.children_with_tokens().into_iter().rev())
+                }
+                SyntaxElement::Token(token) => res.push(token),
+            }
+        }
+        res
+    }
+
+    /// The token covering byte `offset`.
+    pub fn token_at_offset(&self, offset: usize) -> Option<SyntaxToken> {
+        self.descendant_tokens()
+            .into_iter()
+            .find(|x| x.text_range().contains(&offset))
+    }
+
+    /// The node and its ancestors, innermost first.
+    pub fn ancestors(&self) -> impl Iterator<Item = SyntaxNode> {
+        std::iter::successors(Some(self.clone()), SyntaxNode::parent)
+    }
+
+    fn fmt_tree(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut stack = vec![(SyntaxElement::Node(self.clone()), 0)];
+        while let Some((element, depth)) = stack.pop() {
+            match element {
+                SyntaxElement::Node(node) => {
+                    let range = node.text_range();
+                    writeln!(
+                        f,
+                        "{:indent$}{}@{}..{}",
+                        "",
+                        node.kind(),
+                        range.start,
+                        range.end,
+                        indent = depth * 2
+                    )?;
+                    let children = node.children_with_tokens().into_iter().rev();
+                    stack.extend(children.map(|x| (x, depth + 1)));
+                }
+                SyntaxElement::Token(token) => {
+                    writeln!(f, "{:indent$}{:?}", "", token, indent = depth * 2)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            self.fmt_tree(f)
+        } else {
+            let range = self.text_range();
+            write!(f, "{}@{}..{}", self.kind(), range.start, range.end)
+        }
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.green)
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> TokenKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text.len()
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        is_trivia(self.green.kind)
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        self.green.kind == TokenKind::Ident && is_keyword(&self.green.text)
+    }
+
+    pub fn is_ident(&self) -> bool {
+        self.green.kind == TokenKind::Ident && !is_keyword(&self.green.text)
+    }
+}
+
+impl PartialEq for SyntaxToken {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.green, &other.green) && self.offset == other.offset
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = self.text_range();
+        write!(
+            f,
+            "{}@{}..{} {:?}",
+            token_kind_name(self.green.kind, &self.green.text),
+            range.start,
+            range.end,
+            self.green.text
+        )
+    }
+}
+
+/// Parses `src` into a lossless syntax tree, `tree.to_string() == src`.
+pub fn parse(src: &str) -> Result<SyntaxNode, Ll1Error> {
+    Ll1Parser::snl().parse_green(src).map(SyntaxNode::new_root)
+}
@@ -0,0 +1,202 @@
+//! A line debugger for SNL programs, running them under the interpreter.
+//!
+//! The debugger is an `interp::Observer`: before every statement it decides
+//! whether to stop, and when it does it reads commands until one resumes
+//! the program. Stepping goes statement by statement, `next` and `finish`
+//! compare call depths so they run whole procedure calls. Procedures don't
+//! nest in SNL, so the scope enclosing a procedure's locals is the globals.
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use std::ops::ControlFlow;
+
+use crate::interp::{Observer, Program, State, Stm, Value};
+
+const HELP: &str = "\
+break N      stop before line N (b)
+delete N     remove the breakpoint at line N (d)
+continue     run to the next breakpoint (c)
+step         run one statement, entering calls (s)
+next         run one statement, running calls to their end (n)
+finish       run until the current procedure returns (f)
+print NAME   print a variable in scope (p)
+backtrace    print the procedure call stack (bt)
+quit         stop the program (q)
+help         print this help (h)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Step,
+    /// Stop at a depth of at most the one given.
+    Next(usize),
+    /// Stop at a depth below the one given.
+    Finish(usize),
+    Continue,
+}
+
+pub struct Debugger<'a, I, O> {
+    source: Vec<&'a str>,
+    /// Lines with a statement, the only ones a breakpoint can be at.
+    lines: BTreeSet<usize>,
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+    /// Line and depth of the statement last seen, several statements on
+    /// one line hit a breakpoint there once.
+    last: Option<(usize, usize)>,
+    commands: I,
+    output: O,
+}
+
+impl<'a, I: BufRead, O: Write> Debugger<'a, I, O> {
+    /// A debugger for `prog` compiled from `source`, reading commands from
+    /// `commands` and writing what it shows to `output`. It stops before
+    /// the first statement.
+    pub fn new(source: &'a str, prog: &Program, commands: I, output: O) -> Self {
+        let mut lines = BTreeSet::new();
+        for body in std::iter::once(&prog.body).chain(prog.procs.iter().map(|x| &x.body)) {
+            statement_lines(body, &mut lines);
+        }
+        Debugger {
+            source: source.lines().collect(),
+            lines,
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+            last: None,
+            commands,
+            output,
+        }
+    }
+
+    fn stops(&self, state: &State<'_>) -> bool {
+        let at = (state.line(), state.depth());
+        let mode = match self.mode {
+            Mode::Step => true,
+            Mode::Next(depth) => state.depth() <= depth,
+            Mode::Finish(depth) => state.depth() < depth,
+            Mode::Continue => false,
+        };
+        mode || (self.breakpoints.contains(&state.line()) && self.last != Some(at))
+    }
+
+    /// Runs one command.
+    fn command(&mut self, command: &str, state: &State<'_>) -> Outcome {
+        let mut words = command.split_whitespace();
+        let (name, arg) = (words.next().unwrap_or(""), words.next());
+        let line = arg.and_then(|x| x.parse::<usize>().ok());
+        let depth = state.depth();
+        let text = match (name, arg) {
+            ("", _) => return Outcome::Stay,
+            ("c" | "continue", None) => return Outcome::Resume(Mode::Continue),
+            ("s" | "step", None) => return Outcome::Resume(Mode::Step),
+            ("n" | "next", None) => return Outcome::Resume(Mode::Next(depth)),
+            ("f" | "finish", None) if depth == 0 => "not in a procedure".to_string(),
+            ("f" | "finish", None) => return Outcome::Resume(Mode::Finish(depth)),
+            ("q" | "quit", None) => return Outcome::Quit,
+            ("b" | "break", Some(_)) => match line {
+                Some(line) if self.lines.contains(&line) => {
+                    self.breakpoints.insert(line);
+                    format!("breakpoint at line {}", line)
+                }
+                _ => format!("no statement at line {}", arg.unwrap()),
+            },
+            ("d" | "delete", Some(_)) => match line {
+                Some(line) if self.breakpoints.remove(&line) => {
+                    format!("deleted the breakpoint at line {}", line)
+                }
+                _ => format!("no breakpoint at line {}", arg.unwrap()),
+            },
+            ("p" | "print", Some(var)) => match state.lookup(var) {
+                Some((_, value)) => format!("{} = {}", var, show(value)),
+                None => format!("no variable `{}` in scope", var),
+            },
+            ("bt" | "backtrace", None) => state
+                .backtrace()
+                .iter()
+                .enumerate()
+                .map(|(i, (name, line))| format!("#{} {} at line {}", i, name, line))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ("h" | "help", None) => HELP.to_string(),
+            _ => format!("bad command `{}`, try `help`", command.trim()),
+        };
+        let _ = writeln!(self.output, "{}", text);
+        Outcome::Stay
+    }
+}
+
+enum Outcome {
+    /// Read the next command.
+    Stay,
+    Resume(Mode),
+    Quit,
+}
+
+impl<I: BufRead, O: Write> Observer for Debugger<'_, I, O> {
+    fn statement(&mut self, state: &State<'_>) -> ControlFlow<()> {
+        let stops = self.stops(state);
+        self.last = Some((state.line(), state.depth()));
+        if !stops {
+            return ControlFlow::Continue(());
+        }
+        let text = self.source.get(state.line() - 1).map_or("", |x| x.trim());
+        let _ = writeln!(
+            self.output,
+            "{}:{}: {}",
+            state.function(),
+            state.line(),
+            text
+        );
+        loop {
+            let _ = self.output.flush();
+            let mut line = String::new();
+            match self.commands.read_line(&mut line) {
+                Ok(0) | Err(_) => return ControlFlow::Break(()),
+                Ok(_) => {}
+            }
+            match self.command(&line, state) {
+                Outcome::Stay => {}
+                Outcome::Resume(mode) => {
+                    self.mode = mode;
+                    return ControlFlow::Continue(());
+                }
+                Outcome::Quit => return ControlFlow::Break(()),
+            }
+        }
+    }
+}
+
+/// A variable's value as the debugger and the tracer show it.
+pub(crate) fn show(value: Option<Value>) -> String {
+    match value {
+        Some(Value::Char(c)) => format!("{:?}", c),
+        Some(value) => value.to_string(),
+        None => "<unassigned>".to_string(),
+    }
+}
+
+fn statement_lines(body: &[Stm], lines: &mut BTreeSet<usize>) {
+    for stm in body {
+        match stm {
+            Stm::Assign { line, .. }
+            | Stm::Call { line, .. }
+            | Stm::Read { line, .. }
+            | Stm::Write { line, .. } => {
+                lines.insert(*line);
+            }
+            Stm::If {
+                body,
+                else_body,
+                line,
+                ..
+            } => {
+                lines.insert(*line);
+                statement_lines(body, lines);
+                statement_lines(else_body, lines);
+            }
+            Stm::While { body, line, .. } => {
+                lines.insert(*line);
+                statement_lines(body, lines);
+            }
+        }
+    }
+}
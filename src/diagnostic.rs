@@ -0,0 +1,74 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::frontend::line_col;
+use snlc_lexer::LexError;
+
+use crate::ll1::Ll1Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A message about a byte range of the source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub range: Range<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(range: Range<usize>, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            range,
+            message,
+        }
+    }
+
+    pub fn warning(range: Range<usize>, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            range,
+            message,
+        }
+    }
+
+    /// Formats the diagnostic as `path:line:col: severity: message`.
+    pub fn render(&self, path: &str, src: &str) -> String {
+        let (line, col) = line_col(src, self.range.start);
+        format!(
+            "{}:{}:{}: {}: {}",
+            path, line, col, self.severity, self.message
+        )
+    }
+}
+
+impl From<Ll1Error> for Diagnostic {
+    fn from(err: Ll1Error) -> Diagnostic {
+        Diagnostic::error(err.pos..err.pos + 1, err.message)
+    }
+}
+
+impl From<LexError> for Diagnostic {
+    fn from(err: LexError) -> Diagnostic {
+        Diagnostic::error(err.span.clone(), err.to_string())
+    }
+}
+
+/// Whether any of `diagnostics` is an error.
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|x| x.severity == Severity::Error)
+}
@@ -0,0 +1,155 @@
+//! Line diffs in the unified format.
+
+use std::fmt::Write;
+
+/// Lines around a change kept in its hunk.
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// Shortest edit script from `a` to `b`, Myers' greedy algorithm.
+fn edits(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace = Vec::new();
+    'search: for d in 0..=max as isize {
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    // walk the trace back from the end
+    let mut res = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k =
+            if k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize]) {
+                k + 1
+            } else {
+                k - 1
+            };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            res.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            res.push(if x == prev_x {
+                Edit::Insert
+            } else {
+                Edit::Delete
+            });
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    res.reverse();
+    res
+}
+
+/// `diff -u` of `expected` against `actual`, empty when they are equal.
+pub fn unified_diff(
+    expected: &str,
+    actual: &str,
+    expected_name: &str,
+    actual_name: &str,
+) -> String {
+    if expected == actual {
+        return String::new();
+    }
+    let a = expected.split_inclusive('\n').collect::<Vec<_>>();
+    let b = actual.split_inclusive('\n').collect::<Vec<_>>();
+    let edits = edits(&a, &b);
+
+    let mut out = String::new();
+    writeln!(out, "--- {}", expected_name).unwrap();
+    writeln!(out, "+++ {}", actual_name).unwrap();
+
+    // positions in `a` and `b` before each edit
+    let mut pos = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for edit in &edits {
+        pos.push((i, j));
+        match edit {
+            Edit::Keep => (i, j) = (i + 1, j + 1),
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+    }
+    pos.push((i, j));
+
+    let changes = (0..edits.len())
+        .filter(|&x| edits[x] != Edit::Keep)
+        .collect::<Vec<_>>();
+    let mut c = 0;
+    while c < changes.len() {
+        // extend the hunk while the next change is close enough
+        let start = changes[c].saturating_sub(CONTEXT);
+        let mut last = changes[c];
+        while c + 1 < changes.len() && changes[c + 1] - last <= 2 * CONTEXT {
+            c += 1;
+            last = changes[c];
+        }
+        c += 1;
+        let end = (last + 1 + CONTEXT).min(edits.len());
+
+        let (a_start, b_start) = pos[start];
+        let (a_end, b_end) = pos[end];
+        writeln!(
+            out,
+            "@@ -{} +{} @@",
+            range(a_start, a_end - a_start),
+            range(b_start, b_end - b_start)
+        )
+        .unwrap();
+        for (edit, &(i, j)) in edits[start..end].iter().zip(&pos[start..end]) {
+            let (mark, line) = match edit {
+                Edit::Keep => (' ', a[i]),
+                Edit::Delete => ('-', a[i]),
+                Edit::Insert => ('+', b[j]),
+            };
+            out.push(mark);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
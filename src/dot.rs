@@ -0,0 +1,219 @@
+use std::fmt::Write;
+
+use snlc_ast::ast::{ParamDecl, Program, VarDef};
+use snlc_ast::expr::{Expr, Lit};
+use snlc_ast::stm::Stmatment;
+use snlc_lexer::unescape::{escape_char, escape_str};
+
+use crate::cfg::{Cfg, Terminator};
+
+/// Escapes `s` for use inside a double quoted DOT string.
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+struct AstDot {
+    out: String,
+    next_id: usize,
+}
+
+impl AstDot {
+    fn node(&mut self, label: &str, parent: Option<usize>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        writeln!(self.out, "    n{} [label=\"{}\"];", id, escape(label)).unwrap();
+        if let Some(parent) = parent {
+            writeln!(self.out, "    n{} -> n{};", parent, id).unwrap();
+        }
+        id
+    }
+
+    fn var_defs(&mut self, var_defs: &[VarDef], parent: usize) {
+        let var = self.node("var", Some(parent));
+        for var_def in var_defs {
+            let (ty, names) = match var_def {
+                VarDef::CharTyVar(def) => ("char", &def.names),
+                VarDef::IntTyVar(def) => ("integer", &def.names),
+            };
+            let names = names.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+            self.node(&format!("{} {}", ty, names.join(", ")), Some(var));
+        }
+    }
+
+    fn stm_list(&mut self, label: &str, stms: &[Stmatment], parent: usize) {
+        let list = self.node(label, Some(parent));
+        for stm in stms {
+            self.stm(stm, list);
+        }
+    }
+
+    fn stm(&mut self, stm: &Stmatment, parent: usize) {
+        match stm {
+            Stmatment::Expr(expr) => {
+                self.expr(expr, parent);
+            }
+            Stmatment::If(if_stm) => {
+                let node = self.node("if", Some(parent));
+                self.expr(&if_stm.condition, node);
+                self.stm_list("then", &if_stm.body, node);
+                if let Some(else_body) = &if_stm.else_body {
+                    self.stm_list("else", else_body, node);
+                }
+            }
+            Stmatment::While(while_stm) => {
+                let node = self.node("while", Some(parent));
+                self.expr(&while_stm.condition, node);
+                self.stm_list("do", &while_stm.body, node);
+            }
+            Stmatment::Write(write_stm) => {
+                let label = if write_stm.newline {
+                    "writeln"
+                } else {
+                    "write"
+                };
+                let node = self.node(label, Some(parent));
+                for arg in &write_stm.args {
+                    self.expr(arg, node);
+                }
+            }
+            Stmatment::Read(read_stm) => {
+                let node = self.node("read", Some(parent));
+                for param in &read_stm.params {
+                    self.expr(param, node);
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, parent: usize) {
+        match expr {
+            Expr::Assign(expr) => {
+                let node = self.node(":=", Some(parent));
+                self.expr(&expr.target, node);
+                self.expr(&expr.from, node);
+            }
+            Expr::Binary(expr) => {
+                let node = self.node(&expr.op.to_string(), Some(parent));
+                self.expr(&expr.lhs, node);
+                self.expr(&expr.rhs, node);
+            }
+            Expr::Var(expr) => {
+                self.node(&expr.ident.to_string(), Some(parent));
+            }
+            Expr::Lit(expr) => {
+                let label = match &expr.lit {
+                    Lit::Char(lit) => escape_char(lit.value()),
+                    Lit::Integer(lit) => lit.base10_digits().to_string(),
+                    Lit::Str(lit) => escape_str(&lit.value()),
+                };
+                self.node(&label, Some(parent));
+            }
+            Expr::Index(expr) => {
+                let node = self.node(&format!("{}[]", expr.ident), Some(parent));
+                self.expr(&expr.index, node);
+            }
+            Expr::Call(expr) => {
+                let node = self.node(&format!("call {}", expr.ident), Some(parent));
+                for arg in &expr.args {
+                    self.expr(arg, node);
+                }
+            }
+            Expr::Paren(expr) => {
+                let node = self.node("( )", Some(parent));
+                self.expr(&expr.expr, node);
+            }
+        }
+    }
+}
+
+/// Renders `prog` as a DOT tree.
+pub fn ast_to_dot(prog: &Program) -> String {
+    let mut dot = AstDot {
+        out: String::from("digraph ast {\n    node [shape=box, fontname=\"monospace\"];\n"),
+        next_id: 0,
+    };
+
+    let root = dot.node(&format!("program {}", prog.name), None);
+    if let Some(var_defs) = &prog.var_defs {
+        dot.var_defs(var_defs, root);
+    }
+    if let Some(procedure_defs) = &prog.procedure_defs {
+        for proc in procedure_defs {
+            let params = proc
+                .params
+                .iter()
+                .map(|param| match param {
+                    ParamDecl::CharTyParam(x) => format!("char {}", x.name),
+                    ParamDecl::IntTyParam(x) => format!("integer {}", x.name),
+                })
+                .collect::<Vec<_>>();
+            let node = dot.node(
+                &format!("procedure {}({})", proc.name, params.join(", ")),
+                Some(root),
+            );
+            if let Some(var_defs) = &proc.var_defs {
+                dot.var_defs(var_defs, node);
+            }
+            dot.stm_list("begin", &proc.body, node);
+        }
+    }
+    dot.stm_list("begin", &prog.body, root);
+
+    dot.out.push_str("}\n");
+    dot.out
+}
+
+/// Renders every CFG as a cluster of basic blocks with labeled edges.
+pub fn cfg_to_dot(cfgs: &[Cfg]) -> String {
+    let mut out = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+    for (i, cfg) in cfgs.iter().enumerate() {
+        writeln!(out, "    subgraph cluster_{} {{", i).unwrap();
+        writeln!(out, "        label=\"{}\";", escape(&cfg.name)).unwrap();
+        for block in &cfg.blocks {
+            let mut label = if block.id == cfg.entry {
+                format!("B{} (entry)\\l", block.id)
+            } else if block.id == cfg.exit {
+                format!("B{} (exit)\\l", block.id)
+            } else {
+                format!("B{}\\l", block.id)
+            };
+            for stm in &block.stms {
+                label.push_str(&escape(&stm.to_string()));
+                label.push_str("\\l");
+            }
+            if let Terminator::Branch { condition, .. } = block.terminator {
+                label.push_str(&escape(&format!("branch {}", condition)));
+                label.push_str("\\l");
+            }
+            writeln!(out, "        c{}_b{} [label=\"{}\"];", i, block.id, label).unwrap();
+        }
+        for block in &cfg.blocks {
+            for (succ, edge) in block.successors() {
+                if edge.is_empty() {
+                    writeln!(out, "        c{}_b{} -> c{}_b{};", i, block.id, i, succ).unwrap();
+                } else {
+                    writeln!(
+                        out,
+                        "        c{}_b{} -> c{}_b{} [label=\"{}\"];",
+                        i, block.id, i, succ, edge
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        out.push_str("    }\n");
+    }
+
+    out.push_str("}\n");
+    out
+}
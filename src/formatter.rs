@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use snlc_lexer::TokenKind;
+
+use crate::cst::{self, SyntaxElement, SyntaxNode, SyntaxToken};
+use crate::ll1::Ll1Error;
+
+/// Indentation of statements within `begin/end`, `while/endwh` and `if/fi`.
+const INDENT: &str = "  ";
+/// Continuation lines of a `var` section line up after `var `.
+const VAR_INDENT: &str = "    ";
+
+/// A comment together with where it sat in the source.
+struct Comment {
+    text: String,
+    /// Started on a line of its own rather than after code.
+    own_line: bool,
+    /// Preceded by an empty line.
+    blank_before: bool,
+}
+
+struct Printer {
+    out: String,
+    indent: Vec<&'static str>,
+    at_line_start: bool,
+    pending_space: bool,
+    /// Comments keyed by the offset of the token that follows them.
+    comments: HashMap<usize, Vec<Comment>>,
+    /// Offsets of tokens preceded by an empty line.
+    blank_before: HashMap<usize, bool>,
+}
+
+impl Printer {
+    fn new(root: &SyntaxNode) -> (Printer, Vec<Comment>) {
+        let mut comments = HashMap::new();
+        let mut blank_before = HashMap::new();
+        let mut pending = Vec::new();
+        let mut newlines = 0;
+        let mut seen_code = false;
+        for token in root.descendant_tokens() {
+            match token.kind() {
+                TokenKind::Whitespace => newlines += token.text().matches('\n').count(),
+                TokenKind::Comment { .. } => {
+                    pending.push(Comment {
+                        text: token.text().to_string(),
+                        own_line: newlines > 0 || !seen_code,
+                        blank_before: newlines > 1,
+                    });
+                    newlines = 0;
+                }
+                _ => {
+                    let offset = token.text_range().start;
+                    comments.insert(offset, std::mem::take(&mut pending));
+                    blank_before.insert(offset, newlines > 1);
+                    newlines = 0;
+                    seen_code = true;
+                }
+            }
+        }
+
+        let printer = Printer {
+            out: String::new(),
+            indent: Vec::new(),
+            at_line_start: true,
+            pending_space: false,
+            comments,
+            blank_before,
+        };
+        (printer, pending)
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.at_line_start {
+            for indent in &self.indent {
+                self.out.push_str(indent);
+            }
+        } else if self.pending_space {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+        self.at_line_start = false;
+        self.pending_space = false;
+    }
+
+    fn space(&mut self) {
+        self.pending_space = true;
+    }
+
+    /// Ends the current line unless nothing was written to it yet.
+    fn line(&mut self) {
+        if !self.at_line_start {
+            self.out.push('\n');
+            self.at_line_start = true;
+        }
+        self.pending_space = false;
+    }
+
+    fn blank_line(&mut self) {
+        self.line();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn comment(&mut self, comment: &Comment) {
+        if comment.own_line {
+            if comment.blank_before {
+                self.blank_line();
+            }
+            self.line();
+            self.write(&comment.text);
+            self.line();
+        } else if self.at_line_start && self.out.ends_with('\n') {
+            // a trailing comment of the line just finished
+            self.out.pop();
+            self.out.push(' ');
+            self.out.push_str(&comment.text);
+            self.out.push('\n');
+        } else {
+            self.space();
+            self.write(&comment.text);
+            self.space();
+        }
+    }
+
+    fn token(&mut self, token: &SyntaxToken) {
+        let offset = token.text_range().start;
+        if self.at_line_start && self.blank_before.get(&offset) == Some(&true) {
+            self.blank_line();
+        }
+        if let Some(comments) = self.comments.remove(&offset) {
+            for comment in &comments {
+                self.comment(comment);
+            }
+        }
+        self.write(token.text());
+    }
+
+    fn push_indent(&mut self, indent: &'static str) {
+        self.indent.push(indent);
+    }
+
+    fn pop_indent(&mut self) {
+        self.indent.pop();
+    }
+
+    fn node(&mut self, root: &SyntaxNode) {
+        enum Work {
+            Node(SyntaxNode),
+            Token(SyntaxNode, SyntaxToken),
+            EndVarPart,
+        }
+
+        // statement lists nest once per statement, so no recursion
+        let mut work = vec![Work::Node(root.clone())];
+        while let Some(item) = work.pop() {
+            match item {
+                Work::Node(node) => {
+                    let children = node.significant_children();
+                    if children.is_empty() {
+                        continue;
+                    }
+                    if node.kind() == "VarPart" {
+                        work.push(Work::EndVarPart);
+                    }
+                    for child in children.into_iter().rev() {
+                        work.push(match child {
+                            SyntaxElement::Node(child) => Work::Node(child),
+                            SyntaxElement::Token(token) => Work::Token(node.clone(), token),
+                        });
+                    }
+                }
+                Work::Token(parent, token) => self.token_in(parent.kind(), &token),
+                Work::EndVarPart => {
+                    self.pop_indent();
+                    self.line();
+                }
+            }
+        }
+    }
+
+    /// Prints `token` with the spacing and line breaks its position asks for.
+    fn token_in(&mut self, parent: &str, token: &SyntaxToken) {
+        match (parent, token.text()) {
+            ("Program", "program") | ("ProcDec", "procedure") => {
+                self.line();
+                self.token(token);
+                self.space();
+            }
+            ("Program", "begin") | ("ProcDec", "begin") => {
+                self.line();
+                self.token(token);
+                self.push_indent(INDENT);
+                self.line();
+            }
+            ("Program", "end") | ("ProcDec", "end") => {
+                self.pop_indent();
+                self.line();
+                self.token(token);
+                if parent == "ProcDec" {
+                    self.line();
+                }
+            }
+            ("Program", ".") => {
+                self.token(token);
+                self.line();
+            }
+            ("Program", _) | ("ProcDec", ")") => {
+                self.token(token);
+                self.line();
+            }
+            ("VarPart", "var") => {
+                self.line();
+                self.token(token);
+                self.space();
+                self.push_indent(VAR_INDENT);
+            }
+            ("VarDecList", ";") | ("StmMore", ";") => {
+                self.token(token);
+                self.line();
+            }
+            ("IdMore", ",") | ("ParamMore", ",") | ("ArgMore", ",") => {
+                self.token(token);
+                self.space();
+            }
+            ("TypeName", _) => {
+                self.token(token);
+                self.space();
+            }
+            ("Stm", "if") | ("Stm", "while") => {
+                self.token(token);
+                self.space();
+            }
+            ("Stm", "then") | ("Stm", "do") => {
+                self.space();
+                self.token(token);
+                self.push_indent(INDENT);
+                self.line();
+            }
+            ("ElsePart", "else") => {
+                self.pop_indent();
+                self.line();
+                self.token(token);
+                self.push_indent(INDENT);
+                self.line();
+            }
+            ("Stm", "fi") | ("Stm", "endwh") => {
+                self.pop_indent();
+                self.line();
+                self.token(token);
+            }
+            ("AssCall", ":=") | ("CmpOp", _) | ("AddOp", _) | ("MultOp", _) => {
+                self.space();
+                self.token(token);
+                self.space();
+            }
+            _ => self.token(token),
+        }
+    }
+}
+
+/// Pretty prints SNL source. Formatting is idempotent and keeps every
+/// `{ }` comment.
+pub fn format_source(src: &str) -> Result<String, Ll1Error> {
+    let root = cst::parse(src)?;
+    let (mut printer, trailing) = Printer::new(&root);
+    printer.node(&root);
+    for comment in &trailing {
+        printer.comment(comment);
+    }
+    printer.line();
+    Ok(printer.out)
+}
@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use proc_macro2::{LexError, LineColumn, Span, TokenStream, TokenTree};
+use snlc_ast::ast::Program;
+use snlc_ast::token::{is_keyword, Token, TokenKind};
+use snlc_lexer::lex_errors;
+use snlc_lexer::unescape::{unescape_char, unescape_str};
+
+use crate::diagnostic::Diagnostic;
+
+/// Converts SNL source into text `syn` can tokenize, every keyword and every
+/// identifier that is a Rust keyword gets a `r#` prefix so it doesn't collide
+/// with Rust keywords, and character and string literals are rewritten with
+/// Rust escapes.
+pub fn preprocess(src: &str) -> String {
+    preprocess_tokens(Token::tokenize(src))
+}
+
+/// `preprocess` for the case insensitive mode.
+pub fn preprocess_ignore_case(src: &str) -> String {
+    preprocess_tokens(Token::tokenize_ignore_case(src))
+}
+
+fn preprocess_tokens(tokens: Vec<Token>) -> String {
+    Token::to_str(rewrite_tokens(tokens))
+}
+
+/// Rust keywords that are plain identifiers in SNL. `self`, `Self`, `super`
+/// and `crate` can't be raw identifiers, so they are left out.
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+fn rewrite_tokens(tokens: Vec<Token>) -> Vec<Token> {
+    // if a token is keyword, then add 'r#' before to its lexeme
+    tokens
+        .into_iter()
+        .map(|token| {
+            let mut lexeme = token.lexeme;
+            if token.kind == TokenKind::Keyword
+                || token.kind == TokenKind::Ident && RUST_KEYWORDS.contains(&lexeme.as_str())
+            {
+                lexeme.insert_str(0, "r#");
+            }
+            if token.kind == TokenKind::Literal && lexeme.starts_with('\'') {
+                if let Ok(c) = unescape_char(&lexeme) {
+                    lexeme = format!("{:?}", c);
+                } else if let Ok(s) = unescape_str(&lexeme) {
+                    lexeme = format!("{:?}", s);
+                }
+            }
+            Token::new(token.kind, token.pos, lexeme)
+        })
+        .collect::<Vec<Token>>()
+}
+
+/// Lowercases keywords written in any case, leaving everything else,
+/// identifiers included, byte for byte in place. Offsets into the result
+/// are offsets into `src`.
+pub fn fold_keywords(src: &str) -> String {
+    let mut res = String::with_capacity(src.len());
+    let mut pos = 0;
+    for token in snlc_lexer::tokensize(src) {
+        let text = &src[pos..pos + token.len as usize];
+        let lower = text.to_ascii_lowercase();
+        if token.kind == snlc_lexer::TokenKind::Ident && is_keyword(&lower) {
+            res.push_str(&lower);
+        } else {
+            res.push_str(text);
+        }
+        pos += token.len as usize;
+    }
+    res
+}
+
+/// Reports unterminated comments and literals and unknown
+/// characters, which must stop compilation before parsing.
+pub fn check_lexical(src: &str) -> Result<(), Vec<Diagnostic>> {
+    let errors = lex_errors(src);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.into_iter().map(Diagnostic::from).collect())
+    }
+}
+
+/// Parses SNL source into a `Program` outside of the `snl!` macro.
+pub fn parse_program(src: &str) -> syn::Result<Program> {
+    let stream = preprocess(src)
+        .parse::<TokenStream>()
+        .map_err(|e| syn::Error::new(Span::call_site(), e))?;
+    syn::parse2(stream)
+}
+
+/// Maps spans of a `Program` parsed from preprocessed text back to byte
+/// ranges of the SNL source.
+pub struct SourceMap {
+    text: String,
+    line_starts: Vec<usize>,
+    /// Start of every token in `text`, start in the source and length in
+    /// the source.
+    tokens: Vec<(usize, usize, usize)>,
+}
+
+impl SourceMap {
+    /// The map of the text `parse_program_mapped` parses for `src`.
+    pub fn new(src: &str, case_insensitive: bool) -> SourceMap {
+        let tokens = if case_insensitive {
+            Token::tokenize_ignore_case(src)
+        } else {
+            Token::tokenize(src)
+        };
+        let lens = tokens.iter().map(|x| x.lexeme.len()).collect::<Vec<_>>();
+        let mut text = String::new();
+        let mut map = Vec::new();
+        for (token, len) in rewrite_tokens(tokens).into_iter().zip(lens) {
+            map.push((text.len(), token.pos as usize, len));
+            text.push_str(&token.lexeme);
+        }
+        if map.is_empty() {
+            map.push((0, 0, 0));
+        }
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceMap {
+            text,
+            line_starts,
+            tokens: map,
+        }
+    }
+
+    /// Spans of the tokens of the mapped text by the source offset they
+    /// start at, for building a `Program` that `range` maps like a parsed
+    /// one. A `:=` or `<=` is two tokens here, the first one's span is kept.
+    pub fn spans(&self) -> Result<BTreeMap<usize, Span>, LexError> {
+        fn walk(stream: TokenStream, spans: &mut Vec<Span>) {
+            for tree in stream {
+                match tree {
+                    TokenTree::Group(group) => {
+                        spans.push(group.span_open());
+                        walk(group.stream(), spans);
+                        spans.push(group.span_close());
+                    }
+                    tree => spans.push(tree.span()),
+                }
+            }
+        }
+        let mut spans = Vec::new();
+        walk(self.text.parse()?, &mut spans);
+        let mut res = BTreeMap::new();
+        for span in spans {
+            res.entry(self.range(span).start).or_insert(span);
+        }
+        Ok(res)
+    }
+
+    /// Source range of `span`.
+    pub fn range(&self, span: Span) -> Range<usize> {
+        let start = self.offset(span.start());
+        let end = self.offset(span.end()).max(start);
+        let (token_start, pos, len) = self.token_at(start);
+        let start = pos + (start - token_start).min(len);
+        if end == 0 {
+            return start..start;
+        }
+        let (token_start, pos, len) = self.token_at(end - 1);
+        start..(pos + (end - token_start).min(len)).max(start)
+    }
+
+    /// Byte offset into `text` of a line and column in chars.
+    fn offset(&self, at: LineColumn) -> usize {
+        let Some(&line_start) = self.line_starts.get(at.line.saturating_sub(1)) else {
+            return self.text.len();
+        };
+        self.text[line_start..]
+            .char_indices()
+            .nth(at.column)
+            .map_or(self.text.len(), |(i, _)| line_start + i)
+    }
+
+    fn token_at(&self, offset: usize) -> (usize, usize, usize) {
+        let i = self.tokens.partition_point(|x| x.0 <= offset);
+        self.tokens[i.saturating_sub(1)]
+    }
+}
+
+/// `parse_program` that also returns where the parts of the program came
+/// from.
+pub fn parse_program_mapped(
+    src: &str,
+    case_insensitive: bool,
+) -> syn::Result<(Program, SourceMap)> {
+    let map = SourceMap::new(src, case_insensitive);
+    let stream = map
+        .text
+        .parse::<TokenStream>()
+        .map_err(|e| syn::Error::new(Span::call_site(), e))?;
+    let prog = syn::parse2(stream)?;
+    Ok((prog, map))
+}
+
+/// Converts a byte offset into 1-based line and column numbers.
+pub fn line_col(src: &str, pos: usize) -> (usize, usize) {
+    let before = &src[..pos.min(src.len())];
+    let line = before.matches('\n').count() + 1;
+    let col = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    (line, col)
+}
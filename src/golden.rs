@@ -0,0 +1,558 @@
+//! Golden-file tests for SNL programs.
+//!
+//! Every `x.snl` in a directory with an `x.out` or `x.err` next to it is a
+//! case: it runs with `x.in` as standard input (empty without one) and
+//! must print exactly `x.out` and, when `x.err` exists, exactly `x.err` to
+//! standard error. A case without `x.err` must also succeed. Compile
+//! errors count as standard error, rendered against the file name.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use snlc_ast::codegen::Config;
+
+use crate::diagnostic::{has_errors, Diagnostic};
+use crate::diff::unified_diff;
+use crate::frontend::{preprocess, preprocess_ignore_case};
+use crate::interp::{self, ErrorKind, Limits};
+use crate::ir;
+use crate::lint::add_lints;
+use crate::sema;
+use crate::utils::gen_ir_file;
+use crate::vm::{self, regalloc::Allocator};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// `interp::run` in process.
+    Interp,
+    /// The generated Rust program, built with cargo in a throwaway crate.
+    Rust,
+    /// The register machine, with linear scan allocation and peephole
+    /// rules.
+    Vm,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub backend: Backend,
+    pub sema: sema::Options,
+    pub config: Config,
+    pub timeout: Duration,
+    /// Limits of the interpreter and the VM besides `timeout`.
+    pub limits: Limits,
+}
+
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub name: String,
+    pub path: PathBuf,
+    pub input: String,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+}
+
+/// The cases of `dir`, sorted by name.
+pub fn find_cases(dir: &Path) -> io::Result<Vec<Case>> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|x| x.map(|x| x.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    let mut cases = Vec::new();
+    for path in paths {
+        if path.extension().and_then(|x| x.to_str()) != Some("snl") {
+            continue;
+        }
+        let read = |ext: &str| match fs::read_to_string(path.with_extension(ext)) {
+            Ok(text) => Ok(Some(text)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        };
+        let (stdout, stderr) = (read("out")?, read("err")?);
+        if stdout.is_none() && stderr.is_none() {
+            continue;
+        }
+        cases.push(Case {
+            name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+            input: read("in")?.unwrap_or_default(),
+            path,
+            stdout,
+            stderr,
+        });
+    }
+    Ok(cases)
+}
+
+/// What a program printed.
+#[derive(Debug, Clone, Default)]
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    pub success: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    Pass,
+    /// The output differs, with a report of how.
+    Fail(String),
+    TimedOut,
+    /// The case could not be run at all.
+    Error(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub name: String,
+    pub outcome: Outcome,
+    pub time: Duration,
+}
+
+/// Compares `output` with what `case` expects.
+pub fn judge(case: &Case, output: &Output) -> Outcome {
+    let mut report = String::new();
+    if let Some(expected) = &case.stdout {
+        report += &unified_diff(
+            expected,
+            &output.stdout,
+            &format!("{}.out (expected)", case.name),
+            "stdout (actual)",
+        );
+    }
+    match &case.stderr {
+        Some(expected) => {
+            report += &unified_diff(
+                expected,
+                &output.stderr,
+                &format!("{}.err (expected)", case.name),
+                "stderr (actual)",
+            );
+        }
+        None if !output.success => {
+            writeln!(report, "the program failed:").unwrap();
+            report += &output.stderr;
+        }
+        None => {}
+    }
+    if report.is_empty() {
+        Outcome::Pass
+    } else {
+        Outcome::Fail(report)
+    }
+}
+
+/// Checks `case`, the diagnostics are rendered to standard error.
+fn check(case: &Case, options: &Options) -> io::Result<(String, Result<String, Output>)> {
+    let src = fs::read_to_string(&case.path)?;
+    let mut diagnostics = match sema::analyze(&src, &options.sema) {
+        Ok(model) => model.diagnostics,
+        Err(diagnostics) => diagnostics,
+    };
+    add_lints(&src, &options.sema, &mut diagnostics);
+    let file_name = case.path.file_name().unwrap().to_string_lossy();
+    let stderr = render(&diagnostics, &file_name, &src);
+    if has_errors(&diagnostics) {
+        let output = Output {
+            stderr,
+            ..Output::default()
+        };
+        return Ok((src, Err(output)));
+    }
+    Ok((src, Ok(stderr)))
+}
+
+fn render(diagnostics: &[Diagnostic], path: &str, src: &str) -> String {
+    diagnostics
+        .iter()
+        .map(|x| x.render(path, src) + "\n")
+        .collect()
+}
+
+/// Runs a case in process, with the interpreter or the register machine.
+fn run_interp(case: &Case, options: &Options) -> (Outcome, Duration) {
+    let start = Instant::now();
+    let src = match fs::read_to_string(&case.path) {
+        Ok(src) => src,
+        Err(err) => return (Outcome::Error(err.to_string()), start.elapsed()),
+    };
+    let file_name = case.path.file_name().unwrap().to_string_lossy();
+    let output = match interp::compile(&src, &options.sema) {
+        Err(diagnostics) => Output {
+            stderr: render(&diagnostics, &file_name, &src),
+            ..Output::default()
+        },
+        Ok((prog, warnings)) => {
+            let mut stdout = Vec::new();
+            let limits = Limits {
+                time: Some(options.timeout),
+                ..options.limits.clone()
+            };
+            let res = match options.backend {
+                Backend::Vm => {
+                    let mut code = vm::codegen::compile(&ir::lower(&prog), Allocator::LinearScan);
+                    vm::peephole::optimize(&mut code);
+                    let input = case.input.as_bytes();
+                    vm::exec::run(&code, &options.config, input, &mut stdout, &limits).map(drop)
+                }
+                _ => interp::run(
+                    &prog,
+                    &options.config,
+                    case.input.as_bytes(),
+                    &mut stdout,
+                    &limits,
+                ),
+            };
+            let mut stderr = render(&warnings, &file_name, &src);
+            match &res {
+                Err(err) if matches!(err.kind, ErrorKind::Timeout(_)) => {
+                    return (Outcome::TimedOut, start.elapsed());
+                }
+                Err(err) => writeln!(stderr, "{}", err.render(&file_name)).unwrap(),
+                Ok(()) => {}
+            }
+            Output {
+                stdout: String::from_utf8_lossy(&stdout).into_owned(),
+                stderr,
+                success: res.is_ok(),
+            }
+        }
+    };
+    (judge(case, &output), start.elapsed())
+}
+
+/// Runs `cases`, printing a line per case to `log` as it finishes.
+pub fn run_cases(
+    cases: &[Case],
+    options: &Options,
+    log: &mut impl Write,
+) -> io::Result<Vec<CaseResult>> {
+    let mut results = Vec::new();
+    let mut built = match options.backend {
+        Backend::Interp | Backend::Vm => Vec::new(),
+        Backend::Rust => build_all(cases, options)?,
+    };
+    for (i, case) in cases.iter().enumerate() {
+        let (outcome, time) = match options.backend {
+            Backend::Interp | Backend::Vm => run_interp(case, options),
+            Backend::Rust => {
+                let start = Instant::now();
+                let outcome = match std::mem::replace(&mut built[i], Built::Taken) {
+                    Built::Exe(exe, warnings) => {
+                        let res = run_exe(&exe, &case.input, options.timeout);
+                        let _ = fs::remove_file(&exe);
+                        let _ = fs::remove_file(exe.with_extension("d"));
+                        match res {
+                            Ok(Some(mut output)) => {
+                                output.stderr.insert_str(0, &warnings);
+                                judge(case, &output)
+                            }
+                            Ok(None) => Outcome::TimedOut,
+                            Err(err) => Outcome::Error(err.to_string()),
+                        }
+                    }
+                    Built::Failed(output) => judge(case, &output),
+                    Built::Error(err) => Outcome::Error(err),
+                    Built::Taken => unreachable!(),
+                };
+                (outcome, start.elapsed())
+            }
+        };
+        let status = match outcome {
+            Outcome::Pass => "ok",
+            Outcome::Fail(_) => "FAILED",
+            Outcome::TimedOut => "timed out",
+            Outcome::Error(_) => "error",
+        };
+        writeln!(log, "test {} ... {}", case.name, status)?;
+        results.push(CaseResult {
+            name: case.name.clone(),
+            outcome,
+            time,
+        });
+    }
+    Ok(results)
+}
+
+enum Built {
+    /// The executable and the warnings compiling it printed.
+    Exe(PathBuf, String),
+    /// Sema rejected the program.
+    Failed(Output),
+    Error(String),
+    Taken,
+}
+
+/// Where the generated programs find `snlc` and `snlc_parse`: the
+/// `SNLC_SOURCE_DIR` environment variable, or the tree snlc was built
+/// from. Nothing is written there.
+fn source_dir() -> PathBuf {
+    std::env::var_os("SNLC_SOURCE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")))
+}
+
+/// Removes the files and directories in `dir` whose name starts with
+/// `prefix`, as far as possible.
+fn remove_prefixed(dir: &Path, prefix: &str) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(prefix) {
+            let path = entry.path();
+            let _ = fs::remove_file(&path).or_else(|_| fs::remove_dir_all(&path));
+        }
+    }
+}
+
+/// A directory that is removed when dropped.
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Generates a binary for every case into a throwaway crate in the
+/// temporary directory and builds them with one cargo invocation. The
+/// crate is removed afterwards. Builds share a target directory there, so
+/// the binaries get names no other run uses.
+fn build_all(cases: &[Case], options: &Options) -> io::Result<Vec<Built>> {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |x| x.as_nanos());
+    let run = format!("snlc_golden_{}_{}", std::process::id(), nanos);
+    let tmp = std::env::temp_dir();
+    let dir = TempDir(tmp.join(&run));
+    fs::create_dir_all(&dir.0)?;
+
+    let mut built = Vec::new();
+    let mut bins = Vec::new();
+    for (i, case) in cases.iter().enumerate() {
+        let (src, stderr) = match check(case, options) {
+            Ok((src, Ok(stderr))) => (src, stderr),
+            Ok((_, Err(output))) => {
+                built.push(Built::Failed(output));
+                continue;
+            }
+            Err(err) => {
+                built.push(Built::Error(err.to_string()));
+                continue;
+            }
+        };
+        let snl = if options.sema.case_insensitive {
+            preprocess_ignore_case(&src)
+        } else {
+            preprocess(&src)
+        };
+        let name = format!("{}_{}", run, i);
+        let file_name = case.path.file_name().unwrap().to_string_lossy();
+        let code = gen_ir_file(snl, &file_name, &options.config);
+        fs::write(dir.0.join(format!("{}.rs", name)), code)?;
+        bins.push((i, name));
+        built.push(Built::Exe(PathBuf::new(), stderr));
+    }
+    if bins.is_empty() {
+        return Ok(built);
+    }
+
+    let source = source_dir();
+    let mut manifest = format!(
+        "[package]\nname = {:?}\nversion = \"0.0.0\"\nedition = \"2021\"\n\n\
+         [dependencies]\nsnlc = {{ path = {:?} }}\nsnlc_parse = {{ path = {:?} }}\n\n\
+         [workspace]\n",
+        run,
+        source,
+        source.join("snlc_parse")
+    );
+    for (_, name) in &bins {
+        write!(
+            manifest,
+            "\n[[bin]]\nname = {:?}\npath = \"{}.rs\"\n",
+            name, name
+        )
+        .unwrap();
+    }
+    fs::write(dir.0.join("Cargo.toml"), manifest)?;
+    // build with the versions snlc itself was built with
+    if let Ok(lock) = fs::read(source.join("Cargo.lock")) {
+        fs::write(dir.0.join("Cargo.lock"), lock)?;
+    }
+
+    let mut cargo = Command::new(std::env::var("CARGO").unwrap_or("cargo".to_string()));
+    cargo
+        .current_dir(&dir.0)
+        .args(["build", "--quiet", "--keep-going", "--message-format=json"]);
+    let target_dir = match std::env::var_os("CARGO_TARGET_DIR") {
+        Some(target_dir) => dir.0.join(target_dir),
+        None => tmp.join("snlc_golden_target"),
+    };
+    cargo
+        .env("CARGO_TARGET_DIR", &target_dir)
+        .env("CARGO_INCREMENTAL", "0");
+    let res = cargo.stderr(Stdio::piped()).output();
+    // the executables stay until their case ran, everything else of the
+    // run goes now
+    for sub in ["deps", ".fingerprint"] {
+        remove_prefixed(&target_dir.join("debug").join(sub), &run);
+    }
+    let res = res?;
+
+    // cargo names every executable it produced and every error of the
+    // compiler in its json messages
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    let messages = stdout
+        .lines()
+        .filter_map(|x| serde_json::from_str::<Value>(x).ok())
+        .collect::<Vec<_>>();
+    for (i, name) in &bins {
+        let target = |x: &&Value| x["target"]["name"].as_str() == Some(name);
+        let exe = messages
+            .iter()
+            .filter(target)
+            .find_map(|x| x["executable"].as_str());
+        let mut errors = messages
+            .iter()
+            .filter(target)
+            .filter(|x| x["message"]["level"] == "error")
+            .filter_map(|x| x["message"]["rendered"].as_str())
+            .collect::<String>();
+        if errors.is_empty() {
+            // cargo failed before compiling the case, say why
+            errors = String::from_utf8_lossy(&res.stderr).into_owned();
+        }
+        built[*i] = match (exe, std::mem::replace(&mut built[*i], Built::Taken)) {
+            (Some(exe), Built::Exe(_, warnings)) => Built::Exe(PathBuf::from(exe), warnings),
+            _ => Built::Error(format!(
+                "building the generated program failed:\n{}",
+                errors
+            )),
+        };
+    }
+    Ok(built)
+}
+
+/// Runs `exe` on `input`, `None` when it takes longer than `timeout`.
+fn run_exe(exe: &Path, input: &str, timeout: Duration) -> io::Result<Option<Output>> {
+    let mut child = Command::new(exe)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // feed and drain the pipes on threads so a full pipe can't block us
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_string();
+    let writer = std::thread::spawn(move || {
+        let _ = stdin.write_all(input.as_bytes());
+    });
+    let drain = |mut pipe: Box<dyn Read + Send>| {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = pipe.read_to_end(&mut buf);
+            String::from_utf8_lossy(&buf).into_owned()
+        })
+    };
+    let stdout = drain(Box::new(child.stdout.take().unwrap()));
+    let stderr = drain(Box::new(child.stderr.take().unwrap()));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if Instant::now() > deadline {
+            child.kill()?;
+            child.wait()?;
+            break None;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    };
+    let _ = writer.join();
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    Ok(status.map(|status| Output {
+        stdout,
+        stderr,
+        success: status.success(),
+    }))
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            '\t' | '\n' | '\r' => res.push(c),
+            // not allowed in XML 1.0 at all
+            c if c < ' ' => {}
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// A JUnit XML report of `results`.
+pub fn junit(suite: &str, results: &[CaseResult]) -> String {
+    let failures = results
+        .iter()
+        .filter(|x| matches!(x.outcome, Outcome::Fail(_) | Outcome::TimedOut))
+        .count();
+    let errors = results
+        .iter()
+        .filter(|x| matches!(x.outcome, Outcome::Error(_)))
+        .count();
+    let time = results.iter().map(|x| x.time).sum::<Duration>();
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        out,
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">",
+        xml_escape(suite),
+        results.len(),
+        failures,
+        errors,
+        time.as_secs_f64()
+    )
+    .unwrap();
+    for result in results {
+        write!(
+            out,
+            "  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+            xml_escape(&result.name),
+            xml_escape(suite),
+            result.time.as_secs_f64()
+        )
+        .unwrap();
+        match &result.outcome {
+            Outcome::Pass => out.push_str("/>\n"),
+            Outcome::Fail(report) => writeln!(
+                out,
+                ">\n    <failure message=\"output differs\">{}</failure>\n  </testcase>",
+                xml_escape(report)
+            )
+            .unwrap(),
+            Outcome::TimedOut => {
+                out.push_str(">\n    <failure message=\"timed out\"/>\n  </testcase>\n")
+            }
+            Outcome::Error(err) => writeln!(
+                out,
+                ">\n    <error message=\"could not run\">{}</error>\n  </testcase>",
+                xml_escape(err)
+            )
+            .unwrap(),
+        }
+    }
+    out.push_str("</testsuite>\n");
+    out
+}
@@ -0,0 +1,676 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// The SNL grammar recognized by the table-driven parser.
+pub const SNL_GRAMMAR: &str = include_str!("../snl.bnf");
+
+/// End of input marker used in FOLLOW sets and the parse table.
+pub const EOF: &str = "$";
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Symbol {
+    Terminal(String),
+    NonTerminal(String),
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Symbol::Terminal(name) if name == EOF || is_token_class(name) => write!(f, "{}", name),
+            Symbol::Terminal(name) => write!(f, "\"{}\"", name),
+            Symbol::NonTerminal(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Production {
+    pub lhs: String,
+    pub rhs: Vec<Symbol>,
+}
+
+impl fmt::Display for Production {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ::=", self.lhs)?;
+        if self.rhs.is_empty() {
+            return write!(f, " ε");
+        }
+        for sym in &self.rhs {
+            write!(f, " {}", sym)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct GrammarError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn is_token_class(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+}
+
+/// A context free grammar, the first rule's left hand side is the start symbol.
+#[derive(Debug)]
+pub struct Grammar {
+    pub start: String,
+    pub nonterminals: Vec<String>,
+    pub productions: Vec<Production>,
+}
+
+impl Grammar {
+    /// Parses the BNF text format used by `snl.bnf`.
+    pub fn parse(text: &str) -> Result<Grammar, GrammarError> {
+        let mut nonterminals: Vec<String> = Vec::new();
+        let mut alternatives: Vec<(usize, String, String)> = Vec::new();
+        let mut curr_lhs: Option<String> = None;
+
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let rhs = if let Some(rest) = line.strip_prefix('|') {
+                match &curr_lhs {
+                    Some(_) => rest,
+                    None => {
+                        return Err(GrammarError {
+                            line: line_no,
+                            message: "alternative without a rule".to_string(),
+                        })
+                    }
+                }
+            } else {
+                let (lhs, rhs) = line.split_once("::=").ok_or_else(|| GrammarError {
+                    line: line_no,
+                    message: "expected `::=`".to_string(),
+                })?;
+                let lhs = lhs.trim();
+                if !snlc_lexer::is_ident(lhs) {
+                    return Err(GrammarError {
+                        line: line_no,
+                        message: format!("invalid nonterminal `{}`", lhs),
+                    });
+                }
+                if !nonterminals.iter().any(|x| x == lhs) {
+                    nonterminals.push(lhs.to_string());
+                }
+                curr_lhs = Some(lhs.to_string());
+                rhs
+            };
+
+            let lhs = curr_lhs.clone().unwrap();
+            for alt in split_alternatives(rhs) {
+                alternatives.push((line_no, lhs.clone(), alt));
+            }
+        }
+
+        let start = nonterminals.first().cloned().ok_or(GrammarError {
+            line: 0,
+            message: "grammar has no rules".to_string(),
+        })?;
+
+        let mut productions = Vec::new();
+        for (line, lhs, alt) in alternatives {
+            let rhs = parse_alternative(line, &alt, &nonterminals)?;
+            productions.push(Production { lhs, rhs });
+        }
+
+        Ok(Grammar {
+            start,
+            nonterminals,
+            productions,
+        })
+    }
+
+    /// The SNL grammar shipped with the compiler.
+    pub fn snl() -> Grammar {
+        Grammar::parse(SNL_GRAMMAR).expect("snl.bnf is well formed")
+    }
+
+    /// Productions of `nonterminal` along with their indices.
+    pub fn productions_of<'a>(
+        &'a self,
+        nonterminal: &'a str,
+    ) -> impl Iterator<Item = (usize, &'a Production)> + 'a {
+        self.productions
+            .iter()
+            .enumerate()
+            .filter(move |(_, prod)| prod.lhs == nonterminal)
+    }
+
+    pub fn terminals(&self) -> BTreeSet<String> {
+        let mut res = BTreeSet::new();
+        for prod in &self.productions {
+            for sym in &prod.rhs {
+                if let Symbol::Terminal(name) = sym {
+                    res.insert(name.clone());
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Splits on `|` outside of quoted terminals.
+fn split_alternatives(rhs: &str) -> Vec<String> {
+    let mut res = Vec::new();
+    let mut curr = String::new();
+    let mut quoted = false;
+    for c in rhs.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                curr.push(c);
+            }
+            '|' if !quoted => res.push(std::mem::take(&mut curr)),
+            _ => curr.push(c),
+        }
+    }
+    res.push(curr);
+    res
+}
+
+fn parse_alternative(
+    line: usize,
+    alt: &str,
+    nonterminals: &[String],
+) -> Result<Vec<Symbol>, GrammarError> {
+    let mut rhs = Vec::new();
+    let mut chars = alt.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut name = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => name.push(c),
+                    None => {
+                        return Err(GrammarError {
+                            line,
+                            message: "unterminated terminal".to_string(),
+                        })
+                    }
+                }
+            }
+            rhs.push(Symbol::Terminal(name));
+        } else if c == 'ε' {
+            chars.next();
+        } else {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+            if nonterminals.contains(&name) {
+                rhs.push(Symbol::NonTerminal(name));
+            } else if is_token_class(&name) {
+                rhs.push(Symbol::Terminal(name));
+            } else {
+                return Err(GrammarError {
+                    line,
+                    message: format!("undefined nonterminal `{}`", name),
+                });
+            }
+        }
+    }
+    Ok(rhs)
+}
+
+/// Nullable, FIRST and FOLLOW sets of a grammar.
+#[derive(Debug)]
+pub struct Analysis {
+    pub nullable: BTreeSet<String>,
+    pub first: BTreeMap<String, BTreeSet<String>>,
+    pub follow: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Analysis {
+    pub fn new(grammar: &Grammar) -> Analysis {
+        let mut analysis = Analysis {
+            nullable: BTreeSet::new(),
+            first: BTreeMap::new(),
+            follow: BTreeMap::new(),
+        };
+        for nt in &grammar.nonterminals {
+            analysis.first.insert(nt.clone(), BTreeSet::new());
+            analysis.follow.insert(nt.clone(), BTreeSet::new());
+        }
+        analysis
+            .follow
+            .get_mut(&grammar.start)
+            .unwrap()
+            .insert(EOF.to_string());
+
+        // nullable and FIRST
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for prod in &grammar.productions {
+                let (first, nullable) = analysis.first_of_seq(&prod.rhs);
+                let entry = analysis.first.get_mut(&prod.lhs).unwrap();
+                let len = entry.len();
+                entry.extend(first);
+                changed |= entry.len() != len;
+                if nullable && analysis.nullable.insert(prod.lhs.clone()) {
+                    changed = true;
+                }
+            }
+        }
+
+        // FOLLOW
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for prod in &grammar.productions {
+                for (i, sym) in prod.rhs.iter().enumerate() {
+                    let Symbol::NonTerminal(name) = sym else {
+                        continue;
+                    };
+                    let (mut follow, nullable) = analysis.first_of_seq(&prod.rhs[i + 1..]);
+                    if nullable {
+                        follow.extend(analysis.follow[&prod.lhs].iter().cloned());
+                    }
+                    let entry = analysis.follow.get_mut(name).unwrap();
+                    let len = entry.len();
+                    entry.extend(follow);
+                    changed |= entry.len() != len;
+                }
+            }
+        }
+
+        analysis
+    }
+
+    /// FIRST set of a symbol sequence and whether the sequence is nullable.
+    pub fn first_of_seq(&self, seq: &[Symbol]) -> (BTreeSet<String>, bool) {
+        let mut res = BTreeSet::new();
+        for sym in seq {
+            match sym {
+                Symbol::Terminal(name) => {
+                    res.insert(name.clone());
+                    return (res, false);
+                }
+                Symbol::NonTerminal(name) => {
+                    res.extend(self.first[name].iter().cloned());
+                    if !self.nullable.contains(name) {
+                        return (res, false);
+                    }
+                }
+            }
+        }
+        (res, true)
+    }
+
+    /// PREDICT set of `prod`, the lookaheads that select it.
+    pub fn predict(&self, prod: &Production) -> BTreeSet<String> {
+        let (mut res, nullable) = self.first_of_seq(&prod.rhs);
+        if nullable {
+            res.extend(self.follow[&prod.lhs].iter().cloned());
+        }
+        res
+    }
+}
+
+/// Two or more productions predicted by the same lookahead.
+#[derive(Debug)]
+pub struct Conflict {
+    pub nonterminal: String,
+    pub terminal: String,
+    pub productions: Vec<usize>,
+}
+
+/// LL(1) parse table mapping (nonterminal, lookahead) to a production.
+#[derive(Debug)]
+pub struct ParseTable {
+    pub entries: BTreeMap<(String, String), usize>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl ParseTable {
+    /// Builds the table, on a conflict the first production wins and the
+    /// conflict is recorded.
+    pub fn new(grammar: &Grammar, analysis: &Analysis) -> ParseTable {
+        let mut entries = BTreeMap::new();
+        let mut conflicts: BTreeMap<(String, String), Vec<usize>> = BTreeMap::new();
+        for (i, prod) in grammar.productions.iter().enumerate() {
+            for terminal in analysis.predict(prod) {
+                let key = (prod.lhs.clone(), terminal);
+                match entries.get(&key) {
+                    Some(&prev) => conflicts.entry(key).or_insert_with(|| vec![prev]).push(i),
+                    None => {
+                        entries.insert(key, i);
+                    }
+                }
+            }
+        }
+
+        ParseTable {
+            entries,
+            conflicts: conflicts
+                .into_iter()
+                .map(|((nonterminal, terminal), productions)| Conflict {
+                    nonterminal,
+                    terminal,
+                    productions,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, nonterminal: &str, terminal: &str) -> Option<usize> {
+        self.entries
+            .get(&(nonterminal.to_string(), terminal.to_string()))
+            .copied()
+    }
+
+    /// Lookaheads with an entry for `nonterminal`.
+    pub fn expected(&self, nonterminal: &str) -> Vec<&str> {
+        self.entries
+            .keys()
+            .filter(|(nt, _)| nt == nonterminal)
+            .map(|(_, t)| t.as_str())
+            .collect()
+    }
+
+    /// Renders the table one nonterminal at a time.
+    pub fn dump(&self, grammar: &Grammar) -> String {
+        let mut res = String::new();
+        for nt in &grammar.nonterminals {
+            res.push_str(&format!("{}:\n", nt));
+            for ((_, terminal), &prod) in self.entries.iter().filter(|((x, _), _)| x == nt) {
+                let terminal = Symbol::Terminal(terminal.clone()).to_string();
+                res.push_str(&format!(
+                    "    {:<12} => {}\n",
+                    terminal, grammar.productions[prod]
+                ));
+            }
+        }
+        for conflict in &self.conflicts {
+            res.push_str(&format!(
+                "conflict: {} on {}\n",
+                conflict.nonterminal,
+                Symbol::Terminal(conflict.terminal.clone())
+            ));
+            for &prod in &conflict.productions {
+                res.push_str(&format!("    {}\n", grammar.productions[prod]));
+            }
+        }
+        res
+    }
+}
+
+/// Left recursive cycles of `grammar`, e.g. `["A", "B"]` for
+/// `A ::= B x` and `B ::= A y`. Nullable prefixes are taken into account.
+pub fn left_recursion(grammar: &Grammar, analysis: &Analysis) -> Vec<Vec<String>> {
+    // edges[a] holds every b with a ::= α b β where α is nullable
+    let index = |name: &str| grammar.nonterminals.iter().position(|x| x == name).unwrap();
+    let mut edges = vec![BTreeSet::new(); grammar.nonterminals.len()];
+    for prod in &grammar.productions {
+        for sym in &prod.rhs {
+            match sym {
+                Symbol::Terminal(_) => break,
+                Symbol::NonTerminal(name) => {
+                    edges[index(&prod.lhs)].insert(index(name));
+                    if !analysis.nullable.contains(name) {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // every elementary cycle is reported once, from its lowest index
+    fn visit(
+        start: usize,
+        node: usize,
+        edges: &[BTreeSet<usize>],
+        path: &mut Vec<usize>,
+        cycles: &mut Vec<Vec<usize>>,
+    ) {
+        for &next in &edges[node] {
+            if next == start {
+                cycles.push(path.clone());
+            } else if next > start && !path.contains(&next) {
+                path.push(next);
+                visit(start, next, edges, path, cycles);
+                path.pop();
+            }
+        }
+    }
+
+    let mut cycles = Vec::new();
+    for start in 0..grammar.nonterminals.len() {
+        visit(start, start, &edges, &mut vec![start], &mut cycles);
+    }
+    cycles
+        .into_iter()
+        .map(|cycle| {
+            cycle
+                .into_iter()
+                .map(|x| grammar.nonterminals[x].clone())
+                .collect()
+        })
+        .collect()
+}
+
+/// Nonterminals that derive no terminal string.
+pub fn unproductive(grammar: &Grammar) -> Vec<String> {
+    let mut productive: BTreeSet<&str> = BTreeSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for prod in &grammar.productions {
+            let ok = prod.rhs.iter().all(|sym| match sym {
+                Symbol::Terminal(_) => true,
+                Symbol::NonTerminal(name) => productive.contains(name.as_str()),
+            });
+            if ok && productive.insert(&prod.lhs) {
+                changed = true;
+            }
+        }
+    }
+    grammar
+        .nonterminals
+        .iter()
+        .filter(|x| !productive.contains(x.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Nonterminals not reachable from the start symbol.
+pub fn unreachable(grammar: &Grammar) -> Vec<String> {
+    let mut reached: BTreeSet<&str> = BTreeSet::new();
+    let mut work = vec![grammar.start.as_str()];
+    while let Some(nt) = work.pop() {
+        if !reached.insert(nt) {
+            continue;
+        }
+        for (_, prod) in grammar.productions_of(nt) {
+            for sym in &prod.rhs {
+                if let Symbol::NonTerminal(name) = sym {
+                    work.push(name);
+                }
+            }
+        }
+    }
+    grammar
+        .nonterminals
+        .iter()
+        .filter(|x| !reached.contains(x.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Productions of a nonterminal that make the grammar ambiguous: exact
+/// duplicates, or more than one alternative deriving ε.
+pub fn ambiguities(grammar: &Grammar, analysis: &Analysis) -> Vec<(String, Vec<usize>)> {
+    let mut res = Vec::new();
+    for nt in &grammar.nonterminals {
+        let prods = grammar.productions_of(nt).collect::<Vec<_>>();
+        let nullable = prods
+            .iter()
+            .filter(|(_, prod)| analysis.first_of_seq(&prod.rhs).1)
+            .map(|(i, _)| *i)
+            .collect::<Vec<_>>();
+        if nullable.len() > 1 {
+            res.push((
+                format!("{} has more than one alternative deriving ε", nt),
+                nullable,
+            ));
+        }
+        for (i, (a, prod_a)) in prods.iter().enumerate() {
+            for (b, prod_b) in &prods[i + 1..] {
+                if prod_a.rhs == prod_b.rhs {
+                    res.push((format!("{} has a duplicated alternative", nt), vec![*a, *b]));
+                }
+            }
+        }
+    }
+    res
+}
+
+/// Everything `snlc grammar` reports about a grammar.
+pub struct GrammarReport<'a> {
+    pub grammar: &'a Grammar,
+    pub analysis: Analysis,
+    pub table: ParseTable,
+    pub left_recursion: Vec<Vec<String>>,
+    pub ambiguities: Vec<(String, Vec<usize>)>,
+    pub unproductive: Vec<String>,
+    pub unreachable: Vec<String>,
+}
+
+impl<'a> GrammarReport<'a> {
+    pub fn new(grammar: &'a Grammar) -> GrammarReport<'a> {
+        let analysis = Analysis::new(grammar);
+        let table = ParseTable::new(grammar, &analysis);
+        GrammarReport {
+            left_recursion: left_recursion(grammar, &analysis),
+            ambiguities: ambiguities(grammar, &analysis),
+            unproductive: unproductive(grammar),
+            unreachable: unreachable(grammar),
+            grammar,
+            analysis,
+            table,
+        }
+    }
+
+    pub fn is_ll1(&self) -> bool {
+        self.table.conflicts.is_empty() && self.left_recursion.is_empty()
+    }
+
+    /// Whether `conflict` is FIRST/FIRST (two alternatives start with the
+    /// terminal) rather than FIRST/FOLLOW.
+    pub fn is_first_first(&self, conflict: &Conflict) -> bool {
+        conflict
+            .productions
+            .iter()
+            .filter(|&&prod| {
+                self.analysis
+                    .first_of_seq(&self.grammar.productions[prod].rhs)
+                    .0
+                    .contains(&conflict.terminal)
+            })
+            .count()
+            > 1
+    }
+}
+
+fn fmt_set(set: &BTreeSet<String>) -> String {
+    let items = set
+        .iter()
+        .map(|x| Symbol::Terminal(x.clone()).to_string())
+        .collect::<Vec<_>>();
+    format!("{{ {} }}", items.join(", "))
+}
+
+impl fmt::Display for GrammarReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let grammar = self.grammar;
+        writeln!(
+            f,
+            "{} nonterminals, {} terminals, {} productions",
+            grammar.nonterminals.len(),
+            grammar.terminals().len(),
+            grammar.productions.len()
+        )?;
+
+        let nullable = self.analysis.nullable.iter().cloned().collect::<Vec<_>>();
+        writeln!(f, "\nnullable: {}", nullable.join(", "))?;
+
+        writeln!(f, "\nFIRST:")?;
+        for nt in &grammar.nonterminals {
+            writeln!(f, "    {:<12} {}", nt, fmt_set(&self.analysis.first[nt]))?;
+        }
+        writeln!(f, "\nFOLLOW:")?;
+        for nt in &grammar.nonterminals {
+            writeln!(f, "    {:<12} {}", nt, fmt_set(&self.analysis.follow[nt]))?;
+        }
+
+        if !self.unproductive.is_empty() {
+            writeln!(f, "\nunproductive: {}", self.unproductive.join(", "))?;
+        }
+        if !self.unreachable.is_empty() {
+            writeln!(f, "\nunreachable: {}", self.unreachable.join(", "))?;
+        }
+
+        writeln!(f)?;
+        if self.left_recursion.is_empty() {
+            writeln!(f, "left recursion: none")?;
+        }
+        for cycle in &self.left_recursion {
+            writeln!(f, "left recursion: {} -> {}", cycle.join(" -> "), cycle[0])?;
+        }
+
+        for (message, prods) in &self.ambiguities {
+            writeln!(f, "ambiguous: {}", message)?;
+            for &prod in prods {
+                writeln!(f, "    {}", grammar.productions[prod])?;
+            }
+        }
+
+        if self.table.conflicts.is_empty() {
+            writeln!(f, "LL(1) conflicts: none")?;
+        }
+        for conflict in &self.table.conflicts {
+            let kind = if self.is_first_first(conflict) {
+                "FIRST/FIRST"
+            } else {
+                "FIRST/FOLLOW"
+            };
+            writeln!(
+                f,
+                "{} conflict: {} on {}",
+                kind,
+                conflict.nonterminal,
+                Symbol::Terminal(conflict.terminal.clone())
+            )?;
+            for &prod in &conflict.productions {
+                writeln!(f, "    {}", grammar.productions[prod])?;
+            }
+        }
+
+        if self.is_ll1() {
+            write!(f, "\ngrammar is LL(1)")
+        } else {
+            write!(f, "\ngrammar is not LL(1)")
+        }
+    }
+}
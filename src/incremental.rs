@@ -0,0 +1,117 @@
+//! A source buffer that keeps its tokens and syntax tree up to date across
+//! edits, for editors.
+//!
+//! An edit re-lexes only the damaged region (`snlc_lexer::incremental`) and
+//! the reparse puts every procedure declaration whose text survived the
+//! edit back into the tree without parsing it again.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use snlc_lexer::incremental::{relex, Edit};
+use snlc_lexer::{token_errors, tokensize, LexError, Token};
+
+use crate::cst::{GreenNode, SyntaxNode};
+use crate::ll1::{Ll1Error, Ll1Parser, REUSABLE};
+
+pub struct Document {
+    parser: Ll1Parser,
+    text: String,
+    tokens: Vec<Token>,
+    tree: Result<SyntaxNode, Ll1Error>,
+    /// Procedure declarations of the last good tree by offset in `text`.
+    procedures: HashMap<usize, Rc<GreenNode>>,
+    reused: usize,
+}
+
+impl Document {
+    pub fn new(text: impl Into<String>) -> Document {
+        let text = text.into();
+        let tokens = tokensize(&text).collect::<Vec<_>>();
+        let parser = Ll1Parser::snl();
+        let tree = parser
+            .parse_tokens(&text, &tokens, &HashMap::new())
+            .map(SyntaxNode::new_root);
+        let mut doc = Document {
+            parser,
+            text,
+            tokens,
+            tree,
+            procedures: HashMap::new(),
+            reused: 0,
+        };
+        doc.collect_procedures();
+        doc
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    pub fn tree(&self) -> Result<&SyntaxNode, &Ll1Error> {
+        self.tree.as_ref()
+    }
+
+    pub fn lex_errors(&self) -> Vec<LexError> {
+        token_errors(&self.text, &self.tokens)
+    }
+
+    /// How many procedure declarations the last parse took over unchanged.
+    pub fn reused(&self) -> usize {
+        self.reused
+    }
+
+    /// Replaces `edit.range` of the text with `edit.text`.
+    pub fn edit(&mut self, edit: &Edit) {
+        self.text = edit.apply(&self.text);
+        self.tokens = relex(&self.tokens, &self.text, edit);
+
+        // procedures overlapping the edit are parsed again, the others move
+        self.procedures = std::mem::take(&mut self.procedures)
+            .into_iter()
+            .filter_map(|(start, node)| {
+                if start + node.len <= edit.range.start {
+                    Some((start, node))
+                } else if start >= edit.range.end {
+                    Some((edit.map_offset(start)?, node))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        self.tree = self
+            .parser
+            .parse_tokens(&self.text, &self.tokens, &self.procedures)
+            .map(SyntaxNode::new_root);
+        self.collect_procedures();
+    }
+
+    /// Remembers the procedures of a good tree for the next edit, the
+    /// previous ones are kept while the text doesn't parse.
+    fn collect_procedures(&mut self) {
+        self.reused = 0;
+        let Ok(root) = &self.tree else {
+            return;
+        };
+        let procedures = root
+            .descendants()
+            .into_iter()
+            .filter(|x| x.kind() == REUSABLE)
+            .map(|x| (x.text_range().start, x.green().clone()))
+            .collect::<HashMap<_, _>>();
+        self.reused = procedures
+            .iter()
+            .filter(|&(start, node)| {
+                self.procedures
+                    .get(start)
+                    .is_some_and(|old| Rc::ptr_eq(old, node))
+            })
+            .count();
+        self.procedures = procedures;
+    }
+}
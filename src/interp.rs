@@ -0,0 +1,1048 @@
+//! Interpreter for checked SNL programs.
+//!
+//! The CST is lowered once into a small tree with every name resolved to a
+//! storage slot, which is then walked directly. Integers follow the same
+//! `--int-width`/`--overflow` settings as the generated Rust code and
+//! runtime errors read the same, so both backends print identical output.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::ops::ControlFlow;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use snlc_ast::codegen::{Config, IntWidth, OverflowPolicy};
+use snlc_ast::expr::BinOp;
+use snlc_lexer::unescape::{unescape_char, unescape_str};
+
+use crate::cst::ast::{self, FactorKind, StmKind};
+use crate::cst::{SyntaxNode, SyntaxToken};
+use crate::diagnostic::{has_errors, Diagnostic};
+use crate::runtime::{Input, ReadError};
+use crate::sema::{self, SemanticModel, SymbolId, Ty};
+
+pub use crate::runtime::{ErrorKind, Frame, RuntimeError};
+
+/// Where a variable lives, globals are shared and locals belong to the
+/// frame of the running procedure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    Global(usize),
+    Local(usize),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Int(i64),
+    Char(char),
+    /// Only an argument of `write`.
+    Str(Rc<str>),
+    Load(Slot),
+    Binary {
+        op: BinOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+        line: usize,
+    },
+}
+
+/// A statement, `line` is the SNL line runtime errors are reported at.
+#[derive(Debug, Clone)]
+pub enum Stm {
+    Assign {
+        target: Slot,
+        value: Expr,
+        line: usize,
+    },
+    Call {
+        proc: usize,
+        args: Vec<Expr>,
+        line: usize,
+    },
+    /// Each target with its type and line.
+    Read {
+        targets: Vec<(Slot, Ty, usize)>,
+        line: usize,
+    },
+    Write {
+        args: Vec<Expr>,
+        newline: bool,
+        line: usize,
+    },
+    If {
+        condition: Expr,
+        body: Vec<Stm>,
+        else_body: Vec<Stm>,
+        line: usize,
+    },
+    While {
+        condition: Expr,
+        body: Vec<Stm>,
+        line: usize,
+    },
+}
+
+/// A declared variable or parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarDecl {
+    pub name: String,
+    pub ty: Ty,
+}
+
+/// A procedure, its parameters are the first `params` locals.
+#[derive(Debug)]
+pub struct Proc {
+    pub name: String,
+    pub params: usize,
+    pub locals: Vec<VarDecl>,
+    pub body: Vec<Stm>,
+}
+
+#[derive(Debug)]
+pub struct Program {
+    pub name: String,
+    pub globals: Vec<VarDecl>,
+    pub procs: Vec<Proc>,
+    pub body: Vec<Stm>,
+    /// Whether sema matched names ignoring case.
+    pub case_insensitive: bool,
+}
+
+/// Checks `src` and lowers it for `run`. Warnings come along with the
+/// program, errors instead of it.
+pub fn compile(
+    src: &str,
+    options: &sema::Options,
+) -> Result<(Program, Vec<Diagnostic>), Vec<Diagnostic>> {
+    let (root, model) = sema::analyze_tree(src, options)?;
+    if has_errors(&model.diagnostics) {
+        return Err(model.diagnostics);
+    }
+    let prog = lower(&root, &model, src);
+    let mut diagnostics = model.diagnostics;
+    crate::lint::add_lints(src, options, &mut diagnostics);
+    if has_errors(&diagnostics) {
+        return Err(diagnostics);
+    }
+    Ok((prog, diagnostics))
+}
+
+/// Lowers a tree `model` found no errors in.
+pub fn lower(root: &SyntaxNode, model: &SemanticModel, src: &str) -> Program {
+    let mut lowerer = Lowerer {
+        model,
+        line_starts: std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect(),
+        slots: HashMap::new(),
+        procs: HashMap::new(),
+    };
+    lowerer.program(&ast::Program::cast(root.clone()).unwrap())
+}
+
+struct Lowerer<'a> {
+    model: &'a SemanticModel,
+    line_starts: Vec<usize>,
+    slots: HashMap<SymbolId, Slot>,
+    procs: HashMap<SymbolId, usize>,
+}
+
+impl Lowerer<'_> {
+    fn line(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&x| x <= offset)
+    }
+
+    fn symbol(&self, token: &SyntaxToken) -> SymbolId {
+        self.model.symbol_at(token.text_range().start).unwrap()
+    }
+
+    /// Gives each of `names` the next slot after `decls`, which gets them.
+    fn declare(
+        &mut self,
+        names: Vec<SyntaxToken>,
+        decls: &mut Vec<VarDecl>,
+        slot: fn(usize) -> Slot,
+    ) {
+        for name in names {
+            let symbol = self.symbol(&name);
+            self.slots.insert(symbol, slot(decls.len()));
+            decls.push(VarDecl {
+                name: name.text().to_string(),
+                ty: self.model.symbols[symbol].ty().unwrap(),
+            });
+        }
+    }
+
+    fn var_part(
+        &mut self,
+        var_part: Option<ast::VarPart>,
+        decls: &mut Vec<VarDecl>,
+        slot: fn(usize) -> Slot,
+    ) {
+        for var_dec in var_part.map(|x| x.var_decs()).unwrap_or_default() {
+            self.declare(var_dec.names(), decls, slot);
+        }
+    }
+
+    fn program(&mut self, prog: &ast::Program) -> Program {
+        let mut globals = Vec::new();
+        self.var_part(prog.var_part(), &mut globals, Slot::Global);
+        let decs = prog.procedures();
+        for (i, dec) in decs.iter().enumerate() {
+            let symbol = self.symbol(&dec.name().unwrap());
+            self.procs.insert(symbol, i);
+        }
+        let procs = decs.iter().map(|x| self.proc(x)).collect();
+        Program {
+            name: prog
+                .name()
+                .map(|x| x.text().to_string())
+                .unwrap_or_default(),
+            globals,
+            procs,
+            body: self.stm_list(prog.body()),
+            case_insensitive: self.model.case_insensitive,
+        }
+    }
+
+    fn proc(&mut self, dec: &ast::ProcDec) -> Proc {
+        let mut locals = Vec::new();
+        let params = dec.params().iter().filter_map(|x| x.name()).collect();
+        self.declare(params, &mut locals, Slot::Local);
+        let params = locals.len();
+        self.var_part(dec.var_part(), &mut locals, Slot::Local);
+        Proc {
+            name: dec.name().unwrap().text().to_string(),
+            params,
+            locals,
+            body: self.stm_list(dec.body()),
+        }
+    }
+
+    fn stm_list(&mut self, list: Option<ast::StmList>) -> Vec<Stm> {
+        let stms = list.map(|x| x.stms()).unwrap_or_default();
+        stms.iter().map(|x| self.stm(x)).collect()
+    }
+
+    fn stm(&mut self, stm: &ast::Stm) -> Stm {
+        let line = self.line(stm.syntax().trimmed_range().start);
+        match stm.kind().unwrap() {
+            StmKind::If {
+                condition,
+                body,
+                else_body,
+            } => Stm::If {
+                condition: self.exp(&condition),
+                body: self.stm_list(Some(body)),
+                else_body: self.stm_list(else_body),
+                line,
+            },
+            StmKind::While { condition, body } => Stm::While {
+                condition: self.exp(&condition),
+                body: self.stm_list(Some(body)),
+                line,
+            },
+            StmKind::Read { targets } => Stm::Read {
+                targets: targets
+                    .iter()
+                    .map(|x| {
+                        let symbol = self.symbol(x);
+                        let ty = self.model.symbols[symbol].ty().unwrap();
+                        (self.slots[&symbol], ty, self.line(x.text_range().start))
+                    })
+                    .collect(),
+                line,
+            },
+            StmKind::Write { args, newline } => Stm::Write {
+                args: args.iter().map(|x| self.exp(x)).collect(),
+                newline,
+                line,
+            },
+            StmKind::Assign { target, value, .. } => Stm::Assign {
+                target: self.slots[&self.symbol(&target)],
+                value: self.exp(&value),
+                line,
+            },
+            StmKind::Call { name, args } => Stm::Call {
+                proc: self.procs[&self.symbol(&name)],
+                args: args.iter().map(|x| self.exp(x)).collect(),
+                line,
+            },
+        }
+    }
+
+    fn binary(&self, op: &SyntaxToken, lhs: Expr, rhs: Expr, line: usize) -> Expr {
+        let op = match op.text() {
+            "<" => BinOp::Lt,
+            "<=" => BinOp::Le,
+            "=" => BinOp::Eq,
+            "+" => BinOp::Plus,
+            "-" => BinOp::Minus,
+            "*" => BinOp::Star,
+            _ => BinOp::Slash,
+        };
+        Expr::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+            line,
+        }
+    }
+
+    fn exp(&mut self, exp: &ast::Exp) -> Expr {
+        let simple = exp.lhs().unwrap();
+        let line = self.line(simple.syntax().trimmed_range().start);
+        let lhs = self.simple_exp(&simple);
+        match exp.cmp() {
+            Some((op, rhs)) => {
+                let rhs = self.simple_exp(&rhs);
+                self.binary(&op, lhs, rhs, line)
+            }
+            None => lhs,
+        }
+    }
+
+    fn simple_exp(&mut self, exp: &ast::SimpleExp) -> Expr {
+        let first = exp.first().unwrap();
+        let line = self.line(first.syntax().trimmed_range().start);
+        let mut res = self.term(&first);
+        for (op, rhs) in exp.rest() {
+            let rhs = self.term(&rhs);
+            res = self.binary(&op, res, rhs, line);
+        }
+        res
+    }
+
+    fn term(&mut self, term: &ast::Term) -> Expr {
+        let first = term.first().unwrap();
+        let line = self.line(first.syntax().trimmed_range().start);
+        let mut res = self.factor(&first);
+        for (op, rhs) in term.rest() {
+            let rhs = self.factor(&rhs);
+            res = self.binary(&op, res, rhs, line);
+        }
+        res
+    }
+
+    fn factor(&mut self, factor: &ast::Factor) -> Expr {
+        match factor.kind().unwrap() {
+            FactorKind::Paren(exp) => self.exp(&exp),
+            FactorKind::Int(token) => Expr::Int(token.text().parse().unwrap()),
+            FactorKind::Char(token) => Expr::Char(unescape_char(token.text()).unwrap()),
+            FactorKind::Str(token) => Expr::Str(unescape_str(token.text()).unwrap().into()),
+            FactorKind::Var(name) => Expr::Load(self.slots[&self.symbol(&name)]),
+            FactorKind::Index(..) | FactorKind::Call(..) => {
+                unreachable!("rejected by sema")
+            }
+        }
+    }
+}
+
+/// Folds constant expressions and the `if` and `while` statements they
+/// decide, as `snlc_ast::opt` does for the generated code at
+/// `opt_level = 1`. Arithmetic that would stop the program is left for it
+/// to report.
+pub fn optimize(prog: &mut Program, config: &Config) {
+    let folder = Folder {
+        width: config.int_width,
+        overflow: config.overflow,
+        strict_init: config.strict_init,
+    };
+    for proc in &mut prog.procs {
+        proc.body = folder.stm_list(std::mem::take(&mut proc.body));
+    }
+    prog.body = folder.stm_list(std::mem::take(&mut prog.body));
+}
+
+struct Folder {
+    width: IntWidth,
+    overflow: OverflowPolicy,
+    strict_init: bool,
+}
+
+impl Folder {
+    fn stm_list(&self, stms: Vec<Stm>) -> Vec<Stm> {
+        let mut res = Vec::with_capacity(stms.len());
+        for stm in stms {
+            match stm {
+                Stm::Assign {
+                    target,
+                    value,
+                    line,
+                } => res.push(Stm::Assign {
+                    target,
+                    value: self.expr(value),
+                    line,
+                }),
+                Stm::Call { proc, args, line } => res.push(Stm::Call {
+                    proc,
+                    args: args.into_iter().map(|x| self.expr(x)).collect(),
+                    line,
+                }),
+                Stm::Write {
+                    args,
+                    newline,
+                    line,
+                } => res.push(Stm::Write {
+                    args: args.into_iter().map(|x| self.expr(x)).collect(),
+                    newline,
+                    line,
+                }),
+                Stm::Read { .. } => res.push(stm),
+                Stm::If {
+                    condition,
+                    body,
+                    else_body,
+                    line,
+                } => {
+                    let condition = self.expr(condition);
+                    let body = self.stm_list(body);
+                    let else_body = self.stm_list(else_body);
+                    match decided(&condition) {
+                        Some(true) => res.extend(body),
+                        Some(false) => res.extend(else_body),
+                        None => res.push(Stm::If {
+                            condition,
+                            body,
+                            else_body,
+                            line,
+                        }),
+                    }
+                }
+                Stm::While {
+                    condition,
+                    body,
+                    line,
+                } => {
+                    let condition = self.expr(condition);
+                    if decided(&condition) != Some(false) {
+                        res.push(Stm::While {
+                            condition,
+                            body: self.stm_list(body),
+                            line,
+                        });
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    fn expr(&self, expr: Expr) -> Expr {
+        let Expr::Binary { op, lhs, rhs, line } = expr else {
+            return expr;
+        };
+        let (lhs, rhs) = (self.expr(*lhs), self.expr(*rhs));
+        match (op, &lhs, &rhs) {
+            (BinOp::Lt | BinOp::Le | BinOp::Eq, _, _) => {}
+            (op, &Expr::Int(a), &Expr::Int(b)) => {
+                if let Ok(value) = arith(op, a, b, self.width, self.overflow) {
+                    return Expr::Int(value);
+                }
+            }
+            (BinOp::Plus, Expr::Int(0), _) | (BinOp::Star, Expr::Int(1), _) => return rhs,
+            (BinOp::Plus | BinOp::Minus, _, Expr::Int(0))
+            | (BinOp::Star | BinOp::Slash, _, Expr::Int(1)) => return lhs,
+            // the dropped operand must not have had a runtime error to report
+            (BinOp::Star, Expr::Int(0), other) | (BinOp::Star, other, Expr::Int(0))
+                if !self.may_fail(other) =>
+            {
+                return Expr::Int(0);
+            }
+            _ => {}
+        }
+        Expr::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+            line,
+        }
+    }
+
+    /// Whether evaluating `expr` can stop the program.
+    fn may_fail(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Binary { op, lhs, rhs, .. } => {
+                *op == BinOp::Slash
+                    || (self.overflow == OverflowPolicy::Trap
+                        && matches!(op, BinOp::Plus | BinOp::Minus | BinOp::Star))
+                    || self.may_fail(lhs)
+                    || self.may_fail(rhs)
+            }
+            // a strict run stops reading an unassigned variable
+            Expr::Load(_) => self.strict_init,
+            Expr::Int(_) | Expr::Char(_) | Expr::Str(_) => false,
+        }
+    }
+}
+
+/// The value of a comparison of two constants.
+fn decided(condition: &Expr) -> Option<bool> {
+    let constant = |expr: &Expr| match *expr {
+        Expr::Int(x) => Some(x),
+        Expr::Char(c) => Some(c as i64),
+        _ => None,
+    };
+    let Expr::Binary { op, lhs, rhs, .. } = condition else {
+        return None;
+    };
+    let (lhs, rhs) = (constant(lhs)?, constant(rhs)?);
+    match op {
+        BinOp::Lt => Some(lhs < rhs),
+        BinOp::Le => Some(lhs <= rhs),
+        BinOp::Eq => Some(lhs == rhs),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Int(i64),
+    Char(char),
+    Bool(bool),
+}
+
+impl Value {
+    fn default_of(ty: Ty) -> Value {
+        match ty {
+            Ty::Char => Value::Char('\0'),
+            _ => Value::Int(0),
+        }
+    }
+
+    pub(crate) fn int(self) -> i64 {
+        match self {
+            Value::Int(x) => x,
+            Value::Char(c) => c as i64,
+            Value::Bool(b) => b as i64,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(x) => write!(f, "{}", x),
+            Value::Char(c) => write!(f, "{}", c),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// Bounds on a run.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    /// Wall clock time the program may take.
+    pub time: Option<Duration>,
+    /// Statements the interpreter may run, a `while` counting once per
+    /// evaluation of its condition, or instructions the VM may run.
+    pub steps: Option<u64>,
+    /// Procedure calls that may be running at once.
+    pub depth: Option<usize>,
+    /// Bytes the program may write.
+    pub output: Option<usize>,
+    /// Values the program may read, `read(a, b)` reads two.
+    pub reads: Option<u64>,
+}
+
+/// What a run used of its `Limits` so far, shared by every backend so
+/// they stop at the same points.
+#[derive(Debug, Default)]
+pub(crate) struct Usage {
+    limits: Limits,
+    steps: u64,
+    output: usize,
+    reads: u64,
+}
+
+impl Usage {
+    pub(crate) fn new(limits: &Limits) -> Self {
+        Usage {
+            limits: limits.clone(),
+            ..Usage::default()
+        }
+    }
+
+    pub(crate) fn step(&mut self) -> Result<(), ErrorKind> {
+        self.steps += 1;
+        match self.limits.steps {
+            Some(n) if self.steps > n => Err(ErrorKind::StepLimit(n)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a call making `depth` procedures run at once.
+    pub(crate) fn call(&self, depth: usize) -> Result<(), ErrorKind> {
+        match self.limits.depth {
+            Some(n) if depth > n => Err(ErrorKind::DepthLimit(n)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks writing `len` more bytes, none of which may be written when
+    /// they don't all fit.
+    pub(crate) fn write(&mut self, len: usize) -> Result<(), ErrorKind> {
+        self.output += len;
+        match self.limits.output {
+            Some(n) if self.output > n => Err(ErrorKind::OutputLimit(n)),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn read(&mut self) -> Result<(), ErrorKind> {
+        self.reads += 1;
+        match self.limits.reads {
+            Some(n) if self.reads > n => Err(ErrorKind::ReadLimit(n)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// How many statements run between two looks at the clock.
+const CLOCK_INTERVAL: u32 = 1024;
+
+/// Runs `prog` reading `input` and writing `output`.
+pub fn run<R: BufRead, W: Write>(
+    prog: &Program,
+    config: &Config,
+    input: R,
+    output: W,
+    limits: &Limits,
+) -> Result<(), RuntimeError> {
+    run_observed(prog, config, input, output, limits, &mut ())
+}
+
+/// A procedure call on the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Call {
+    pub proc: usize,
+    /// Line of the call statement.
+    pub line: usize,
+}
+
+/// What a running program looks like to an `Observer`.
+pub struct State<'a> {
+    prog: &'a Program,
+    globals: &'a [Option<Value>],
+    frames: &'a [Vec<Option<Value>>],
+    calls: &'a [Call],
+    line: usize,
+}
+
+impl State<'_> {
+    /// Line of the statement about to run.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Number of procedure calls on the stack.
+    pub fn depth(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// Name of the running procedure, the program's in its body.
+    pub fn function(&self) -> &str {
+        match self.calls.last() {
+            Some(call) => &self.prog.procs[call.proc].name,
+            None => &self.prog.name,
+        }
+    }
+
+    /// Running procedures innermost first, with the line each one is at.
+    pub fn backtrace(&self) -> Vec<(&str, usize)> {
+        let mut lines = self.calls.iter().map(|x| x.line).collect::<Vec<_>>();
+        lines.push(self.line);
+        let mut names = vec![self.prog.name.as_str()];
+        names.extend(
+            self.calls
+                .iter()
+                .map(|x| self.prog.procs[x.proc].name.as_str()),
+        );
+        // a procedure is at the line its callee was called from
+        names.into_iter().zip(lines).rev().collect()
+    }
+
+    /// Variables in scope, locals of the running procedure first, with
+    /// their type and value, `None` while unassigned.
+    pub fn variables(&self) -> Vec<(&str, Ty, Option<Value>)> {
+        let locals = match (self.calls.last(), self.frames.last()) {
+            (Some(call), Some(frame)) => self.prog.procs[call.proc]
+                .locals
+                .iter()
+                .zip(frame)
+                .collect(),
+            _ => Vec::new(),
+        };
+        locals
+            .into_iter()
+            .chain(self.prog.globals.iter().zip(self.globals))
+            .map(|(decl, value)| (decl.name.as_str(), decl.ty, *value))
+            .collect()
+    }
+
+    /// Parameters of the running procedure with their values.
+    pub fn arguments(&self) -> Vec<(&str, Option<Value>)> {
+        let params = match self.calls.last() {
+            Some(call) => self.prog.procs[call.proc].params,
+            None => 0,
+        };
+        self.variables()
+            .into_iter()
+            .take(params)
+            .map(|(name, _, value)| (name, value))
+            .collect()
+    }
+
+    /// The variable `name` a statement here would refer to, matching
+    /// names the way sema did.
+    pub fn lookup(&self, name: &str) -> Option<(Ty, Option<Value>)> {
+        self.variables()
+            .into_iter()
+            .find(|(x, ..)| {
+                if self.prog.case_insensitive {
+                    x.eq_ignore_ascii_case(name)
+                } else {
+                    *x == name
+                }
+            })
+            .map(|(_, ty, value)| (ty, value))
+    }
+}
+
+/// Watches a run statement by statement.
+pub trait Observer {
+    /// Called before every statement, and before every evaluation of a
+    /// `while` condition. Breaking stops the program with
+    /// `ErrorKind::Stopped`.
+    fn statement(&mut self, state: &State<'_>) -> ControlFlow<()>;
+
+    /// Called when a procedure or the program body starts, before its
+    /// first statement.
+    fn enter(&mut self, _state: &State<'_>) {}
+
+    /// Called when a procedure or the program body finishes, after its
+    /// last statement ran.
+    fn leave(&mut self, _state: &State<'_>) {}
+}
+
+impl Observer for () {
+    fn statement(&mut self, _: &State<'_>) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
+
+/// `run` telling `observer` about every statement.
+pub fn run_observed<R: BufRead, W: Write>(
+    prog: &Program,
+    config: &Config,
+    input: R,
+    output: W,
+    limits: &Limits,
+    observer: &mut dyn Observer,
+) -> Result<(), RuntimeError> {
+    let mut machine = Machine {
+        prog,
+        width: config.int_width,
+        overflow: config.overflow,
+        input: Input::new(input),
+        output,
+        strict_init: config.strict_init,
+        globals: Vec::new(),
+        frames: Vec::new(),
+        calls: Vec::new(),
+        observer,
+        line: 0,
+        deadline: limits.time.map(|x| (Instant::now() + x, x)),
+        ticks: 0,
+        usage: Usage::new(limits),
+    };
+    machine.globals = prog.globals.iter().map(|x| machine.initial(x.ty)).collect();
+    machine.observe(|x, state| x.enter(state));
+    machine.stm_list(&prog.body)?;
+    machine.observe(|x, state| x.leave(state));
+    machine
+        .output
+        .flush()
+        .map_err(|err| machine.error(ErrorKind::Output(err.to_string()), 0))
+}
+
+struct Machine<'a, 'o, R, W> {
+    prog: &'a Program,
+    width: IntWidth,
+    overflow: OverflowPolicy,
+    input: Input<R>,
+    output: W,
+    /// Variables start out unassigned instead of zero.
+    strict_init: bool,
+    globals: Vec<Option<Value>>,
+    /// Locals of the running procedures, innermost last.
+    frames: Vec<Vec<Option<Value>>>,
+    calls: Vec<Call>,
+    observer: &'o mut dyn Observer,
+    /// Line of the statement that runs.
+    line: usize,
+    deadline: Option<(Instant, Duration)>,
+    ticks: u32,
+    usage: Usage,
+}
+
+impl<R: BufRead, W: Write> Machine<'_, '_, R, W> {
+    fn error(&self, kind: ErrorKind, line: usize) -> RuntimeError {
+        let stack = self.calls.iter().rev().map(|call| Frame {
+            name: self.prog.procs[call.proc].name.clone(),
+            line: call.line,
+        });
+        RuntimeError {
+            kind,
+            line,
+            stack: stack.collect(),
+        }
+    }
+
+    fn initial(&self, ty: Ty) -> Option<Value> {
+        if self.strict_init {
+            None
+        } else {
+            Some(Value::default_of(ty))
+        }
+    }
+
+    /// Shows the observer the state at the current line.
+    fn observe<T>(&mut self, f: impl FnOnce(&mut dyn Observer, &State<'_>) -> T) -> T {
+        let state = State {
+            prog: self.prog,
+            globals: &self.globals,
+            frames: &self.frames,
+            calls: &self.calls,
+            line: self.line,
+        };
+        f(&mut *self.observer, &state)
+    }
+
+    fn tick(&mut self, line: usize) -> Result<(), RuntimeError> {
+        self.line = line;
+        if self.observe(|x, state| x.statement(state)).is_break() {
+            return Err(self.error(ErrorKind::Stopped, line));
+        }
+        self.usage.step().map_err(|kind| self.error(kind, line))?;
+        self.ticks += 1;
+        if self.ticks < CLOCK_INTERVAL {
+            return Ok(());
+        }
+        self.ticks = 0;
+        match self.deadline {
+            Some((deadline, time)) if Instant::now() > deadline => {
+                Err(self.error(ErrorKind::Timeout(time), line))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn load(&self, slot: Slot) -> Result<Value, RuntimeError> {
+        let value = match slot {
+            Slot::Global(i) => self.globals[i],
+            Slot::Local(i) => self.frames.last().unwrap()[i],
+        };
+        value.ok_or_else(|| self.error(ErrorKind::Unassigned, self.line))
+    }
+
+    fn store(&mut self, slot: Slot, value: Value) {
+        match slot {
+            Slot::Global(i) => self.globals[i] = Some(value),
+            Slot::Local(i) => self.frames.last_mut().unwrap()[i] = Some(value),
+        }
+    }
+
+    fn stm_list(&mut self, stms: &[Stm]) -> Result<(), RuntimeError> {
+        for stm in stms {
+            self.stm(stm)?;
+        }
+        Ok(())
+    }
+
+    fn stm(&mut self, stm: &Stm) -> Result<(), RuntimeError> {
+        match stm {
+            Stm::Assign {
+                target,
+                value,
+                line,
+            } => {
+                self.tick(*line)?;
+                let value = self.eval(value)?;
+                self.store(*target, value);
+            }
+            Stm::Call {
+                proc: index,
+                args,
+                line,
+            } => {
+                self.tick(*line)?;
+                self.usage
+                    .call(self.calls.len() + 1)
+                    .map_err(|kind| self.error(kind, *line))?;
+                let proc = &self.prog.procs[*index];
+                let mut frame = proc
+                    .locals
+                    .iter()
+                    .map(|x| self.initial(x.ty))
+                    .collect::<Vec<_>>();
+                for (i, arg) in args.iter().enumerate() {
+                    frame[i] = Some(self.eval(arg)?);
+                }
+                self.frames.push(frame);
+                self.calls.push(Call {
+                    proc: *index,
+                    line: *line,
+                });
+                self.observe(|x, state| x.enter(state));
+                let res = self.stm_list(&proc.body);
+                if res.is_ok() {
+                    self.observe(|x, state| x.leave(state));
+                }
+                self.line = *line;
+                self.calls.pop();
+                self.frames.pop();
+                res?;
+            }
+            Stm::Read { targets, line } => {
+                self.tick(*line)?;
+                // a prompt written before the read should be visible
+                self.output
+                    .flush()
+                    .map_err(|err| self.error(ErrorKind::Output(err.to_string()), *line))?;
+                for &(slot, ty, line) in targets {
+                    self.usage.read().map_err(|kind| self.error(kind, line))?;
+                    let value = read_value(&mut self.input, ty, self.width)
+                        .map_err(|err| self.error(ErrorKind::Input(err), line))?;
+                    self.store(slot, value);
+                }
+            }
+            Stm::Write {
+                args,
+                newline,
+                line,
+            } => {
+                self.tick(*line)?;
+                let mut pieces = Vec::new();
+                for arg in args {
+                    match arg {
+                        Expr::Str(s) => pieces.push(s.to_string()),
+                        _ => pieces.push(self.eval(arg)?.to_string()),
+                    }
+                }
+                if *newline {
+                    pieces.push("\n".to_string());
+                }
+                // arguments count for the output limit one by one, as in
+                // the VM
+                for piece in pieces {
+                    self.usage
+                        .write(piece.len())
+                        .map_err(|kind| self.error(kind, *line))?;
+                    self.output
+                        .write_all(piece.as_bytes())
+                        .map_err(|err| self.error(ErrorKind::Output(err.to_string()), *line))?;
+                }
+            }
+            Stm::If {
+                condition,
+                body,
+                else_body,
+                line,
+            } => {
+                self.tick(*line)?;
+                if self.eval(condition)? == Value::Bool(true) {
+                    self.stm_list(body)?;
+                } else {
+                    self.stm_list(else_body)?;
+                }
+            }
+            Stm::While {
+                condition,
+                body,
+                line,
+            } => loop {
+                self.tick(*line)?;
+                if self.eval(condition)? != Value::Bool(true) {
+                    break;
+                }
+                self.stm_list(body)?;
+            },
+        }
+        Ok(())
+    }
+
+    fn eval(&self, expr: &Expr) -> Result<Value, RuntimeError> {
+        let res = match expr {
+            Expr::Int(x) => Value::Int(*x),
+            Expr::Char(c) => Value::Char(*c),
+            Expr::Str(_) => unreachable!("strings are only written"),
+            Expr::Load(slot) => self.load(*slot)?,
+            Expr::Binary { op, lhs, rhs, line } => {
+                let lhs = self.eval(lhs)?.int();
+                let rhs = self.eval(rhs)?.int();
+                match op {
+                    BinOp::Lt => Value::Bool(lhs < rhs),
+                    BinOp::Le => Value::Bool(lhs <= rhs),
+                    BinOp::Eq => Value::Bool(lhs == rhs),
+                    _ => Value::Int(self.arith(*op, lhs, rhs, *line)?),
+                }
+            }
+        };
+        Ok(res)
+    }
+
+    fn arith(&self, op: BinOp, lhs: i64, rhs: i64, line: usize) -> Result<i64, RuntimeError> {
+        arith(op, lhs, rhs, self.width, self.overflow).map_err(|kind| self.error(kind, line))
+    }
+}
+
+/// Integer arithmetic under the overflow policy, like the generated
+/// `wrapping_*`, `checked_*` and `saturating_*` calls.
+pub(crate) fn arith(
+    op: BinOp,
+    lhs: i64,
+    rhs: i64,
+    width: IntWidth,
+    overflow: OverflowPolicy,
+) -> Result<i64, ErrorKind> {
+    let (lhs, rhs) = (lhs as i128, rhs as i128);
+    let exact = match op {
+        BinOp::Plus => lhs + rhs,
+        BinOp::Minus => lhs - rhs,
+        BinOp::Star => lhs * rhs,
+        _ => {
+            if rhs == 0 {
+                return Err(ErrorKind::DivisionByZero);
+            }
+            lhs / rhs
+        }
+    };
+    let (min, max) = (width.min(), width.max());
+    if (min as i128..=max as i128).contains(&exact) {
+        return Ok(exact as i64);
+    }
+    match overflow {
+        OverflowPolicy::Wrap => {
+            let shift = 128 - width.bits();
+            Ok(((exact << shift) >> shift) as i64)
+        }
+        OverflowPolicy::Saturate => Ok(if exact > 0 { max } else { min }),
+        OverflowPolicy::Trap => Err(ErrorKind::Overflow),
+    }
+}
+
+/// Reads the next value of type `ty` for `read`.
+pub(crate) fn read_value<R: BufRead>(
+    input: &mut Input<R>,
+    ty: Ty,
+    width: IntWidth,
+) -> Result<Value, ReadError> {
+    match (ty, width) {
+        (Ty::Char, _) => input.read().map(Value::Char),
+        (_, IntWidth::I16) => input.read::<i16>().map(|x| Value::Int(x as i64)),
+        (_, IntWidth::I32) => input.read::<i32>().map(|x| Value::Int(x as i64)),
+        (_, IntWidth::I64) => input.read::<i64>().map(Value::Int),
+    }
+}
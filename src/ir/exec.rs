@@ -0,0 +1,192 @@
+//! Runs IR directly, so passes can be checked against the interpreter.
+//!
+//! Every register holds an `i64`, characters as their code point. Phis at
+//! the start of a block read their operands together, for the edge control
+//! came along.
+
+use std::io::{BufRead, Write};
+use std::time::Instant;
+
+use snlc_ast::codegen::Config;
+use snlc_ast::expr::BinOp;
+
+use crate::interp::{arith, read_value, ErrorKind, Frame, Limits, RuntimeError};
+use crate::runtime::Input;
+use crate::sema::Ty;
+
+use super::{Function, Inst, Module, Operand, Terminator, WriteArg};
+
+/// Runs `module` reading `input` and writing `output`.
+pub fn run<R: BufRead, W: Write>(
+    module: &Module,
+    config: &Config,
+    input: R,
+    output: W,
+    limits: &Limits,
+) -> Result<(), RuntimeError> {
+    let mut machine = Machine {
+        module,
+        config,
+        input: Input::new(input),
+        output,
+        globals: vec![0; module.globals.len()],
+        deadline: limits.time.map(|x| (Instant::now() + x, x)),
+        stack: Vec::new(),
+    };
+    machine.call(&module.main, Vec::new())?;
+    machine
+        .output
+        .flush()
+        .map_err(|err| machine.error(ErrorKind::Output(err.to_string()), 0))
+}
+
+struct Machine<'a, R, W> {
+    module: &'a Module,
+    config: &'a Config,
+    input: Input<R>,
+    output: W,
+    globals: Vec<i64>,
+    deadline: Option<(Instant, std::time::Duration)>,
+    /// Procedures running, innermost last.
+    stack: Vec<Frame>,
+}
+
+impl<R: BufRead, W: Write> Machine<'_, R, W> {
+    fn error(&self, kind: ErrorKind, line: usize) -> RuntimeError {
+        RuntimeError {
+            kind,
+            line,
+            stack: self.stack.iter().rev().cloned().collect(),
+        }
+    }
+
+    fn call(&mut self, func: &Function, args: Vec<i64>) -> Result<(), RuntimeError> {
+        let mut regs = vec![0; func.vars.len()];
+        for (&param, arg) in func.params.iter().zip(args) {
+            regs[param] = arg;
+        }
+        let mut prev = 0;
+        let mut block = 0;
+        loop {
+            if let Some((deadline, time)) = self.deadline {
+                if Instant::now() > deadline {
+                    return Err(self.error(ErrorKind::Timeout(time), 0));
+                }
+            }
+            let insts = &func.blocks[block].insts;
+            let phis = insts
+                .iter()
+                .take_while(|x| matches!(x, Inst::Phi { .. }))
+                .count();
+            let values = insts[..phis]
+                .iter()
+                .map(|phi| {
+                    let Inst::Phi { dst, args } = phi else {
+                        unreachable!()
+                    };
+                    let op = args.iter().find(|(x, _)| *x == prev).unwrap().1;
+                    (*dst, value(&regs, op))
+                })
+                .collect::<Vec<_>>();
+            for (dst, value) in values {
+                regs[dst] = value;
+            }
+
+            for inst in &insts[phis..] {
+                match inst {
+                    Inst::Copy { dst, src } => regs[*dst] = value(&regs, *src),
+                    Inst::Binary {
+                        dst,
+                        op,
+                        lhs,
+                        rhs,
+                        line,
+                    } => {
+                        let (lhs, rhs) = (value(&regs, *lhs), value(&regs, *rhs));
+                        regs[*dst] =
+                            arith(*op, lhs, rhs, self.config.int_width, self.config.overflow)
+                                .map_err(|kind| self.error(kind, *line))?;
+                    }
+                    Inst::Load { dst, global } => regs[*dst] = self.globals[*global],
+                    Inst::Store { global, src } => self.globals[*global] = value(&regs, *src),
+                    Inst::Call { proc, args, line } => {
+                        let args = args.iter().map(|x| value(&regs, *x)).collect();
+                        let callee = &self.module.procs[*proc];
+                        self.stack.push(Frame {
+                            name: callee.name.clone(),
+                            line: *line,
+                        });
+                        self.call(callee, args)?;
+                        self.stack.pop();
+                    }
+                    Inst::Read { dst, ty, line } => {
+                        self.output
+                            .flush()
+                            .map_err(|err| self.error(ErrorKind::Output(err.to_string()), *line))?;
+                        regs[*dst] = read_value(&mut self.input, *ty, self.config.int_width)
+                            .map_err(|err| self.error(ErrorKind::Input(err), *line))?
+                            .int();
+                    }
+                    Inst::Write {
+                        args,
+                        newline,
+                        line,
+                    } => {
+                        let mut text = String::new();
+                        for arg in args {
+                            match arg {
+                                WriteArg::Str(s) => text.push_str(s),
+                                WriteArg::Value(op, Ty::Char) => {
+                                    let c = char::from_u32(value(&regs, *op) as u32);
+                                    text.push(c.unwrap_or(char::REPLACEMENT_CHARACTER));
+                                }
+                                WriteArg::Value(op, _) => {
+                                    text.push_str(&value(&regs, *op).to_string())
+                                }
+                            }
+                        }
+                        if *newline {
+                            text.push('\n');
+                        }
+                        self.output
+                            .write_all(text.as_bytes())
+                            .map_err(|err| self.error(ErrorKind::Output(err.to_string()), *line))?;
+                    }
+                    Inst::Phi { .. } => unreachable!("phis start a block"),
+                }
+            }
+
+            prev = block;
+            block = match &func.blocks[block].terminator {
+                Terminator::Goto(target) => *target,
+                Terminator::Branch {
+                    op,
+                    lhs,
+                    rhs,
+                    then_block,
+                    else_block,
+                } => {
+                    let (lhs, rhs) = (value(&regs, *lhs), value(&regs, *rhs));
+                    let taken = match op {
+                        BinOp::Lt => lhs < rhs,
+                        BinOp::Le => lhs <= rhs,
+                        _ => lhs == rhs,
+                    };
+                    if taken {
+                        *then_block
+                    } else {
+                        *else_block
+                    }
+                }
+                Terminator::Return => return Ok(()),
+            };
+        }
+    }
+}
+
+fn value(regs: &[i64], op: Operand) -> i64 {
+    match op {
+        Operand::Var(var) => regs[var],
+        Operand::Const(x) => x,
+    }
+}
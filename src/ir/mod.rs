@@ -0,0 +1,852 @@
+//! Three-address intermediate representation.
+//!
+//! Every procedure and the program body become a `Function`: a CFG of
+//! basic blocks over an unbounded set of virtual registers (`Var`). Locals
+//! and temporaries live in registers, globals a procedure touches live in
+//! memory and are reached through `Load` and `Store`. Lowering starts from
+//! the interpreter's resolved tree, `ssa` converts functions to SSA form
+//! and back, and `passes` runs named transformations over them.
+
+pub mod exec;
+pub mod passes;
+pub mod ssa;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
+
+use snlc_ast::codegen::OverflowPolicy;
+use snlc_ast::expr::BinOp;
+
+use crate::interp::{self, Slot, VarDecl};
+use crate::sema::Ty;
+
+pub type BlockId = usize;
+pub type Var = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Operand {
+    Var(Var),
+    /// An integer, or the code point of a character.
+    Const(i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteArg {
+    Value(Operand, Ty),
+    Str(Rc<str>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inst {
+    Copy {
+        dst: Var,
+        src: Operand,
+    },
+    /// Arithmetic, `line` is where runtime errors are reported.
+    Binary {
+        dst: Var,
+        op: BinOp,
+        lhs: Operand,
+        rhs: Operand,
+        line: usize,
+    },
+    Load {
+        dst: Var,
+        global: usize,
+    },
+    Store {
+        global: usize,
+        src: Operand,
+    },
+    Call {
+        proc: usize,
+        args: Vec<Operand>,
+        line: usize,
+    },
+    Read {
+        dst: Var,
+        ty: Ty,
+        line: usize,
+    },
+    Write {
+        args: Vec<WriteArg>,
+        newline: bool,
+        line: usize,
+    },
+    /// The operand of the predecessor control came from, only in SSA form
+    /// and only at the start of a block.
+    Phi {
+        dst: Var,
+        args: Vec<(BlockId, Operand)>,
+    },
+}
+
+impl Inst {
+    /// The register the instruction assigns.
+    pub fn dst(&self) -> Option<Var> {
+        match self {
+            Inst::Copy { dst, .. }
+            | Inst::Binary { dst, .. }
+            | Inst::Load { dst, .. }
+            | Inst::Read { dst, .. }
+            | Inst::Phi { dst, .. } => Some(*dst),
+            Inst::Store { .. } | Inst::Call { .. } | Inst::Write { .. } => None,
+        }
+    }
+
+    pub fn dst_mut(&mut self) -> Option<&mut Var> {
+        match self {
+            Inst::Copy { dst, .. }
+            | Inst::Binary { dst, .. }
+            | Inst::Load { dst, .. }
+            | Inst::Read { dst, .. }
+            | Inst::Phi { dst, .. } => Some(dst),
+            Inst::Store { .. } | Inst::Call { .. } | Inst::Write { .. } => None,
+        }
+    }
+
+    /// The operands the instruction reads.
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Inst::Copy { src, .. } | Inst::Store { src, .. } => vec![src],
+            Inst::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+            Inst::Load { .. } | Inst::Read { .. } => Vec::new(),
+            Inst::Call { args, .. } => args.iter().collect(),
+            Inst::Write { args, .. } => args
+                .iter()
+                .filter_map(|x| match x {
+                    WriteArg::Value(op, _) => Some(op),
+                    WriteArg::Str(_) => None,
+                })
+                .collect(),
+            Inst::Phi { args, .. } => args.iter().map(|(_, op)| op).collect(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Inst::Copy { src, .. } | Inst::Store { src, .. } => vec![src],
+            Inst::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+            Inst::Load { .. } | Inst::Read { .. } => Vec::new(),
+            Inst::Call { args, .. } => args.iter_mut().collect(),
+            Inst::Write { args, .. } => args
+                .iter_mut()
+                .filter_map(|x| match x {
+                    WriteArg::Value(op, _) => Some(op),
+                    WriteArg::Str(_) => None,
+                })
+                .collect(),
+            Inst::Phi { args, .. } => args.iter_mut().map(|(_, op)| op).collect(),
+        }
+    }
+
+    /// Whether the instruction can go if nothing reads its result: it has
+    /// no effect and can't stop the program.
+    pub fn is_pure(&self, overflow: OverflowPolicy) -> bool {
+        match self {
+            Inst::Copy { .. } | Inst::Load { .. } | Inst::Phi { .. } => true,
+            Inst::Binary { op, .. } => !may_fail(*op, overflow),
+            Inst::Store { .. } | Inst::Call { .. } | Inst::Read { .. } | Inst::Write { .. } => {
+                false
+            }
+        }
+    }
+}
+
+/// Whether `op` can stop the program with a runtime error.
+pub fn may_fail(op: BinOp, overflow: OverflowPolicy) -> bool {
+    op == BinOp::Slash || overflow == OverflowPolicy::Trap
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Terminator {
+    Goto(BlockId),
+    /// Goes to `then_block` when the comparison holds.
+    Branch {
+        op: BinOp,
+        lhs: Operand,
+        rhs: Operand,
+        then_block: BlockId,
+        else_block: BlockId,
+    },
+    Return,
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Goto(target) => vec![*target],
+            Terminator::Branch {
+                then_block,
+                else_block,
+                ..
+            } => vec![*then_block, *else_block],
+            Terminator::Return => Vec::new(),
+        }
+    }
+
+    pub fn successors_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Terminator::Goto(target) => vec![target],
+            Terminator::Branch {
+                then_block,
+                else_block,
+                ..
+            } => vec![then_block, else_block],
+            Terminator::Return => Vec::new(),
+        }
+    }
+
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Terminator::Branch { lhs, rhs, .. } => vec![lhs, rhs],
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Terminator::Branch { lhs, rhs, .. } => vec![lhs, rhs],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub insts: Vec<Inst>,
+    pub terminator: Terminator,
+}
+
+/// A procedure or the program body, block 0 is the entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    /// Registers the arguments arrive in.
+    pub params: Vec<Var>,
+    /// Name and type of every register, temporaries have no name.
+    pub vars: Vec<VarDecl>,
+    pub blocks: Vec<Block>,
+    /// Whether the function is in SSA form.
+    pub ssa: bool,
+}
+
+impl Function {
+    pub fn new_var(&mut self, name: String, ty: Ty) -> Var {
+        self.vars.push(VarDecl { name, ty });
+        self.vars.len() - 1
+    }
+
+    pub fn new_block(&mut self) -> BlockId {
+        self.blocks.push(Block {
+            insts: Vec::new(),
+            terminator: Terminator::Return,
+        });
+        self.blocks.len() - 1
+    }
+
+    /// Predecessors of every block, in the order of their ids.
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![Vec::new(); self.blocks.len()];
+        for (id, block) in self.blocks.iter().enumerate() {
+            for succ in block.terminator.successors() {
+                if !preds[succ].contains(&id) {
+                    preds[succ].push(id);
+                }
+            }
+        }
+        preds
+    }
+
+    /// Blocks reachable from the entry, in reverse postorder.
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        self.depth_first(true)
+    }
+
+    /// Blocks reachable from the entry in reverse postorder of a search
+    /// taking the last successor first, so the first successor of a branch
+    /// follows it unless control reaches it from elsewhere too.
+    pub fn layout(&self) -> Vec<BlockId> {
+        self.depth_first(false)
+    }
+
+    fn depth_first(&self, first_successor_first: bool) -> Vec<BlockId> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut order = Vec::new();
+        // (block, whether its successors were pushed)
+        let mut stack = vec![(0, false)];
+        while let Some((block, done)) = stack.pop() {
+            if done {
+                order.push(block);
+                continue;
+            }
+            if std::mem::replace(&mut visited[block], true) {
+                continue;
+            }
+            stack.push((block, true));
+            let mut succs = self.blocks[block].terminator.successors();
+            if first_successor_first {
+                succs.reverse();
+            }
+            for succ in succs {
+                if !visited[succ] {
+                    stack.push((succ, false));
+                }
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// Drops the blocks control can't reach and renumbers the rest.
+    pub fn remove_unreachable(&mut self) {
+        let mut order = self.reverse_postorder();
+        order.sort_unstable();
+        if order.len() == self.blocks.len() {
+            return;
+        }
+        let mut new_id = vec![usize::MAX; self.blocks.len()];
+        for (i, &block) in order.iter().enumerate() {
+            new_id[block] = i;
+        }
+        let mut blocks = std::mem::take(&mut self.blocks);
+        for &block in &order {
+            let mut block = std::mem::replace(
+                &mut blocks[block],
+                Block {
+                    insts: Vec::new(),
+                    terminator: Terminator::Return,
+                },
+            );
+            for succ in block.terminator.successors_mut() {
+                *succ = new_id[*succ];
+            }
+            for inst in &mut block.insts {
+                if let Inst::Phi { args, .. } = inst {
+                    args.retain(|(pred, _)| new_id[*pred] != usize::MAX);
+                    for (pred, _) in args {
+                        *pred = new_id[*pred];
+                    }
+                }
+            }
+            self.blocks.push(block);
+        }
+    }
+
+    /// How many instructions read each register, terminators included.
+    pub fn use_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.vars.len()];
+        for block in &self.blocks {
+            let operands = block
+                .insts
+                .iter()
+                .flat_map(|x| x.operands())
+                .chain(block.terminator.operands());
+            for op in operands {
+                if let Operand::Var(var) = op {
+                    counts[*var] += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Applies `f` to every operand of every instruction and terminator.
+    pub fn for_each_operand(&mut self, mut f: impl FnMut(&mut Operand)) {
+        for block in &mut self.blocks {
+            for inst in &mut block.insts {
+                inst.operands_mut().into_iter().for_each(&mut f);
+            }
+            block.terminator.operands_mut().into_iter().for_each(&mut f);
+        }
+    }
+
+    /// Number of instructions, terminators included.
+    pub fn len(&self) -> usize {
+        self.blocks.iter().map(|x| x.insts.len() + 1).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+/// A whole program, `procs` are indexed by `Inst::Call::proc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    pub name: String,
+    pub globals: Vec<VarDecl>,
+    pub procs: Vec<Function>,
+    pub main: Function,
+}
+
+impl Module {
+    pub fn functions(&self) -> impl Iterator<Item = &Function> {
+        self.procs.iter().chain(std::iter::once(&self.main))
+    }
+
+    pub fn functions_mut(&mut self) -> impl Iterator<Item = &mut Function> {
+        self.procs.iter_mut().chain(std::iter::once(&mut self.main))
+    }
+}
+
+/// Lowers a program the interpreter would run.
+pub fn lower(prog: &interp::Program) -> Module {
+    // globals only the body uses can live in its registers
+    let mut shared = HashSet::new();
+    for proc in &prog.procs {
+        globals_used(&proc.body, &mut shared);
+    }
+    let procs = prog
+        .procs
+        .iter()
+        .map(|proc| {
+            let vars = proc.locals.clone();
+            let mut lowerer = Lowerer::new(&proc.name, vars, &prog.globals);
+            lowerer.func.params = (0..proc.params).collect();
+            for var in proc.params..proc.locals.len() {
+                lowerer.zero(var);
+            }
+            lowerer.lower(&proc.body)
+        })
+        .collect();
+
+    let mut lowerer = Lowerer::new(&prog.name, Vec::new(), &prog.globals);
+    for (i, global) in prog.globals.iter().enumerate() {
+        if !shared.contains(&i) {
+            let var = lowerer.func.new_var(global.name.clone(), global.ty);
+            lowerer.globals[i] = Some(var);
+            lowerer.zero(var);
+        }
+    }
+    let main = lowerer.lower(&prog.body);
+
+    Module {
+        name: prog.name.clone(),
+        globals: prog.globals.clone(),
+        procs,
+        main,
+    }
+}
+
+/// Adds the globals `stms` mention to `used`.
+fn globals_used(stms: &[interp::Stm], used: &mut HashSet<usize>) {
+    fn expr(e: &interp::Expr, used: &mut HashSet<usize>) {
+        match e {
+            interp::Expr::Load(Slot::Global(i)) => {
+                used.insert(*i);
+            }
+            interp::Expr::Binary { lhs, rhs, .. } => {
+                expr(lhs, used);
+                expr(rhs, used);
+            }
+            _ => {}
+        }
+    }
+    let slot = |slot: &Slot, used: &mut HashSet<usize>| {
+        if let Slot::Global(i) = slot {
+            used.insert(*i);
+        }
+    };
+    for stm in stms {
+        match stm {
+            interp::Stm::Assign { target, value, .. } => {
+                slot(target, used);
+                expr(value, used);
+            }
+            interp::Stm::Call { args, .. } | interp::Stm::Write { args, .. } => {
+                args.iter().for_each(|x| expr(x, used));
+            }
+            interp::Stm::Read { targets, .. } => {
+                targets.iter().for_each(|(x, _, _)| slot(x, used));
+            }
+            interp::Stm::If {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                expr(condition, used);
+                globals_used(body, used);
+                globals_used(else_body, used);
+            }
+            interp::Stm::While {
+                condition, body, ..
+            } => {
+                expr(condition, used);
+                globals_used(body, used);
+            }
+        }
+    }
+}
+
+struct Lowerer<'a> {
+    func: Function,
+    global_decls: &'a [VarDecl],
+    /// The register of each global kept in one, `None` for memory.
+    globals: Vec<Option<Var>>,
+    curr: BlockId,
+}
+
+impl<'a> Lowerer<'a> {
+    fn new(name: &str, vars: Vec<VarDecl>, global_decls: &'a [VarDecl]) -> Lowerer<'a> {
+        let mut func = Function {
+            name: name.to_string(),
+            params: Vec::new(),
+            vars,
+            blocks: Vec::new(),
+            ssa: false,
+        };
+        let curr = func.new_block();
+        Lowerer {
+            func,
+            global_decls,
+            globals: vec![None; global_decls.len()],
+            curr,
+        }
+    }
+
+    fn lower(mut self, stms: &[interp::Stm]) -> Function {
+        self.stm_list(stms);
+        self.func
+    }
+
+    fn push(&mut self, inst: Inst) {
+        self.func.blocks[self.curr].insts.push(inst);
+    }
+
+    /// Variables start out as zero, `'\0'` for characters.
+    fn zero(&mut self, var: Var) {
+        self.push(Inst::Copy {
+            dst: var,
+            src: Operand::Const(0),
+        });
+    }
+
+    fn ty(&self, expr: &interp::Expr) -> Ty {
+        match expr {
+            interp::Expr::Int(_) | interp::Expr::Binary { .. } => Ty::Integer,
+            interp::Expr::Char(_) => Ty::Char,
+            interp::Expr::Str(_) => Ty::Str,
+            interp::Expr::Load(Slot::Local(i)) => self.func.vars[*i].ty,
+            interp::Expr::Load(Slot::Global(i)) => self.global_decls[*i].ty,
+        }
+    }
+
+    fn stm_list(&mut self, stms: &[interp::Stm]) {
+        for stm in stms {
+            self.stm(stm);
+        }
+    }
+
+    fn stm(&mut self, stm: &interp::Stm) {
+        match stm {
+            interp::Stm::Assign { target, value, .. } => match self.register(*target) {
+                Some(var) => {
+                    self.expr(value, Some(var));
+                }
+                None => {
+                    let src = self.expr(value, None);
+                    let Slot::Global(global) = *target else {
+                        unreachable!()
+                    };
+                    self.push(Inst::Store { global, src });
+                }
+            },
+            interp::Stm::Call { proc, args, line } => {
+                let args = args.iter().map(|x| self.expr(x, None)).collect();
+                self.push(Inst::Call {
+                    proc: *proc,
+                    args,
+                    line: *line,
+                });
+            }
+            interp::Stm::Read { targets, .. } => {
+                for &(slot, ty, line) in targets {
+                    match self.register(slot) {
+                        Some(dst) => self.push(Inst::Read { dst, ty, line }),
+                        None => {
+                            let dst = self.func.new_var(String::new(), ty);
+                            self.push(Inst::Read { dst, ty, line });
+                            let Slot::Global(global) = slot else {
+                                unreachable!()
+                            };
+                            self.push(Inst::Store {
+                                global,
+                                src: Operand::Var(dst),
+                            });
+                        }
+                    }
+                }
+            }
+            interp::Stm::Write {
+                args,
+                newline,
+                line,
+            } => {
+                let args = args
+                    .iter()
+                    .map(|x| match x {
+                        interp::Expr::Str(s) => WriteArg::Str(s.clone()),
+                        x => WriteArg::Value(self.expr(x, None), self.ty(x)),
+                    })
+                    .collect();
+                self.push(Inst::Write {
+                    args,
+                    newline: *newline,
+                    line: *line,
+                });
+            }
+            interp::Stm::If {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                let then_block = self.func.new_block();
+                let else_block = self.func.new_block();
+                let join = self.func.new_block();
+                self.branch(condition, then_block, else_block);
+                self.curr = then_block;
+                self.stm_list(body);
+                self.goto(join);
+                self.curr = else_block;
+                self.stm_list(else_body);
+                self.goto(join);
+                self.curr = join;
+            }
+            interp::Stm::While {
+                condition, body, ..
+            } => {
+                let header = self.func.new_block();
+                let body_block = self.func.new_block();
+                let after = self.func.new_block();
+                self.goto(header);
+                self.curr = header;
+                self.branch(condition, body_block, after);
+                self.curr = body_block;
+                self.stm_list(body);
+                self.goto(header);
+                self.curr = after;
+            }
+        }
+    }
+
+    fn goto(&mut self, target: BlockId) {
+        self.func.blocks[self.curr].terminator = Terminator::Goto(target);
+    }
+
+    fn branch(&mut self, condition: &interp::Expr, then_block: BlockId, else_block: BlockId) {
+        let interp::Expr::Binary { op, lhs, rhs, .. } = condition else {
+            unreachable!("conditions are comparisons")
+        };
+        let lhs = self.expr(lhs, None);
+        let rhs = self.expr(rhs, None);
+        self.func.blocks[self.curr].terminator = Terminator::Branch {
+            op: *op,
+            lhs,
+            rhs,
+            then_block,
+            else_block,
+        };
+    }
+
+    /// The register `slot` lives in, `None` for a global in memory.
+    fn register(&self, slot: Slot) -> Option<Var> {
+        match slot {
+            Slot::Local(i) => Some(i),
+            Slot::Global(i) => self.globals[i],
+        }
+    }
+
+    /// Evaluates `expr`, into `dst` when given.
+    fn expr(&mut self, expr: &interp::Expr, dst: Option<Var>) -> Operand {
+        let value = match expr {
+            interp::Expr::Int(x) => Operand::Const(*x),
+            interp::Expr::Char(c) => Operand::Const(*c as i64),
+            interp::Expr::Str(_) => unreachable!("strings are only written"),
+            interp::Expr::Load(slot) => match self.register(*slot) {
+                Some(var) => Operand::Var(var),
+                None => {
+                    let Slot::Global(global) = *slot else {
+                        unreachable!()
+                    };
+                    let dst =
+                        dst.unwrap_or_else(|| self.func.new_var(String::new(), self.ty(expr)));
+                    self.push(Inst::Load { dst, global });
+                    return Operand::Var(dst);
+                }
+            },
+            interp::Expr::Binary { op, lhs, rhs, line } => {
+                let lhs = self.expr(lhs, None);
+                let rhs = self.expr(rhs, None);
+                let dst = dst.unwrap_or_else(|| self.func.new_var(String::new(), Ty::Integer));
+                self.push(Inst::Binary {
+                    dst,
+                    op: *op,
+                    lhs,
+                    rhs,
+                    line: *line,
+                });
+                return Operand::Var(dst);
+            }
+        };
+        if let Some(dst) = dst {
+            self.push(Inst::Copy { dst, src: value });
+            return Operand::Var(dst);
+        }
+        value
+    }
+}
+
+fn op_name(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Plus => "add",
+        BinOp::Minus => "sub",
+        BinOp::Star => "mul",
+        BinOp::Slash => "div",
+        BinOp::Lt => "lt",
+        BinOp::Le => "le",
+        BinOp::Eq => "eq",
+        BinOp::Assign | BinOp::Unknown => unreachable!("not an operation"),
+    }
+}
+
+/// Prints registers and globals by name, `%a.1` is another version of `%a`
+/// and temporaries are numbered.
+struct Printer<'a> {
+    module: &'a Module,
+    func: &'a Function,
+}
+
+impl Printer<'_> {
+    fn var(&self, var: Var) -> String {
+        match self.func.vars[var].name.as_str() {
+            "" => format!("%{}", var),
+            name => format!("%{}", name),
+        }
+    }
+
+    fn operand(&self, op: &Operand) -> String {
+        match op {
+            Operand::Var(var) => self.var(*var),
+            Operand::Const(x) => x.to_string(),
+        }
+    }
+
+    fn global(&self, global: usize) -> String {
+        format!("@{}", self.module.globals[global].name)
+    }
+
+    fn inst(&self, inst: &Inst) -> String {
+        match inst {
+            Inst::Copy { dst, src } => format!("{} = copy {}", self.var(*dst), self.operand(src)),
+            Inst::Binary {
+                dst, op, lhs, rhs, ..
+            } => format!(
+                "{} = {} {}, {}",
+                self.var(*dst),
+                op_name(*op),
+                self.operand(lhs),
+                self.operand(rhs)
+            ),
+            Inst::Load { dst, global } => {
+                format!("{} = load {}", self.var(*dst), self.global(*global))
+            }
+            Inst::Store { global, src } => {
+                format!("store {}, {}", self.global(*global), self.operand(src))
+            }
+            Inst::Call { proc, args, .. } => format!(
+                "call {}({})",
+                self.module.procs[*proc].name,
+                args.iter()
+                    .map(|x| self.operand(x))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Inst::Read { dst, ty, .. } => format!("{} = read {}", self.var(*dst), ty),
+            Inst::Write { args, newline, .. } => {
+                let args = args
+                    .iter()
+                    .map(|x| match x {
+                        WriteArg::Value(op, Ty::Char) => format!("char {}", self.operand(op)),
+                        WriteArg::Value(op, _) => self.operand(op),
+                        WriteArg::Str(s) => format!("{:?}", s),
+                    })
+                    .collect::<Vec<_>>();
+                let name = if *newline { "writeln" } else { "write" };
+                if args.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{} {}", name, args.join(", "))
+                }
+            }
+            Inst::Phi { dst, args } => format!(
+                "{} = phi {}",
+                self.var(*dst),
+                args.iter()
+                    .map(|(block, op)| format!("[bb{}: {}]", block, self.operand(op)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    fn terminator(&self, terminator: &Terminator) -> String {
+        match terminator {
+            Terminator::Goto(target) => format!("goto bb{}", target),
+            Terminator::Branch {
+                op,
+                lhs,
+                rhs,
+                then_block,
+                else_block,
+            } => format!(
+                "br {} {}, {}, bb{}, bb{}",
+                op_name(*op),
+                self.operand(lhs),
+                self.operand(rhs),
+                then_block,
+                else_block
+            ),
+            Terminator::Return => "ret".to_string(),
+        }
+    }
+
+    fn function(&self, f: &mut fmt::Formatter<'_>, kind: &str) -> fmt::Result {
+        let params = self
+            .func
+            .params
+            .iter()
+            .map(|&x| format!("{}: {}", self.var(x), self.func.vars[x].ty))
+            .collect::<Vec<_>>();
+        writeln!(f, "{} {}({}) {{", kind, self.func.name, params.join(", "))?;
+        for (id, block) in self.func.blocks.iter().enumerate() {
+            writeln!(f, "bb{}:", id)?;
+            for inst in &block.insts {
+                writeln!(f, "    {}", self.inst(inst))?;
+            }
+            writeln!(f, "    {}", self.terminator(&block.terminator))?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for global in &self.globals {
+            writeln!(f, "global @{}: {}", global.name, global.ty)?;
+        }
+        for func in &self.procs {
+            writeln!(f)?;
+            Printer { module: self, func }.function(f, "proc")?;
+        }
+        writeln!(f)?;
+        Printer {
+            module: self,
+            func: &self.main,
+        }
+        .function(f, "program")
+    }
+}
@@ -0,0 +1,300 @@
+//! Named transformations over a `Module` and the manager running them.
+//!
+//! A pipeline is a comma separated list of pass names, `--passes=ssa,gvn,
+//! copy-prop,dce,out-of-ssa` for instance. Passes that need SSA form, or
+//! need to be out of it, are checked against the pipeline before anything
+//! runs.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use snlc_ast::codegen::Config;
+use snlc_ast::expr::BinOp;
+
+use super::ssa::{from_ssa, to_ssa, Dominators};
+use super::{Function, Inst, Module, Operand, Var};
+
+pub struct Pass {
+    pub name: &'static str,
+    pub help: &'static str,
+    /// `Some(true)` runs on SSA form only, `Some(false)` outside of it.
+    pub needs_ssa: Option<bool>,
+    pub run: fn(&mut Function, &Config),
+}
+
+pub const PASSES: &[Pass] = &[
+    Pass {
+        name: "ssa",
+        help: "convert to SSA form",
+        needs_ssa: Some(false),
+        run: |func, _| to_ssa(func),
+    },
+    Pass {
+        name: "out-of-ssa",
+        help: "replace phis by copies",
+        needs_ssa: Some(true),
+        run: |func, _| from_ssa(func),
+    },
+    Pass {
+        name: "copy-prop",
+        help: "read the source of a copy instead of its destination",
+        needs_ssa: Some(true),
+        run: |func, _| copy_prop(func),
+    },
+    Pass {
+        name: "gvn",
+        help: "reuse the value of an identical dominating computation",
+        needs_ssa: Some(true),
+        run: |func, _| gvn(func),
+    },
+    Pass {
+        name: "dce",
+        help: "remove instructions whose result is never read",
+        needs_ssa: None,
+        run: dce,
+    },
+];
+
+pub fn find_pass(name: &str) -> Option<&'static Pass> {
+    PASSES.iter().find(|x| x.name == name)
+}
+
+/// Runs passes in order, printing the module after the chosen ones.
+pub struct PassManager {
+    passes: Vec<&'static Pass>,
+    print_after: Vec<String>,
+}
+
+impl PassManager {
+    /// A pipeline from comma separated pass names, `print_after` names
+    /// passes of the pipeline or is `all`.
+    pub fn new(passes: &str, print_after: &str) -> Result<PassManager, String> {
+        let passes = names(passes)
+            .map(|name| find_pass(name).ok_or_else(|| unknown(name)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut ssa = false;
+        for pass in &passes {
+            match pass.needs_ssa {
+                Some(true) if !ssa => {
+                    return Err(format!(
+                        "pass `{}` needs SSA form, run `ssa` before it",
+                        pass.name
+                    ))
+                }
+                Some(false) if ssa => {
+                    return Err(format!(
+                        "pass `{}` can't run on SSA form, run `out-of-ssa` before it",
+                        pass.name
+                    ))
+                }
+                _ => {}
+            }
+            match pass.name {
+                "ssa" => ssa = true,
+                "out-of-ssa" => ssa = false,
+                _ => {}
+            }
+        }
+        let print_after = names(print_after).map(str::to_string).collect::<Vec<_>>();
+        for name in &print_after {
+            if name != "all" && find_pass(name).is_none() {
+                return Err(unknown(name));
+            }
+        }
+        Ok(PassManager {
+            passes,
+            print_after,
+        })
+    }
+
+    pub fn run(
+        &self,
+        module: &mut Module,
+        config: &Config,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        for pass in &self.passes {
+            for func in module.functions_mut() {
+                (pass.run)(func, config);
+            }
+            if self
+                .print_after
+                .iter()
+                .any(|x| x == "all" || x == pass.name)
+            {
+                writeln!(out, "; after {}", pass.name)?;
+                writeln!(out, "{}", module)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn names(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|x| !x.is_empty())
+}
+
+fn unknown(name: &str) -> String {
+    let known = PASSES.iter().map(|x| x.name).collect::<Vec<_>>();
+    format!(
+        "unknown pass `{}`, expected one of {}",
+        name,
+        known.join(", ")
+    )
+}
+
+/// Replaces every read of a copy's destination by its source, and phis
+/// whose operands are all the same by that operand.
+pub fn copy_prop(func: &mut Function) {
+    loop {
+        let mut replace: HashMap<Var, Operand> = HashMap::new();
+        for block in &func.blocks {
+            for inst in &block.insts {
+                match inst {
+                    Inst::Copy { dst, src } => {
+                        replace.insert(*dst, *src);
+                    }
+                    Inst::Phi { dst, args } => {
+                        let mut ops = args
+                            .iter()
+                            .map(|(_, op)| *op)
+                            .filter(|x| *x != Operand::Var(*dst));
+                        if let Some(first) = ops.next() {
+                            if ops.all(|x| x == first) {
+                                replace.insert(*dst, first);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if replace.is_empty() {
+            return;
+        }
+        let resolve = |mut op: Operand| {
+            // chains of copies end at a source that isn't replaced
+            let mut steps = 0;
+            while let Operand::Var(var) = op {
+                match replace.get(&var) {
+                    Some(&next) if steps < replace.len() => op = next,
+                    _ => break,
+                }
+                steps += 1;
+            }
+            op
+        };
+        func.for_each_operand(|op| *op = resolve(*op));
+        for block in &mut func.blocks {
+            block
+                .insts
+                .retain(|x| !x.dst().is_some_and(|x| replace.contains_key(&x)));
+        }
+    }
+}
+
+/// Global value numbering over the dominator tree: an arithmetic
+/// instruction or load equal to one that dominates it reuses its result.
+/// A load is only reused up to the next store or call.
+pub fn gvn(func: &mut Function) {
+    let doms = Dominators::new(func);
+    let children = doms.children();
+    let mut table = HashMap::new();
+    let mut replace = HashMap::new();
+    gvn_block(func, 0, &children, &mut table, &mut replace);
+    func.for_each_operand(|op| {
+        if let Operand::Var(var) = op {
+            if let Some(&new) = replace.get(var) {
+                *op = Operand::Var(new);
+            }
+        }
+    });
+    for block in &mut func.blocks {
+        block
+            .insts
+            .retain(|x| !x.dst().is_some_and(|x| replace.contains_key(&x)));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Binary(BinOp, Operand, Operand),
+    Load(usize),
+}
+
+fn gvn_block(
+    func: &Function,
+    block: usize,
+    children: &[Vec<usize>],
+    table: &mut HashMap<Key, Var>,
+    replace: &mut HashMap<Var, Var>,
+) {
+    let resolve = |op: Operand, replace: &HashMap<Var, Var>| match op {
+        Operand::Var(var) => Operand::Var(*replace.get(&var).unwrap_or(&var)),
+        op => op,
+    };
+    let saved = table.clone();
+    for inst in &func.blocks[block].insts {
+        let key = match inst {
+            Inst::Binary {
+                dst, op, lhs, rhs, ..
+            } => {
+                let (mut lhs, mut rhs) = (resolve(*lhs, replace), resolve(*rhs, replace));
+                if matches!(op, BinOp::Plus | BinOp::Star) && lhs > rhs {
+                    std::mem::swap(&mut lhs, &mut rhs);
+                }
+                Some((Key::Binary(*op, lhs, rhs), *dst))
+            }
+            Inst::Load { dst, global } => Some((Key::Load(*global), *dst)),
+            Inst::Store { .. } | Inst::Call { .. } => {
+                table.retain(|k, _| !matches!(k, Key::Load(_)));
+                None
+            }
+            _ => None,
+        };
+        if let Some((key, dst)) = key {
+            match table.get(&key) {
+                Some(&prev) => {
+                    replace.insert(dst, prev);
+                }
+                None => {
+                    table.insert(key, dst);
+                }
+            }
+        }
+    }
+    // memory may change on the way to a child
+    table.retain(|k, _| !matches!(k, Key::Load(_)));
+    for &child in &children[block] {
+        gvn_block(func, child, children, table, replace);
+    }
+    *table = saved;
+}
+
+/// Removes instructions without effects whose result nobody reads.
+pub fn dce(func: &mut Function, config: &Config) {
+    loop {
+        let counts = func.use_counts();
+        let mut removed = false;
+        for block in &mut func.blocks {
+            block.insts.retain(|inst| {
+                let Some(dst) = inst.dst() else {
+                    return true;
+                };
+                // a phi may read itself around a loop
+                let self_uses = match inst {
+                    Inst::Phi { args, .. } => {
+                        args.iter().filter(|(_, x)| *x == Operand::Var(dst)).count()
+                    }
+                    _ => 0,
+                };
+                let dead = inst.is_pure(config.overflow) && counts[dst] == self_uses;
+                removed |= dead;
+                !dead
+            });
+        }
+        if !removed {
+            return;
+        }
+    }
+}
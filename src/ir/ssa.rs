@@ -0,0 +1,382 @@
+//! Conversion to SSA form and back.
+//!
+//! Phis go at the iterated dominance frontier of the blocks assigning a
+//! register that some block reads before assigning it (semi-pruned SSA),
+//! then a walk over the dominator tree gives every assignment its own
+//! register. Leaving SSA splits critical edges and replaces the phis of a
+//! block by parallel copies at the end of each predecessor.
+
+use std::collections::HashSet;
+
+use super::{BlockId, Function, Inst, Operand, Terminator, Var};
+
+/// Immediate dominators of the reachable blocks of a function.
+#[derive(Debug, Clone)]
+pub struct Dominators {
+    /// `None` for the entry and unreachable blocks.
+    pub idom: Vec<Option<BlockId>>,
+    /// Reachable blocks in reverse postorder.
+    pub order: Vec<BlockId>,
+}
+
+impl Dominators {
+    /// Cooper, Harvey and Kennedy's iterative algorithm.
+    pub fn new(func: &Function) -> Dominators {
+        let order = func.reverse_postorder();
+        let mut rpo_index = vec![usize::MAX; func.blocks.len()];
+        for (i, &block) in order.iter().enumerate() {
+            rpo_index[block] = i;
+        }
+        let preds = func.predecessors();
+        let mut idom: Vec<Option<BlockId>> = vec![None; func.blocks.len()];
+        idom[0] = Some(0);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let mut new: Option<BlockId> = None;
+                for &pred in &preds[block] {
+                    if idom[pred].is_none() {
+                        continue;
+                    }
+                    new = Some(match new {
+                        None => pred,
+                        Some(new) => intersect(&idom, &rpo_index, pred, new),
+                    });
+                }
+                if new.is_some() && idom[block] != new {
+                    idom[block] = new;
+                    changed = true;
+                }
+            }
+        }
+        idom[0] = None;
+        Dominators { idom, order }
+    }
+
+    /// Whether `a` dominates `b`.
+    pub fn dominates(&self, a: BlockId, mut b: BlockId) -> bool {
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom[b] {
+                Some(parent) => b = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Children of every block in the dominator tree.
+    pub fn children(&self) -> Vec<Vec<BlockId>> {
+        let mut children = vec![Vec::new(); self.idom.len()];
+        for &block in &self.order {
+            if let Some(parent) = self.idom[block] {
+                children[parent].push(block);
+            }
+        }
+        children
+    }
+
+    /// Dominance frontier of every block.
+    pub fn frontiers(&self, func: &Function) -> Vec<HashSet<BlockId>> {
+        let preds = func.predecessors();
+        let mut frontiers = vec![HashSet::new(); func.blocks.len()];
+        for &block in &self.order {
+            let preds = preds[block]
+                .iter()
+                .filter(|&&x| x == 0 || self.idom[x].is_some())
+                .collect::<Vec<_>>();
+            if preds.len() < 2 {
+                continue;
+            }
+            for &pred in preds {
+                let mut runner = pred;
+                while Some(runner) != self.idom[block] {
+                    frontiers[runner].insert(block);
+                    match self.idom[runner] {
+                        Some(parent) => runner = parent,
+                        None => break,
+                    }
+                }
+            }
+        }
+        frontiers
+    }
+}
+
+fn intersect(
+    idom: &[Option<BlockId>],
+    rpo_index: &[usize],
+    mut a: BlockId,
+    mut b: BlockId,
+) -> BlockId {
+    while a != b {
+        while rpo_index[a] > rpo_index[b] {
+            a = idom[a].unwrap();
+        }
+        while rpo_index[b] > rpo_index[a] {
+            b = idom[b].unwrap();
+        }
+    }
+    a
+}
+
+/// Converts `func` to SSA form, unreachable blocks are dropped.
+pub fn to_ssa(func: &mut Function) {
+    assert!(!func.ssa, "`{}` is already in SSA form", func.name);
+    func.remove_unreachable();
+    let doms = Dominators::new(func);
+    let frontiers = doms.frontiers(func);
+
+    // registers read in some block before it assigns them
+    let mut global_names = HashSet::new();
+    let mut def_blocks = vec![Vec::new(); func.vars.len()];
+    for &param in &func.params {
+        def_blocks[param].push(0);
+    }
+    for (id, block) in func.blocks.iter().enumerate() {
+        let mut defined = HashSet::new();
+        for inst in &block.insts {
+            for op in inst.operands() {
+                if let Operand::Var(var) = op {
+                    if !defined.contains(var) {
+                        global_names.insert(*var);
+                    }
+                }
+            }
+            if let Some(dst) = inst.dst() {
+                defined.insert(dst);
+                if !def_blocks[dst].contains(&id) {
+                    def_blocks[dst].push(id);
+                }
+            }
+        }
+        for op in block.terminator.operands() {
+            if let Operand::Var(var) = op {
+                if !defined.contains(var) {
+                    global_names.insert(*var);
+                }
+            }
+        }
+    }
+
+    let preds = func.predecessors();
+    let mut global_names = global_names.into_iter().collect::<Vec<_>>();
+    global_names.sort_unstable();
+    for var in global_names {
+        let mut has_phi = HashSet::new();
+        let mut work = def_blocks[var].clone();
+        while let Some(block) = work.pop() {
+            for &frontier in &frontiers[block] {
+                if has_phi.insert(frontier) {
+                    let args = preds[frontier]
+                        .iter()
+                        .map(|&x| (x, Operand::Var(var)))
+                        .collect();
+                    func.blocks[frontier]
+                        .insts
+                        .insert(0, Inst::Phi { dst: var, args });
+                    if !def_blocks[var].contains(&frontier) {
+                        work.push(frontier);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut renamer = Renamer {
+        stacks: vec![Vec::new(); func.vars.len()],
+        versions: vec![0; func.vars.len()],
+        children: doms.children(),
+    };
+    for &param in &func.params {
+        renamer.stacks[param].push(param);
+        renamer.versions[param] = 1;
+    }
+    renamer.rename(func, 0);
+    func.ssa = true;
+}
+
+struct Renamer {
+    /// The current register of every original register.
+    stacks: Vec<Vec<Var>>,
+    /// How many registers each original register has been split into.
+    versions: Vec<usize>,
+    children: Vec<Vec<BlockId>>,
+}
+
+impl Renamer {
+    fn current(&self, op: &mut Operand) {
+        if let Operand::Var(var) = op {
+            // a register no path assigned is never read, see `to_ssa`
+            *op = match self.stacks[*var].last() {
+                Some(&current) => Operand::Var(current),
+                None => Operand::Const(0),
+            };
+        }
+    }
+
+    /// A new register for an assignment to `var`, the first keeps `var`.
+    fn define(&mut self, func: &mut Function, var: Var) -> Var {
+        let version = self.versions[var];
+        self.versions[var] += 1;
+        let new = if version == 0 {
+            var
+        } else {
+            let decl = &func.vars[var];
+            let name = match decl.name.as_str() {
+                "" => String::new(),
+                name => format!("{}.{}", name, version),
+            };
+            func.new_var(name, decl.ty)
+        };
+        self.stacks[var].push(new);
+        new
+    }
+
+    fn rename(&mut self, func: &mut Function, block: BlockId) {
+        let mut defined = Vec::new();
+        let mut insts = std::mem::take(&mut func.blocks[block].insts);
+        for inst in &mut insts {
+            if !matches!(inst, Inst::Phi { .. }) {
+                inst.operands_mut()
+                    .into_iter()
+                    .for_each(|x| self.current(x));
+            }
+            if let Some(dst) = inst.dst_mut() {
+                let var = *dst;
+                // `define` grows `stacks` only for the original registers
+                *dst = self.define(func, var);
+                defined.push(var);
+            }
+        }
+        func.blocks[block].insts = insts;
+        let mut terminator = func.blocks[block].terminator.clone();
+        terminator
+            .operands_mut()
+            .into_iter()
+            .for_each(|x| self.current(x));
+        func.blocks[block].terminator = terminator;
+
+        for succ in func.blocks[block].terminator.successors() {
+            for inst in &mut func.blocks[succ].insts {
+                let Inst::Phi { args, .. } = inst else {
+                    break;
+                };
+                for (pred, op) in args {
+                    if *pred == block {
+                        self.current(op);
+                    }
+                }
+            }
+        }
+
+        for child in self.children[block].clone() {
+            self.rename(func, child);
+        }
+        for var in defined {
+            self.stacks[var].pop();
+        }
+    }
+}
+
+/// Converts `func` out of SSA form.
+pub fn from_ssa(func: &mut Function) {
+    assert!(func.ssa, "`{}` is not in SSA form", func.name);
+    split_critical_edges(func);
+    let preds = func.predecessors();
+    for (block, preds) in preds.iter().enumerate() {
+        let phis = func.blocks[block]
+            .insts
+            .iter()
+            .take_while(|x| matches!(x, Inst::Phi { .. }))
+            .count();
+        if phis == 0 {
+            continue;
+        }
+        let phis = func.blocks[block].insts.drain(..phis).collect::<Vec<_>>();
+        for &pred in preds {
+            let copies = phis
+                .iter()
+                .filter_map(|phi| {
+                    let Inst::Phi { dst, args } = phi else {
+                        unreachable!()
+                    };
+                    let src = args.iter().find(|(x, _)| *x == pred)?.1;
+                    Some((*dst, src))
+                })
+                .collect();
+            let copies = sequentialize(func, copies);
+            func.blocks[pred].insts.extend(copies);
+        }
+    }
+    func.ssa = false;
+}
+
+/// Puts an empty block on every edge from a block with several
+/// successors to a block with several predecessors.
+fn split_critical_edges(func: &mut Function) {
+    let preds = func.predecessors();
+    for block in 0..func.blocks.len() {
+        let succs = func.blocks[block].terminator.successors();
+        if succs.len() < 2 {
+            continue;
+        }
+        for succ in succs {
+            if preds[succ].len() < 2 {
+                continue;
+            }
+            let middle = func.new_block();
+            func.blocks[middle].terminator = Terminator::Goto(succ);
+            for target in func.blocks[block].terminator.successors_mut() {
+                if *target == succ {
+                    *target = middle;
+                }
+            }
+            for inst in &mut func.blocks[succ].insts {
+                let Inst::Phi { args, .. } = inst else {
+                    break;
+                };
+                for (pred, _) in args {
+                    if *pred == block {
+                        *pred = middle;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Orders the parallel copies `copies` so no copy overwrites a register
+/// another one still has to read, a cycle goes through a new register.
+fn sequentialize(func: &mut Function, mut copies: Vec<(Var, Operand)>) -> Vec<Inst> {
+    copies.retain(|(dst, src)| *src != Operand::Var(*dst));
+    let mut res = Vec::new();
+    while !copies.is_empty() {
+        let ready = copies
+            .iter()
+            .position(|(dst, _)| !copies.iter().any(|(_, src)| *src == Operand::Var(*dst)));
+        match ready {
+            Some(i) => {
+                let (dst, src) = copies.remove(i);
+                res.push(Inst::Copy { dst, src });
+            }
+            None => {
+                // every destination is still read, save one of them
+                let (dst, _) = copies[0];
+                let temp = func.new_var(String::new(), func.vars[dst].ty);
+                res.push(Inst::Copy {
+                    dst: temp,
+                    src: Operand::Var(dst),
+                });
+                for (_, src) in &mut copies {
+                    if *src == Operand::Var(dst) {
+                        *src = Operand::Var(temp);
+                    }
+                }
+            }
+        }
+    }
+    res
+}
@@ -1 +1,24 @@
-pub mod utils;
\ No newline at end of file
+mod api;
+pub mod cfg;
+pub mod cst;
+pub mod debugger;
+pub mod diagnostic;
+pub mod diff;
+pub mod dot;
+pub mod formatter;
+pub mod frontend;
+pub mod golden;
+pub mod grammar;
+pub mod incremental;
+pub mod interp;
+pub mod ir;
+pub mod lint;
+pub mod ll1;
+pub mod lsp;
+pub mod runtime;
+pub mod sema;
+pub mod trace;
+pub mod utils;
+pub mod vm;
+
+pub use api::{compile, run, Artifact, Diagnostics, Error, Options};
@@ -0,0 +1,55 @@
+//! Warnings about dead code and reads of unassigned variables, found by
+//! `snlc_ast::deadcode` and `snlc_ast::init` on the program `snl!`
+//! compiles and located in the SNL source.
+
+use snlc_ast::deadcode::find_dead_code;
+use snlc_ast::init::find_uninit_reads;
+
+use crate::diagnostic::{has_errors, Diagnostic};
+use crate::frontend::parse_program_mapped;
+use crate::sema;
+
+/// Dead code warnings for `src`, which must have passed sema.
+pub fn dead_code(src: &str, options: &sema::Options) -> Vec<Diagnostic> {
+    let Ok((prog, map)) = parse_program_mapped(src, options.case_insensitive) else {
+        return Vec::new();
+    };
+    let mut diagnostics = find_dead_code(&prog)
+        .iter()
+        .map(|x| Diagnostic::warning(map.range(x.span()), x.to_string()))
+        .collect::<Vec<_>>();
+    diagnostics.sort_by_key(|x| (x.range.start, x.message.clone()));
+    diagnostics.dedup();
+    diagnostics
+}
+
+/// Reads in `src` that may come before the variable is assigned, errors
+/// under `options.strict_init` and warnings otherwise.
+pub fn uninit_reads(src: &str, options: &sema::Options) -> Vec<Diagnostic> {
+    let Ok((prog, map)) = parse_program_mapped(src, options.case_insensitive) else {
+        return Vec::new();
+    };
+    let diagnostic = if options.strict_init {
+        Diagnostic::error
+    } else {
+        Diagnostic::warning
+    };
+    let mut diagnostics = find_uninit_reads(&prog)
+        .iter()
+        .map(|x| diagnostic(map.range(x.span()), x.to_string()))
+        .collect::<Vec<_>>();
+    diagnostics.sort_by_key(|x| (x.range.start, x.message.clone()));
+    diagnostics.dedup();
+    diagnostics
+}
+
+/// Adds both kinds of diagnostics to what sema reported for `src`, unless
+/// it found errors, keeping the diagnostics in source order.
+pub fn add_lints(src: &str, options: &sema::Options, diagnostics: &mut Vec<Diagnostic>) {
+    if has_errors(diagnostics) {
+        return;
+    }
+    diagnostics.extend(uninit_reads(src, options));
+    diagnostics.extend(dead_code(src, options));
+    diagnostics.sort_by_key(|x| x.range.start);
+}
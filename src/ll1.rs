@@ -0,0 +1,526 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::rc::Rc;
+
+use proc_macro2::Span;
+use snlc_ast::ast::*;
+use snlc_ast::expr::*;
+use snlc_ast::kw;
+use snlc_ast::stm::*;
+use snlc_ast::token::is_keyword;
+use snlc_lexer::unescape::{unescape_char, unescape_str};
+use snlc_lexer::LiteralKind;
+use syn::punctuated::Punctuated;
+
+use crate::cst::{self, ast::FactorKind, ast::StmKind, is_trivia};
+use crate::cst::{GreenElement, GreenNode, GreenToken, SyntaxNode, SyntaxToken};
+use crate::frontend::{line_col, SourceMap};
+use crate::grammar::{Analysis, Grammar, ParseTable, Symbol, EOF};
+
+#[derive(Debug)]
+pub struct Ll1Error {
+    pub pos: usize,
+    pub message: String,
+}
+
+impl Ll1Error {
+    /// Formats the error with a `line:col` location within `src`.
+    pub fn render(&self, src: &str) -> String {
+        let (line, col) = line_col(src, self.pos);
+        format!("{}:{}: {}", line, col, self.message)
+    }
+}
+
+impl fmt::Display for Ll1Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Name of the grammar terminal matched by a lexer token.
+pub fn terminal_of(kind: snlc_lexer::TokenKind, text: &str) -> String {
+    match kind {
+        snlc_lexer::TokenKind::Ident if is_keyword(text) => text.to_string(),
+        snlc_lexer::TokenKind::Ident => "ID".to_string(),
+        snlc_lexer::TokenKind::Literal {
+            kind: LiteralKind::Integer,
+        } => "INTC".to_string(),
+        snlc_lexer::TokenKind::Literal {
+            kind: LiteralKind::Str { .. },
+        } => "STRC".to_string(),
+        snlc_lexer::TokenKind::Literal { .. } => "CHARC".to_string(),
+        _ => text.to_string(),
+    }
+}
+
+/// The nonterminal whose subtrees `parse_tokens` can reuse, a procedure
+/// declaration only depends on its own tokens.
+pub const REUSABLE: &str = "ProcDec";
+
+pub struct Ll1Parser {
+    pub grammar: Grammar,
+    pub table: ParseTable,
+}
+
+impl Ll1Parser {
+    pub fn new(grammar: Grammar) -> Ll1Parser {
+        let analysis = Analysis::new(&grammar);
+        let table = ParseTable::new(&grammar, &analysis);
+        Ll1Parser { grammar, table }
+    }
+
+    pub fn snl() -> Ll1Parser {
+        Ll1Parser::new(Grammar::snl())
+    }
+
+    /// Runs the predictive parser over the tokens of `src` with an explicit
+    /// stack. Whitespace and comments are kept in the tree, each attached
+    /// to the node of the token that follows it.
+    pub fn parse_green(&self, src: &str) -> Result<Rc<GreenNode>, Ll1Error> {
+        let tokens = snlc_lexer::tokensize(src).collect::<Vec<_>>();
+        self.parse_tokens(src, &tokens, &HashMap::new())
+    }
+
+    /// `parse_green` over already lexed `tokens`. `reuse` maps offsets to
+    /// `ProcDec` nodes of an earlier parse; one whose text is found
+    /// unchanged at its offset is put in the tree as is instead of being
+    /// parsed again.
+    pub fn parse_tokens(
+        &self,
+        src: &str,
+        lexed: &[snlc_lexer::Token],
+        reuse: &HashMap<usize, Rc<GreenNode>>,
+    ) -> Result<Rc<GreenNode>, Ll1Error> {
+        enum Slot {
+            Node { name: Rc<str>, children: Vec<usize> },
+            Leaf(GreenToken),
+            Reused(Rc<GreenNode>),
+        }
+
+        let mut tokens = Vec::new();
+        let mut pos = 0;
+        for token in lexed {
+            let len = token.len as usize;
+            tokens.push((token.kind, pos, &src[pos..pos + len]));
+            pos += len;
+        }
+
+        let mut slots: Vec<Slot> = Vec::new();
+        // (symbol, parent slot)
+        let mut stack: Vec<(Symbol, Option<usize>)> =
+            vec![(Symbol::NonTerminal(self.grammar.start.clone()), None)];
+        let mut curr = 0;
+
+        let push = |slots: &mut Vec<Slot>, slot: Slot, parent: Option<usize>| {
+            let id = slots.len();
+            slots.push(slot);
+            if let Some(parent) = parent {
+                if let Slot::Node { children, .. } = &mut slots[parent] {
+                    children.push(id);
+                }
+            }
+            id
+        };
+
+        while let Some((sym, parent)) = stack.pop() {
+            // skip to the next significant token, trivia is attached on match
+            let mut next = curr;
+            while next < tokens.len() && is_trivia(tokens[next].0) {
+                next += 1;
+            }
+            let (lookahead, pos) = match tokens.get(next) {
+                Some(&(snlc_lexer::TokenKind::Unknown, pos, text)) => {
+                    return Err(Ll1Error {
+                        pos,
+                        message: format!("unexpected `{}`", text),
+                    })
+                }
+                Some(&(kind, pos, text)) => (terminal_of(kind, text), pos),
+                None => (EOF.to_string(), src.len()),
+            };
+
+            match sym {
+                Symbol::Terminal(name) => {
+                    if name != lookahead {
+                        return Err(Ll1Error {
+                            pos,
+                            message: format!(
+                                "expected {}, found {}",
+                                Symbol::Terminal(name),
+                                Symbol::Terminal(lookahead)
+                            ),
+                        });
+                    }
+                    for &(kind, _, text) in &tokens[curr..=next] {
+                        let leaf = Slot::Leaf(GreenToken {
+                            kind,
+                            text: text.to_string(),
+                        });
+                        push(&mut slots, leaf, parent);
+                    }
+                    curr = next + 1;
+                }
+                Symbol::NonTerminal(name) => {
+                    if let Some((node, end)) = self.reusable(&name, src, &tokens, curr, reuse) {
+                        push(&mut slots, Slot::Reused(node), parent);
+                        curr = end;
+                        continue;
+                    }
+                    let prod = self.table.get(&name, &lookahead).ok_or_else(|| {
+                        let expected = self
+                            .table
+                            .expected(&name)
+                            .into_iter()
+                            .map(|x| Symbol::Terminal(x.to_string()).to_string())
+                            .collect::<Vec<_>>();
+                        Ll1Error {
+                            pos,
+                            message: format!(
+                                "expected one of {}, found {}",
+                                expected.join(", "),
+                                Symbol::Terminal(lookahead.clone())
+                            ),
+                        }
+                    })?;
+                    let node = Slot::Node {
+                        name: name.into(),
+                        children: Vec::new(),
+                    };
+                    let id = push(&mut slots, node, parent);
+                    for sym in self.grammar.productions[prod].rhs.iter().rev() {
+                        stack.push((sym.clone(), Some(id)));
+                    }
+                }
+            }
+        }
+
+        // trailing whitespace and comments belong to the root
+        for &(kind, pos, text) in &tokens[curr..] {
+            if !is_trivia(kind) {
+                return Err(Ll1Error {
+                    pos,
+                    message: format!("unexpected `{}` after end of program", text),
+                });
+            }
+            let leaf = Slot::Leaf(GreenToken {
+                kind,
+                text: text.to_string(),
+            });
+            push(&mut slots, leaf, Some(0));
+        }
+
+        // children come after their parent, so building from the back
+        // finds them done
+        let mut built: Vec<Option<GreenElement>> = Vec::new();
+        built.resize_with(slots.len(), || None);
+        for (id, slot) in slots.into_iter().enumerate().rev() {
+            let element = match slot {
+                Slot::Node { name, children } => {
+                    let children = children
+                        .into_iter()
+                        .map(|x| built[x].take().unwrap())
+                        .collect();
+                    GreenElement::Node(Rc::new(GreenNode::new(name, children)))
+                }
+                Slot::Leaf(token) => GreenElement::Token(Rc::new(token)),
+                Slot::Reused(node) => GreenElement::Node(node),
+            };
+            built[id] = Some(element);
+        }
+        match built[0].take() {
+            Some(GreenElement::Node(root)) => Ok(root),
+            _ => unreachable!(),
+        }
+    }
+
+    /// An entry of `reuse` for nonterminal `name` at token `curr`, with the
+    /// index of the first token after it.
+    fn reusable(
+        &self,
+        name: &str,
+        src: &str,
+        tokens: &[(snlc_lexer::TokenKind, usize, &str)],
+        curr: usize,
+        reuse: &HashMap<usize, Rc<GreenNode>>,
+    ) -> Option<(Rc<GreenNode>, usize)> {
+        if name != REUSABLE {
+            return None;
+        }
+        let start = tokens.get(curr)?.1;
+        let node = reuse.get(&start)?;
+        let end = start + node.len;
+        // the node must still cover whole tokens with the same text
+        let after = curr + tokens[curr..].iter().take_while(|x| x.1 < end).count();
+        let at_boundary = tokens.get(after).map_or(end == src.len(), |x| x.1 == end);
+        if !at_boundary || src.get(start..end)? != node.to_string() {
+            return None;
+        }
+        Some((node.clone(), after))
+    }
+
+    /// Parses SNL source into the same `Program` the `snl!` macro builds.
+    pub fn parse_program(&self, src: &str) -> Result<Program, Ll1Error> {
+        self.parse_program_mapped(src).map(|(prog, _)| prog)
+    }
+
+    /// `parse_program` that also returns where the parts of the program
+    /// came from, as `frontend::parse_program_mapped` does.
+    pub fn parse_program_mapped(&self, src: &str) -> Result<(Program, SourceMap), Ll1Error> {
+        let root = SyntaxNode::new_root(self.parse_green(src)?);
+        let prog = cst::ast::Program::cast(root).unwrap();
+        let map = SourceMap::new(src, false);
+        let spans = map.spans().map_err(|err| Ll1Error {
+            pos: 0,
+            message: err.to_string(),
+        })?;
+        Ok((Lower { spans }.program(&prog), map))
+    }
+}
+
+/// Builds the `Program` of a syntax tree, giving every part the span of its
+/// token in the text `parse_program_mapped` parses.
+struct Lower {
+    spans: BTreeMap<usize, Span>,
+}
+
+// The grammar guarantees the pieces below exist in a successfully parsed tree.
+
+impl Lower {
+    fn span_at(&self, pos: usize) -> Span {
+        self.spans
+            .get(&pos)
+            .copied()
+            .unwrap_or_else(Span::call_site)
+    }
+
+    fn span(&self, token: &SyntaxToken) -> Span {
+        self.span_at(token.text_range().start)
+    }
+
+    /// Span of the first token after `token`, the bracket or parenthesis
+    /// following a name.
+    fn span_after(&self, token: &SyntaxToken) -> Span {
+        self.spans
+            .range(token.text_range().end..)
+            .next()
+            .map_or_else(Span::call_site, |(_, span)| *span)
+    }
+
+    fn ident(&self, token: &SyntaxToken) -> syn::Ident {
+        syn::Ident::new(token.text(), self.span(token))
+    }
+
+    fn program(&self, prog: &cst::ast::Program) -> Program {
+        let procedure_defs = prog
+            .procedures()
+            .iter()
+            .map(|x| self.proc_dec(x))
+            .collect::<Vec<_>>();
+        Program {
+            name: self.ident(&prog.name().unwrap()),
+            var_defs: prog.var_part().and_then(|x| self.var_part(&x)),
+            procedure_defs: if procedure_defs.is_empty() {
+                None
+            } else {
+                Some(procedure_defs)
+            },
+            body: self.stm_list(&prog.body().unwrap()),
+        }
+    }
+
+    fn var_part(&self, var_part: &cst::ast::VarPart) -> Option<Vec<VarDef>> {
+        if !var_part.is_present() {
+            return None;
+        }
+        Some(
+            var_part
+                .var_decs()
+                .iter()
+                .map(|x| self.var_dec(x))
+                .collect(),
+        )
+    }
+
+    fn var_dec(&self, var_dec: &cst::ast::VarDec) -> VarDef {
+        let names = var_dec.names().iter().map(|x| self.ident(x)).collect();
+        let type_name = var_dec.type_name().unwrap();
+        let span = self.span(&type_name);
+        match type_name.text() {
+            "char" => VarDef::CharTyVar(CharTyVarDef {
+                ty_kw: kw::char(span),
+                names,
+            }),
+            _ => VarDef::IntTyVar(IntTyVarDef {
+                ty_kw: kw::integer(span),
+                names,
+            }),
+        }
+    }
+
+    fn proc_dec(&self, proc: &cst::ast::ProcDec) -> ProcedureDef {
+        let mut params = Punctuated::new();
+        for x in proc.params() {
+            params.push(self.param(&x));
+        }
+
+        ProcedureDef {
+            name: self.ident(&proc.name().unwrap()),
+            params,
+            var_defs: proc.var_part().and_then(|x| self.var_part(&x)),
+            body: self.stm_list(&proc.body().unwrap()),
+        }
+    }
+
+    fn param(&self, param: &cst::ast::Param) -> ParamDecl {
+        let name = self.ident(&param.name().unwrap());
+        let type_name = param.type_name().unwrap();
+        let span = self.span(&type_name);
+        match type_name.text() {
+            "char" => ParamDecl::CharTyParam(CharTyParamDecl {
+                ty_kw: kw::char(span),
+                name,
+            }),
+            _ => ParamDecl::IntTyParam(IntTyParamDecl {
+                ty_kw: kw::integer(span),
+                name,
+            }),
+        }
+    }
+
+    fn stm_list(&self, list: &cst::ast::StmList) -> Vec<Stmatment> {
+        list.stms().iter().map(|x| self.stm(x)).collect()
+    }
+
+    fn stm(&self, stm: &cst::ast::Stm) -> Stmatment {
+        let span = self.span(&stm.first_token().unwrap());
+        match stm.kind().unwrap() {
+            StmKind::If {
+                condition,
+                body,
+                else_body,
+            } => Stmatment::If(IfStm {
+                if_token: kw::r#if(span),
+                condition: self.exp(&condition),
+                body: self.stm_list(&body),
+                else_body: else_body.map(|x| self.stm_list(&x)),
+            }),
+            StmKind::While { condition, body } => Stmatment::While(WhileStm {
+                while_token: kw::r#while(span),
+                condition: self.exp(&condition),
+                body: self.stm_list(&body),
+            }),
+            StmKind::Read { targets } => Stmatment::Read(ReadStm {
+                read_token: kw::read(span),
+                params: targets
+                    .iter()
+                    .map(|x| {
+                        Expr::Var(ExprVar {
+                            ident: self.ident(x),
+                        })
+                    })
+                    .collect(),
+            }),
+            StmKind::Write { args, newline } => Stmatment::Write(WriteStm {
+                span,
+                args: args.iter().map(|x| self.exp(x)).collect(),
+                newline,
+            }),
+            StmKind::Assign {
+                target,
+                index,
+                value,
+            } => {
+                let target = match index {
+                    Some(index) => Expr::Index(ExprIndex {
+                        ident: self.ident(&target),
+                        bracket_token: syn::token::Bracket(self.span_after(&target)),
+                        index: Box::new(self.exp(&index)),
+                    }),
+                    None => Expr::Var(ExprVar {
+                        ident: self.ident(&target),
+                    }),
+                };
+                Stmatment::Expr(make_binary(target, BinOp::Assign, self.exp(&value)))
+            }
+            StmKind::Call { name, args } => Stmatment::Expr(Expr::Call(ExprCall {
+                ident: self.ident(&name),
+                paren_token: syn::token::Paren(self.span_after(&name)),
+                args: args.iter().map(|x| self.exp(x)).collect(),
+            })),
+        }
+    }
+
+    fn exp(&self, exp: &cst::ast::Exp) -> Expr {
+        let lhs = self.simple_exp(&exp.lhs().unwrap());
+        match exp.cmp() {
+            Some((op, rhs)) => make_binary(lhs, bin_op(&op), self.simple_exp(&rhs)),
+            None => lhs,
+        }
+    }
+
+    fn simple_exp(&self, simple_exp: &cst::ast::SimpleExp) -> Expr {
+        let mut res = self.term(&simple_exp.first().unwrap());
+        for (op, rhs) in simple_exp.rest() {
+            res = make_binary(res, bin_op(&op), self.term(&rhs));
+        }
+        res
+    }
+
+    fn term(&self, term: &cst::ast::Term) -> Expr {
+        let mut res = self.factor(&term.first().unwrap());
+        for (op, rhs) in term.rest() {
+            res = make_binary(res, bin_op(&op), self.factor(&rhs));
+        }
+        res
+    }
+
+    fn factor(&self, factor: &cst::ast::Factor) -> Expr {
+        match factor.kind().unwrap() {
+            FactorKind::Paren(inner) => Expr::Paren(ExprParen {
+                paren_token: syn::token::Paren(self.span_at(factor.syntax().trimmed_range().start)),
+                expr: Box::new(self.exp(&inner)),
+            }),
+            FactorKind::Int(token) => Expr::Lit(ExprLit {
+                lit: Lit::Integer(syn::LitInt::new(token.text(), self.span(&token))),
+            }),
+            FactorKind::Char(token) => {
+                let value = unescape_char(token.text()).unwrap_or('\0');
+                Expr::Lit(ExprLit {
+                    lit: Lit::Char(syn::LitChar::new(value, self.span(&token))),
+                })
+            }
+            FactorKind::Str(token) => {
+                let value = unescape_str(token.text()).unwrap_or_default();
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(syn::LitStr::new(&value, self.span(&token))),
+                })
+            }
+            FactorKind::Var(name) => Expr::Var(ExprVar {
+                ident: self.ident(&name),
+            }),
+            FactorKind::Index(name, index) => Expr::Index(ExprIndex {
+                ident: self.ident(&name),
+                bracket_token: syn::token::Bracket(self.span_after(&name)),
+                index: Box::new(self.exp(&index)),
+            }),
+            FactorKind::Call(name, args) => Expr::Call(ExprCall {
+                ident: self.ident(&name),
+                paren_token: syn::token::Paren(self.span_after(&name)),
+                args: args.iter().map(|x| self.exp(x)).collect(),
+            }),
+        }
+    }
+}
+
+fn bin_op(token: &SyntaxToken) -> BinOp {
+    match token.text() {
+        "<" => BinOp::Lt,
+        "<=" => BinOp::Le,
+        "=" => BinOp::Eq,
+        "+" => BinOp::Plus,
+        "-" => BinOp::Minus,
+        "*" => BinOp::Star,
+        "/" => BinOp::Slash,
+        _ => BinOp::Unknown,
+    }
+}
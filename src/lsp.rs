@@ -0,0 +1,417 @@
+//! A small language server speaking LSP over stdio.
+//!
+//! Documents are synced incrementally: an edit re-lexes the damaged region
+//! and reparses reusing unchanged procedures, sema then rechecks the whole
+//! file, which is cheap for SNL sized programs.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+use serde_json::{json, Value};
+use snlc_ast::kw::KEYWORDS;
+use snlc_lexer::incremental::Edit;
+
+use crate::cst::ast::Program;
+use crate::cst::SyntaxNode;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::incremental;
+use crate::sema::{self, SemanticModel, SymbolKind};
+
+/// Reads one `Content-Length` framed message, `None` at end of input.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                len = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let len =
+        len.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; len];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn write_message(output: &mut impl Write, msg: &Value) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// Converts between byte offsets and LSP positions, which count lines and
+/// UTF-16 code units.
+pub struct LineIndex<'a> {
+    src: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(src: &'a str) -> LineIndex<'a> {
+        let mut line_starts = vec![0];
+        line_starts.extend(src.match_indices('\n').map(|(i, _)| i + 1));
+        LineIndex { src, line_starts }
+    }
+
+    pub fn position(&self, offset: usize) -> Value {
+        let offset = offset.min(self.src.len());
+        let line = self.line_starts.partition_point(|&x| x <= offset) - 1;
+        let character: usize = self.src[self.line_starts[line]..offset]
+            .chars()
+            .map(char::len_utf16)
+            .sum();
+        json!({ "line": line, "character": character })
+    }
+
+    pub fn range(&self, range: &Range<usize>) -> Value {
+        json!({ "start": self.position(range.start), "end": self.position(range.end) })
+    }
+
+    pub fn offset(&self, position: &Value) -> Option<usize> {
+        let line = position["line"].as_u64()? as usize;
+        let mut character = position["character"].as_u64()? as usize;
+        let start = *self.line_starts.get(line)?;
+        let mut offset = start;
+        for c in self.src[start..].chars() {
+            if character == 0 || c == '\n' {
+                break;
+            }
+            character = character.saturating_sub(c.len_utf16());
+            offset += c.len_utf8();
+        }
+        Some(offset)
+    }
+}
+
+/// An open document and what was learned from it.
+struct Document {
+    source: incremental::Document,
+    model: SemanticModel,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    fn new(text: String) -> Document {
+        let mut doc = Document {
+            source: incremental::Document::new(text),
+            model: SemanticModel::default(),
+            diagnostics: Vec::new(),
+        };
+        doc.analyze();
+        doc
+    }
+
+    fn text(&self) -> &str {
+        self.source.text()
+    }
+
+    fn root(&self) -> Option<SyntaxNode> {
+        self.source.tree().ok().cloned()
+    }
+
+    /// Applies an LSP content change, with a range it is an incremental
+    /// edit, without one it replaces the whole text.
+    fn apply_change(&mut self, change: &Value) {
+        let text = change["text"].as_str().unwrap_or_default();
+        if change.get("range").is_none() {
+            self.source = incremental::Document::new(text);
+            return;
+        }
+        let index = LineIndex::new(self.source.text());
+        let start = index.offset(&change["range"]["start"]);
+        let end = index.offset(&change["range"]["end"]);
+        if let (Some(start), Some(end)) = (start, end) {
+            self.source.edit(&Edit::new(start..end.max(start), text));
+        }
+    }
+
+    fn analyze(&mut self) {
+        let lex_errors = self.source.lex_errors();
+        (self.model, self.diagnostics) = if !lex_errors.is_empty() {
+            let diagnostics = lex_errors.into_iter().map(Diagnostic::from).collect();
+            (SemanticModel::default(), diagnostics)
+        } else {
+            match self.source.tree() {
+                Ok(root) => {
+                    let model = sema::check(root, &sema::Options::default());
+                    let diagnostics = model.diagnostics.clone();
+                    (model, diagnostics)
+                }
+                Err(err) => (
+                    SemanticModel::default(),
+                    vec![Diagnostic::error(err.pos..err.pos + 1, err.message.clone())],
+                ),
+            }
+        };
+    }
+}
+
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Server::new()
+    }
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server {
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// Handles one incoming message, returning the messages to send back.
+    /// The second value is `Some(code)` once the client asked to exit.
+    pub fn handle(&mut self, msg: &Value) -> (Vec<Value>, Option<i32>) {
+        let method = msg["method"].as_str().unwrap_or_default();
+        let params = &msg["params"];
+        let mut out = Vec::new();
+
+        let result = match method {
+            "initialize" => Some(Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 2,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "snlc-lsp" },
+            }))),
+            "shutdown" => {
+                self.shutdown = true;
+                Some(Ok(Value::Null))
+            }
+            "exit" => return (out, Some(if self.shutdown { 0 } else { 1 })),
+            "textDocument/didOpen" => {
+                let doc = &params["textDocument"];
+                out.push(self.update(doc["uri"].as_str(), doc["text"].as_str()));
+                None
+            }
+            "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                if let Some(doc) = self.documents.get_mut(uri) {
+                    for change in params["contentChanges"].as_array().into_iter().flatten() {
+                        doc.apply_change(change);
+                    }
+                    doc.analyze();
+                    let index = LineIndex::new(doc.text());
+                    out.push(publish_diagnostics(uri, &index, &doc.diagnostics));
+                }
+                None
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                self.documents.remove(uri);
+                out.push(publish_diagnostics(uri, &LineIndex::new(""), &[]));
+                None
+            }
+            "textDocument/definition" => Some(Ok(self.definition(params))),
+            "textDocument/references" => Some(Ok(self.references(params))),
+            "textDocument/hover" => Some(Ok(self.hover(params))),
+            "textDocument/documentSymbol" => Some(Ok(self.document_symbols(params))),
+            "textDocument/completion" => Some(Ok(self.completion(params))),
+            _ if msg.get("id").is_some() => Some(Err(format!("unsupported method `{}`", method))),
+            _ => None,
+        };
+
+        if let (Some(result), Some(id)) = (result, msg.get("id")) {
+            out.push(match result {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err(message) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": message },
+                }),
+            });
+        }
+        (out, None)
+    }
+
+    fn update(&mut self, uri: Option<&str>, text: Option<&str>) -> Value {
+        let uri = uri.unwrap_or_default().to_string();
+        let doc = Document::new(text.unwrap_or_default().to_string());
+        let msg = publish_diagnostics(&uri, &LineIndex::new(doc.text()), &doc.diagnostics);
+        self.documents.insert(uri, doc);
+        msg
+    }
+
+    /// The document and byte offset a `TextDocumentPositionParams` points at.
+    fn locate<'a>(&'a self, params: &'a Value) -> Option<(&'a str, &'a Document, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let doc = self.documents.get(uri)?;
+        let offset = LineIndex::new(doc.text()).offset(&params["position"])?;
+        Some((uri, doc, offset))
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let Some((uri, doc, offset)) = self.locate(params) else {
+            return Value::Null;
+        };
+        let Some(id) = doc.model.symbol_at(offset) else {
+            return Value::Null;
+        };
+        let index = LineIndex::new(doc.text());
+        json!({ "uri": uri, "range": index.range(&doc.model.symbols[id].def) })
+    }
+
+    fn references(&self, params: &Value) -> Value {
+        let Some((uri, doc, offset)) = self.locate(params) else {
+            return Value::Null;
+        };
+        let Some(id) = doc.model.symbol_at(offset) else {
+            return Value::Null;
+        };
+        let include_declaration = params["context"]["includeDeclaration"]
+            .as_bool()
+            .unwrap_or(true);
+        let def = &doc.model.symbols[id].def;
+        let index = LineIndex::new(doc.text());
+        let locations = doc
+            .model
+            .references_to(id)
+            .iter()
+            .filter(|x| include_declaration || *x != def)
+            .map(|x| json!({ "uri": uri, "range": index.range(x) }))
+            .collect::<Vec<_>>();
+        Value::Array(locations)
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let Some((_, doc, offset)) = self.locate(params) else {
+            return Value::Null;
+        };
+        let Some(id) = doc.model.symbol_at(offset) else {
+            return Value::Null;
+        };
+        let range = doc
+            .model
+            .references
+            .iter()
+            .find(|x| x.range.contains(&offset))
+            .map(|x| x.range.clone())
+            .unwrap_or_default();
+        let index = LineIndex::new(doc.text());
+        json!({
+            "contents": {
+                "kind": "markdown",
+                "value": format!("```snl\n{}\n```", doc.model.symbols[id].signature()),
+            },
+            "range": index.range(&range),
+        })
+    }
+
+    fn document_symbols(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(doc) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let Some(prog) = doc.root().and_then(Program::cast) else {
+            return json!([]);
+        };
+
+        let index = LineIndex::new(doc.text());
+        let symbols = prog
+            .procedures()
+            .iter()
+            .filter_map(|proc| {
+                let name = proc.name()?;
+                let detail = doc
+                    .model
+                    .symbol_at(name.text_range().start)
+                    .map(|id| doc.model.symbols[id].signature());
+                Some(json!({
+                    "name": name.text(),
+                    "detail": detail,
+                    // SymbolKind.Function
+                    "kind": 12,
+                    "range": index.range(&proc.syntax().trimmed_range()),
+                    "selectionRange": index.range(&name.text_range()),
+                }))
+            })
+            .collect::<Vec<_>>();
+        Value::Array(symbols)
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        // CompletionItemKind.Keyword
+        let mut items = KEYWORDS
+            .iter()
+            .map(|x| json!({ "label": x, "kind": 14 }))
+            .collect::<Vec<_>>();
+
+        if let Some((_, doc, _)) = self.locate(params) {
+            for symbol in &doc.model.symbols {
+                let kind = match symbol.kind {
+                    // CompletionItemKind.Function
+                    SymbolKind::Procedure(_) => 3,
+                    // CompletionItemKind.Variable
+                    SymbolKind::Var(_) | SymbolKind::Param(_) => 6,
+                };
+                items.push(json!({
+                    "label": symbol.name,
+                    "kind": kind,
+                    "detail": symbol.signature(),
+                }));
+            }
+        }
+        Value::Array(items)
+    }
+}
+
+fn publish_diagnostics(uri: &str, index: &LineIndex, diagnostics: &[Diagnostic]) -> Value {
+    let diagnostics = diagnostics
+        .iter()
+        .map(|x| {
+            json!({
+                "range": index.range(&x.range),
+                "severity": match x.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2,
+                },
+                "source": "snlc",
+                "message": x.message,
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// Serves requests read from `input` until the client sends `exit`,
+/// returning the process exit code.
+pub fn run(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<i32> {
+    let mut server = Server::new();
+    while let Some(msg) = read_message(input)? {
+        let (replies, exit) = server.handle(&msg);
+        for reply in &replies {
+            write_message(output, reply)?;
+        }
+        if let Some(code) = exit {
+            return Ok(code);
+        }
+    }
+    Ok(1)
+}
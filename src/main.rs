@@ -1,28 +1,399 @@
-use clap::{Arg, Command};
-use std::fs;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use snlc::cfg::build_program_cfgs;
+use snlc::cst::{self, SyntaxNode};
+use snlc::debugger::Debugger;
+use snlc::diagnostic::{has_errors, Diagnostic};
+use snlc::diff::unified_diff;
+use snlc::dot::{ast_to_dot, cfg_to_dot};
+use snlc::formatter::format_source;
+use snlc::frontend::{
+    check_lexical, fold_keywords, parse_program, parse_program_mapped, preprocess,
+    preprocess_ignore_case, SourceMap,
+};
+use snlc::golden::{self, Backend, Outcome};
+use snlc::grammar::{Grammar, GrammarReport, SNL_GRAMMAR};
+use snlc::interp::{self, ErrorKind, Limits, Observer, Program};
+use snlc::ir::{self, passes::PassManager};
+use snlc::lint::add_lints;
+use snlc::ll1::Ll1Parser;
+use snlc::sema::{self, analyze};
+use snlc::trace::{Profiler, Tracer};
 use snlc::utils::gen_ir_file;
-use snlc_ast::token::{Token, TokenKind};
+use snlc::vm;
+use snlc_ast::codegen::{Config, IntWidth, OverflowPolicy};
+use snlc_ast::opt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+fn input_arg() -> Arg {
+    Arg::new("INPUT")
+        .help("Sets the input file to use")
+        .required(true)
+        .index(1)
+}
+
+fn int_width_arg() -> Arg {
+    Arg::new("int-width")
+        .long("int-width")
+        .help("Sets the width in bits of the integer type")
+        .value_parser(["16", "32", "64"])
+        .default_value("32")
+}
+
+fn overflow_arg() -> Arg {
+    Arg::new("overflow")
+        .long("overflow")
+        .help("Sets what integer overflow does at runtime")
+        .value_parser(["wrap", "trap", "saturate"])
+        .default_value("trap")
+}
+
+fn opt_level_arg() -> Arg {
+    Arg::new("opt-level")
+        .short('O')
+        .help("Sets the optimization level, 1 folds constant expressions")
+        .value_parser(["0", "1"])
+        .default_value("0")
+}
+
+fn case_insensitive_arg() -> Arg {
+    Arg::new("case-insensitive")
+        .long("case-insensitive")
+        .help("Ignores the case of keywords and identifiers")
+        .action(ArgAction::SetTrue)
+}
+
+fn strict_init_arg() -> Arg {
+    Arg::new("strict-init")
+        .long("strict-init")
+        .help("Rejects reads of possibly unassigned variables instead of zero-initializing them")
+        .action(ArgAction::SetTrue)
+}
+
+fn limit_args() -> [Arg; 4] {
+    let arg = |name: &'static str, help: &'static str| {
+        Arg::new(name)
+            .long(name)
+            .help(help)
+            .value_parser(clap::value_parser!(u64))
+    };
+    [
+        arg("max-steps", "Sets how many statements the program may run"),
+        arg("max-depth", "Sets how many procedure calls may run at once"),
+        arg("max-output", "Sets how many bytes the program may write"),
+        arg("max-reads", "Sets how many values the program may read"),
+    ]
+}
+
+/// Limits of commands running untrusted programs, none by default.
+fn limits(matches: &ArgMatches) -> Limits {
+    let limit = |name| matches.get_one::<u64>(name).copied();
+    Limits {
+        steps: limit("max-steps"),
+        depth: limit("max-depth").map(|x| x as usize),
+        output: limit("max-output").map(|x| x as usize),
+        reads: limit("max-reads"),
+        ..Limits::default()
+    }
+}
+
+/// Sema settings shared by every command that compiles.
+fn sema_options(matches: &ArgMatches) -> sema::Options {
+    sema::Options {
+        int_width: int_width(matches),
+        case_insensitive: matches.get_flag("case-insensitive"),
+        strict_init: matches.get_flag("strict-init"),
+    }
+}
+
+/// Codegen settings shared by every command that runs a program.
+fn config(matches: &ArgMatches) -> Config {
+    Config {
+        int_width: int_width(matches),
+        overflow: overflow(matches),
+        // `run` interprets the program as written
+        opt_level: matches
+            .try_get_one::<String>("opt-level")
+            .ok()
+            .flatten()
+            .map_or(0, |x| x.parse().unwrap()),
+        strict_init: matches.get_flag("strict-init"),
+        ..Config::default()
+    }
+}
+
+fn int_width(matches: &ArgMatches) -> IntWidth {
+    matches
+        .get_one::<String>("int-width")
+        .map_or(IntWidth::default(), |x| x.parse().unwrap())
+}
+
+fn overflow(matches: &ArgMatches) -> OverflowPolicy {
+    matches
+        .get_one::<String>("overflow")
+        .map_or(OverflowPolicy::default(), |x| x.parse().unwrap())
+}
+
+/// Runs sema, printing every diagnostic, and exits on errors.
+fn check_or_exit(input_file_path: &str, input_file: &str, options: &sema::Options) {
+    let mut diagnostics = match analyze(input_file, options) {
+        Ok(model) => model.diagnostics,
+        Err(diagnostics) => diagnostics,
+    };
+    add_lints(input_file, options, &mut diagnostics);
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic.render(input_file_path, input_file));
+    }
+    if has_errors(&diagnostics) {
+        std::process::exit(3);
+    }
+}
+
+fn read_input(matches: &ArgMatches) -> (String, String) {
+    let input_file_path = matches.get_one::<String>("INPUT").unwrap_or_else(|| {
+        eprintln!("Error: no input file specified");
+        std::process::exit(1);
+    });
+
+    let input_file = fs::read_to_string(input_file_path).unwrap_or_else(|err| {
+        eprintln!("Error: {err}");
+        std::process::exit(2);
+    });
+
+    (input_file_path.clone(), input_file)
+}
+
+/// Prints lexical errors and exits, so nothing malformed reaches a parser.
+fn lex_or_exit(input_file_path: &str, input_file: &str) {
+    if let Err(diagnostics) = check_lexical(input_file) {
+        for diagnostic in &diagnostics {
+            eprintln!("{}", diagnostic.render(input_file_path, input_file));
+        }
+        std::process::exit(3);
+    }
+}
+
+/// Parses the syntax tree, printing a syntax error as `check` does and
+/// exiting on it.
+fn cst_or_exit(input_file_path: &str, input_file: &str, case_insensitive: bool) -> SyntaxNode {
+    // folding keeps every offset, so errors still point into the file
+    let folded;
+    let src = if case_insensitive {
+        folded = fold_keywords(input_file);
+        folded.as_str()
+    } else {
+        input_file
+    };
+    cst::parse(src).unwrap_or_else(|err| {
+        let diagnostic = Diagnostic::from(err);
+        eprintln!("{}", diagnostic.render(input_file_path, input_file));
+        std::process::exit(3);
+    })
+}
+
 fn main() {
     //use clap mod to read user input file path
     let matches = Command::new("snlc")
         .version("0.1")
         .author("yurzi")
         .about("compiler for snl")
-        .arg(
-            Arg::new("INPUT")
-                .help("Sets the input file to use")
-                .required(true)
-                .index(1),
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .arg(input_arg())
+        .arg(int_width_arg())
+        .arg(overflow_arg())
+        .arg(opt_level_arg())
+        .arg(case_insensitive_arg())
+        .arg(strict_init_arg())
+        .subcommand(
+            Command::new("emit")
+                .about("emit an intermediate representation to stdout")
+                .arg(
+                    Arg::new("emit")
+                        .long("emit")
+                        .help("Sets the representation to emit")
+                        .value_parser(["ast-dot", "cfg-dot", "cst", "ir", "asm", "asm-diff"])
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("passes")
+                        .long("passes")
+                        .help("Runs comma separated IR passes in order, with --emit=ir")
+                        .default_value(""),
+                )
+                .arg(
+                    Arg::new("print-after")
+                        .long("print-after")
+                        .help("Prints the IR after the named passes, or after all of them")
+                        .default_value(""),
+                )
+                .arg(
+                    Arg::new("regalloc")
+                        .long("regalloc")
+                        .help("Sets the register allocator, with --emit=asm")
+                        .value_parser(["linear-scan", "spill"])
+                        .default_value("linear-scan"),
+                )
+                .arg(
+                    Arg::new("peephole")
+                        .long("peephole")
+                        .help("Applies the peephole rules, with --emit=asm")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(int_width_arg())
+                .arg(overflow_arg())
+                .arg(opt_level_arg())
+                .arg(case_insensitive_arg())
+                .arg(strict_init_arg())
+                .arg(input_arg()),
+        )
+        .subcommand(
+            Command::new("parse")
+                .about("parse a file and print its syntax tree")
+                .arg(
+                    Arg::new("parser")
+                        .long("parser")
+                        .help("Sets the parser backend")
+                        .value_parser(["rd", "ll1"])
+                        .default_value("rd"),
+                )
+                .arg(
+                    Arg::new("dump-table")
+                        .long("dump-table")
+                        .help("Prints the LL(1) parse table")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    input_arg()
+                        .required(false)
+                        .required_unless_present("dump-table"),
+                ),
+        )
+        .subcommand(
+            Command::new("grammar")
+                .about("analyze a BNF grammar, the builtin SNL grammar by default")
+                .arg(
+                    Arg::new("INPUT")
+                        .help("Sets the grammar file to use")
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            Command::new("fmt")
+                .about("format SNL source files in place")
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .help("Reports unformatted files instead of rewriting them")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(input_arg().num_args(1..)),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("check a file for syntax and type errors")
+                .arg(int_width_arg())
+                .arg(case_insensitive_arg())
+                .arg(strict_init_arg())
+                .arg(input_arg()),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("run a file with the interpreter")
+                .arg(
+                    Arg::new("trace")
+                        .long("trace")
+                        .help("Prints every statement run and the variables it changed to stderr")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("profile")
+                        .long("profile")
+                        .help("Prints how often each line and procedure ran and for how long to stderr")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("trace"),
+                )
+                .args(limit_args())
+                .arg(int_width_arg())
+                .arg(overflow_arg())
+                .arg(opt_level_arg())
+                .arg(case_insensitive_arg())
+                .arg(strict_init_arg())
+                .arg(input_arg()),
+        )
+        .subcommand(
+            Command::new("debug")
+                .about("run a file with the interpreter under a debugger")
+                .arg(
+                    Arg::new("stdin")
+                        .long("stdin")
+                        .help("Reads the program's input from the given file"),
+                )
+                .arg(int_width_arg())
+                .arg(overflow_arg())
+                .arg(case_insensitive_arg())
+                .arg(strict_init_arg())
+                .arg(input_arg()),
+        )
+        .subcommand(
+            Command::new("test")
+                .about("run the golden tests of a directory")
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .help("Sets what runs the programs")
+                        .value_parser(["interp", "rust", "vm"])
+                        .default_value("interp"),
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .help("Sets the time limit in seconds of every test")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::new("junit")
+                        .long("junit")
+                        .help("Writes a JUnit XML report to the given file"),
+                )
+                .args(limit_args())
+                .arg(int_width_arg())
+                .arg(overflow_arg())
+                .arg(opt_level_arg())
+                .arg(case_insensitive_arg())
+                .arg(strict_init_arg())
+                .arg(
+                    Arg::new("DIR")
+                        .help("Sets the directory of the tests")
+                        .required(true)
+                        .index(1),
+                ),
         )
         .get_matches();
 
-    let input_file_path = matches.get_one::<String>("INPUT").unwrap_or_else(|| {
-        eprintln!("Error: no input file specified");
-        std::process::exit(1);
-    });
+    match matches.subcommand() {
+        Some(("emit", matches)) => emit(matches),
+        Some(("parse", matches)) => parse(matches),
+        Some(("grammar", matches)) => grammar(matches),
+        Some(("fmt", matches)) => fmt(matches),
+        Some(("check", matches)) => check(matches),
+        Some(("run", matches)) => run(matches),
+        Some(("debug", matches)) => debug(matches),
+        Some(("test", matches)) => test(matches),
+        _ => build(&matches),
+    }
+}
+
+fn build(matches: &ArgMatches) {
+    let (input_file_path, input_file) = read_input(matches);
+    let options = sema_options(matches);
+    check_or_exit(&input_file_path, &input_file, &options);
 
     //get file basename
-    let src_file_name = input_file_path.split('/').last().unwrap_or_else(|| {
+    let src_file_name = input_file_path.split('/').next_back().unwrap_or_else(|| {
         eprintln!("Error: invalid input file path");
         std::process::exit(1);
     });
@@ -35,24 +406,337 @@ fn main() {
 
     let target_file_name = format!("src/bin/{}.rs", src_file_name);
 
+    let pre_process_file = if options.case_insensitive {
+        preprocess_ignore_case(input_file.as_str())
+    } else {
+        preprocess(input_file.as_str())
+    };
+    let ir_file = gen_ir_file(pre_process_file, &input_file_path, &config(matches));
 
-    let input_file = fs::read_to_string(input_file_path).unwrap_or_else(|err| {
+    fs::write(target_file_name, ir_file).unwrap();
+}
+
+fn emit(matches: &ArgMatches) {
+    let (input_file_path, input_file) = read_input(matches);
+    lex_or_exit(&input_file_path, &input_file);
+
+    let case_insensitive = matches.get_flag("case-insensitive");
+    if matches!(
+        matches.get_one::<String>("emit").map(String::as_str),
+        Some("ir" | "asm" | "asm-diff")
+    ) {
+        emit_ir(matches, &input_file_path, &input_file);
+        return;
+    }
+
+    // syntax errors read as they do in `check`
+    let tree = cst_or_exit(&input_file_path, &input_file, case_insensitive);
+    if matches.get_one::<String>("emit").map(String::as_str) == Some("cst") {
+        print!("{:#?}", tree);
+        return;
+    }
+    let map = SourceMap::new(&input_file, case_insensitive);
+    let diagnostic = |err: syn::Error| {
+        let message = err.to_string().replace("r#", "");
+        Diagnostic::error(map.range(err.span()), message)
+    };
+    let mut prog = parse_program_mapped(&input_file, case_insensitive)
+        .map(|(prog, _)| prog)
+        .unwrap_or_else(|err| {
+            eprintln!("{}", diagnostic(err).render(&input_file_path, &input_file));
+            std::process::exit(3);
+        });
+    let config = config(matches);
+    if config.opt_level >= 1 {
+        let errors = opt::optimize(&mut prog, &config);
+        for err in &errors {
+            eprintln!(
+                "{}",
+                diagnostic(err.clone()).render(&input_file_path, &input_file)
+            );
+        }
+        if !errors.is_empty() {
+            std::process::exit(3);
+        }
+    }
+
+    let output = match matches.get_one::<String>("emit").map(String::as_str) {
+        Some("ast-dot") => ast_to_dot(&prog),
+        Some("cfg-dot") => cfg_to_dot(&build_program_cfgs(&prog)),
+        _ => unreachable!(),
+    };
+    print!("{output}");
+}
+
+fn emit_ir(matches: &ArgMatches, input_file_path: &str, input_file: &str) {
+    let passes = PassManager::new(
+        matches.get_one::<String>("passes").unwrap(),
+        matches.get_one::<String>("print-after").unwrap(),
+    )
+    .unwrap_or_else(|err| {
         eprintln!("Error: {err}");
         std::process::exit(2);
     });
+    let mut prog = compile_or_exit(input_file_path, input_file, &sema_options(matches));
+    let config = config(matches);
+    if config.opt_level >= 1 {
+        interp::optimize(&mut prog, &config);
+    }
+    let mut module = ir::lower(&prog);
+    let mut stdout = io::stdout().lock();
+    passes.run(&mut module, &config, &mut stdout).unwrap();
+    let allocator = matches.get_one::<String>("regalloc").unwrap();
+    let allocator = allocator.parse().unwrap();
+    match matches.get_one::<String>("emit").map(String::as_str) {
+        Some("asm") => {
+            let mut code = vm::codegen::compile(&module, allocator);
+            if matches.get_flag("peephole") {
+                vm::peephole::optimize(&mut code);
+            }
+            print!("{code}");
+        }
+        Some("asm-diff") => {
+            let mut code = vm::codegen::compile(&module, allocator);
+            let before = code.to_string();
+            vm::peephole::optimize(&mut code);
+            print!(
+                "{}",
+                unified_diff(&before, &code.to_string(), "before", "after")
+            );
+        }
+        _ => print!("{module}"),
+    }
+}
 
-    let tokens = Token::from_str(input_file.as_str());
-    // if a token is keyword, then add 'r#' before to its lexeme
-    let tokens = tokens.into_iter().map(|token| {
-        let mut lexeme = token.lexeme;
-        if token.kind == TokenKind::Keyword {
-            lexeme.insert_str(0, "r#");
+fn parse(matches: &ArgMatches) {
+    let ll1 = Ll1Parser::snl();
+    if matches.get_flag("dump-table") {
+        print!("{}", ll1.table.dump(&ll1.grammar));
+        if !matches.contains_id("INPUT") {
+            return;
         }
-        Token::new(token.kind, token.pos, lexeme)
-    }).collect::<Vec<Token>>();
+    }
 
-    let pre_process_file = Token::to_str(tokens);
-    let ir_file = gen_ir_file(pre_process_file);
+    let (input_file_path, input_file) = read_input(matches);
+    lex_or_exit(&input_file_path, &input_file);
+    let prog = match matches.get_one::<String>("parser").map(String::as_str) {
+        Some("ll1") => ll1
+            .parse_program(input_file.as_str())
+            .map_err(|err| err.render(&input_file)),
+        _ => parse_program(input_file.as_str()).map_err(|err| err.to_string()),
+    };
+    let prog = prog.unwrap_or_else(|err| {
+        eprintln!("Error: {err}");
+        std::process::exit(3);
+    });
+    println!("{:#?}", prog);
+}
 
-    fs::write(target_file_name, ir_file).unwrap();
+fn grammar(matches: &ArgMatches) {
+    let text = if matches.contains_id("INPUT") {
+        read_input(matches).1
+    } else {
+        SNL_GRAMMAR.to_string()
+    };
+
+    let grammar = Grammar::parse(text.as_str()).unwrap_or_else(|err| {
+        eprintln!("Error: {err}");
+        std::process::exit(3);
+    });
+    let report = GrammarReport::new(&grammar);
+    println!("{report}");
+    if !report.is_ll1() {
+        std::process::exit(1);
+    }
+}
+
+fn fmt(matches: &ArgMatches) {
+    let check = matches.get_flag("check");
+    let mut unformatted = false;
+    for input_file_path in matches.get_many::<String>("INPUT").into_iter().flatten() {
+        let input_file = fs::read_to_string(input_file_path).unwrap_or_else(|err| {
+            eprintln!("Error: {err}");
+            std::process::exit(2);
+        });
+        lex_or_exit(input_file_path, &input_file);
+
+        let formatted = format_source(input_file.as_str()).unwrap_or_else(|err| {
+            eprintln!("Error: {}:{}", input_file_path, err.render(&input_file));
+            std::process::exit(3);
+        });
+        if formatted == input_file {
+            continue;
+        }
+
+        if check {
+            println!("would reformat {}", input_file_path);
+            unformatted = true;
+        } else {
+            fs::write(input_file_path, formatted).unwrap_or_else(|err| {
+                eprintln!("Error: {err}");
+                std::process::exit(2);
+            });
+        }
+    }
+
+    if unformatted {
+        std::process::exit(1);
+    }
+}
+
+fn check(matches: &ArgMatches) {
+    let (input_file_path, input_file) = read_input(matches);
+    let options = sema_options(matches);
+    check_or_exit(&input_file_path, &input_file, &options);
+}
+
+/// Compiles for the interpreter, printing warnings, and exits on errors.
+fn compile_or_exit(input_file_path: &str, input_file: &str, options: &sema::Options) -> Program {
+    match interp::compile(input_file, options) {
+        Ok((prog, warnings)) => {
+            for diagnostic in &warnings {
+                eprintln!("{}", diagnostic.render(input_file_path, input_file));
+            }
+            prog
+        }
+        Err(diagnostics) => {
+            for diagnostic in &diagnostics {
+                eprintln!("{}", diagnostic.render(input_file_path, input_file));
+            }
+            std::process::exit(3);
+        }
+    }
+}
+
+fn run(matches: &ArgMatches) {
+    let (input_file_path, input_file) = read_input(matches);
+    let mut prog = compile_or_exit(&input_file_path, &input_file, &sema_options(matches));
+    let config = config(matches);
+    if config.opt_level >= 1 {
+        interp::optimize(&mut prog, &config);
+    }
+
+    let mut tracer;
+    let mut profiler = Profiler::new();
+    let observer: &mut dyn Observer = if matches.get_flag("trace") {
+        tracer = Tracer::new(&input_file, io::stderr());
+        &mut tracer
+    } else if matches.get_flag("profile") {
+        &mut profiler
+    } else {
+        &mut ()
+    };
+    let res = interp::run_observed(
+        &prog,
+        &config,
+        io::stdin().lock(),
+        io::stdout().lock(),
+        &limits(matches),
+        observer,
+    );
+    // a profile helps most when the program fails
+    if matches.get_flag("profile") {
+        eprint!("{}", profiler.report(&input_file));
+    }
+    if let Err(err) = res {
+        eprintln!("{}", err.render(&input_file_path));
+        std::process::exit(1);
+    }
+}
+
+fn debug(matches: &ArgMatches) {
+    let (input_file_path, input_file) = read_input(matches);
+    let prog = compile_or_exit(&input_file_path, &input_file, &sema_options(matches));
+    let stdin = match matches.get_one::<String>("stdin") {
+        Some(path) => fs::read(path).unwrap_or_else(|err| {
+            eprintln!("Error: {path}: {err}");
+            std::process::exit(2);
+        }),
+        None => Vec::new(),
+    };
+
+    // commands come from standard input, so the program reads a file
+    let mut debugger = Debugger::new(&input_file, &prog, io::stdin().lock(), io::stdout());
+    let res = interp::run_observed(
+        &prog,
+        &config(matches),
+        &stdin[..],
+        io::stdout(),
+        &Limits::default(),
+        &mut debugger,
+    );
+    match res {
+        Ok(()) => println!("program exited"),
+        Err(err) if err.kind == ErrorKind::Stopped => {}
+        Err(err) => {
+            println!("{}", err.render(&input_file_path));
+            std::process::exit(1);
+        }
+    }
+}
+
+fn test(matches: &ArgMatches) {
+    let dir = Path::new(matches.get_one::<String>("DIR").unwrap());
+    let cases = golden::find_cases(dir).unwrap_or_else(|err| {
+        eprintln!("Error: {}: {err}", dir.display());
+        std::process::exit(2);
+    });
+    let options = golden::Options {
+        backend: match matches.get_one::<String>("backend").map(String::as_str) {
+            Some("rust") => Backend::Rust,
+            Some("vm") => Backend::Vm,
+            _ => Backend::Interp,
+        },
+        sema: sema_options(matches),
+        config: config(matches),
+        timeout: Duration::from_secs_f64(*matches.get_one::<f64>("timeout").unwrap()),
+        limits: limits(matches),
+    };
+
+    println!("\nrunning {} tests", cases.len());
+    let mut stdout = io::stdout().lock();
+    let results = golden::run_cases(&cases, &options, &mut stdout).unwrap_or_else(|err| {
+        eprintln!("Error: {err}");
+        std::process::exit(2);
+    });
+
+    let failed = results
+        .iter()
+        .filter(|x| !matches!(x.outcome, Outcome::Pass))
+        .collect::<Vec<_>>();
+    if !failed.is_empty() {
+        writeln!(stdout, "\nfailures:").unwrap();
+        for result in &failed {
+            writeln!(stdout, "\n---- {} ----", result.name).unwrap();
+            match &result.outcome {
+                Outcome::Fail(report) => write!(stdout, "{}", report).unwrap(),
+                Outcome::TimedOut => {
+                    writeln!(stdout, "timed out after {:?}", options.timeout).unwrap()
+                }
+                Outcome::Error(err) => writeln!(stdout, "{}", err).unwrap(),
+                Outcome::Pass => unreachable!(),
+            }
+        }
+    }
+    writeln!(
+        stdout,
+        "\ntest result: {}. {} passed; {} failed",
+        if failed.is_empty() { "ok" } else { "FAILED" },
+        results.len() - failed.len(),
+        failed.len()
+    )
+    .unwrap();
+
+    if let Some(path) = matches.get_one::<String>("junit") {
+        let suite = dir
+            .file_name()
+            .map_or("snlc".into(), |x| x.to_string_lossy());
+        fs::write(path, golden::junit(&suite, &results)).unwrap_or_else(|err| {
+            eprintln!("Error: {path}: {err}");
+            std::process::exit(2);
+        });
+    }
+    if !failed.is_empty() {
+        std::process::exit(1);
+    }
 }
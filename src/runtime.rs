@@ -0,0 +1,271 @@
+//! Support code for the programs `snlc` generates.
+//!
+//! `read` takes whitespace separated tokens from standard input, a token
+//! may be on the same line as the previous one or on any later line.
+//!
+//! Runtime errors are the same for every backend: a `RuntimeError` names
+//! the SNL line and the procedures running, and `render` adds the file.
+//! Generated code keeps the running procedures with `enter` and `leave`
+//! and stops through `fail`, the interpreter and the VM return the error.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, BufRead, StdinLock, Write};
+use std::num::IntErrorKind;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadError {
+    Eof,
+    InvalidInteger(String),
+    IntegerOutOfRange { token: String, bits: u32 },
+    Io(String),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Eof => write!(f, "unexpected end of input"),
+            ReadError::InvalidInteger(token) => write!(f, "invalid integer input `{}`", token),
+            ReadError::IntegerOutOfRange { token, bits } => write!(
+                f,
+                "integer input `{}` does not fit in a {}-bit integer",
+                token, bits
+            ),
+            ReadError::Io(err) => write!(f, "cannot read input: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    DivisionByZero,
+    Overflow,
+    Input(ReadError),
+    Output(String),
+    Timeout(Duration),
+    /// A read of a variable never assigned, under `strict_init`.
+    Unassigned,
+    /// An `Observer` ended the run.
+    Stopped,
+    /// More statements, or machine instructions, than `interp::Limits::steps`.
+    StepLimit(u64),
+    /// A call deeper than `interp::Limits::depth`.
+    DepthLimit(usize),
+    /// More output than `interp::Limits::output` bytes.
+    OutputLimit(usize),
+    /// More values read than `interp::Limits::reads`.
+    ReadLimit(u64),
+    /// An array index outside the bounds of its type. Sema rejects arrays
+    /// for now, so no backend raises it yet.
+    IndexOutOfBounds {
+        index: i64,
+        low: i64,
+        top: i64,
+    },
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::DivisionByZero => write!(f, "division by zero"),
+            ErrorKind::Overflow => write!(f, "integer overflow"),
+            ErrorKind::Input(err) => write!(f, "{}", err),
+            ErrorKind::Output(err) => write!(f, "cannot write output: {}", err),
+            ErrorKind::Timeout(time) => write!(f, "time limit of {:?} exceeded", time),
+            ErrorKind::Unassigned => write!(f, "read of an unassigned variable"),
+            ErrorKind::Stopped => write!(f, "program stopped"),
+            ErrorKind::StepLimit(n) => write!(f, "step limit of {} exceeded", n),
+            ErrorKind::DepthLimit(n) => write!(f, "call depth limit of {} exceeded", n),
+            ErrorKind::OutputLimit(n) => write!(f, "output limit of {} bytes exceeded", n),
+            ErrorKind::ReadLimit(n) => write!(f, "read limit of {} values exceeded", n),
+            ErrorKind::IndexOutOfBounds { index, low, top } => {
+                write!(f, "array index {} out of bounds {}..{}", index, low, top)
+            }
+        }
+    }
+}
+
+/// A running procedure and the line it was called from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub name: String,
+    pub line: usize,
+}
+
+/// An error that stops the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub line: usize,
+    /// Procedures running, innermost first, empty in the program body.
+    pub stack: Vec<Frame>,
+}
+
+impl RuntimeError {
+    /// The error with the line in `path` and the procedures running, one
+    /// per line.
+    pub fn render(&self, path: &str) -> String {
+        let mut res = match self.line {
+            0 => format!("{}: runtime error: {}", path, self.kind),
+            line => format!("{}:{}: runtime error: {}", path, line, self.kind),
+        };
+        for frame in &self.stack {
+            res.push_str(&format!(
+                "\n    in {}, called at {}:{}",
+                frame.name, path, frame.line
+            ));
+        }
+        res
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "runtime error: {} at line {}", self.kind, self.line)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Whitespace separated input over a line reader.
+pub struct Input<R> {
+    reader: R,
+    line: String,
+    pos: usize,
+}
+
+impl<R: BufRead> Input<R> {
+    pub fn new(reader: R) -> Input<R> {
+        Input {
+            reader,
+            line: String::new(),
+            pos: 0,
+        }
+    }
+
+    /// Skips whitespace, reading more lines as needed. False at the end of
+    /// the input.
+    fn skip_whitespace(&mut self) -> Result<bool, ReadError> {
+        loop {
+            let rest = &self.line[self.pos..];
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if !trimmed.is_empty() {
+                return Ok(true);
+            }
+            self.line.clear();
+            self.pos = 0;
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return Ok(false),
+                Ok(_) => {}
+                Err(err) => return Err(ReadError::Io(err.to_string())),
+            }
+        }
+    }
+
+    /// The next run of non-whitespace characters.
+    pub fn token(&mut self) -> Result<String, ReadError> {
+        if !self.skip_whitespace()? {
+            return Err(ReadError::Eof);
+        }
+        let rest = &self.line[self.pos..];
+        let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.pos += len;
+        Ok(rest[..len].to_string())
+    }
+
+    /// The next non-whitespace character, the rest of its token is left
+    /// for the following read.
+    pub fn char(&mut self) -> Result<char, ReadError> {
+        if !self.skip_whitespace()? {
+            return Err(ReadError::Eof);
+        }
+        let c = self.line[self.pos..].chars().next().unwrap();
+        self.pos += c.len_utf8();
+        Ok(c)
+    }
+
+    pub fn read<T: FromInput>(&mut self) -> Result<T, ReadError> {
+        T::from_input(self)
+    }
+}
+
+/// A type `read` can fill a variable of.
+pub trait FromInput: Sized {
+    fn from_input<R: BufRead>(input: &mut Input<R>) -> Result<Self, ReadError>;
+}
+
+impl FromInput for char {
+    fn from_input<R: BufRead>(input: &mut Input<R>) -> Result<Self, ReadError> {
+        input.char()
+    }
+}
+
+macro_rules! impl_from_input {
+    ($($ty:ty),*) => {$(
+        impl FromInput for $ty {
+            fn from_input<R: BufRead>(input: &mut Input<R>) -> Result<Self, ReadError> {
+                let token = input.token()?;
+                token.parse().map_err(|err: std::num::ParseIntError| match err.kind() {
+                    IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+                        ReadError::IntegerOutOfRange { token, bits: <$ty>::BITS }
+                    }
+                    _ => ReadError::InvalidInteger(token),
+                })
+            }
+        }
+    )*};
+}
+
+impl_from_input!(i16, i32, i64);
+
+thread_local! {
+    static STDIN: RefCell<Input<StdinLock<'static>>> = RefCell::new(Input::new(io::stdin().lock()));
+    static FILE: RefCell<String> = const { RefCell::new(String::new()) };
+    /// Procedures running and the lines they were called from.
+    static CALLS: RefCell<Vec<(&'static str, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Reads the next value from standard input, bad input ends the program
+/// with an error naming the SNL `line` of the read.
+pub fn read<T: FromInput>(line: usize) -> T {
+    match STDIN.with(|x| x.borrow_mut().read()) {
+        Ok(value) => value,
+        Err(err) => fail(ErrorKind::Input(err), line),
+    }
+}
+
+/// Names the SNL file errors are reported in.
+pub fn set_file(path: &str) {
+    FILE.with(|x| *x.borrow_mut() = path.to_string());
+}
+
+/// Notes a call of the procedure `name` at `line`, until `leave`.
+pub fn enter(name: &'static str, line: usize) {
+    CALLS.with(|x| x.borrow_mut().push((name, line)));
+}
+
+pub fn leave() {
+    CALLS.with(|x| x.borrow_mut().pop());
+}
+
+/// Ends the program with the runtime error `kind` at the SNL `line`.
+pub fn fail(kind: ErrorKind, line: usize) -> ! {
+    let stack = CALLS.with(|x| {
+        x.borrow()
+            .iter()
+            .rev()
+            .map(|&(name, line)| Frame {
+                name: name.to_string(),
+                line,
+            })
+            .collect()
+    });
+    let err = RuntimeError { kind, line, stack };
+    let _ = io::stdout().flush();
+    eprintln!("{}", FILE.with(|x| err.render(&x.borrow())));
+    std::process::exit(1)
+}
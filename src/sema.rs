@@ -0,0 +1,689 @@
+//! Name resolution and type checking over the concrete syntax tree.
+
+use std::fmt;
+use std::ops::Range;
+
+use snlc_ast::codegen::IntWidth;
+use snlc_lexer::unescape::unescape_char;
+
+use crate::cst::ast::*;
+use crate::cst::{SyntaxNode, SyntaxToken};
+use crate::diagnostic::Diagnostic;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ty {
+    Char,
+    Integer,
+    /// Result of a comparison, only valid as an `if`/`while` condition.
+    Bool,
+    /// A string literal, only valid as an argument of `write`.
+    Str,
+}
+
+impl Ty {
+    fn from_keyword(token: &SyntaxToken) -> Ty {
+        match token.text() {
+            "char" => Ty::Char,
+            _ => Ty::Integer,
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Char => write!(f, "char"),
+            Ty::Integer => write!(f, "integer"),
+            Ty::Bool => write!(f, "boolean"),
+            Ty::Str => write!(f, "string"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolKind {
+    Var(Ty),
+    Param(Ty),
+    Procedure(Vec<Ty>),
+}
+
+pub type SymbolId = usize;
+pub type ScopeId = usize;
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub scope: ScopeId,
+    /// Range of the name at its declaration.
+    pub def: Range<usize>,
+}
+
+impl Symbol {
+    /// Type of a variable or parameter.
+    pub fn ty(&self) -> Option<Ty> {
+        match self.kind {
+            SymbolKind::Var(ty) | SymbolKind::Param(ty) => Some(ty),
+            SymbolKind::Procedure(_) => None,
+        }
+    }
+
+    /// Human readable declaration, e.g. `var i: integer`.
+    pub fn signature(&self) -> String {
+        match &self.kind {
+            SymbolKind::Var(ty) => format!("var {}: {}", self.name, ty),
+            SymbolKind::Param(ty) => format!("param {}: {}", self.name, ty),
+            SymbolKind::Procedure(params) => {
+                let params = params.iter().map(Ty::to_string).collect::<Vec<_>>();
+                format!("procedure {}({})", self.name, params.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Scope {
+    pub parent: Option<ScopeId>,
+    /// Name of the procedure owning the scope, `None` for the program.
+    pub procedure: Option<String>,
+    pub symbols: Vec<SymbolId>,
+}
+
+/// A use of a symbol in the source, declarations included.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub symbol: SymbolId,
+    pub range: Range<usize>,
+}
+
+/// Everything sema learned about a file.
+#[derive(Debug, Default)]
+pub struct SemanticModel {
+    pub symbols: Vec<Symbol>,
+    pub scopes: Vec<Scope>,
+    pub references: Vec<Reference>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Names differing only in ASCII case are the same name.
+    pub case_insensitive: bool,
+}
+
+impl SemanticModel {
+    /// The symbol referenced or declared at byte `offset`.
+    pub fn symbol_at(&self, offset: usize) -> Option<SymbolId> {
+        self.references
+            .iter()
+            .find(|x| x.range.contains(&offset))
+            .map(|x| x.symbol)
+    }
+
+    /// Every reference to `symbol`, its declaration first.
+    pub fn references_to(&self, symbol: SymbolId) -> Vec<Range<usize>> {
+        self.references
+            .iter()
+            .filter(|x| x.symbol == symbol)
+            .map(|x| x.range.clone())
+            .collect()
+    }
+
+    pub fn same_name(&self, a: &str, b: &str) -> bool {
+        if self.case_insensitive {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
+        }
+    }
+
+    pub fn lookup(&self, mut scope: ScopeId, name: &str) -> Option<SymbolId> {
+        loop {
+            let found = self.scopes[scope]
+                .symbols
+                .iter()
+                .rev()
+                .find(|&&x| self.same_name(&self.symbols[x].name, name));
+            if let Some(&found) = found {
+                return Some(found);
+            }
+            scope = self.scopes[scope].parent?;
+        }
+    }
+}
+
+/// Settings that change what sema accepts.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub int_width: IntWidth,
+    /// Keywords and identifiers ignore ASCII case, see
+    /// `frontend::fold_keywords`.
+    pub case_insensitive: bool,
+    /// Reads of possibly unassigned variables are errors, see `lint`.
+    pub strict_init: bool,
+}
+
+struct Checker<'a> {
+    options: &'a Options,
+    model: SemanticModel,
+    scope: ScopeId,
+}
+
+impl Checker<'_> {
+    fn error(&mut self, range: Range<usize>, message: String) {
+        self.model
+            .diagnostics
+            .push(Diagnostic::error(range, message));
+    }
+
+    fn new_scope(&mut self, procedure: Option<String>) -> ScopeId {
+        let parent = if self.model.scopes.is_empty() {
+            None
+        } else {
+            Some(self.scope)
+        };
+        self.model.scopes.push(Scope {
+            parent,
+            procedure,
+            symbols: Vec::new(),
+        });
+        self.model.scopes.len() - 1
+    }
+
+    fn declare(&mut self, token: &SyntaxToken, kind: SymbolKind) -> SymbolId {
+        let name = token.text().to_string();
+        let duplicate = self.model.scopes[self.scope]
+            .symbols
+            .iter()
+            .any(|&x| self.model.same_name(&self.model.symbols[x].name, &name));
+        if duplicate {
+            self.error(
+                token.text_range(),
+                format!("`{}` is declared more than once in this scope", name),
+            );
+        }
+
+        let id = self.model.symbols.len();
+        self.model.symbols.push(Symbol {
+            name,
+            kind,
+            scope: self.scope,
+            def: token.text_range(),
+        });
+        self.model.scopes[self.scope].symbols.push(id);
+        self.model.references.push(Reference {
+            symbol: id,
+            range: token.text_range(),
+        });
+        id
+    }
+
+    fn resolve(&mut self, token: &SyntaxToken) -> Option<SymbolId> {
+        match self.model.lookup(self.scope, token.text()) {
+            Some(id) => {
+                let declared = &self.model.symbols[id].name;
+                if declared != token.text() {
+                    let message = format!(
+                        "inconsistent spelling: `{}` is declared as `{}`",
+                        token.text(),
+                        declared
+                    );
+                    self.model
+                        .diagnostics
+                        .push(Diagnostic::warning(token.text_range(), message));
+                }
+                self.model.references.push(Reference {
+                    symbol: id,
+                    range: token.text_range(),
+                });
+                Some(id)
+            }
+            None => {
+                self.error(
+                    token.text_range(),
+                    format!("cannot find `{}` in this scope", token.text()),
+                );
+                None
+            }
+        }
+    }
+
+    /// Resolves a variable or parameter and returns its type.
+    fn resolve_var(&mut self, token: &SyntaxToken) -> Option<Ty> {
+        let id = self.resolve(token)?;
+        let ty = self.model.symbols[id].ty();
+        if ty.is_none() {
+            self.error(
+                token.text_range(),
+                format!("expected a variable, found procedure `{}`", token.text()),
+            );
+        }
+        ty
+    }
+
+    fn var_part(&mut self, var_part: Option<VarPart>) {
+        for var_dec in var_part.map(|x| x.var_decs()).unwrap_or_default() {
+            let Some(ty) = var_dec.type_name() else {
+                continue;
+            };
+            let ty = Ty::from_keyword(&ty);
+            for name in var_dec.names() {
+                self.declare(&name, SymbolKind::Var(ty));
+            }
+        }
+    }
+
+    fn program(&mut self, prog: &Program) {
+        self.scope = self.new_scope(None);
+        self.var_part(prog.var_part());
+
+        for proc in prog.procedures() {
+            let Some(name) = proc.name() else {
+                continue;
+            };
+            let params = proc.params();
+            let tys = params
+                .iter()
+                .filter_map(|x| x.type_name())
+                .map(|x| Ty::from_keyword(&x))
+                .collect();
+            self.declare(&name, SymbolKind::Procedure(tys));
+
+            let global = self.scope;
+            self.scope = self.new_scope(Some(name.text().to_string()));
+            for param in params {
+                if let (Some(ty), Some(name)) = (param.type_name(), param.name()) {
+                    self.declare(&name, SymbolKind::Param(Ty::from_keyword(&ty)));
+                }
+            }
+            self.var_part(proc.var_part());
+            if let Some(body) = proc.body() {
+                self.stm_list(&body);
+            }
+            self.scope = global;
+        }
+
+        if let Some(body) = prog.body() {
+            self.stm_list(&body);
+        }
+    }
+
+    fn stm_list(&mut self, list: &StmList) {
+        for stm in list.stms() {
+            self.stm(&stm);
+        }
+    }
+
+    fn condition(&mut self, condition: &Exp) {
+        if let Some(ty) = self.exp(condition) {
+            if ty != Ty::Bool {
+                self.error(
+                    condition.syntax().trimmed_range(),
+                    format!("condition must be a comparison, found {}", ty),
+                );
+            }
+        }
+    }
+
+    fn stm(&mut self, stm: &Stm) {
+        let Some(kind) = stm.kind() else {
+            return;
+        };
+        match kind {
+            StmKind::If {
+                condition,
+                body,
+                else_body,
+            } => {
+                self.condition(&condition);
+                self.stm_list(&body);
+                if let Some(else_body) = else_body {
+                    self.stm_list(&else_body);
+                }
+            }
+            StmKind::While { condition, body } => {
+                self.condition(&condition);
+                self.stm_list(&body);
+            }
+            StmKind::Read { targets } => {
+                for target in &targets {
+                    self.resolve_var(target);
+                }
+            }
+            StmKind::Write { args, .. } => {
+                for arg in &args {
+                    self.write_arg(arg);
+                }
+            }
+            StmKind::Assign {
+                target,
+                index,
+                value,
+            } => {
+                let target_ty = self.resolve_var(&target);
+                if let Some(index) = index {
+                    self.error(
+                        index.syntax().trimmed_range(),
+                        "arrays are not supported".to_string(),
+                    );
+                }
+                let value_ty = if target_ty == Some(Ty::Char) {
+                    self.char_value(&value)
+                } else {
+                    self.value(&value)
+                };
+                if let (Some(expected), Some(found)) = (target_ty, value_ty) {
+                    if expected != found {
+                        self.error(
+                            value.syntax().trimmed_range(),
+                            format!("mismatched types: expected {}, found {}", expected, found),
+                        );
+                    }
+                }
+            }
+            StmKind::Call { name, args } => self.call(stm.syntax(), &name, &args),
+        }
+    }
+
+    fn call(&mut self, stm: &SyntaxNode, name: &SyntaxToken, args: &[Exp]) {
+        let arg_tys = args.iter().map(|x| self.value(x)).collect::<Vec<_>>();
+        let Some(id) = self.resolve(name) else {
+            return;
+        };
+        let symbol = self.model.symbols[id].clone();
+        let SymbolKind::Procedure(params) = &symbol.kind else {
+            self.error(
+                name.text_range(),
+                format!("`{}` is not a procedure", symbol.name),
+            );
+            return;
+        };
+
+        let current = self.model.scopes[self.scope].procedure.as_deref();
+        if current.is_some_and(|x| self.model.same_name(x, &symbol.name)) {
+            self.error(
+                name.text_range(),
+                format!("recursive call to `{}` is not supported", symbol.name),
+            );
+        }
+        if params.len() != args.len() {
+            self.error(
+                stm.trimmed_range(),
+                format!(
+                    "`{}` takes {} argument(s) but {} were supplied",
+                    symbol.name,
+                    params.len(),
+                    args.len()
+                ),
+            );
+            return;
+        }
+        for ((arg, found), expected) in args.iter().zip(arg_tys).zip(params) {
+            if let Some(found) = found {
+                if found != *expected {
+                    self.error(
+                        arg.syntax().trimmed_range(),
+                        format!("mismatched types: expected {}, found {}", expected, found),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Type of an expression used as a value, comparisons and strings are
+    /// rejected.
+    fn value(&mut self, exp: &Exp) -> Option<Ty> {
+        let ty = self.write_arg(exp)?;
+        if ty == Ty::Str {
+            self.string_error(exp.syntax().trimmed_range());
+            return None;
+        }
+        Some(ty)
+    }
+
+    /// `value` that also accepts a string.
+    fn write_arg(&mut self, exp: &Exp) -> Option<Ty> {
+        let ty = self.exp(exp)?;
+        if ty == Ty::Bool {
+            self.error(
+                exp.syntax().trimmed_range(),
+                "a comparison can only be used as a condition".to_string(),
+            );
+            return None;
+        }
+        Some(ty)
+    }
+
+    /// `value` where a char is required, a lone string literal there is
+    /// reported as a malformed character literal.
+    fn char_value(&mut self, exp: &Exp) -> Option<Ty> {
+        let lhs = exp.lhs().filter(|_| exp.cmp().is_none());
+        match lhs.as_ref().and_then(lone_string) {
+            Some(lit) => {
+                self.char_literal_error(&lit);
+                None
+            }
+            None => self.value(exp),
+        }
+    }
+
+    fn char_literal_error(&mut self, lit: &SyntaxToken) {
+        if let Err((err, _)) = unescape_char(lit.text()) {
+            self.error(lit.text_range(), err.to_string());
+        }
+    }
+
+    fn string_error(&mut self, range: Range<usize>) {
+        self.error(
+            range,
+            "a string can only be used as an argument of `write`".to_string(),
+        );
+    }
+
+    fn exp(&mut self, exp: &Exp) -> Option<Ty> {
+        let lhs = self.simple_exp(&exp.lhs()?);
+        let Some((op, rhs_exp)) = exp.cmp() else {
+            return lhs;
+        };
+        let rhs = self.simple_exp(&rhs_exp);
+        if let (Some(lhs), Some(rhs)) = (lhs, rhs) {
+            if lhs == Ty::Str || rhs == Ty::Str {
+                let lit = match (lhs, rhs) {
+                    (Ty::Char, Ty::Str) => lone_string(&rhs_exp),
+                    (Ty::Str, Ty::Char) => exp.lhs().as_ref().and_then(lone_string),
+                    _ => None,
+                };
+                match lit {
+                    Some(lit) => self.char_literal_error(&lit),
+                    None => self.string_error(exp.syntax().trimmed_range()),
+                }
+            } else if lhs != rhs {
+                self.error(
+                    exp.syntax().trimmed_range(),
+                    format!("cannot compare {} with {} using `{}`", lhs, rhs, op.text()),
+                );
+            }
+        }
+        Some(Ty::Bool)
+    }
+
+    fn arith(&mut self, op: &SyntaxToken, range: Range<usize>, ty: Option<Ty>) -> Option<Ty> {
+        match ty {
+            Some(Ty::Integer) | None => ty,
+            Some(ty) => {
+                self.error(
+                    range,
+                    format!("`{}` cannot be applied to {}", op.text(), ty),
+                );
+                None
+            }
+        }
+    }
+
+    fn simple_exp(&mut self, exp: &SimpleExp) -> Option<Ty> {
+        let mut ty = self.term(&exp.first()?);
+        let first_range = exp.first()?.syntax().trimmed_range();
+        for (i, (op, term)) in exp.rest().into_iter().enumerate() {
+            if i == 0 {
+                ty = self.arith(&op, first_range.clone(), ty);
+            }
+            let rhs = self.term(&term);
+            let rhs = self.arith(&op, term.syntax().trimmed_range(), rhs);
+            ty = ty.and(rhs);
+        }
+        ty
+    }
+
+    fn term(&mut self, term: &Term) -> Option<Ty> {
+        let mut ty = self.factor(&term.first()?);
+        let first_range = term.first()?.syntax().trimmed_range();
+        for (i, (op, factor)) in term.rest().into_iter().enumerate() {
+            if i == 0 {
+                ty = self.arith(&op, first_range.clone(), ty);
+            }
+            let rhs = self.factor(&factor);
+            let rhs = self.arith(&op, factor.syntax().trimmed_range(), rhs);
+            if op.text() == "/" && const_factor(&factor) == Some(0) {
+                self.error(
+                    factor.syntax().trimmed_range(),
+                    "division by zero".to_string(),
+                );
+            }
+            ty = ty.and(rhs);
+        }
+        ty
+    }
+
+    fn factor(&mut self, factor: &Factor) -> Option<Ty> {
+        match factor.kind()? {
+            FactorKind::Paren(exp) => self.value(&exp),
+            FactorKind::Int(lit) => {
+                let width = self.options.int_width;
+                let fits = lit.text().parse::<i64>().is_ok_and(|x| x <= width.max());
+                if !fits {
+                    self.error(
+                        lit.text_range(),
+                        format!(
+                            "integer literal `{}` does not fit in a {}-bit integer (max {})",
+                            lit.text(),
+                            width.bits(),
+                            width.max()
+                        ),
+                    );
+                }
+                Some(Ty::Integer)
+            }
+            FactorKind::Char(_) => Some(Ty::Char),
+            FactorKind::Str(_) => Some(Ty::Str),
+            FactorKind::Var(name) => self.resolve_var(&name),
+            FactorKind::Index(name, index) => {
+                self.resolve_var(&name);
+                self.value(&index);
+                self.error(
+                    factor.syntax().trimmed_range(),
+                    "arrays are not supported".to_string(),
+                );
+                None
+            }
+            FactorKind::Call(name, args) => {
+                for arg in &args {
+                    self.value(arg);
+                }
+                self.resolve(&name);
+                self.error(
+                    factor.syntax().trimmed_range(),
+                    format!("procedure `{}` does not return a value", name.text()),
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Value of an integer expression made of literals only.
+fn const_exp(exp: &Exp) -> Option<i128> {
+    if exp.cmp().is_some() {
+        return None;
+    }
+    let simple = exp.lhs()?;
+    let mut value = const_term(&simple.first()?)?;
+    for (op, term) in simple.rest() {
+        let rhs = const_term(&term)?;
+        value = match op.text() {
+            "+" => value.checked_add(rhs)?,
+            "-" => value.checked_sub(rhs)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+fn const_term(term: &Term) -> Option<i128> {
+    let mut value = const_factor(&term.first()?)?;
+    for (op, factor) in term.rest() {
+        let rhs = const_factor(&factor)?;
+        value = match op.text() {
+            "*" => value.checked_mul(rhs)?,
+            "/" => value.checked_div(rhs)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+fn const_factor(factor: &Factor) -> Option<i128> {
+    match factor.kind()? {
+        FactorKind::Int(lit) => lit.text().parse().ok(),
+        FactorKind::Paren(exp) => const_exp(&exp),
+        _ => None,
+    }
+}
+
+/// The string literal `exp` is, when it is nothing else.
+fn lone_string(exp: &SimpleExp) -> Option<SyntaxToken> {
+    let term = exp.first()?;
+    if !exp.rest().is_empty() || !term.rest().is_empty() {
+        return None;
+    }
+    match term.first()?.kind()? {
+        FactorKind::Str(lit) => Some(lit),
+        _ => None,
+    }
+}
+
+/// Resolves names and checks types of a parsed file.
+pub fn check(root: &SyntaxNode, options: &Options) -> SemanticModel {
+    let mut checker = Checker {
+        options,
+        model: SemanticModel {
+            case_insensitive: options.case_insensitive,
+            ..SemanticModel::default()
+        },
+        scope: 0,
+    };
+    if let Some(prog) = Program::cast(root.clone()) {
+        checker.program(&prog);
+    }
+    checker.model.diagnostics.sort_by_key(|x| x.range.start);
+    checker.model
+}
+
+/// Lexes, parses and checks `src`, lexical or syntax errors stop before
+/// sema runs.
+pub fn analyze(src: &str, options: &Options) -> Result<SemanticModel, Vec<Diagnostic>> {
+    analyze_tree(src, options).map(|(_, model)| model)
+}
+
+/// `analyze` that also returns the syntax tree the model refers to.
+pub fn analyze_tree(
+    src: &str,
+    options: &Options,
+) -> Result<(SyntaxNode, SemanticModel), Vec<Diagnostic>> {
+    crate::frontend::check_lexical(src)?;
+    let folded;
+    let src = if options.case_insensitive {
+        folded = crate::frontend::fold_keywords(src);
+        folded.as_str()
+    } else {
+        src
+    };
+    let root = crate::cst::parse(src).map_err(|err| vec![err.into()])?;
+    let model = check(&root, options);
+    Ok((root, model))
+}
@@ -0,0 +1,208 @@
+//! Tracing and profiling interpreted programs.
+//!
+//! Both are `interp::Observer`s. The tracer prints every statement before
+//! it runs and the variables it changed after, the profiler counts how
+//! often each line and procedure runs and how long it takes. A line's time
+//! is the time until the next statement starts, so time spent in a called
+//! procedure counts for the procedure's lines, not for the call. A
+//! procedure's time is from its first statement to its return.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+
+use crate::debugger::show;
+use crate::interp::{Observer, State, Value};
+
+pub struct Tracer<'a, O> {
+    source: Vec<&'a str>,
+    output: O,
+    /// Variables in scope before the last statement, `None` right after a
+    /// return.
+    before: Option<Vec<(String, Option<Value>)>>,
+}
+
+impl<'a, O: Write> Tracer<'a, O> {
+    /// A tracer for a program compiled from `source`, writing to `output`.
+    pub fn new(source: &'a str, output: O) -> Self {
+        Tracer {
+            source: source.lines().collect(),
+            output,
+            before: None,
+        }
+    }
+
+    /// Prints what the last statement changed.
+    fn changes(&mut self, state: &State<'_>) {
+        let after = variables(state);
+        if let Some(before) = self.before.replace(after.clone()) {
+            let changed = after
+                .iter()
+                .zip(&before)
+                .filter(|(x, y)| x != y)
+                .map(|((name, value), _)| format!("{} = {}", name, show(*value)))
+                .collect::<Vec<_>>();
+            if !changed.is_empty() {
+                let _ = writeln!(self.output, "    {}", changed.join(", "));
+            }
+        }
+    }
+}
+
+fn variables(state: &State<'_>) -> Vec<(String, Option<Value>)> {
+    state
+        .variables()
+        .into_iter()
+        .map(|(name, _, value)| (name.to_string(), value))
+        .collect()
+}
+
+impl<O: Write> Observer for Tracer<'_, O> {
+    fn statement(&mut self, state: &State<'_>) -> ControlFlow<()> {
+        self.changes(state);
+        let text = self.source.get(state.line() - 1).map_or("", |x| x.trim());
+        let _ = writeln!(
+            self.output,
+            "{}:{}: {}",
+            state.function(),
+            state.line(),
+            text
+        );
+        ControlFlow::Continue(())
+    }
+
+    fn enter(&mut self, state: &State<'_>) {
+        // a call changes no variable of the caller
+        self.before = Some(variables(state));
+        if state.depth() > 0 {
+            let args = state
+                .arguments()
+                .iter()
+                .map(|(name, value)| format!("{} = {}", name, show(*value)))
+                .collect::<Vec<_>>();
+            let _ = writeln!(
+                self.output,
+                "call {}({})",
+                state.function(),
+                args.join(", ")
+            );
+        }
+    }
+
+    fn leave(&mut self, state: &State<'_>) {
+        self.changes(state);
+        self.before = None;
+        if state.depth() > 0 {
+            let _ = writeln!(self.output, "return from {}", state.function());
+        }
+    }
+}
+
+/// How often something ran and for how long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Counter {
+    pub count: u64,
+    pub time: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct Profiler {
+    lines: BTreeMap<usize, Counter>,
+    procedures: BTreeMap<String, Counter>,
+    /// The line running and since when.
+    current: Option<(usize, Instant)>,
+    /// When each running procedure started.
+    started: Vec<Instant>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    /// Statements run by line, a `while` once per evaluation of its
+    /// condition.
+    pub fn lines(&self) -> &BTreeMap<usize, Counter> {
+        &self.lines
+    }
+
+    /// Calls by procedure, the program body counts as one call under the
+    /// program's name.
+    pub fn procedures(&self) -> &BTreeMap<String, Counter> {
+        &self.procedures
+    }
+
+    fn stop_line(&mut self, now: Instant) {
+        if let Some((line, start)) = self.current.take() {
+            self.lines.entry(line).or_default().time += now - start;
+        }
+    }
+
+    /// The counters as tables, lines in order and procedures slowest first.
+    pub fn report(&self, source: &str) -> String {
+        let source = source.lines().collect::<Vec<_>>();
+        let ms = |x: Duration| x.as_secs_f64() * 1000.0;
+        let mut res = format!("{:>5} {:>9} {:>10}  source\n", "line", "count", "time ms");
+        for (line, counter) in &self.lines {
+            let text = source.get(line - 1).map_or("", |x| x.trim());
+            let _ = writeln!(
+                res,
+                "{:>5} {:>9} {:>10.3}  {}",
+                line,
+                counter.count,
+                ms(counter.time),
+                text
+            );
+        }
+        let mut procedures = self.procedures.iter().collect::<Vec<_>>();
+        procedures.sort_by_key(|(_, x)| std::cmp::Reverse(x.time));
+        let _ = write!(
+            res,
+            "\n{:<15} {:>9} {:>10}\n",
+            "procedure", "calls", "time ms"
+        );
+        for (name, counter) in procedures {
+            let _ = writeln!(
+                res,
+                "{:<15} {:>9} {:>10.3}",
+                name,
+                counter.count,
+                ms(counter.time)
+            );
+        }
+        res
+    }
+}
+
+impl Observer for Profiler {
+    fn statement(&mut self, state: &State<'_>) -> ControlFlow<()> {
+        let now = Instant::now();
+        self.stop_line(now);
+        self.lines.entry(state.line()).or_default().count += 1;
+        self.current = Some((state.line(), now));
+        ControlFlow::Continue(())
+    }
+
+    fn enter(&mut self, state: &State<'_>) {
+        let now = Instant::now();
+        self.stop_line(now);
+        self.procedures
+            .entry(state.function().to_string())
+            .or_default()
+            .count += 1;
+        self.started.push(now);
+    }
+
+    fn leave(&mut self, state: &State<'_>) {
+        let now = Instant::now();
+        self.stop_line(now);
+        if let Some(start) = self.started.pop() {
+            self.procedures
+                .entry(state.function().to_string())
+                .or_default()
+                .time += now - start;
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! Instruction selection from IR, with spill code.
+//!
+//! Every IR instruction becomes a few machine instructions reading its
+//! operands from their locations: a register in a frame slot or a constant
+//! goes through a scratch register first, and a result for a frame slot is
+//! computed in `r0` and stored. Blocks keep their IR numbers as labels.
+
+use crate::ir::{self, Inst, Operand, Terminator, Var, WriteArg};
+use crate::sema::Ty;
+
+use super::regalloc::{allocate, block_order, Allocation, Allocator, Location};
+use super::{Cond, Function, Instr, Program, Reg, Slot, Src, ARG_REGS, SCRATCH};
+
+/// Selects instructions for every function of `module`, out of SSA form
+/// if need be.
+pub fn compile(module: &ir::Module, allocator: Allocator) -> Program {
+    let mut module = module.clone();
+    for func in module.functions_mut() {
+        if func.ssa {
+            ir::ssa::from_ssa(func);
+        }
+    }
+    let procs = module
+        .procs
+        .iter()
+        .map(|func| Emitter::new(func, allocator, false).function())
+        .collect();
+    Program {
+        name: module.name.clone(),
+        globals: module.globals.clone(),
+        procs,
+        main: Emitter::new(&module.main, allocator, true).function(),
+    }
+}
+
+struct Emitter<'a> {
+    func: &'a ir::Function,
+    alloc: Allocation,
+    /// Callee-saved registers to restore before returning.
+    saved: Vec<Reg>,
+    code: Vec<Instr>,
+}
+
+impl<'a> Emitter<'a> {
+    fn new(func: &'a ir::Function, allocator: Allocator, main: bool) -> Emitter<'a> {
+        let alloc = allocate(func, allocator);
+        // the program body has no caller to preserve registers for
+        let saved = if main {
+            Vec::new()
+        } else {
+            alloc.callee_saved()
+        };
+        Emitter {
+            func,
+            alloc,
+            saved,
+            code: Vec::new(),
+        }
+    }
+
+    fn function(mut self) -> Function {
+        self.prologue();
+        for block in block_order(self.func) {
+            self.code.push(Instr::Label(block));
+            for inst in &self.func.blocks[block].insts {
+                self.inst(inst);
+            }
+            self.terminator(&self.func.blocks[block].terminator);
+        }
+        Function {
+            name: self.func.name.clone(),
+            frame: self.alloc.frame,
+            code: self.code,
+        }
+    }
+
+    fn location(&self, var: Var) -> Location {
+        self.alloc.locations[var].expect("registers in use have a location")
+    }
+
+    /// A machine register holding `op`, `scratch` if it has to be loaded.
+    fn reg(&mut self, op: Operand, scratch: Reg) -> Reg {
+        match op {
+            Operand::Const(imm) => {
+                self.code.push(Instr::Li { dst: scratch, imm });
+                scratch
+            }
+            Operand::Var(var) => match self.location(var) {
+                Location::Reg(reg) => reg,
+                Location::Slot(slot) => {
+                    self.code.push(Instr::Ld {
+                        dst: scratch,
+                        slot: Slot::Frame(slot),
+                    });
+                    scratch
+                }
+            },
+        }
+    }
+
+    fn src(&mut self, op: Operand, scratch: Reg) -> Src {
+        match op {
+            Operand::Const(imm) => Src::Imm(imm),
+            op => Src::Reg(self.reg(op, scratch)),
+        }
+    }
+
+    /// Puts `op` in `dst`.
+    fn load(&mut self, dst: Reg, op: Operand) {
+        match op {
+            Operand::Var(var) if self.location(var) == Location::Reg(dst) => {}
+            Operand::Var(var) => match self.location(var) {
+                Location::Reg(src) => self.code.push(Instr::Mov { dst, src }),
+                Location::Slot(slot) => self.code.push(Instr::Ld {
+                    dst,
+                    slot: Slot::Frame(slot),
+                }),
+            },
+            Operand::Const(imm) => self.code.push(Instr::Li { dst, imm }),
+        }
+    }
+
+    /// The register to compute the value of `var` in.
+    fn target(&self, var: Var) -> Reg {
+        match self.location(var) {
+            Location::Reg(reg) => reg,
+            Location::Slot(_) => SCRATCH[0],
+        }
+    }
+
+    /// Stores `var` computed in `target(var)` if it lives in the frame.
+    fn spill(&mut self, var: Var) {
+        if let Location::Slot(slot) = self.location(var) {
+            self.code.push(Instr::St {
+                slot: Slot::Frame(slot),
+                src: SCRATCH[0],
+            });
+        }
+    }
+
+    /// Moves the value of `src` into `var`.
+    fn assign(&mut self, var: Var, src: Reg) {
+        match self.location(var) {
+            Location::Reg(dst) if dst == src => {}
+            Location::Reg(dst) => self.code.push(Instr::Mov { dst, src }),
+            Location::Slot(slot) => self.code.push(Instr::St {
+                slot: Slot::Frame(slot),
+                src,
+            }),
+        }
+    }
+
+    /// Copies registers to registers as if all at once, a cycle goes
+    /// through `r0`.
+    fn parallel_move(&mut self, mut moves: Vec<(Reg, Reg)>) {
+        moves.retain(|(dst, src)| dst != src);
+        while !moves.is_empty() {
+            let ready = moves
+                .iter()
+                .position(|(dst, _)| !moves.iter().any(|(_, src)| src == dst));
+            match ready {
+                Some(i) => {
+                    let (dst, src) = moves.remove(i);
+                    self.code.push(Instr::Mov { dst, src });
+                }
+                None => {
+                    let (dst, _) = moves[0];
+                    self.code.push(Instr::Mov {
+                        dst: SCRATCH[0],
+                        src: dst,
+                    });
+                    for (_, src) in &mut moves {
+                        if *src == dst {
+                            *src = SCRATCH[0];
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Takes the stack arguments, saves callee-saved registers and moves
+    /// the register arguments where the parameters live.
+    fn prologue(&mut self) {
+        let params = &self.func.params;
+        for &param in params.iter().skip(ARG_REGS.len()).rev() {
+            self.code.push(Instr::Pop(SCRATCH[0]));
+            self.assign(param, SCRATCH[0]);
+        }
+        for &reg in &self.saved {
+            self.code.push(Instr::Push(reg));
+        }
+        let mut moves = Vec::new();
+        for (&param, &arg) in params.iter().zip(&ARG_REGS) {
+            match self.alloc.locations[param] {
+                Some(Location::Reg(reg)) => moves.push((reg, arg)),
+                Some(Location::Slot(_)) => self.assign(param, arg),
+                None => {}
+            }
+        }
+        self.parallel_move(moves);
+    }
+
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Copy { dst, src } => {
+                let target = self.target(*dst);
+                self.load(target, *src);
+                self.spill(*dst);
+            }
+            Inst::Binary {
+                dst,
+                op,
+                lhs,
+                rhs,
+                line,
+            } => {
+                let lhs = self.reg(*lhs, SCRATCH[0]);
+                let rhs = self.src(*rhs, SCRATCH[1]);
+                self.code.push(Instr::Op {
+                    op: *op,
+                    dst: self.target(*dst),
+                    lhs,
+                    rhs,
+                    line: *line,
+                });
+                self.spill(*dst);
+            }
+            Inst::Load { dst, global } => {
+                self.code.push(Instr::Ld {
+                    dst: self.target(*dst),
+                    slot: Slot::Global(*global),
+                });
+                self.spill(*dst);
+            }
+            Inst::Store { global, src } => {
+                let src = self.reg(*src, SCRATCH[0]);
+                self.code.push(Instr::St {
+                    slot: Slot::Global(*global),
+                    src,
+                });
+            }
+            Inst::Call { proc, args, line } => {
+                for &arg in args.iter().skip(ARG_REGS.len()) {
+                    let reg = self.reg(arg, SCRATCH[0]);
+                    self.code.push(Instr::Push(reg));
+                }
+                // registers first, loading an argument register may
+                // overwrite the source of another move
+                let mut moves = Vec::new();
+                let mut loads = Vec::new();
+                for (&arg, &dst) in args.iter().zip(&ARG_REGS) {
+                    match arg {
+                        Operand::Var(var) => match self.location(var) {
+                            Location::Reg(src) => moves.push((dst, src)),
+                            Location::Slot(_) => loads.push((dst, arg)),
+                        },
+                        Operand::Const(_) => loads.push((dst, arg)),
+                    }
+                }
+                self.parallel_move(moves);
+                for (dst, arg) in loads {
+                    self.load(dst, arg);
+                }
+                self.code.push(Instr::Call {
+                    proc: *proc,
+                    line: *line,
+                });
+            }
+            Inst::Read { dst, ty, line } => {
+                self.code.push(Instr::Read {
+                    ty: *ty,
+                    line: *line,
+                });
+                self.assign(*dst, SCRATCH[0]);
+            }
+            Inst::Write {
+                args,
+                newline,
+                line,
+            } => {
+                let line = *line;
+                for arg in args {
+                    match arg {
+                        WriteArg::Str(text) => self.code.push(Instr::WriteStr {
+                            text: text.clone(),
+                            line,
+                        }),
+                        WriteArg::Value(op, ty) => {
+                            self.load(SCRATCH[0], *op);
+                            self.code.push(match ty {
+                                Ty::Char => Instr::WriteChar { line },
+                                _ => Instr::WriteInt { line },
+                            });
+                        }
+                    }
+                }
+                if *newline {
+                    self.code.push(Instr::WriteLn { line });
+                }
+            }
+            Inst::Phi { .. } => unreachable!("out of SSA form"),
+        }
+    }
+
+    fn terminator(&mut self, terminator: &Terminator) {
+        match terminator {
+            Terminator::Goto(target) => self.code.push(Instr::Jmp(*target)),
+            Terminator::Branch {
+                op,
+                lhs,
+                rhs,
+                then_block,
+                else_block,
+            } => {
+                let lhs = self.reg(*lhs, SCRATCH[0]);
+                let rhs = self.src(*rhs, SCRATCH[1]);
+                self.code.push(Instr::Cmp { lhs, rhs });
+                self.code.push(Instr::Jcc {
+                    cond: Cond::of(*op),
+                    target: *then_block,
+                });
+                self.code.push(Instr::Jmp(*else_block));
+            }
+            Terminator::Return => {
+                for &reg in self.saved.iter().rev() {
+                    self.code.push(Instr::Pop(reg));
+                }
+                self.code.push(Instr::Ret);
+            }
+        }
+    }
+}
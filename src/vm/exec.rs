@@ -0,0 +1,281 @@
+//! Runs machine code, counting what it executes.
+//!
+//! Calls leave garbage in the caller-saved registers, as they may on
+//! hardware, so code relying on them surviving a call prints wrong values
+//! instead of working by accident.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+use snlc_ast::codegen::Config;
+
+use crate::interp::{arith, read_value, ErrorKind, Frame, Limits, RuntimeError, Usage};
+use crate::runtime::Input;
+
+use super::{Function, Instr, Program, Reg, Slot, Src, CALLER_SAVED, REGS, SCRATCH};
+
+/// What a caller-saved register holds after a call.
+const GARBAGE: i64 = 0x5eed_dead_beef;
+
+/// Instructions a run executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    pub instructions: u64,
+    /// Loads, stores, pushes and pops.
+    pub memory: u64,
+}
+
+/// Runs `prog` reading `input` and writing `output`.
+pub fn run<R: BufRead, W: Write>(
+    prog: &Program,
+    config: &Config,
+    input: R,
+    output: W,
+    limits: &Limits,
+) -> Result<Stats, RuntimeError> {
+    let proc_labels = prog.procs.iter().map(labels).collect::<Vec<_>>();
+    let mut machine = Machine {
+        config,
+        input: Input::new(input),
+        output,
+        regs: [0; REGS],
+        stack: Vec::new(),
+        globals: vec![0; prog.globals.len()],
+        compare: (0, 0),
+        stats: Stats::default(),
+        deadline: limits.time.map(|x| (Instant::now() + x, x)),
+        usage: Usage::new(limits),
+        line: 0,
+        calls: Vec::new(),
+    };
+    let main_labels = labels(&prog.main);
+    let mut calls: Vec<(Option<usize>, usize, Vec<i64>)> = Vec::new();
+    let mut current: Option<usize> = None;
+    let mut pc = 0;
+    let mut frame = vec![GARBAGE; prog.main.frame];
+    loop {
+        let (func, labels) = match current {
+            Some(proc) => (&prog.procs[proc], &proc_labels[proc]),
+            None => (&prog.main, &main_labels),
+        };
+        let instr = &func.code[pc];
+        pc += 1;
+        if matches!(instr, Instr::Label(_)) {
+            continue;
+        }
+        if let Some(line) = instr.line() {
+            machine.line = line;
+        }
+        machine.tick()?;
+        match instr {
+            &Instr::Call { proc, line } => {
+                machine
+                    .usage
+                    .call(calls.len() + 1)
+                    .map_err(|kind| machine.error(kind, line))?;
+                machine.calls.push(Frame {
+                    name: prog.procs[proc].name.clone(),
+                    line,
+                });
+                let callee = std::mem::replace(&mut frame, vec![GARBAGE; prog.procs[proc].frame]);
+                calls.push((current, pc, callee));
+                current = Some(proc);
+                pc = 0;
+            }
+            Instr::Ret => {
+                machine.clobber(&SCRATCH);
+                machine.clobber(&CALLER_SAVED);
+                match calls.pop() {
+                    Some((caller, ret, caller_frame)) => {
+                        machine.calls.pop();
+                        current = caller;
+                        pc = ret;
+                        frame = caller_frame;
+                    }
+                    None => break,
+                }
+            }
+            Instr::Jmp(target) => pc = labels[target],
+            Instr::Jcc { cond, target } => {
+                let (lhs, rhs) = machine.compare;
+                if cond.holds(lhs, rhs) {
+                    pc = labels[target];
+                }
+            }
+            instr => machine.step(instr, &mut frame)?,
+        }
+    }
+    machine
+        .output
+        .flush()
+        .map_err(|err| machine.error(ErrorKind::Output(err.to_string()), 0))?;
+    Ok(machine.stats)
+}
+
+/// Where each label of `func` is.
+fn labels(func: &Function) -> HashMap<usize, usize> {
+    func.code
+        .iter()
+        .enumerate()
+        .filter_map(|(pc, x)| match x {
+            Instr::Label(label) => Some((*label, pc)),
+            _ => None,
+        })
+        .collect()
+}
+
+struct Machine<'a, R, W> {
+    config: &'a Config,
+    input: Input<R>,
+    output: W,
+    regs: [i64; REGS],
+    stack: Vec<i64>,
+    globals: Vec<i64>,
+    /// Operands of the last `cmp`.
+    compare: (i64, i64),
+    stats: Stats,
+    deadline: Option<(Instant, Duration)>,
+    usage: Usage,
+    /// Line of the last instruction run that has one.
+    line: usize,
+    /// Procedures running, innermost last.
+    calls: Vec<Frame>,
+}
+
+impl<R: BufRead, W: Write> Machine<'_, R, W> {
+    fn error(&self, kind: ErrorKind, line: usize) -> RuntimeError {
+        RuntimeError {
+            kind,
+            line,
+            stack: self.calls.iter().rev().cloned().collect(),
+        }
+    }
+
+    fn tick(&mut self) -> Result<(), RuntimeError> {
+        self.stats.instructions += 1;
+        self.usage
+            .step()
+            .map_err(|kind| self.error(kind, self.line))?;
+        if let Some((deadline, time)) = self.deadline {
+            if self.stats.instructions.is_multiple_of(1024) && Instant::now() > deadline {
+                return Err(self.error(ErrorKind::Timeout(time), self.line));
+            }
+        }
+        Ok(())
+    }
+
+    fn clobber(&mut self, regs: &[Reg]) {
+        for reg in regs {
+            self.regs[reg.0 as usize] = GARBAGE;
+        }
+    }
+
+    fn reg(&self, reg: Reg) -> i64 {
+        self.regs[reg.0 as usize]
+    }
+
+    fn set(&mut self, reg: Reg, value: i64) {
+        self.regs[reg.0 as usize] = value;
+    }
+
+    fn src(&self, src: &Src) -> i64 {
+        match src {
+            Src::Reg(reg) => self.reg(*reg),
+            Src::Imm(imm) => *imm,
+        }
+    }
+
+    fn slot<'s>(&'s mut self, frame: &'s mut [i64], slot: Slot) -> &'s mut i64 {
+        self.stats.memory += 1;
+        match slot {
+            Slot::Frame(i) => &mut frame[i],
+            Slot::Global(i) => &mut self.globals[i],
+        }
+    }
+
+    fn write(&mut self, text: &str, line: usize) -> Result<(), RuntimeError> {
+        self.usage
+            .write(text.len())
+            .map_err(|kind| self.error(kind, line))?;
+        self.output
+            .write_all(text.as_bytes())
+            .map_err(|err| self.error(ErrorKind::Output(err.to_string()), line))?;
+        self.clobber(&SCRATCH);
+        self.clobber(&CALLER_SAVED);
+        Ok(())
+    }
+
+    /// Executes an instruction not changing control flow.
+    fn step(&mut self, instr: &Instr, frame: &mut [i64]) -> Result<(), RuntimeError> {
+        match instr {
+            Instr::Li { dst, imm } => self.set(*dst, *imm),
+            Instr::Mov { dst, src } => self.set(*dst, self.reg(*src)),
+            Instr::Op {
+                op,
+                dst,
+                lhs,
+                rhs,
+                line,
+            } => {
+                let value = arith(
+                    *op,
+                    self.reg(*lhs),
+                    self.src(rhs),
+                    self.config.int_width,
+                    self.config.overflow,
+                )
+                .map_err(|kind| self.error(kind, *line))?;
+                self.set(*dst, value);
+            }
+            Instr::Ld { dst, slot } => {
+                let value = *self.slot(frame, *slot);
+                self.set(*dst, value);
+            }
+            Instr::St { slot, src } => {
+                let value = self.reg(*src);
+                *self.slot(frame, *slot) = value;
+            }
+            Instr::Push(reg) => {
+                self.stats.memory += 1;
+                self.stack.push(self.reg(*reg));
+            }
+            Instr::Pop(reg) => {
+                self.stats.memory += 1;
+                let value = self.stack.pop().expect("pop from an empty stack");
+                self.set(*reg, value);
+            }
+            Instr::Cmp { lhs, rhs } => self.compare = (self.reg(*lhs), self.src(rhs)),
+            Instr::Read { ty, line } => {
+                self.usage.read().map_err(|kind| self.error(kind, *line))?;
+                self.output
+                    .flush()
+                    .map_err(|err| self.error(ErrorKind::Output(err.to_string()), *line))?;
+                let value = read_value(&mut self.input, *ty, self.config.int_width)
+                    .map_err(|err| self.error(ErrorKind::Input(err), *line))?;
+                self.clobber(&SCRATCH);
+                self.clobber(&CALLER_SAVED);
+                self.set(SCRATCH[0], value.int());
+            }
+            Instr::WriteInt { line } => {
+                let text = self.reg(SCRATCH[0]).to_string();
+                self.write(&text, *line)?;
+            }
+            Instr::WriteChar { line } => {
+                let c = char::from_u32(self.reg(SCRATCH[0]) as u32)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                self.write(c.encode_utf8(&mut [0; 4]), *line)?;
+            }
+            Instr::WriteStr { text, line } => self.write(text, *line)?,
+            Instr::WriteLn { line } => self.write("\n", *line)?,
+            Instr::Label(_)
+            | Instr::Jcc { .. }
+            | Instr::Jmp(_)
+            | Instr::Call { .. }
+            | Instr::Ret => {
+                unreachable!("handled by `run`")
+            }
+        }
+        Ok(())
+    }
+}
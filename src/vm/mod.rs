@@ -0,0 +1,341 @@
+//! A small register machine, the target of the native backend.
+//!
+//! The machine has `REGS` integer registers, a frame of memory slots per
+//! call, global slots and a stack for `push` and `pop`. `codegen` selects
+//! instructions from IR, keeping every register either in a machine
+//! register or in a frame slot as `regalloc` decided, and `exec` runs the
+//! result.
+//!
+//! Calling convention: `r0` and `r1` are scratch registers, never
+//! allocated, used for spill code and to pass values to and from the
+//! runtime (`read` leaves its result in `r0`, `wri` and `wrc` print `r0`).
+//! The first `ARG_REGS.len()` arguments of a procedure arrive in `r2` to
+//! `r5`, the others are pushed in order and popped by the callee. `r0` to
+//! `r5` are caller-saved: calls and runtime calls leave garbage in them.
+//! `r6` to `r9` are callee-saved: a procedure using one saves it first.
+
+pub mod codegen;
+pub mod exec;
+pub mod peephole;
+pub mod regalloc;
+
+use std::fmt;
+use std::rc::Rc;
+
+use snlc_ast::expr::BinOp;
+
+use crate::interp::VarDecl;
+use crate::sema::Ty;
+
+pub const REGS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Reg(pub u8);
+
+pub const SCRATCH: [Reg; 2] = [Reg(0), Reg(1)];
+pub const ARG_REGS: [Reg; 4] = [Reg(2), Reg(3), Reg(4), Reg(5)];
+pub const CALLER_SAVED: [Reg; 4] = ARG_REGS;
+pub const CALLEE_SAVED: [Reg; 4] = [Reg(6), Reg(7), Reg(8), Reg(9)];
+
+/// A memory location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Slot {
+    /// A slot of the current call's frame.
+    Frame(usize),
+    Global(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Src {
+    Reg(Reg),
+    Imm(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cond {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+    Ne,
+}
+
+impl Cond {
+    /// The condition of a comparison operator.
+    pub fn of(op: BinOp) -> Cond {
+        match op {
+            BinOp::Lt => Cond::Lt,
+            BinOp::Le => Cond::Le,
+            _ => Cond::Eq,
+        }
+    }
+
+    /// The condition holding exactly when `self` doesn't.
+    pub fn negate(self) -> Cond {
+        match self {
+            Cond::Lt => Cond::Ge,
+            Cond::Le => Cond::Gt,
+            Cond::Eq => Cond::Ne,
+            Cond::Ge => Cond::Lt,
+            Cond::Gt => Cond::Le,
+            Cond::Ne => Cond::Eq,
+        }
+    }
+
+    pub fn holds(self, lhs: i64, rhs: i64) -> bool {
+        match self {
+            Cond::Lt => lhs < rhs,
+            Cond::Le => lhs <= rhs,
+            Cond::Eq => lhs == rhs,
+            Cond::Ge => lhs >= rhs,
+            Cond::Gt => lhs > rhs,
+            Cond::Ne => lhs != rhs,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Cond::Lt => "lt",
+            Cond::Le => "le",
+            Cond::Eq => "eq",
+            Cond::Ge => "ge",
+            Cond::Gt => "gt",
+            Cond::Ne => "ne",
+        }
+    }
+}
+
+pub type Label = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instr {
+    /// A jump target, not an instruction of its own.
+    Label(Label),
+    Li {
+        dst: Reg,
+        imm: i64,
+    },
+    Mov {
+        dst: Reg,
+        src: Reg,
+    },
+    /// Arithmetic, `line` is where runtime errors are reported.
+    Op {
+        op: BinOp,
+        dst: Reg,
+        lhs: Reg,
+        rhs: Src,
+        line: usize,
+    },
+    Ld {
+        dst: Reg,
+        slot: Slot,
+    },
+    St {
+        slot: Slot,
+        src: Reg,
+    },
+    Push(Reg),
+    Pop(Reg),
+    Cmp {
+        lhs: Reg,
+        rhs: Src,
+    },
+    /// Jumps if the last comparison satisfied `cond`.
+    Jcc {
+        cond: Cond,
+        target: Label,
+    },
+    Jmp(Label),
+    Call {
+        proc: usize,
+        line: usize,
+    },
+    Ret,
+    /// Runtime call reading a value of type `ty` into `r0`.
+    Read {
+        ty: Ty,
+        line: usize,
+    },
+    /// Runtime call printing `r0` as an integer.
+    WriteInt {
+        line: usize,
+    },
+    /// Runtime call printing `r0` as a character.
+    WriteChar {
+        line: usize,
+    },
+    WriteStr {
+        text: Rc<str>,
+        line: usize,
+    },
+    WriteLn {
+        line: usize,
+    },
+}
+
+impl Instr {
+    /// Whether the instruction is a runtime or procedure call, after which
+    /// caller-saved registers hold garbage.
+    pub fn is_call(&self) -> bool {
+        matches!(
+            self,
+            Instr::Call { .. }
+                | Instr::Read { .. }
+                | Instr::WriteInt { .. }
+                | Instr::WriteChar { .. }
+                | Instr::WriteStr { .. }
+                | Instr::WriteLn { .. }
+        )
+    }
+
+    /// The SNL line of the instruction, for instructions that may fail.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Instr::Op { line, .. }
+            | Instr::Call { line, .. }
+            | Instr::Read { line, .. }
+            | Instr::WriteInt { line }
+            | Instr::WriteChar { line }
+            | Instr::WriteStr { line, .. }
+            | Instr::WriteLn { line } => Some(*line),
+            _ => None,
+        }
+    }
+}
+
+/// Code of a procedure or of the program body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    /// Size of the frame in slots.
+    pub frame: usize,
+    pub code: Vec<Instr>,
+}
+
+impl Function {
+    /// Number of instructions, labels excluded.
+    pub fn len(&self) -> usize {
+        self.code
+            .iter()
+            .filter(|x| !matches!(x, Instr::Label(_)))
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A whole program, `procs` are indexed by `Instr::Call::proc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub name: String,
+    pub globals: Vec<VarDecl>,
+    pub procs: Vec<Function>,
+    pub main: Function,
+}
+
+impl Program {
+    pub fn functions(&self) -> impl Iterator<Item = &Function> {
+        self.procs.iter().chain(std::iter::once(&self.main))
+    }
+
+    pub fn functions_mut(&mut self) -> impl Iterator<Item = &mut Function> {
+        self.procs.iter_mut().chain(std::iter::once(&mut self.main))
+    }
+
+    /// Number of instructions of every function.
+    pub fn len(&self) -> usize {
+        self.functions().map(Function::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "r{}", self.0)
+    }
+}
+
+impl fmt::Display for Src {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Src::Reg(reg) => write!(f, "{}", reg),
+            Src::Imm(imm) => write!(f, "{}", imm),
+        }
+    }
+}
+
+struct Printer<'a> {
+    prog: &'a Program,
+}
+
+impl Printer<'_> {
+    fn slot(&self, slot: Slot) -> String {
+        match slot {
+            Slot::Frame(i) => format!("[fp+{}]", i),
+            Slot::Global(i) => format!("[@{}]", self.prog.globals[i].name),
+        }
+    }
+
+    fn instr(&self, instr: &Instr) -> String {
+        match instr {
+            Instr::Label(label) => format!(".L{}:", label),
+            Instr::Li { dst, imm } => format!("    li {}, {}", dst, imm),
+            Instr::Mov { dst, src } => format!("    mov {}, {}", dst, src),
+            Instr::Op {
+                op, dst, lhs, rhs, ..
+            } => {
+                let name = match op {
+                    BinOp::Plus => "add",
+                    BinOp::Minus => "sub",
+                    BinOp::Star => "mul",
+                    _ => "div",
+                };
+                format!("    {} {}, {}, {}", name, dst, lhs, rhs)
+            }
+            Instr::Ld { dst, slot } => format!("    ld {}, {}", dst, self.slot(*slot)),
+            Instr::St { slot, src } => format!("    st {}, {}", self.slot(*slot), src),
+            Instr::Push(reg) => format!("    push {}", reg),
+            Instr::Pop(reg) => format!("    pop {}", reg),
+            Instr::Cmp { lhs, rhs } => format!("    cmp {}, {}", lhs, rhs),
+            Instr::Jcc { cond, target } => format!("    j{} .L{}", cond.name(), target),
+            Instr::Jmp(target) => format!("    jmp .L{}", target),
+            Instr::Call { proc, .. } => format!("    call {}", self.prog.procs[*proc].name),
+            Instr::Ret => "    ret".to_string(),
+            Instr::Read { ty, .. } => format!("    read {}", ty),
+            Instr::WriteInt { .. } => "    wri".to_string(),
+            Instr::WriteChar { .. } => "    wrc".to_string(),
+            Instr::WriteStr { text, .. } => format!("    wrs {:?}", text),
+            Instr::WriteLn { .. } => "    wrln".to_string(),
+        }
+    }
+
+    fn function(&self, f: &mut fmt::Formatter<'_>, func: &Function) -> fmt::Result {
+        writeln!(f, "{}:  ; frame {}", func.name, func.frame)?;
+        for instr in &func.code {
+            writeln!(f, "{}", self.instr(instr))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let printer = Printer { prog: self };
+        for global in &self.globals {
+            writeln!(f, "global @{}: {}", global.name, global.ty)?;
+        }
+        for func in &self.procs {
+            writeln!(f)?;
+            printer.function(f, func)?;
+        }
+        writeln!(f)?;
+        printer.function(f, &self.main)
+    }
+}
@@ -0,0 +1,227 @@
+//! Peephole rules over machine code.
+//!
+//! A rule looks at the code from some position on and either leaves it
+//! alone or says how many instructions it replaces and with what. Rules
+//! only ever see instructions next to each other, a label in between
+//! stops them since control may arrive there from elsewhere. `optimize`
+//! applies `RULES` until none matches any more, adding a rule is adding an
+//! entry to the table.
+
+use super::{Function, Instr, Program, Src};
+
+/// How many instructions to replace, and by what.
+pub type Rewrite = (usize, Vec<Instr>);
+
+pub struct Rule {
+    pub name: &'static str,
+    pub help: &'static str,
+    /// The rewrite of the start of `code`, if the rule applies there.
+    pub rewrite: fn(code: &[Instr]) -> Option<Rewrite>,
+}
+
+pub const RULES: &[Rule] = &[
+    Rule {
+        name: "load-after-store",
+        help: "read a value just stored from its register instead",
+        rewrite: |code| match code {
+            [store @ Instr::St { slot, src }, Instr::Ld { dst, slot: from }, ..]
+                if slot == from =>
+            {
+                let mut res = vec![store.clone()];
+                if dst != src {
+                    res.push(Instr::Mov {
+                        dst: *dst,
+                        src: *src,
+                    });
+                }
+                Some((2, res))
+            }
+            _ => None,
+        },
+    },
+    Rule {
+        name: "store-after-load",
+        help: "drop storing a value back where it was just loaded from",
+        rewrite: |code| match code {
+            [load @ Instr::Ld { dst, slot }, Instr::St { slot: to, src }, ..]
+                if slot == to && dst == src =>
+            {
+                Some((2, vec![load.clone()]))
+            }
+            _ => None,
+        },
+    },
+    Rule {
+        name: "store-over-store",
+        help: "drop a store overwritten by the next one",
+        rewrite: |code| match code {
+            [Instr::St { slot, .. }, store @ Instr::St { slot: to, .. }, ..] if slot == to => {
+                Some((2, vec![store.clone()]))
+            }
+            _ => None,
+        },
+    },
+    Rule {
+        name: "self-move",
+        help: "drop moving a register to itself",
+        rewrite: |code| match code {
+            [Instr::Mov { dst, src }, ..] if dst == src => Some((1, Vec::new())),
+            _ => None,
+        },
+    },
+    Rule {
+        name: "push-pop",
+        help: "move a pushed register instead of popping it right away",
+        rewrite: |code| match code {
+            [Instr::Push(src), Instr::Pop(dst), ..] if src == dst => Some((2, Vec::new())),
+            [Instr::Push(src), Instr::Pop(dst), ..] => Some((
+                2,
+                vec![Instr::Mov {
+                    dst: *dst,
+                    src: *src,
+                }],
+            )),
+            _ => None,
+        },
+    },
+    Rule {
+        name: "compare-again",
+        help: "drop comparing registers unchanged since the same comparison",
+        rewrite: |code| {
+            let [Instr::Cmp { .. }, rest @ ..] = code else {
+                return None;
+            };
+            // none of these writes a register or compares
+            let between = rest
+                .iter()
+                .take_while(|x| matches!(x, Instr::Jcc { .. } | Instr::St { .. } | Instr::Push(_)))
+                .count();
+            (rest.get(between) == Some(&code[0])).then(|| (between + 2, code[..=between].to_vec()))
+        },
+    },
+    Rule {
+        name: "constant-compare",
+        help: "jump or fall through on comparing constants",
+        rewrite: |code| {
+            let (len, lhs, rhs) = match code {
+                [Instr::Li { dst, imm }, Instr::Cmp {
+                    lhs,
+                    rhs: Src::Imm(rhs),
+                }, ..]
+                    if lhs == dst =>
+                {
+                    (1, *imm, *rhs)
+                }
+                [Instr::Li { dst: a, imm: x }, Instr::Li { dst: b, imm: y }, Instr::Cmp {
+                    lhs,
+                    rhs: Src::Reg(rhs),
+                }, ..]
+                    if a != b && lhs == a && rhs == b =>
+                {
+                    (2, *x, *y)
+                }
+                [Instr::Li { dst: a, imm: x }, Instr::Li { dst: b, imm: y }, Instr::Cmp {
+                    lhs,
+                    rhs: Src::Reg(rhs),
+                }, ..]
+                    if a != b && lhs == b && rhs == a =>
+                {
+                    (2, *y, *x)
+                }
+                _ => return None,
+            };
+            // the comparison may only decide the branch right after it
+            match &code[len + 1..] {
+                [Instr::Jcc { cond, target }, rest @ ..] if !branches(rest) => {
+                    let mut res = code[..len].to_vec();
+                    if cond.holds(lhs, rhs) {
+                        res.push(Instr::Jmp(*target));
+                    }
+                    Some((len + 2, res))
+                }
+                _ => None,
+            }
+        },
+    },
+    Rule {
+        name: "jump-to-next",
+        help: "drop a jump to the label right after it",
+        rewrite: |code| match code {
+            [Instr::Jmp(target) | Instr::Jcc { target, .. }, Instr::Label(next), ..]
+                if target == next =>
+            {
+                Some((1, Vec::new()))
+            }
+            _ => None,
+        },
+    },
+    Rule {
+        name: "branch-over-jump",
+        help: "branch on the negated condition instead of jumping over a jump",
+        rewrite: |code| match code {
+            [Instr::Jcc { cond, target }, Instr::Jmp(other), label @ Instr::Label(next), ..]
+                if target == next =>
+            {
+                let branch = Instr::Jcc {
+                    cond: cond.negate(),
+                    target: *other,
+                };
+                Some((3, vec![branch, label.clone()]))
+            }
+            _ => None,
+        },
+    },
+    Rule {
+        name: "unreachable",
+        help: "drop code after a jump or return up to the next label",
+        rewrite: |code| match code {
+            [Instr::Jmp(_) | Instr::Ret, next, ..] if !matches!(next, Instr::Label(_)) => {
+                Some((2, vec![code[0].clone()]))
+            }
+            _ => None,
+        },
+    },
+];
+
+/// Whether `code` branches on the last comparison before it writes a
+/// register or compares again.
+fn branches(code: &[Instr]) -> bool {
+    matches!(
+        code.iter()
+            .find(|x| !matches!(x, Instr::St { .. } | Instr::Push(_))),
+        Some(Instr::Jcc { .. })
+    )
+}
+
+pub fn find_rule(name: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|x| x.name == name)
+}
+
+/// Applies `rules` to `func` until none matches, returning the names of
+/// the rules applied in order.
+pub fn optimize_function(func: &mut Function, rules: &[Rule]) -> Vec<&'static str> {
+    let mut applied = Vec::new();
+    let mut i = 0;
+    while i < func.code.len() {
+        let rewrite = rules
+            .iter()
+            .find_map(|rule| Some((rule.name, (rule.rewrite)(&func.code[i..])?)));
+        match rewrite {
+            Some((name, (len, replacement))) => {
+                func.code.splice(i..i + len, replacement);
+                applied.push(name);
+                // the replacement may complete a pattern starting earlier
+                i = i.saturating_sub(2);
+            }
+            None => i += 1,
+        }
+    }
+    applied
+}
+
+/// Applies `RULES` to every function of `prog`.
+pub fn optimize(prog: &mut Program) -> Vec<&'static str> {
+    prog.functions_mut()
+        .flat_map(|func| optimize_function(func, RULES))
+        .collect()
+}
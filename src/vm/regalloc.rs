@@ -0,0 +1,297 @@
+//! Register allocation: where every IR register of a function lives.
+//!
+//! `linear_scan` is Poletto and Sarkar's allocator over one live interval
+//! per register. Instructions are numbered in layout order, an
+//! instruction's operands are read at an even position and its result is
+//! written at the odd one after it, which is also where a call clobbers
+//! the caller-saved registers. An interval live across such a position
+//! only gets a callee-saved register. When no register is free, the
+//! interval ending last is spilled to the frame for its whole life.
+//! `spill_all` is the naive baseline keeping everything in the frame.
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::ir::{BlockId, Function, Inst, Operand, Var, WriteArg};
+
+use super::{Reg, ARG_REGS, CALLEE_SAVED, CALLER_SAVED};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Reg(Reg),
+    /// A slot of the function's frame.
+    Slot(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Allocator {
+    /// Every register in a frame slot.
+    Spill,
+    #[default]
+    LinearScan,
+}
+
+impl FromStr for Allocator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spill" => Ok(Allocator::Spill),
+            "linear-scan" => Ok(Allocator::LinearScan),
+            _ => Err(format!("unknown register allocator `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    /// `None` for registers no instruction mentions.
+    pub locations: Vec<Option<Location>>,
+    /// Number of frame slots used.
+    pub frame: usize,
+}
+
+impl Allocation {
+    fn new(func: &Function) -> Allocation {
+        Allocation {
+            locations: vec![None; func.vars.len()],
+            frame: 0,
+        }
+    }
+
+    fn spill(&mut self, var: Var) {
+        self.locations[var] = Some(Location::Slot(self.frame));
+        self.frame += 1;
+    }
+
+    /// Callee-saved registers holding some IR register.
+    pub fn callee_saved(&self) -> Vec<Reg> {
+        CALLEE_SAVED
+            .into_iter()
+            .filter(|&reg| self.locations.contains(&Some(Location::Reg(reg))))
+            .collect()
+    }
+}
+
+pub fn allocate(func: &Function, allocator: Allocator) -> Allocation {
+    match allocator {
+        Allocator::Spill => spill_all(func),
+        Allocator::LinearScan => linear_scan(func),
+    }
+}
+
+/// Order the blocks of `func` are numbered and emitted in.
+pub fn block_order(func: &Function) -> Vec<BlockId> {
+    func.layout()
+}
+
+/// Positions from the first to the last one a register is live at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interval {
+    pub var: Var,
+    pub start: usize,
+    pub end: usize,
+    /// Whether a call clobbers caller-saved registers while it is live.
+    pub crosses_call: bool,
+}
+
+/// The live interval of every register instructions mention, in order of
+/// register.
+pub fn intervals(func: &Function) -> Vec<Interval> {
+    assert!(!func.ssa, "`{}` is in SSA form", func.name);
+    let order = block_order(func);
+
+    // uses of each instruction with their position, then the definition
+    let mut index = 1;
+    let mut calls = Vec::new();
+    let mut blocks = Vec::new();
+    for &block in &order {
+        let start = 2 * index;
+        let mut insts = Vec::new();
+        for inst in &func.blocks[block].insts {
+            let (use_at, def_at) = (2 * index, 2 * index + 1);
+            let mut uses = Vec::new();
+            match inst {
+                // the first runtime call of a `write` is the only one
+                // coming before its operands are clobbered
+                Inst::Write { args, .. } => {
+                    for (i, arg) in args.iter().enumerate() {
+                        if let WriteArg::Value(Operand::Var(var), _) = arg {
+                            uses.push((*var, if i == 0 { use_at } else { def_at }));
+                        }
+                    }
+                }
+                _ => {
+                    for op in inst.operands() {
+                        if let Operand::Var(var) = op {
+                            uses.push((*var, use_at));
+                        }
+                    }
+                }
+            }
+            if matches!(
+                inst,
+                Inst::Call { .. } | Inst::Read { .. } | Inst::Write { .. }
+            ) {
+                calls.push(def_at);
+            }
+            insts.push((uses, inst.dst().map(|x| (x, def_at))));
+            index += 1;
+        }
+        let uses = func.blocks[block]
+            .terminator
+            .operands()
+            .into_iter()
+            .filter_map(|x| match x {
+                Operand::Var(var) => Some((*var, 2 * index)),
+                Operand::Const(_) => None,
+            })
+            .collect();
+        insts.push((uses, None));
+        let end = 2 * index + 1;
+        index += 1;
+        blocks.push((block, insts, start, end));
+    }
+
+    let live_out = live_out(func, &order, &blocks);
+    let mut range: Vec<Option<(usize, usize)>> = vec![None; func.vars.len()];
+    let mut extend = |var: Var, pos: usize| {
+        range[var] = Some(match range[var] {
+            Some((start, end)) => (start.min(pos), end.max(pos)),
+            None => (pos, pos),
+        });
+    };
+    for &param in &func.params {
+        extend(param, 1);
+    }
+    for (i, (_, insts, start, end)) in blocks.iter().enumerate() {
+        let mut live = live_out[i].clone();
+        for &var in &live {
+            extend(var, *end);
+        }
+        for (uses, def) in insts.iter().rev() {
+            if let Some((var, pos)) = *def {
+                extend(var, pos);
+                live.remove(&var);
+            }
+            for &(var, pos) in uses {
+                extend(var, pos);
+                live.insert(var);
+            }
+        }
+        for &var in &live {
+            extend(var, *start);
+        }
+    }
+
+    range
+        .into_iter()
+        .enumerate()
+        .filter_map(|(var, range)| {
+            let (start, end) = range?;
+            Some(Interval {
+                var,
+                start,
+                end,
+                crosses_call: calls.iter().any(|&x| start < x && end >= x),
+            })
+        })
+        .collect()
+}
+
+type Insts = Vec<(Vec<(Var, usize)>, Option<(Var, usize)>)>;
+
+/// Registers live at the end of each block, iterating to a fixed point.
+fn live_out(
+    func: &Function,
+    order: &[BlockId],
+    blocks: &[(BlockId, Insts, usize, usize)],
+) -> Vec<HashSet<Var>> {
+    let mut position = vec![usize::MAX; func.blocks.len()];
+    for (i, &block) in order.iter().enumerate() {
+        position[block] = i;
+    }
+    let mut live_in = vec![HashSet::new(); blocks.len()];
+    let mut live_out = vec![HashSet::new(); blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (i, (block, insts, ..)) in blocks.iter().enumerate().rev() {
+            let mut live = HashSet::new();
+            for succ in func.blocks[*block].terminator.successors() {
+                live.extend(live_in[position[succ]].iter().copied());
+            }
+            live_out[i] = live.clone();
+            for (uses, def) in insts.iter().rev() {
+                if let Some((var, _)) = def {
+                    live.remove(var);
+                }
+                live.extend(uses.iter().map(|(var, _)| *var));
+            }
+            if live != live_in[i] {
+                live_in[i] = live;
+                changed = true;
+            }
+        }
+    }
+    live_out
+}
+
+/// Keeps every register in the frame.
+pub fn spill_all(func: &Function) -> Allocation {
+    let mut alloc = Allocation::new(func);
+    for interval in intervals(func) {
+        alloc.spill(interval.var);
+    }
+    alloc
+}
+
+/// Linear scan allocation, caller-saved registers first.
+pub fn linear_scan(func: &Function) -> Allocation {
+    let mut alloc = Allocation::new(func);
+    // arguments past the argument registers arrive on the stack
+    for &param in func.params.iter().skip(ARG_REGS.len()) {
+        alloc.spill(param);
+    }
+    let mut intervals = intervals(func);
+    intervals.retain(|x| alloc.locations[x.var].is_none());
+    intervals.sort_by_key(|x| (x.start, x.var));
+
+    let any = CALLER_SAVED
+        .into_iter()
+        .chain(CALLEE_SAVED)
+        .collect::<Vec<_>>();
+    let mut active: Vec<(Interval, Reg)> = Vec::new();
+    for current in intervals {
+        active.retain(|(x, _)| x.end >= current.start);
+        let allowed = if current.crosses_call {
+            &CALLEE_SAVED[..]
+        } else {
+            &any[..]
+        };
+        let free = allowed
+            .iter()
+            .find(|&&reg| !active.iter().any(|(_, x)| *x == reg));
+        if let Some(&reg) = free {
+            alloc.locations[current.var] = Some(Location::Reg(reg));
+            active.push((current, reg));
+            continue;
+        }
+        let victim = active
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, reg))| allowed.contains(reg))
+            .max_by_key(|(_, (x, _))| x.end)
+            .map(|(i, _)| i);
+        match victim {
+            Some(i) if active[i].0.end > current.end => {
+                let (spilled, reg) = active.remove(i);
+                alloc.spill(spilled.var);
+                alloc.locations[current.var] = Some(Location::Reg(reg));
+                active.push((current, reg));
+            }
+            _ => alloc.spill(current.var),
+        }
+    }
+    alloc
+}
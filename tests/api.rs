@@ -0,0 +1,66 @@
+use snlc::interp::{ErrorKind, Limits};
+use snlc::{Error, Options};
+
+const SRC: &str = "program p
+var integer n, unused;
+begin
+  read(n);
+  writeln(100 / n)
+end.
+";
+
+#[test]
+fn compile_and_run() {
+    let artifact = snlc::compile(SRC, &Options::default()).unwrap();
+    assert_eq!(
+        artifact.warnings.render("p.snl"),
+        "p.snl:2:16: warning: variable `unused` is never read\n"
+    );
+    let mut output = Vec::new();
+    artifact
+        .run("4".as_bytes(), &mut output, &Limits::default())
+        .unwrap();
+    assert_eq!(output, b"25\n");
+    let mut output = Vec::new();
+    artifact
+        .run_vm("5".as_bytes(), &mut output, &Limits::default())
+        .unwrap();
+    assert_eq!(output, b"20\n");
+    assert!(artifact
+        .rust_source("p.snl")
+        .contains("snlc::runtime::set_file(\"p.snl\")"));
+}
+
+#[test]
+fn errors() {
+    let err = snlc::compile("program p\nbegin\n  x := 1\nend.\n", &Options::default()).unwrap_err();
+    assert_eq!(
+        err.render("p.snl"),
+        "p.snl:3:3: error: cannot find `x` in this scope\n"
+    );
+    let err = snlc::run(SRC, "0".as_bytes(), Vec::new(), &Limits::default()).unwrap_err();
+    assert!(matches!(&err, Error::Runtime(x) if x.kind == ErrorKind::DivisionByZero));
+    assert_eq!(
+        err.render("p.snl"),
+        "p.snl:5: runtime error: division by zero\n"
+    );
+    let limits = Limits {
+        reads: Some(0),
+        ..Limits::default()
+    };
+    let err = snlc::run(SRC, "1".as_bytes(), Vec::new(), &limits).unwrap_err();
+    assert_eq!(
+        err.render("p.snl"),
+        "p.snl:4: runtime error: read limit of 0 values exceeded\n"
+    );
+}
+
+#[test]
+fn sample_binary_is_generated_from_the_sample() {
+    let artifact = snlc::compile(include_str!("../hello.snl"), &Options::default()).unwrap();
+    assert!(artifact.warnings.is_empty());
+    assert_eq!(
+        artifact.rust_source("hello.snl"),
+        include_str!("../src/bin/hello.rs")
+    );
+}
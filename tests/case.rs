@@ -0,0 +1,77 @@
+use snlc::frontend::{fold_keywords, preprocess_ignore_case};
+use snlc::sema::{analyze, Options};
+use snlc_ast::token::{Token, TokenKind};
+
+const SRC: &str = "PROGRAM p
+VAR INTEGER Count;
+BEGIN
+  count := 1; { Mixed Case comment }
+  WRITE(COUNT)
+END.
+";
+
+fn diagnostics(src: &str, case_insensitive: bool) -> Vec<String> {
+    let options = Options {
+        case_insensitive,
+        ..Options::default()
+    };
+    match analyze(src, &options) {
+        Ok(model) => model.diagnostics,
+        Err(diagnostics) => diagnostics,
+    }
+    .iter()
+    .map(|x| x.render("p.snl", src))
+    .collect()
+}
+
+#[test]
+fn uppercase_keywords_need_the_mode() {
+    assert_eq!(
+        diagnostics(SRC, false),
+        ["p.snl:1:1: error: expected one of \"program\", found ID"]
+    );
+}
+
+#[test]
+fn names_differing_in_case_are_one_symbol() {
+    assert_eq!(
+        diagnostics(SRC, true),
+        [
+            "p.snl:4:3: warning: inconsistent spelling: `count` is declared as `Count`",
+            "p.snl:5:9: warning: inconsistent spelling: `COUNT` is declared as `Count`",
+        ]
+    );
+
+    let src = SRC.replace("Count;", "Count, count;");
+    assert!(diagnostics(&src, true).contains(
+        &"p.snl:2:20: error: `count` is declared more than once in this scope".to_string()
+    ));
+}
+
+#[test]
+fn token_layer_normalizes_keywords_and_identifiers() {
+    let tokens = Token::tokenize_ignore_case("Begin Count End");
+    let tokens = tokens
+        .iter()
+        .filter(|x| x.kind != TokenKind::Whitespace)
+        .map(|x| (x.kind.clone(), x.lexeme.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tokens,
+        [
+            (TokenKind::Keyword, "begin"),
+            (TokenKind::Ident, "count"),
+            (TokenKind::Keyword, "end"),
+        ]
+    );
+
+    assert_eq!(preprocess_ignore_case("WRITE(Count)"), "r#write(count)");
+}
+
+#[test]
+fn folding_keywords_keeps_offsets() {
+    let folded = fold_keywords(SRC);
+    assert_eq!(folded.len(), SRC.len());
+    assert!(folded.starts_with("program p\nvar integer Count;\nbegin\n"));
+    assert!(folded.contains("{ Mixed Case comment }"));
+}
@@ -0,0 +1,179 @@
+use snlc::cst;
+use snlc::cst::ast::{FactorKind, Program, StmKind, StmList};
+use snlc::formatter::format_source;
+use snlc::interp::Limits;
+use snlc::ll1::Ll1Parser;
+
+const COMMENTED: &str = "{ header }
+program  demo {name}
+var integer i ,j;   char c;
+procedure p(integer a, char b)
+begin
+   write(a);{ between }write( b )
+end
+begin
+  read(i);
+  j := i * (2 + i) ;
+  if j < 10 then p(j, 'x') else writeln('big') fi
+end. { done }
+";
+
+#[test]
+fn printing_gives_back_the_source() {
+    for src in [
+        COMMENTED,
+        include_str!("../hello.snl"),
+        include_str!("dot/loops.snl"),
+    ] {
+        let tree = cst::parse(src).unwrap();
+        assert_eq!(tree.to_string(), src);
+        assert_eq!(tree.text_range(), 0..src.len());
+        let tokens = tree.descendant_tokens();
+        assert_eq!(tokens.iter().map(|x| x.text()).collect::<String>(), src);
+    }
+}
+
+#[test]
+fn typed_view() {
+    let tree = cst::parse(COMMENTED).unwrap();
+    assert!(StmList::cast(tree.clone()).is_none());
+    let prog = Program::cast(tree.clone()).unwrap();
+    assert_eq!(prog.syntax(), &tree);
+    assert_eq!(Program::KIND, "Program");
+    assert_eq!(prog.name().unwrap().text(), "demo");
+
+    let var_part = prog.var_part().unwrap();
+    assert!(var_part.is_present());
+    let decs = var_part
+        .var_decs()
+        .iter()
+        .map(|dec| {
+            let names = dec.names().iter().map(|x| x.text().to_string()).collect();
+            (dec.type_name().unwrap().text().to_string(), names)
+        })
+        .collect::<Vec<(String, Vec<String>)>>();
+    assert_eq!(
+        decs,
+        [
+            (
+                "integer".to_string(),
+                vec!["i".to_string(), "j".to_string()]
+            ),
+            ("char".to_string(), vec!["c".to_string()]),
+        ]
+    );
+
+    let procs = prog.procedures();
+    assert_eq!(procs.len(), 1);
+    assert_eq!(procs[0].name().unwrap().text(), "p");
+    let params = procs[0]
+        .params()
+        .iter()
+        .map(|x| {
+            format!(
+                "{} {}",
+                x.type_name().unwrap().text(),
+                x.name().unwrap().text()
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(params, ["integer a", "char b"]);
+    assert!(!procs[0].var_part().unwrap().is_present());
+    assert_eq!(procs[0].body().unwrap().stms().len(), 2);
+
+    let stms = prog.body().unwrap().stms();
+    assert_eq!(stms.len(), 3);
+    let Some(StmKind::Read { targets }) = stms[0].kind() else {
+        panic!("not a read");
+    };
+    assert_eq!(targets[0].text(), "i");
+
+    let Some(StmKind::Assign {
+        target,
+        index: None,
+        value,
+    }) = stms[1].kind()
+    else {
+        panic!("not an assignment");
+    };
+    assert_eq!(target.text(), "j");
+    assert!(value.cmp().is_none());
+    let term = value.lhs().unwrap().first().unwrap();
+    let Some(FactorKind::Var(i)) = term.first().unwrap().kind() else {
+        panic!("not a variable");
+    };
+    assert_eq!(i.text(), "i");
+    let rest = term.rest();
+    assert_eq!(rest[0].0.text(), "*");
+    let Some(FactorKind::Paren(inner)) = rest[0].1.kind() else {
+        panic!("not parenthesized");
+    };
+    assert_eq!(inner.syntax().text().trim(), "2 + i");
+
+    let Some(StmKind::If {
+        condition,
+        body,
+        else_body: Some(else_body),
+    }) = stms[2].kind()
+    else {
+        panic!("not an if with an else");
+    };
+    assert_eq!(condition.cmp().unwrap().0.text(), "<");
+    let Some(StmKind::Call { name, args }) = body.stms()[0].kind() else {
+        panic!("not a call");
+    };
+    assert_eq!(name.text(), "p");
+    let Some(FactorKind::Char(x)) = args[1]
+        .lhs()
+        .unwrap()
+        .first()
+        .unwrap()
+        .first()
+        .unwrap()
+        .kind()
+    else {
+        panic!("not a char");
+    };
+    assert_eq!(x.text(), "'x'");
+    let Some(StmKind::Write { args, newline }) = else_body.stms()[0].kind() else {
+        panic!("not a write");
+    };
+    assert!(newline);
+    assert_eq!(args.len(), 1);
+}
+
+/// A program whose statement and write lists each hold `n` items.
+fn long_program(n: usize) -> String {
+    let mut src = String::from("program big\nvar integer x;\nbegin\n  x := 0;\n");
+    for _ in 0..n {
+        src.push_str("  x := x + 1;\n");
+    }
+    src.push_str("  write(x");
+    for _ in 0..n {
+        src.push_str(", x");
+    }
+    src.push_str(")\nend.\n");
+    src
+}
+
+#[test]
+fn long_lists_do_not_overflow_the_stack() {
+    let n = 3000;
+    let src = long_program(n);
+
+    let tree = cst::parse(&src).unwrap();
+    assert_eq!(tree.to_string(), src);
+    assert!(tree.descendants().len() > n);
+    assert!(format!("{tree:?}").starts_with("Program@"));
+    let body = Program::cast(tree).and_then(|x| x.body()).unwrap();
+    assert_eq!(body.stms().len(), n + 2);
+
+    assert_eq!(format_source(&src).unwrap(), src);
+    let prog = Ll1Parser::snl().parse_program(&src).unwrap();
+    assert_eq!(prog.body.len(), n + 2);
+
+    let mut out = Vec::new();
+    snlc::run(&src, &b""[..], &mut out, &Limits::default()).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out, format!("{n}").repeat(n + 1));
+}
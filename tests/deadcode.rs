@@ -0,0 +1,89 @@
+use snlc::frontend::parse_program;
+use snlc::lint::dead_code;
+use snlc::sema::Options;
+use snlc_ast::codegen::Config;
+use snlc_ast::opt::optimize;
+
+const SRC: &str = "program p
+var integer a, b, unused;
+    char c;
+procedure never(integer x)
+begin
+  writeln(x)
+end
+procedure show(integer x)
+var integer l;
+begin
+  l := x;
+  writeln(a)
+end
+begin
+  a := 1;
+  b := 2;
+  read(c);
+  show(b);
+  unused := b;
+  b := 3;
+  while 1 = 1 do
+    writeln(c)
+  endwh;
+  writeln(b)
+end.
+";
+
+#[test]
+fn warns_about_dead_code() {
+    let warnings = dead_code(SRC, &Options::default())
+        .iter()
+        .map(|x| x.render("p.snl", SRC))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        warnings,
+        [
+            "p.snl:2:19: warning: variable `unused` is never read",
+            "p.snl:4:11: warning: procedure `never` is never called",
+            "p.snl:9:13: warning: variable `l` is never read",
+            "p.snl:11:3: warning: value assigned to `l` is never read",
+            "p.snl:19:3: warning: value assigned to `unused` is never read",
+            "p.snl:20:3: warning: value assigned to `b` is never read",
+            "p.snl:24:3: warning: unreachable statement",
+        ]
+    );
+}
+
+#[test]
+fn calls_read_the_globals_of_the_callee() {
+    // `a` is only read by `show`, so the store before the call is live
+    let warnings = dead_code(SRC, &Options::default());
+    assert!(!warnings.iter().any(|x| x.message.contains("`a`")));
+}
+
+#[test]
+fn removes_dead_code_at_o1() {
+    let mut prog = parse_program(SRC).unwrap();
+    let config = Config {
+        opt_level: 1,
+        ..Config::default()
+    };
+    assert!(optimize(&mut prog, &config).is_empty());
+    let procs = prog
+        .procedure_defs
+        .iter()
+        .flatten()
+        .map(|x| x.name.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(procs, ["show"]);
+    let show = &prog.procedure_defs.as_ref().unwrap()[0];
+    assert!(show.var_defs.iter().flatten().next().is_none());
+    assert_eq!(show.body.len(), 1);
+
+    let body = prog
+        .body
+        .iter()
+        .map(|x| x.to_string() + "\n")
+        .collect::<String>();
+    assert_eq!(
+        body,
+        "a := 1\nb := 2\nread(c)\nshow(b)\nwhile 1 = 1 do writeln(c) endwh\n"
+    );
+}
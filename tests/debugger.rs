@@ -0,0 +1,136 @@
+use snlc::debugger::Debugger;
+use snlc::interp::{self, ErrorKind, Limits};
+use snlc::sema::Options;
+use snlc_ast::codegen::Config;
+
+const SRC: &str = "program p
+var integer n, s;
+    char c;
+procedure add(integer x)
+var integer t;
+begin
+  t := x * 2;
+  s := s + t
+end
+begin
+  read(n, c);
+  s := 0;
+  while 0 < n do
+    add(n);
+    n := n - 1
+  endwh;
+  writeln(s)
+end.
+";
+
+/// What the debugger shows running `SRC` on `input` with `commands`, and
+/// whether the program ran to its end.
+fn debug(input: &str, commands: &str) -> (String, bool) {
+    debug_src(SRC, &Options::default(), input, commands)
+}
+
+fn debug_src(src: &str, options: &Options, input: &str, commands: &str) -> (String, bool) {
+    let (prog, _) = interp::compile(src, options).unwrap();
+    let mut shown = Vec::new();
+    let mut debugger = Debugger::new(src, &prog, commands.as_bytes(), &mut shown);
+    let res = interp::run_observed(
+        &prog,
+        &Config::default(),
+        input.as_bytes(),
+        Vec::new(),
+        &Limits::default(),
+        &mut debugger,
+    );
+    let finished = match res {
+        Ok(()) => true,
+        Err(err) => {
+            assert_eq!(err.kind, ErrorKind::Stopped);
+            false
+        }
+    };
+    (String::from_utf8(shown).unwrap(), finished)
+}
+
+#[test]
+fn breakpoints_print_and_backtrace() {
+    let (shown, finished) = debug("2 z", "b 8\nc\np t\np s\np c\np x\np q\nbt\nd 8\nc\n");
+    assert!(finished);
+    assert_eq!(
+        shown,
+        "p:11: read(n, c);
+breakpoint at line 8
+add:8: s := s + t
+t = 4
+s = 0
+c = 'z'
+x = 2
+no variable `q` in scope
+#0 add at line 8
+#1 p at line 14
+deleted the breakpoint at line 8
+"
+    );
+}
+
+#[test]
+fn step_next_and_finish() {
+    let (shown, finished) = debug("1 z", "n\nn\nn\ns\ns\nf\nn\nq\n");
+    assert!(!finished);
+    let stops = shown.lines().map(|x| x.split(' ').next().unwrap());
+    assert_eq!(
+        stops.collect::<Vec<_>>(),
+        ["p:11:", "p:12:", "p:13:", "p:14:", "add:7:", "add:8:", "p:15:", "p:13:"]
+    );
+
+    // `next` runs a call to its end
+    let (shown, _) = debug("1 z", "b 14\nc\nn\nq\n");
+    assert!(
+        shown.ends_with("p:14: add(n);\np:15: n := n - 1\n"),
+        "{shown}"
+    );
+}
+
+#[test]
+fn bad_commands_are_reported() {
+    let (shown, finished) = debug("0 z", "b 3\nb x\nd 12\nf\njump\nc\n");
+    assert!(finished);
+    assert_eq!(
+        shown,
+        "p:11: read(n, c);
+no statement at line 3
+no statement at line x
+no breakpoint at line 12
+not in a procedure
+bad command `jump`, try `help`
+"
+    );
+}
+
+#[test]
+fn print_matches_names_like_sema() {
+    let src = "program p
+var integer Total;
+begin
+  total := 3;
+  writeln(TOTAL)
+end.
+";
+    let options = Options {
+        case_insensitive: true,
+        ..Options::default()
+    };
+    let (shown, finished) = debug_src(src, &options, "", "n\np total\np TOTAL\np Total\nc\n");
+    assert!(finished);
+    assert_eq!(
+        shown,
+        "p:4: total := 3;
+p:5: writeln(TOTAL)
+total = 3
+TOTAL = 3
+Total = 3
+"
+    );
+
+    let (shown, _) = debug("1 z", "p N\nq\n");
+    assert!(shown.ends_with("no variable `N` in scope\n"), "{shown}");
+}
@@ -0,0 +1,65 @@
+use std::process::Command;
+
+/// Output of `snlc emit --emit=<emit> <args>` for `tests/dot/<name>.snl`.
+fn emit(emit: &str, name: &str, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_snlc"))
+        .args(["emit", &format!("--emit={emit}")])
+        .args(args)
+        .arg(format!("tests/dot/{name}.snl"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn ast_dot() {
+    assert_eq!(
+        emit("ast-dot", "loops", &[]),
+        include_str!("dot/loops.ast.dot")
+    );
+}
+
+#[test]
+fn cfg_dot() {
+    assert_eq!(
+        emit("cfg-dot", "loops", &[]),
+        include_str!("dot/loops.cfg.dot")
+    );
+}
+
+#[test]
+fn case_insensitive_mode() {
+    let args = ["--case-insensitive"];
+    assert_eq!(
+        emit("ast-dot", "upper", &args),
+        include_str!("dot/loops.ast.dot")
+    );
+    assert_eq!(
+        emit("cfg-dot", "upper", &args),
+        include_str!("dot/loops.cfg.dot")
+    );
+    let cst = emit("cst", "upper", &args);
+    assert!(cst.starts_with("Program@0..323\n  Keyword@0..7 \"program\""));
+}
+
+#[test]
+fn syntax_errors_read_as_in_check() {
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_snlc"))
+            .args(args)
+            .arg("tests/dot/unclosed.snl")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(3), "{:?}", output);
+        String::from_utf8(output.stderr).unwrap()
+    };
+    let check = run(&["check"]);
+    assert!(
+        check.starts_with("tests/dot/unclosed.snl:5:1: error: expected one of"),
+        "{check}"
+    );
+    for emit in ["ast-dot", "cfg-dot", "cst"] {
+        assert_eq!(run(&["emit", &format!("--emit={emit}")]), check);
+    }
+}
@@ -0,0 +1,109 @@
+use snlc::interp::{self, ErrorKind, Frame, Limits, RuntimeError};
+use snlc::ir;
+use snlc::runtime::ReadError;
+use snlc::sema::Options;
+use snlc::vm;
+use snlc::vm::regalloc::Allocator;
+use snlc_ast::codegen::{Config, IntWidth};
+
+const SRC: &str = "program p
+var integer z, r;
+procedure inner(integer a)
+begin
+  r := 1000 / a;
+  r := r * r * r
+end
+procedure outer(integer a)
+begin
+  inner(a - 1)
+end
+begin
+  read(z);
+  outer(z);
+  writeln(r)
+end.
+";
+
+/// The error of the interpreter, the IR evaluator and the VM running `SRC`
+/// with 16-bit integers.
+fn errors(input: &str) -> [RuntimeError; 3] {
+    let options = Options {
+        int_width: IntWidth::I16,
+        ..Options::default()
+    };
+    let config = Config {
+        int_width: IntWidth::I16,
+        ..Config::default()
+    };
+    let limits = Limits::default();
+    let (prog, _) = interp::compile(SRC, &options).unwrap();
+    let module = ir::lower(&prog);
+    let code = vm::codegen::compile(&module, Allocator::LinearScan);
+    let input = input.as_bytes();
+    [
+        interp::run(&prog, &config, input, Vec::new(), &limits).unwrap_err(),
+        ir::exec::run(&module, &config, input, Vec::new(), &limits).unwrap_err(),
+        vm::exec::run(&code, &config, input, Vec::new(), &limits).unwrap_err(),
+    ]
+}
+
+fn nested() -> Vec<Frame> {
+    vec![
+        Frame {
+            name: "inner".to_string(),
+            line: 10,
+        },
+        Frame {
+            name: "outer".to_string(),
+            line: 14,
+        },
+    ]
+}
+
+#[test]
+fn every_backend_reports_the_call_stack() {
+    for err in errors("1") {
+        assert_eq!(
+            err,
+            RuntimeError {
+                kind: ErrorKind::DivisionByZero,
+                line: 5,
+                stack: nested(),
+            }
+        );
+    }
+    for err in errors("3") {
+        assert_eq!((&err.kind, err.line), (&ErrorKind::Overflow, 6));
+        assert_eq!(err.stack, nested());
+    }
+    for err in errors("x") {
+        let kind = ErrorKind::Input(ReadError::InvalidInteger("x".to_string()));
+        assert_eq!((err.kind, err.line, err.stack), (kind, 13, Vec::new()));
+    }
+}
+
+#[test]
+fn rendered_errors_name_the_file() {
+    let [err, ..] = errors("1");
+    assert_eq!(err.to_string(), "runtime error: division by zero at line 5");
+    assert_eq!(
+        err.render("p.snl"),
+        "p.snl:5: runtime error: division by zero
+    in inner, called at p.snl:10
+    in outer, called at p.snl:14"
+    );
+
+    let err = RuntimeError {
+        kind: ErrorKind::IndexOutOfBounds {
+            index: 11,
+            low: 1,
+            top: 10,
+        },
+        line: 3,
+        stack: Vec::new(),
+    };
+    assert_eq!(
+        err.render("a.snl"),
+        "a.snl:3: runtime error: array index 11 out of bounds 1..10"
+    );
+}
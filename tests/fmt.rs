@@ -0,0 +1,90 @@
+use snlc::formatter::format_source;
+
+const MESSY: &str = "{ header }
+program   demo{name}
+var integer i,j ;char   c;
+procedure p(integer a,char b)
+begin
+write(a);write( b )
+end
+
+
+procedure q()
+var integer k;
+begin k:=1; { trailing } while k<=10 do k:=k+1*2;
+{ own line }
+if k=3 then write(k) else write('c') fi endwh end
+begin
+  read(i);
+     p(i,c) ; q( )
+end. { done }
+";
+
+fn assert_idempotent(src: &str) -> String {
+    let once = format_source(src).unwrap();
+    let twice = format_source(&once).unwrap();
+    assert_eq!(once, twice);
+    once
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    assert_idempotent(MESSY);
+    assert_idempotent(include_str!("../hello.snl"));
+}
+
+#[test]
+fn formatted_file_is_unchanged() {
+    let src = include_str!("../hello.snl");
+    assert_eq!(format_source(src).unwrap(), src);
+}
+
+#[test]
+fn comments_are_preserved() {
+    let formatted = assert_idempotent(MESSY);
+    for comment in [
+        "{ header }",
+        "{name}",
+        "{ trailing }",
+        "{ own line }",
+        "{ done }",
+    ] {
+        assert!(formatted.contains(comment), "lost {comment}");
+    }
+}
+
+#[test]
+fn layout_is_normalized() {
+    let formatted = assert_idempotent(MESSY);
+    let expected = "{ header }
+program demo {name}
+var integer i, j;
+    char c;
+procedure p(integer a, char b)
+begin
+  write(a);
+  write(b)
+end
+
+procedure q()
+var integer k;
+begin
+  k := 1; { trailing }
+  while k <= 10 do
+    k := k + 1 * 2;
+    { own line }
+    if k = 3 then
+      write(k)
+    else
+      write('c')
+    fi
+  endwh
+end
+begin
+  read(i);
+  p(i, c);
+  q()
+end. { done }
+";
+    assert_eq!(formatted, expected);
+}
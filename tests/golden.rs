@@ -0,0 +1,300 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use snlc::diff::unified_diff;
+use snlc::golden::{self, Backend, CaseResult, Outcome};
+use snlc::interp::{self, ErrorKind, Limits};
+use snlc::sema;
+use snlc_ast::codegen::{Config, IntWidth, OverflowPolicy};
+
+fn run(src: &str, input: &str, config: &Config) -> (String, Result<(), interp::RuntimeError>) {
+    let options = sema::Options {
+        int_width: config.int_width,
+        ..sema::Options::default()
+    };
+    let (prog, _) = interp::compile(src, &options).unwrap();
+    let mut output = Vec::new();
+    let res = interp::run(
+        &prog,
+        config,
+        input.as_bytes(),
+        &mut output,
+        &Limits::default(),
+    );
+    (String::from_utf8(output).unwrap(), res)
+}
+
+const FACT: &str = "program fact
+var integer n, r;
+procedure f(integer k)
+begin
+  r := 1;
+  while 1 < k do
+    r := r * k;
+    k := k - 1
+  endwh
+end
+begin
+  read(n);
+  f(n);
+  write('fact ', n, ' = ', r);
+  writeln
+end.
+";
+
+#[test]
+fn interp_runs_procedures() {
+    let (out, res) = run(FACT, "10", &Config::default());
+    assert_eq!(res, Ok(()));
+    assert_eq!(out, "fact 10 = 3628800\n");
+}
+
+#[test]
+fn interp_overflow_policies() {
+    let config = |overflow| Config {
+        int_width: IntWidth::I16,
+        overflow,
+        ..Config::default()
+    };
+    let (_, res) = run(FACT, "8", &config(OverflowPolicy::Trap));
+    let err = res.unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Overflow);
+    assert_eq!(err.to_string(), "runtime error: integer overflow at line 7");
+
+    let (out, _) = run(FACT, "8", &config(OverflowPolicy::Wrap));
+    assert_eq!(out, "fact 8 = -25216\n");
+    let (out, _) = run(FACT, "8", &config(OverflowPolicy::Saturate));
+    assert_eq!(out, "fact 8 = 32767\n");
+}
+
+#[test]
+fn interp_runtime_errors_name_the_line() {
+    let src = "program p\nvar integer a, b;\nbegin\n  read(a, b);\n  writeln(a);\n  writeln(a / b)\nend.\n";
+    let (out, res) = run(src, "7 0", &Config::default());
+    assert_eq!(out, "7\n");
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "runtime error: division by zero at line 6"
+    );
+
+    let (_, res) = run(src, "7", &Config::default());
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "runtime error: unexpected end of input at line 4"
+    );
+}
+
+#[test]
+fn unified_diffs() {
+    assert_eq!(unified_diff("a\nb\n", "a\nb\n", "x", "y"), "");
+    let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+    let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+    assert_eq!(
+        unified_diff(old, new, "x", "y"),
+        "--- x\n+++ y\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n"
+    );
+    assert_eq!(
+        unified_diff("a\n", "a", "x", "y"),
+        "--- x\n+++ y\n@@ -1 +1 @@\n-a\n+a\n\\ No newline at end of file\n"
+    );
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("snlc-golden-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn options(backend: Backend) -> golden::Options {
+    golden::Options {
+        backend,
+        sema: sema::Options::default(),
+        config: Config::default(),
+        timeout: Duration::from_millis(300),
+        limits: Limits::default(),
+    }
+}
+
+#[test]
+fn runner_reports_each_case() {
+    let dir = temp_dir("runner");
+    let files = [
+        ("fact.snl", FACT),
+        ("fact.in", "5\n"),
+        ("fact.out", "fact 5 = 120\n"),
+        ("wrong.snl", FACT),
+        ("wrong.in", "4\n"),
+        ("wrong.out", "fact 4 = 120\n"),
+        (
+            "div.snl",
+            "program div\nvar integer z;\nbegin\n  read(z);\n  writeln(1 / z)\nend.\n",
+        ),
+        ("div.in", "0\n"),
+        ("div.err", "div.snl:5: runtime error: division by zero\n"),
+        ("bad.snl", "program bad\nbegin\n  x := 1\nend.\n"),
+        (
+            "bad.err",
+            "bad.snl:3:3: error: cannot find `x` in this scope\n",
+        ),
+        (
+            "forever.snl",
+            "program forever\nbegin\n  while 0 < 1 do writeln endwh\nend.\n",
+        ),
+        ("forever.out", ""),
+        ("skipped.snl", FACT),
+        ("notes.txt", ""),
+    ];
+    for (name, text) in files {
+        fs::write(dir.join(name), text).unwrap();
+    }
+
+    let cases = golden::find_cases(&dir).unwrap();
+    let names = cases.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["bad", "div", "fact", "forever", "wrong"]);
+
+    for backend in [Backend::Interp, Backend::Vm] {
+        let mut log = Vec::new();
+        let results = golden::run_cases(&cases, &options(backend), &mut log).unwrap();
+        assert_eq!(
+            String::from_utf8(log).unwrap(),
+            "test bad ... ok\ntest div ... ok\ntest fact ... ok\n\
+             test forever ... timed out\ntest wrong ... FAILED\n"
+        );
+        match &results[4].outcome {
+            Outcome::Fail(report) => assert_eq!(
+                report,
+                "--- wrong.out (expected)\n+++ stdout (actual)\n@@ -1 +1 @@\n\
+                 -fact 4 = 120\n+fact 4 = 24\n"
+            ),
+            outcome => panic!("{:?}", outcome),
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn junit_report() {
+    let result = |name: &str, outcome| CaseResult {
+        name: name.to_string(),
+        outcome,
+        time: Duration::from_millis(1500),
+    };
+    let results = [
+        result("a", Outcome::Pass),
+        result("b<1>", Outcome::Fail("-x & y\n".to_string())),
+        result("c", Outcome::TimedOut),
+    ];
+    assert_eq!(
+        golden::junit("suite", &results),
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<testsuite name=\"suite\" tests=\"3\" failures=\"2\" errors=\"0\" time=\"4.500\">
+  <testcase name=\"a\" classname=\"suite\" time=\"1.500\"/>
+  <testcase name=\"b&lt;1&gt;\" classname=\"suite\" time=\"1.500\">
+    <failure message=\"output differs\">-x &amp; y
+</failure>
+  </testcase>
+  <testcase name=\"c\" classname=\"suite\" time=\"1.500\">
+    <failure message=\"timed out\"/>
+  </testcase>
+</testsuite>
+"
+    );
+}
+
+#[test]
+fn rust_backend_builds_outside_the_source_tree() {
+    let dir = temp_dir("rust");
+    let files = [
+        (
+            "double.snl",
+            "program double\nvar integer n;\nbegin\n  read(n);\n  writeln(n * 2)\nend.\n",
+        ),
+        ("double.in", "21\n"),
+        ("double.out", "42\n"),
+        (
+            "param.snl",
+            "program param\nprocedure p(integer n)\nbegin\n  n := n + 1;\n  writeln(n)\nend\nbegin\n  p(1)\nend.\n",
+        ),
+        ("param.out", "2\n"),
+        (
+            "globals.snl",
+            "program globals
+var integer n, total;
+procedure add(integer k)
+begin
+  total := total + k
+end
+procedure twice(integer k)
+begin
+  add(k);
+  add(k)
+end
+begin
+  read(n);
+  total := 0;
+  twice(n);
+  add(total);
+  writeln(total)
+end.
+",
+        ),
+        ("globals.in", "5\n"),
+        ("globals.out", "20\n"),
+        (
+            "keyword.snl",
+            "program keyword\nvar integer fn;\nbegin\n  fn := 1;\n  writeln(fn)\nend.\n",
+        ),
+        ("keyword.out", "1\n"),
+        // `self` can't be a raw identifier
+        (
+            "reserved.snl",
+            "program reserved\nvar integer self;\nbegin\n  self := 1\nend.\n",
+        ),
+        ("reserved.out", ""),
+        ("bad.snl", "program bad\nbegin\n  x := 1\nend.\n"),
+        ("bad.err", "bad.snl:3:3: error: cannot find `x` in this scope\n"),
+    ];
+    for (name, text) in files {
+        fs::write(dir.join(name), text).unwrap();
+    }
+    let bins = || fs::read_dir("src/bin").unwrap().count();
+    let before = bins();
+
+    let cases = golden::find_cases(&dir).unwrap();
+    let options = golden::Options {
+        timeout: Duration::from_secs(10),
+        ..options(Backend::Rust)
+    };
+    let mut log = Vec::new();
+    let results = golden::run_cases(&cases, &options, &mut log).unwrap();
+    assert_eq!(
+        String::from_utf8(log).unwrap(),
+        "test bad ... ok
+test double ... ok
+test globals ... ok
+test keyword ... ok
+test param ... ok
+test reserved ... error
+"
+    );
+    match &results[5].outcome {
+        Outcome::Error(err) => {
+            assert!(err.contains("found keyword `self`"), "{err}");
+            assert_eq!(err.matches("error:").count(), 1, "{err}");
+        }
+        outcome => panic!("{:?}", outcome),
+    }
+    assert_eq!(bins(), before);
+    let leftovers = fs::read_dir(std::env::temp_dir())
+        .unwrap()
+        .flatten()
+        .filter(|x| {
+            let name = x.file_name().to_string_lossy().into_owned();
+            name.starts_with(&format!("snlc_golden_{}_", std::process::id()))
+        })
+        .count();
+    assert_eq!(leftovers, 0);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,101 @@
+use std::collections::BTreeSet;
+use std::process::Command;
+
+use snlc::grammar::{left_recursion, Analysis, Grammar, GrammarReport};
+
+const EXPR: &str = r#"
+E  ::= T E2
+E2 ::= "+" T E2 | ε
+T  ::= F T2
+T2 ::= "*" F T2 | ε
+F  ::= "(" E ")" | ID
+"#;
+
+fn set(items: &[&str]) -> BTreeSet<String> {
+    items.iter().map(|x| x.to_string()).collect()
+}
+
+fn cycles(text: &str) -> Vec<Vec<String>> {
+    let grammar = Grammar::parse(text).unwrap();
+    left_recursion(&grammar, &Analysis::new(&grammar))
+}
+
+#[test]
+fn nullable_first_and_follow() {
+    let grammar = Grammar::parse(EXPR).unwrap();
+    let analysis = Analysis::new(&grammar);
+    assert_eq!(analysis.nullable, set(&["E2", "T2"]));
+    assert_eq!(analysis.first["E"], set(&["(", "ID"]));
+    assert_eq!(analysis.first["E2"], set(&["+"]));
+    assert_eq!(analysis.first["T2"], set(&["*"]));
+    assert_eq!(analysis.follow["E"], set(&["$", ")"]));
+    assert_eq!(analysis.follow["E2"], set(&["$", ")"]));
+    assert_eq!(analysis.follow["T"], set(&["$", ")", "+"]));
+    assert_eq!(analysis.follow["F"], set(&["$", ")", "*", "+"]));
+    assert!(GrammarReport::new(&grammar).is_ll1());
+}
+
+#[test]
+fn left_recursion_direct_and_indirect() {
+    assert_eq!(cycles(EXPR), Vec::<Vec<String>>::new());
+    assert_eq!(cycles("E ::= E \"+\" ID | ID"), [["E"]]);
+    assert_eq!(
+        cycles("A ::= B \"a\" | \"x\"\nB ::= A \"b\" | \"y\""),
+        [["A", "B"]]
+    );
+    // a nullable prefix hides the recursion
+    assert_eq!(cycles("A ::= N A \"a\" | \"x\"\nN ::= \"n\" | ε"), [["A"]]);
+
+    let grammar = Grammar::parse("E ::= E \"+\" ID | ID").unwrap();
+    let report = GrammarReport::new(&grammar);
+    assert!(!report.is_ll1());
+    assert!(report.to_string().contains("left recursion: E -> E"));
+}
+
+#[test]
+fn conflicts_are_classified() {
+    let grammar = Grammar::parse("S ::= \"a\" \"b\" | \"a\" \"c\"").unwrap();
+    let report = GrammarReport::new(&grammar);
+    let [conflict] = &report.table.conflicts[..] else {
+        panic!("{:?}", report.table.conflicts);
+    };
+    assert_eq!((&*conflict.nonterminal, &*conflict.terminal), ("S", "a"));
+    assert_eq!(conflict.productions, [0, 1]);
+    assert!(report.is_first_first(conflict));
+    assert!(report.to_string().contains(
+        "FIRST/FIRST conflict: S on \"a\"\n    S ::= \"a\" \"b\"\n    S ::= \"a\" \"c\"\n"
+    ));
+
+    let grammar = Grammar::parse(include_str!("grammar/conflict.bnf")).unwrap();
+    let report = GrammarReport::new(&grammar);
+    let [conflict] = &report.table.conflicts[..] else {
+        panic!("{:?}", report.table.conflicts);
+    };
+    assert_eq!((&*conflict.nonterminal, &*conflict.terminal), ("A", "a"));
+    assert!(!report.is_first_first(conflict));
+    assert!(report
+        .to_string()
+        .contains("FIRST/FOLLOW conflict: A on \"a\"\n    A ::= \"a\"\n    A ::= ε\n"));
+}
+
+#[test]
+fn exit_code() {
+    let grammar = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_snlc"))
+            .arg("grammar")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let output = grammar(&[]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .ends_with("grammar is LL(1)\n"));
+
+    let output = grammar(&["tests/grammar/conflict.bnf"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .ends_with("grammar is not LL(1)\n"));
+}
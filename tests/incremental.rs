@@ -0,0 +1,118 @@
+use std::rc::Rc;
+
+use snlc::cst::SyntaxNode;
+use snlc::incremental::Document;
+use snlc::ll1::REUSABLE;
+use snlc_lexer::incremental::{relex, Edit};
+use snlc_lexer::tokensize;
+
+const SOURCE: &str = "program p
+var integer i;
+    char c;
+procedure a(integer n)
+var integer t;
+begin
+  t := n + 1;
+  write(t)
+end
+procedure b(char ch)
+begin
+  write(ch)
+end
+begin
+  read(i);
+  c := ''''; { a quote }
+  a(i);
+  b(c)
+end.
+";
+
+fn check_relex(src: &str, edit: Edit) {
+    let tokens = tokensize(src).collect::<Vec<_>>();
+    let new_src = edit.apply(src);
+    let expected = tokensize(&new_src).collect::<Vec<_>>();
+    assert_eq!(relex(&tokens, &new_src, &edit), expected, "{edit:?}");
+}
+
+#[test]
+fn relex_matches_full_lexing() {
+    let edits = [
+        // extend and shrink identifiers
+        (SOURCE.find("read(i)").unwrap() + 6, 0, "dx"),
+        (SOURCE.find("integer i").unwrap() + 3, 4, ""),
+        // open and close a comment over following code
+        (SOURCE.find("{ a quote }").unwrap() + 10, 1, ""),
+        (SOURCE.find("read").unwrap(), 0, "{"),
+        (SOURCE.find("{ a quote }").unwrap(), 1, ""),
+        // break the doubled quote and unterminate a char literal
+        (SOURCE.find("''''").unwrap() + 1, 1, ""),
+        (SOURCE.find("''''").unwrap() + 2, 0, "'"),
+        (SOURCE.find("''''").unwrap() + 3, 1, ""),
+        // join two lines and split a token
+        (SOURCE.find(";\n  b(c)").unwrap() + 1, 3, ""),
+        (SOURCE.find(":=").unwrap() + 1, 0, " "),
+        (SOURCE.find(":=").unwrap(), 1, ""),
+        // at both ends of the text
+        (0, 0, "  "),
+        (0, 7, "x"),
+        (SOURCE.len(), 0, "@"),
+        (SOURCE.len() - 2, 2, ""),
+    ];
+    for (start, len, text) in edits {
+        check_relex(SOURCE, Edit::new(start..start + len, text));
+    }
+    for start in 0..=SOURCE.len() {
+        for text in ["", "'", "{", "}", " ", "x1", "\n"] {
+            check_relex(SOURCE, Edit::new(start..start, text));
+            if start < SOURCE.len() {
+                check_relex(SOURCE, Edit::new(start..start + 1, text));
+            }
+        }
+    }
+}
+
+fn procedures(root: &SyntaxNode) -> Vec<SyntaxNode> {
+    root.descendants()
+        .into_iter()
+        .filter(|x| x.kind() == REUSABLE)
+        .collect()
+}
+
+#[test]
+fn editing_a_body_reuses_other_procedures() {
+    let mut doc = Document::new(SOURCE);
+    let before = procedures(doc.tree().unwrap());
+    assert_eq!(before.len(), 2);
+
+    let pos = SOURCE.find("n + 1").unwrap() + 4;
+    doc.edit(&Edit::new(pos..pos + 1, "20"));
+    let after = procedures(doc.tree().unwrap());
+    assert_eq!(doc.reused(), 1);
+    assert!(!Rc::ptr_eq(before[0].green(), after[0].green()));
+    assert!(Rc::ptr_eq(before[1].green(), after[1].green()));
+    assert_eq!(doc.tokens(), tokensize(doc.text()).collect::<Vec<_>>());
+
+    // an edit in the main body moves both procedures along unchanged
+    let pos = doc.text().find("read(i)").unwrap();
+    doc.edit(&Edit::new(pos..pos, "i := 0;\n  "));
+    assert_eq!(doc.reused(), 2);
+    let full = Document::new(doc.text().to_string());
+    assert_eq!(
+        doc.tree().unwrap().to_string(),
+        full.tree().unwrap().to_string()
+    );
+}
+
+#[test]
+fn broken_text_keeps_procedures_for_later() {
+    let mut doc = Document::new(SOURCE);
+    let pos = SOURCE.find("b(c)").unwrap();
+    doc.edit(&Edit::new(pos..pos + 4, "b(c"));
+    assert!(doc.tree().is_err());
+    assert!(doc.lex_errors().is_empty());
+
+    doc.edit(&Edit::new(pos + 3..pos + 3, ")"));
+    assert_eq!(doc.text(), SOURCE);
+    assert!(doc.tree().is_ok());
+    assert_eq!(doc.reused(), 2);
+}
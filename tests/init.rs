@@ -0,0 +1,147 @@
+use quote::ToTokens;
+use snlc::frontend::parse_program;
+use snlc::interp::{self, ErrorKind, Limits};
+use snlc::lint::uninit_reads;
+use snlc::sema::Options;
+use snlc_ast::codegen::{with_config, Config};
+
+const SRC: &str = "program p
+var integer a, b, c, g;
+    char ch;
+procedure setg(integer x)
+var integer l;
+begin
+  g := x;
+  writeln(l)
+end
+procedure useg(integer x)
+begin
+  writeln(g + x)
+end
+begin
+  read(a);
+  if a < 1 then b := 1 fi;
+  writeln(b);
+  writeln(c);
+  useg(a);
+  setg(a);
+  useg(a);
+  while a < 10 do
+    ch := 'x';
+    a := a + 1
+  endwh;
+  writeln(ch)
+end.
+";
+
+fn warnings(src: &str, options: &Options) -> Vec<String> {
+    uninit_reads(src, options)
+        .iter()
+        .map(|x| x.render("p.snl", src))
+        .collect()
+}
+
+#[test]
+fn reads_before_assignment_are_reported() {
+    assert_eq!(
+        warnings(SRC, &Options::default()),
+        [
+            "p.snl:8:11: warning: variable `l` is read before it is assigned",
+            "p.snl:17:11: warning: variable `b` may be read before it is assigned",
+            "p.snl:18:11: warning: variable `c` is read before it is assigned",
+            "p.snl:19:3: warning: call to `useg` reads `g` before it is assigned",
+            "p.snl:26:11: warning: variable `ch` may be read before it is assigned",
+        ]
+    );
+}
+
+#[test]
+fn every_path_must_assign() {
+    let src = "program p
+var integer a, b;
+begin
+  read(a);
+  if a < 1 then b := 1 else read(b) fi;
+  while b < a do b := b + 1 endwh;
+  writeln(b)
+end.
+";
+    assert_eq!(warnings(src, &Options::default()), Vec::<String>::new());
+}
+
+#[test]
+fn strict_init_makes_them_errors() {
+    let options = Options {
+        strict_init: true,
+        ..Options::default()
+    };
+    assert!(warnings(SRC, &options)
+        .iter()
+        .all(|x| x.contains(": error: ")));
+    assert!(interp::compile(SRC, &options).is_err());
+}
+
+/// Generated Rust code with whitespace removed.
+fn codegen(src: &str, config: Config) -> String {
+    let prog = parse_program(src).unwrap();
+    let code = with_config(config, || prog.to_token_stream().to_string());
+    code.split_whitespace().collect()
+}
+
+#[test]
+fn strict_init_stops_zero_initializing() {
+    let src = "program p
+var integer a, g;
+    char c;
+procedure q(integer x)
+var integer l;
+begin
+  l := x;
+  writeln(l + g)
+end
+begin
+  g := 1;
+  read(a, c);
+  q(a)
+end.
+";
+    let code = codegen(src, Config::default());
+    assert!(code.contains("letmuta:i32=0;"), "{code}");
+    assert!(code.contains("letmutc='\\0';"), "{code}");
+
+    let config = Config {
+        strict_init: true,
+        ..Config::default()
+    };
+    let code = codegen(src, config);
+    assert!(code.contains("letmuta:i32;"), "{code}");
+    assert!(code.contains("letmutc:char;"), "{code}");
+    assert!(code.contains("letmutl:i32;"), "{code}");
+    // `q` reads `g` wherever it is expanded, so `g` keeps its value
+    assert!(code.contains("letmutg:i32=0;"), "{code}");
+}
+
+#[test]
+fn interpreter_does_not_zero_initialize() {
+    let src = "program p\nvar integer a;\nbegin\n  writeln(a)\nend.\n";
+    let (prog, _) = interp::compile(src, &Options::default()).unwrap();
+    let config = Config {
+        strict_init: true,
+        ..Config::default()
+    };
+    let mut output = Vec::new();
+    let err = interp::run(&prog, &config, &b""[..], &mut output, &Limits::default()).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Unassigned);
+    assert_eq!(err.line, 4);
+
+    let mut output = Vec::new();
+    interp::run(
+        &prog,
+        &Config::default(),
+        &b""[..],
+        &mut output,
+        &Limits::default(),
+    )
+    .unwrap();
+    assert_eq!(output, b"0\n");
+}
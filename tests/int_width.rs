@@ -0,0 +1,116 @@
+use std::process::Command;
+
+use quote::ToTokens;
+use snlc::frontend::parse_program;
+use snlc::sema::{analyze, Options};
+use snlc_ast::codegen::{with_config, Config, IntWidth, OverflowPolicy};
+
+const SRC: &str = "program p
+var integer a;
+begin
+  a := 32767;
+  a := a * 2 + 40000
+end.
+";
+
+fn errors(src: &str, int_width: IntWidth) -> Vec<String> {
+    let options = Options {
+        int_width,
+        ..Options::default()
+    };
+    analyze(src, &options)
+        .unwrap()
+        .diagnostics
+        .iter()
+        .map(|x| x.render("p.snl", src))
+        .collect()
+}
+
+/// Generated Rust code with whitespace removed.
+fn codegen(config: Config) -> String {
+    let prog = parse_program(SRC).unwrap();
+    let code = with_config(config, || prog.to_token_stream().to_string());
+    code.split_whitespace().collect()
+}
+
+#[test]
+fn literals_must_fit_the_integer_width() {
+    assert_eq!(
+        errors(SRC, IntWidth::I16),
+        ["p.snl:5:16: error: integer literal `40000` does not fit in a 16-bit integer (max 32767)"]
+    );
+    assert_eq!(errors(SRC, IntWidth::I32), Vec::<String>::new());
+
+    let src = SRC.replace("40000", "2147483648");
+    assert_eq!(errors(&src, IntWidth::I32).len(), 1);
+    assert_eq!(errors(&src, IntWidth::I64), Vec::<String>::new());
+
+    let src = SRC.replace("40000", "99999999999999999999");
+    assert_eq!(errors(&src, IntWidth::I64).len(), 1);
+}
+
+#[test]
+fn integer_type_follows_the_width() {
+    let code = codegen(Config {
+        int_width: IntWidth::I64,
+        ..Config::default()
+    });
+    assert!(code.contains("letmuta:i64=0;"), "{code}");
+    assert!(code.contains("let(lhs,rhs):(i64,i64)"), "{code}");
+}
+
+#[test]
+fn arithmetic_follows_the_overflow_policy() {
+    let config = |overflow| Config {
+        overflow,
+        ..Config::default()
+    };
+    let code = codegen(config(OverflowPolicy::Wrap));
+    assert!(code.contains("lhs.wrapping_mul(rhs)"), "{code}");
+    assert!(code.contains("lhs.wrapping_add(rhs)"), "{code}");
+
+    let code = codegen(config(OverflowPolicy::Saturate));
+    assert!(code.contains("lhs.saturating_mul(rhs)"), "{code}");
+
+    let code = codegen(config(OverflowPolicy::Trap));
+    assert!(code.contains("lhs.checked_add(rhs)"), "{code}");
+    assert!(
+        code.contains("::snlc::runtime::fail(::snlc::runtime::ErrorKind::Overflow"),
+        "{code}"
+    );
+}
+
+#[test]
+fn config_roundtrips_through_the_attribute() {
+    let config = Config {
+        int_width: IntWidth::I16,
+        overflow: OverflowPolicy::Saturate,
+        ..Config::default()
+    };
+    let file: syn::File = syn::parse_str(&config.to_attr()).unwrap();
+    assert_eq!(Config::from_attrs(&file.attrs).unwrap(), config);
+
+    let file: syn::File = syn::parse_str("#![snlc(overflow = \"explode\")]").unwrap();
+    assert!(Config::from_attrs(&file.attrs).is_err());
+}
+
+#[test]
+fn emit_checks_with_the_integer_width() {
+    let emit = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_snlc"))
+            .arg("emit")
+            .args(args)
+            .arg("tests/int_width/wide.snl")
+            .output()
+            .unwrap()
+    };
+    for emit_kind in ["--emit=ir", "--emit=asm"] {
+        assert!(emit(&[emit_kind]).status.success());
+        let output = emit(&[emit_kind, "--int-width", "16"]);
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "tests/int_width/wide.snl:4:8: error: integer literal `40000` does not fit in a 16-bit integer (max 32767)\n"
+        );
+    }
+}
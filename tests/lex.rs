@@ -0,0 +1,131 @@
+use snlc::frontend::check_lexical;
+use snlc_lexer::{lex_errors, LexErrorKind};
+
+#[test]
+fn clean_source_has_no_errors() {
+    let src = std::fs::read_to_string("hello.snl").unwrap();
+    assert_eq!(lex_errors(&src), Vec::new());
+    assert!(check_lexical(&src).is_ok());
+}
+
+#[test]
+fn reports_kinds_spans_and_positions() {
+    let src = "program p\nbegin\n  write(1) @\n  c := 'a\n  { never closed\nend.\n";
+    let errors = lex_errors(src);
+    assert_eq!(errors.len(), 3);
+
+    assert_eq!(errors[0].kind, LexErrorKind::UnexpectedChar('@'));
+    assert_eq!(&src[errors[0].span.clone()], "@");
+    assert_eq!(errors[0].to_string(), "unexpected character '@'");
+
+    assert_eq!(errors[1].kind, LexErrorKind::UnterminatedChar);
+    assert_eq!((errors[1].line, errors[1].col), (4, 8));
+    assert_eq!(
+        errors[1].to_string(),
+        "unterminated character literal starting at 4:8"
+    );
+
+    assert_eq!(errors[2].kind, LexErrorKind::UnterminatedComment);
+    assert_eq!(errors[2].span.end, src.len());
+    assert_eq!(
+        errors[2].to_string(),
+        "unterminated comment starting at 5:3"
+    );
+}
+
+#[test]
+fn diagnostics_render_with_path() {
+    let src = "program p\nbegin\n  write(1 # 2)\nend.\n";
+    let diagnostics = check_lexical(src).unwrap_err();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].render("p.snl", src),
+        "p.snl:3:11: error: unexpected character '#'"
+    );
+}
+
+#[test]
+fn char_literal_escapes() {
+    use snlc_lexer::unescape::{escape_char, unescape_char, CharError};
+
+    assert_eq!(unescape_char("'a'"), Ok('a'));
+    assert_eq!(unescape_char("''''"), Ok('\''));
+    assert_eq!(unescape_char("'\\''"), Ok('\''));
+    assert_eq!(unescape_char("'\\n'"), Ok('\n'));
+    assert_eq!(unescape_char("'\\t'"), Ok('\t'));
+    assert_eq!(unescape_char("'\\\\'"), Ok('\\'));
+    assert_eq!(unescape_char("''"), Err((CharError::Empty, 0..2)));
+    assert_eq!(
+        unescape_char("'ab'"),
+        Err((CharError::MoreThanOneChar, 0..4))
+    );
+    assert_eq!(
+        unescape_char("'\\q'"),
+        Err((CharError::UnknownEscape('q'), 1..3))
+    );
+
+    for c in ['a', '\'', '\n', '\t', '\r', '\0', '\\', 'é'] {
+        assert_eq!(unescape_char(&escape_char(c)), Ok(c));
+    }
+}
+
+#[test]
+fn string_literal_escapes() {
+    use snlc_lexer::unescape::{escape_str, unescape_str, CharError};
+
+    assert_eq!(unescape_str("''"), Ok(String::new()));
+    assert_eq!(unescape_str("'x = '"), Ok("x = ".to_string()));
+    assert_eq!(unescape_str("'it''s'"), Ok("it's".to_string()));
+    assert_eq!(unescape_str("'a\\tb\\''"), Ok("a\tb'".to_string()));
+    assert_eq!(
+        unescape_str("'ab\\q'"),
+        Err((CharError::UnknownEscape('q'), 3..5))
+    );
+    for s in ["", "it's", "{ x }", "a\\b\n"] {
+        assert_eq!(unescape_str(&escape_str(s)).as_deref(), Ok(s));
+    }
+}
+
+#[test]
+fn literal_kinds() {
+    use snlc_lexer::{tokensize, LiteralKind, TokenKind};
+
+    let kinds = tokensize("'a' '''' '\\n' '' 'ab' 'it''s' 'x")
+        .filter_map(|x| match x.kind {
+            TokenKind::Literal { kind } => Some(kind),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let char = LiteralKind::Char { terminated: true };
+    let str = LiteralKind::Str { terminated: true };
+    assert_eq!(
+        kinds,
+        [
+            char,
+            char,
+            char,
+            str,
+            str,
+            str,
+            LiteralKind::Char { terminated: false }
+        ]
+    );
+}
+
+#[test]
+fn char_literal_errors() {
+    let src = "begin\n  write('');\n  write('ab', 'x\\qy');\n  c := '\\q';\n  c := '''';\n  c := 'x\n  write('abc\nend.\n";
+    let errors = lex_errors(src)
+        .iter()
+        .map(|x| format!("{}:{}: {}", x.line, x.col, x))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        [
+            "3:17: unknown character escape `\\q`",
+            "4:9: unknown character escape `\\q`",
+            "6:8: unterminated character literal starting at 6:8",
+            "7:9: unterminated string literal starting at 7:9",
+        ]
+    );
+}
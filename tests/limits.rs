@@ -0,0 +1,144 @@
+use snlc::interp::{self, ErrorKind, Frame, Limits, RuntimeError};
+use snlc::ir;
+use snlc::sema::Options;
+use snlc::vm;
+use snlc::vm::regalloc::Allocator;
+use snlc_ast::codegen::Config;
+
+const SRC: &str = "program p
+var integer n;
+procedure c(integer k)
+begin
+  write(k, ' ')
+end
+procedure b(integer k)
+begin
+  c(k + 1)
+end
+begin
+  read(n);
+  while 0 < n do
+    b(n);
+    n := n - 1
+  endwh;
+  writeln('done')
+end.
+";
+
+/// Output and error of the interpreter and of the VM running `SRC`.
+fn run(limits: &Limits) -> [(String, Option<RuntimeError>); 2] {
+    let (prog, _) = interp::compile(SRC, &Options::default()).unwrap();
+    let config = Config::default();
+    let mut output = Vec::new();
+    let err = interp::run(&prog, &config, &b"3"[..], &mut output, limits).err();
+    let interp = (String::from_utf8(output).unwrap(), err);
+
+    let code = vm::codegen::compile(&ir::lower(&prog), Allocator::LinearScan);
+    let mut output = Vec::new();
+    let err = vm::exec::run(&code, &config, &b"3"[..], &mut output, limits).err();
+    [interp, (String::from_utf8(output).unwrap(), err)]
+}
+
+#[test]
+fn no_limits_by_default() {
+    for (output, err) in run(&Limits::default()) {
+        assert_eq!(output, "4 3 2 done\n");
+        assert_eq!(err, None);
+    }
+}
+
+#[test]
+fn limits_stop_both_backends_at_the_same_place() {
+    let cases = [
+        (
+            Limits {
+                depth: Some(1),
+                ..Limits::default()
+            },
+            "",
+            ErrorKind::DepthLimit(1),
+            9,
+            &[("b", 14)][..],
+        ),
+        (
+            Limits {
+                output: Some(5),
+                ..Limits::default()
+            },
+            "4 3 2",
+            ErrorKind::OutputLimit(5),
+            5,
+            &[("c", 9), ("b", 14)],
+        ),
+        (
+            Limits {
+                reads: Some(0),
+                ..Limits::default()
+            },
+            "",
+            ErrorKind::ReadLimit(0),
+            12,
+            &[],
+        ),
+    ];
+    for (limits, output, kind, line, stack) in cases {
+        let stack = stack
+            .iter()
+            .map(|&(name, line)| Frame {
+                name: name.to_string(),
+                line,
+            })
+            .collect::<Vec<_>>();
+        for res in run(&limits) {
+            assert_eq!(
+                res,
+                (
+                    output.to_string(),
+                    Some(RuntimeError {
+                        kind: kind.clone(),
+                        line,
+                        stack: stack.clone(),
+                    })
+                )
+            );
+        }
+    }
+
+    // nested calls up to the limit are fine
+    let limits = Limits {
+        depth: Some(2),
+        ..Limits::default()
+    };
+    for (_, err) in run(&limits) {
+        assert_eq!(err, None);
+    }
+}
+
+#[test]
+fn step_limits_count_statements_or_instructions() {
+    let limits = Limits {
+        steps: Some(6),
+        ..Limits::default()
+    };
+    let [interp, vm] = run(&limits);
+    // read, while, call, call, write and assignment run, the next `while`
+    // test fails
+    assert_eq!(interp.0, "4 ");
+    assert_eq!(
+        interp.1,
+        Some(RuntimeError {
+            kind: ErrorKind::StepLimit(6),
+            line: 13,
+            stack: Vec::new(),
+        })
+    );
+    assert_eq!(vm.1.unwrap().kind, ErrorKind::StepLimit(6));
+
+    let limits = Limits {
+        steps: Some(1000),
+        ..Limits::default()
+    };
+    for (_, err) in run(&limits) {
+        assert_eq!(err, None);
+    }
+}
@@ -0,0 +1,310 @@
+use std::io::{BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use serde_json::{json, Value};
+use snlc::lsp::{read_message, write_message};
+
+const URI: &str = "file:///test.snl";
+
+const SOURCE: &str = "program p
+var integer i;
+    char c;
+procedure q(integer num, char ch)
+var integer t;
+begin
+  t := num + i;
+  write(t)
+end
+begin
+  read(i);
+  q(i, c);
+  write(i)
+end.
+";
+
+/// Drives `snlc-lsp` through a scripted session.
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl Client {
+    fn start() -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_snlc-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut client = Client {
+            child,
+            stdin,
+            stdout,
+            next_id: 1,
+        };
+        client.request("initialize", json!({ "capabilities": {} }));
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        let msg = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        write_message(&mut self.stdin, &msg).unwrap();
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        let msg = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        write_message(&mut self.stdin, &msg).unwrap();
+        loop {
+            let reply = self.receive();
+            if reply["id"] == json!(id) {
+                return reply["result"].clone();
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Value {
+        read_message(&mut self.stdout)
+            .unwrap()
+            .expect("server closed")
+    }
+
+    /// Opens `text` and returns the published diagnostics.
+    fn open(&mut self, text: &str) -> Vec<Value> {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": URI, "languageId": "snl", "version": 1, "text": text },
+            }),
+        );
+        self.diagnostics()
+    }
+
+    fn change(&mut self, text: &str) -> Vec<Value> {
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": URI, "version": 2 },
+                "contentChanges": [{ "text": text }],
+            }),
+        );
+        self.diagnostics()
+    }
+
+    /// Sends incremental `changes`, each a range and its replacement.
+    fn edit(&mut self, changes: &[(Value, &str)]) -> Vec<Value> {
+        let changes = changes
+            .iter()
+            .map(|(range, text)| json!({ "range": range, "text": text }))
+            .collect::<Vec<_>>();
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": URI, "version": 2 },
+                "contentChanges": changes,
+            }),
+        );
+        self.diagnostics()
+    }
+
+    fn diagnostics(&mut self) -> Vec<Value> {
+        let msg = self.receive();
+        assert_eq!(msg["method"], "textDocument/publishDiagnostics");
+        assert_eq!(msg["params"]["uri"], URI);
+        msg["params"]["diagnostics"].as_array().unwrap().clone()
+    }
+
+    fn at(&mut self, method: &str, line: u64, character: u64) -> Value {
+        self.request(
+            method,
+            json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+                "context": { "includeDeclaration": true },
+            }),
+        )
+    }
+
+    fn shutdown(mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        drop(self.stdin);
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+fn range(line: u64, start: u64, end: u64) -> Value {
+    json!({
+        "start": { "line": line, "character": start },
+        "end": { "line": line, "character": end },
+    })
+}
+
+#[test]
+fn initialize_advertises_capabilities() {
+    let mut client = Client::start();
+    let res = client.request("initialize", json!({ "capabilities": {} }));
+    let caps = &res["capabilities"];
+    for cap in [
+        "definitionProvider",
+        "referencesProvider",
+        "hoverProvider",
+        "documentSymbolProvider",
+    ] {
+        assert_eq!(caps[cap], true, "{cap}");
+    }
+    assert_eq!(caps["textDocumentSync"], 2);
+    assert!(caps["completionProvider"].is_object());
+    client.shutdown();
+}
+
+#[test]
+fn diagnostics_follow_edits() {
+    let mut client = Client::start();
+    assert_eq!(client.open(SOURCE), Vec::<Value>::new());
+
+    let diags = client.change(&SOURCE.replace("write(i)\nend.", "write(x)\nend."));
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0]["message"], "cannot find `x` in this scope");
+    assert_eq!(diags[0]["severity"], 1);
+    assert_eq!(diags[0]["range"], range(12, 8, 9));
+
+    let diags = client.change(&SOURCE.replace("q(i, c)", "q(c, c)"));
+    assert_eq!(diags.len(), 1);
+    assert_eq!(
+        diags[0]["message"],
+        "mismatched types: expected integer, found char"
+    );
+
+    let diags = client.change("program p\nbegin\n  write(1\nend.\n");
+    assert_eq!(diags.len(), 1);
+    assert_eq!(
+        diags[0]["range"]["start"],
+        json!({ "line": 3, "character": 0 })
+    );
+
+    assert_eq!(client.change(SOURCE), Vec::<Value>::new());
+    client.shutdown();
+}
+
+#[test]
+fn ranged_changes_apply_in_order() {
+    let mut client = Client::start();
+    assert_eq!(client.open(SOURCE), Vec::<Value>::new());
+
+    // `write(i)` in the main body becomes `write(x)`, then `write(ix)`
+    let diags = client.edit(&[(range(12, 8, 9), "x"), (range(12, 8, 8), "i")]);
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0]["message"], "cannot find `ix` in this scope");
+    assert_eq!(diags[0]["range"], range(12, 8, 10));
+
+    assert_eq!(client.edit(&[(range(12, 9, 10), "")]), Vec::<Value>::new());
+    let res = client.at("textDocument/definition", 12, 8);
+    assert_eq!(res["range"], range(1, 12, 13));
+    client.shutdown();
+}
+
+#[test]
+fn goto_definition_and_references() {
+    let mut client = Client::start();
+    client.open(SOURCE);
+
+    // `num` in `t := num + i`
+    let def = client.at("textDocument/definition", 6, 8);
+    assert_eq!(def["uri"], URI);
+    assert_eq!(def["range"], range(3, 20, 23));
+
+    // the global `i` used inside the procedure
+    let def = client.at("textDocument/definition", 6, 13);
+    assert_eq!(def["range"], range(1, 12, 13));
+
+    let refs = client.at("textDocument/references", 1, 12);
+    let refs = refs
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["range"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        refs,
+        [
+            range(1, 12, 13),
+            range(6, 13, 14),
+            range(10, 7, 8),
+            range(11, 4, 5),
+            range(12, 8, 9),
+        ]
+    );
+
+    assert_eq!(client.at("textDocument/definition", 0, 0), Value::Null);
+    client.shutdown();
+}
+
+#[test]
+fn hover_shows_types() {
+    let mut client = Client::start();
+    client.open(SOURCE);
+
+    let hover = client.at("textDocument/hover", 11, 7);
+    assert_eq!(hover["contents"]["value"], "```snl\nvar c: char\n```");
+    assert_eq!(hover["range"], range(11, 7, 8));
+
+    let hover = client.at("textDocument/hover", 6, 2);
+    assert_eq!(hover["contents"]["value"], "```snl\nvar t: integer\n```");
+
+    let hover = client.at("textDocument/hover", 11, 2);
+    assert_eq!(
+        hover["contents"]["value"],
+        "```snl\nprocedure q(integer, char)\n```"
+    );
+    client.shutdown();
+}
+
+#[test]
+fn document_symbols_and_completion() {
+    let mut client = Client::start();
+    client.open(SOURCE);
+
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    let symbols = symbols.as_array().unwrap();
+    assert_eq!(symbols.len(), 1);
+    assert_eq!(symbols[0]["name"], "q");
+    assert_eq!(symbols[0]["kind"], 12);
+    assert_eq!(symbols[0]["selectionRange"], range(3, 10, 11));
+    assert_eq!(
+        symbols[0]["range"]["start"],
+        json!({ "line": 3, "character": 0 })
+    );
+    assert_eq!(
+        symbols[0]["range"]["end"],
+        json!({ "line": 8, "character": 3 })
+    );
+
+    let items = client.at("textDocument/completion", 12, 2);
+    let labels = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["label"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    for label in ["while", "endwh", "procedure", "integer", "q", "i", "c"] {
+        assert!(labels.iter().any(|x| x == label), "{label}");
+    }
+    client.shutdown();
+}
+
+#[test]
+fn exit_without_shutdown_fails() {
+    let mut client = Client::start();
+    client.notify("exit", Value::Null);
+    client.stdin.flush().unwrap();
+    assert_eq!(client.child.wait().unwrap().code(), Some(1));
+}
@@ -0,0 +1,166 @@
+use std::process::Command;
+
+use snlc::frontend::parse_program;
+use snlc::interp::{self, Expr, Stm};
+use snlc::sema::{analyze, Options};
+use snlc_ast::codegen::{Config, IntWidth, OverflowPolicy};
+use snlc_ast::opt::optimize;
+
+/// The body of `src` after optimizing, one statement per line.
+fn optimized(src: &str, config: Config) -> String {
+    let mut prog = parse_program(src).unwrap();
+    let errors = optimize(&mut prog, &config);
+    assert!(errors.is_empty(), "{:?}", errors);
+    prog.body.iter().map(|x| x.to_string() + "\n").collect()
+}
+
+fn body(stms: &str) -> String {
+    format!(
+        "program p\nvar integer i, x;\n    char c;\nbegin\n{}\nend.\n",
+        stms
+    )
+}
+
+#[test]
+fn folds_constants_and_identities() {
+    let src = body(
+        "write(2 * 3 + x * 1);
+         write((x + 0) * (4 - 4));
+         write(0 + x - 0);
+         write(1 * (x / 1));
+         write((x + 1) * 0);
+         write(10 / 3 - 7 / 2);
+         write(c, 'a', (x))",
+    );
+    assert_eq!(
+        optimized(&src, Config::default()),
+        "write(6 + x)\n\
+         write(0)\n\
+         write(x)\n\
+         write(x)\n\
+         write((x + 1) * 0)\n\
+         write(0)\n\
+         write(c, 'a', x)\n"
+    );
+}
+
+#[test]
+fn folding_follows_the_overflow_policy() {
+    let src = body("write(200 * 200 + x);\nwrite(x * 0 + 32767 + 1)");
+    let config = |overflow| Config {
+        int_width: IntWidth::I16,
+        overflow,
+        ..Config::default()
+    };
+    // `trap` leaves the overflow for the program to report
+    assert_eq!(
+        optimized(&src, config(OverflowPolicy::Trap)),
+        "write(200 * 200 + x)\nwrite(32767 + 1)\n"
+    );
+    assert_eq!(
+        optimized(&src, config(OverflowPolicy::Wrap)),
+        "write(-25536 + x)\nwrite(-32768)\n"
+    );
+    assert_eq!(
+        optimized(&src, config(OverflowPolicy::Saturate)),
+        "write(32767 + x)\nwrite(32767)\n"
+    );
+}
+
+#[test]
+fn prunes_constant_conditions() {
+    let src = body(
+        "if 1 < 2 then write(1); write(2) else write(3) fi;
+         if 'b' <= 'a' then write(4) else write(5) fi;
+         if 1 = 2 then write(6) fi;
+         if x < 2 * 3 then write(7) fi;
+         while 2 < 1 do write(8) endwh;
+         while 1 < 2 * 2 do write(9) endwh",
+    );
+    assert_eq!(
+        optimized(&src, Config::default()),
+        "write(1)\n\
+         write(2)\n\
+         write(5)\n\
+         if x < 6 then write(7) fi\n\
+         while 1 < 4 do write(9) endwh\n"
+    );
+}
+
+#[test]
+fn division_by_a_constant_zero_is_an_error() {
+    let src = body("i := x / (2 - 2);\ni := x / 0;\ni := x / (0 * x)");
+    let diagnostics = analyze(&src, &Options::default()).unwrap().diagnostics;
+    let errors = diagnostics
+        .iter()
+        .map(|x| x.render("p.snl", &src))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        [
+            "p.snl:5:10: error: division by zero",
+            "p.snl:6:10: error: division by zero"
+        ]
+    );
+
+    let mut prog = parse_program(&src).unwrap();
+    let errors = optimize(&mut prog, &Config::default());
+    assert_eq!(errors.len(), 3);
+    assert!(errors.iter().all(|x| x.to_string() == "division by zero"));
+}
+
+#[test]
+fn interpreter_folds_like_the_generated_code() {
+    let src = body(
+        "write(2 * 3 + x * 1, (x + 1) * 0, 0 + x - 0);
+         if 1 < 2 then write(1); write(2) else write(3) fi;
+         if 'b' <= 'a' then write(4) fi;
+         if x < 2 * 3 then write(7) fi;
+         while 2 < 1 do write(8) endwh",
+    );
+    let (mut prog, _) = interp::compile(&src, &Options::default()).unwrap();
+    interp::optimize(&mut prog, &Config::default());
+    let [Stm::Write { args, .. }, Stm::Write { .. }, Stm::Write { .. }, Stm::If { condition, .. }] =
+        &prog.body[..]
+    else {
+        panic!("{:#?}", prog.body);
+    };
+    assert!(matches!(&args[0], Expr::Binary { lhs, rhs, .. }
+        if matches!(**lhs, Expr::Int(6)) && matches!(**rhs, Expr::Load(_))));
+    // under `trap` the dropped `x + 1` could have overflowed
+    assert!(matches!(args[1], Expr::Binary { .. }));
+    assert!(matches!(args[2], Expr::Load(_)));
+    assert!(matches!(condition, Expr::Binary { rhs, .. } if matches!(**rhs, Expr::Int(6))));
+}
+
+/// Stdout and stderr of `snlc <args> tests/opt/fold.snl`.
+fn snlc(args: &[&str]) -> (String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_snlc"))
+        .args(args)
+        .arg("tests/opt/fold.snl")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let text = |x| String::from_utf8(x).unwrap();
+    (text(output.stdout), text(output.stderr))
+}
+
+#[test]
+fn emit_and_run_take_the_opt_level() {
+    assert_eq!(
+        snlc(&["emit", "--emit=cfg-dot", "-O1"]).0,
+        include_str!("opt/fold.cfg.dot")
+    );
+    let (dot, _) = snlc(&["emit", "--emit=ast-dot"]);
+    assert!(dot.contains("label=\"*\""), "{dot}");
+    let (dot, _) = snlc(&["emit", "--emit=ast-dot", "-O1"]);
+    assert!(!dot.contains("label=\"*\""), "{dot}");
+    assert!(dot.contains("label=\"6\""), "{dot}");
+
+    let (out, trace) = snlc(&["run", "--trace"]);
+    assert_eq!(out, "6\n");
+    assert!(trace.contains("p:6: while 2 < 1"), "{trace}");
+    let (out, trace) = snlc(&["run", "--trace", "-O1"]);
+    assert_eq!(out, "6\n");
+    assert!(!trace.contains("p:6:"), "{trace}");
+}
@@ -0,0 +1,214 @@
+use std::ops::Range;
+use std::process::Command;
+
+use quote::ToTokens;
+use snlc::frontend::{line_col, parse_program, parse_program_mapped, SourceMap};
+use snlc::ll1::Ll1Parser;
+use snlc_ast::ast::Program;
+use snlc_ast::expr::Expr;
+use snlc_ast::stm::Stmatment;
+
+#[test]
+fn procedures_end_before_the_next_one() {
+    let src = "program p
+var integer x;
+procedure a(integer n)
+begin
+  x := n
+end
+procedure b()
+var integer k;
+begin
+  k := 1;
+  a(k)
+end
+begin
+  b()
+end.
+";
+    let prog = parse_program(src).unwrap();
+    let procs = prog.procedure_defs.unwrap();
+    let names = procs.iter().map(|x| x.name.to_string()).collect::<Vec<_>>();
+    assert_eq!(names, ["a", "b"]);
+    assert_eq!(procs[1].body.len(), 2);
+    assert_eq!(prog.body.len(), 1);
+    assert_eq!(prog.body[0].to_string(), "b()");
+
+    let missing_end = src.replacen("  x := n\nend\n", "  x := n\n", 1);
+    assert!(parse_program(&missing_end).is_err());
+}
+
+const EXPRS: &str = "program p
+var integer x, y;
+    char c;
+procedure show(integer n, char d)
+begin
+  write(n, d, 'str');
+  writeln
+end
+begin
+  read(x, c);
+  y := (x + 1) * 2 - x / 3;
+  if x < y then show(y, c) else writeln(c = 'a') fi;
+  while 1 - 2 - 3 <= x do
+    x := x - 1
+  endwh
+end.
+";
+
+/// Every statement in `stms`, nested ones included, with the source range
+/// its span maps to.
+fn located(stms: &[Stmatment], map: &SourceMap, res: &mut Vec<(Range<usize>, String)>) {
+    for stm in stms {
+        res.push((map.range(stm.span()), stm.to_string()));
+        match stm {
+            Stmatment::If(x) => {
+                located(&x.body, map, res);
+                located(x.else_body.as_deref().unwrap_or_default(), map, res);
+            }
+            Stmatment::While(x) => located(&x.body, map, res),
+            _ => {}
+        }
+    }
+}
+
+fn all_located(prog: &Program, map: &SourceMap) -> Vec<(Range<usize>, String)> {
+    let mut res = Vec::new();
+    for proc in prog.procedure_defs.iter().flatten() {
+        res.push((map.range(proc.name.span()), proc.name.to_string()));
+        located(&proc.body, map, &mut res);
+    }
+    located(&prog.body, map, &mut res);
+    res
+}
+
+#[test]
+fn ll1_builds_the_same_program() {
+    for src in [EXPRS, include_str!("dot/loops.snl")] {
+        let (rd, rd_map) = parse_program_mapped(src, false).unwrap();
+        let (ll1, ll1_map) = Ll1Parser::snl().parse_program_mapped(src).unwrap();
+        assert_eq!(
+            ll1.to_token_stream().to_string(),
+            rd.to_token_stream().to_string()
+        );
+        assert_eq!(all_located(&ll1, &ll1_map), all_located(&rd, &rd_map));
+    }
+}
+
+#[test]
+fn ll1_spans_point_into_the_source() {
+    let (prog, map) = Ll1Parser::snl().parse_program_mapped(EXPRS).unwrap();
+    let located = all_located(&prog, &map);
+    let at = |text: &str| {
+        let (range, _) = located.iter().find(|(_, x)| x == text).unwrap();
+        let (line, col) = line_col(EXPRS, range.start);
+        (line, col, &EXPRS[range.clone()])
+    };
+    assert_eq!(at("show"), (4, 11, "show"));
+    assert_eq!(at("writeln"), (7, 3, "writeln"));
+    assert_eq!(at("read(x, c)"), (10, 3, "read"));
+    assert_eq!(at("y := (x + 1) * 2 - x / 3"), (11, 3, "y"));
+    assert_eq!(at("x := x - 1"), (14, 5, "x"));
+}
+
+/// `expr` with every binary operation in parentheses.
+fn grouped(expr: &Expr) -> String {
+    match expr {
+        Expr::Assign(x) => format!("{} := {}", grouped(&x.target), grouped(&x.from)),
+        Expr::Binary(x) => format!("({} {} {})", grouped(&x.lhs), x.op, grouped(&x.rhs)),
+        Expr::Paren(x) => grouped(&x.expr),
+        expr => expr.to_string(),
+    }
+}
+
+#[test]
+fn operators_bind_by_precedence() {
+    let prog = parse_program(EXPRS).unwrap();
+    let Stmatment::Expr(assign) = &prog.body[1] else {
+        panic!("not an assignment");
+    };
+    assert_eq!(grouped(assign), "y := (((x + 1) * 2) - (x / 3))");
+    let Stmatment::While(stm) = &prog.body[3] else {
+        panic!("not a loop");
+    };
+    assert_eq!(grouped(&stm.condition), "(((1 - 2) - 3) <= x)");
+
+    let src = "program p\nvar integer a;\nbegin\n  a := 1 + 2 * 3 < 4 / 2 - 1\nend.\n";
+    let Stmatment::Expr(assign) = &parse_program(src).unwrap().body[0] else {
+        panic!("not an assignment");
+    };
+    assert_eq!(grouped(assign), "a := ((1 + (2 * 3)) < ((4 / 2) - 1))");
+}
+
+#[test]
+fn rd_and_ll1_agree_on_precedence_and_associativity() {
+    let cases = [
+        ("1 - 2 - 3", "((1 - 2) - 3)"),
+        ("8 / 4 / 2", "((8 / 4) / 2)"),
+        ("8 / 4 * 2", "((8 / 4) * 2)"),
+        ("1 - 2 + 3", "((1 - 2) + 3)"),
+        ("1 + 2 * 3", "(1 + (2 * 3))"),
+        ("1 * 2 + 3", "((1 * 2) + 3)"),
+        ("1 - 2 * 3 - 4", "((1 - (2 * 3)) - 4)"),
+        ("a - b / c * a + b", "((a - ((b / c) * a)) + b)"),
+        ("(1 + 2) * 3", "((1 + 2) * 3)"),
+        ("a - (b - c)", "(a - (b - c))"),
+    ];
+    let parser = Ll1Parser::snl();
+    for (expr, expected) in cases {
+        let src = format!(
+            "program p\nvar integer a, b, c;\nbegin\n  a := {expr};\n  if {expr} < {expr} then a := 1 fi\nend.\n"
+        );
+        let rd = parse_program(&src).unwrap();
+        let ll1 = parser.parse_program(&src).unwrap();
+        assert_eq!(
+            ll1.to_token_stream().to_string(),
+            rd.to_token_stream().to_string(),
+            "{expr}"
+        );
+        for prog in [&rd, &ll1] {
+            let Stmatment::Expr(assign) = &prog.body[0] else {
+                panic!("not an assignment");
+            };
+            assert_eq!(grouped(assign), format!("a := {expected}"));
+            let Stmatment::If(stm) = &prog.body[1] else {
+                panic!("not an if");
+            };
+            assert_eq!(
+                grouped(&stm.condition),
+                format!("({expected} < {expected})")
+            );
+        }
+    }
+}
+
+#[test]
+fn dump_table() {
+    let output = Command::new(env!("CARGO_BIN_EXE_snlc"))
+        .args(["parse", "--dump-table"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let table = String::from_utf8(output.stdout).unwrap();
+    let ll1 = Ll1Parser::snl();
+    assert_eq!(table, ll1.table.dump(&ll1.grammar));
+    assert!(table.starts_with("Program:\n    \"program\"    => Program ::= \"program\" ID"));
+    assert!(table.contains(
+        "VarPart:\n    \"begin\"      => VarPart ::= ε\n    \"procedure\"  => VarPart ::= ε\n    \"var\"        => VarPart ::= \"var\" VarDecList\n"
+    ));
+    assert!(!table.contains("conflict:"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_snlc"))
+        .args([
+            "parse",
+            "--dump-table",
+            "--parser=ll1",
+            "tests/dot/loops.snl",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let program = stdout.strip_prefix(table.as_str()).unwrap();
+    assert!(program.starts_with("Program {"));
+}
@@ -0,0 +1,297 @@
+use snlc::interp::{self, Limits};
+use snlc::ir;
+use snlc::sema::Options;
+use snlc::vm::codegen::compile;
+use snlc::vm::exec;
+use snlc::vm::peephole::{find_rule, optimize, optimize_function, RULES};
+use snlc::vm::regalloc::Allocator;
+use snlc::vm::{Cond, Function, Instr, Reg, Slot, Src};
+use snlc_ast::codegen::Config;
+
+fn function(code: Vec<Instr>) -> Function {
+    Function {
+        name: "f".to_string(),
+        frame: 2,
+        code,
+    }
+}
+
+/// `code` after applying the rule `name` alone, and how often it applied.
+fn apply(name: &str, code: Vec<Instr>) -> (Vec<Instr>, usize) {
+    let mut func = function(code);
+    let rule = find_rule(name).unwrap();
+    let applied = optimize_function(&mut func, std::slice::from_ref(rule));
+    (func.code, applied.len())
+}
+
+const R0: Reg = Reg(0);
+const R2: Reg = Reg(2);
+const X: Slot = Slot::Frame(0);
+const Y: Slot = Slot::Frame(1);
+
+#[test]
+fn redundant_loads_and_stores() {
+    let (code, n) = apply(
+        "load-after-store",
+        vec![
+            Instr::St { slot: X, src: R0 },
+            Instr::Ld { dst: R0, slot: X },
+            Instr::St { slot: Y, src: R2 },
+            Instr::Ld { dst: R0, slot: Y },
+            Instr::St { slot: X, src: R0 },
+            Instr::Label(1),
+            Instr::Ld { dst: R0, slot: X },
+        ],
+    );
+    assert_eq!(n, 2);
+    assert_eq!(
+        code,
+        [
+            Instr::St { slot: X, src: R0 },
+            Instr::St { slot: Y, src: R2 },
+            Instr::Mov { dst: R0, src: R2 },
+            Instr::St { slot: X, src: R0 },
+            Instr::Label(1),
+            // control may come from elsewhere
+            Instr::Ld { dst: R0, slot: X },
+        ]
+    );
+
+    let (code, n) = apply(
+        "store-after-load",
+        vec![
+            Instr::Ld { dst: R0, slot: X },
+            Instr::St { slot: X, src: R0 },
+            Instr::St { slot: Y, src: R0 },
+        ],
+    );
+    assert_eq!(n, 1);
+    assert_eq!(
+        code,
+        [
+            Instr::Ld { dst: R0, slot: X },
+            Instr::St { slot: Y, src: R0 }
+        ]
+    );
+}
+
+#[test]
+fn jumps_and_branches() {
+    let (code, _) = apply(
+        "jump-to-next",
+        vec![
+            Instr::Jmp(1),
+            Instr::Label(1),
+            Instr::Jmp(1),
+            Instr::Label(2),
+        ],
+    );
+    assert_eq!(code, [Instr::Label(1), Instr::Jmp(1), Instr::Label(2)]);
+
+    let (code, n) = apply(
+        "branch-over-jump",
+        vec![
+            Instr::Cmp {
+                lhs: R2,
+                rhs: Src::Imm(10),
+            },
+            Instr::Jcc {
+                cond: Cond::Lt,
+                target: 2,
+            },
+            Instr::Jmp(3),
+            Instr::Label(2),
+        ],
+    );
+    assert_eq!(n, 1);
+    assert_eq!(
+        code[1..],
+        [
+            Instr::Jcc {
+                cond: Cond::Ge,
+                target: 3
+            },
+            Instr::Label(2)
+        ]
+    );
+}
+
+#[test]
+fn compares() {
+    let cmp = |rhs| Instr::Cmp { lhs: R2, rhs };
+    let jcc = |cond, target| Instr::Jcc { cond, target };
+    let (code, n) = apply(
+        "compare-again",
+        vec![
+            cmp(Src::Imm(3)),
+            jcc(Cond::Lt, 1),
+            Instr::St { slot: X, src: R2 },
+            cmp(Src::Imm(3)),
+            jcc(Cond::Eq, 2),
+            Instr::Label(2),
+            cmp(Src::Imm(3)),
+        ],
+    );
+    assert_eq!(n, 1);
+    assert_eq!(
+        code,
+        [
+            cmp(Src::Imm(3)),
+            jcc(Cond::Lt, 1),
+            Instr::St { slot: X, src: R2 },
+            jcc(Cond::Eq, 2),
+            Instr::Label(2),
+            cmp(Src::Imm(3)),
+        ]
+    );
+
+    let li = |dst, imm| Instr::Li { dst, imm };
+    let (code, n) = apply(
+        "constant-compare",
+        vec![
+            li(R2, 1),
+            cmp(Src::Imm(2)),
+            jcc(Cond::Lt, 1),
+            Instr::Jmp(2),
+            li(R0, 5),
+            li(R2, 1),
+            cmp(Src::Reg(R0)),
+            jcc(Cond::Ge, 1),
+            Instr::Ret,
+        ],
+    );
+    assert_eq!(n, 2);
+    assert_eq!(
+        code,
+        [
+            li(R2, 1),
+            Instr::Jmp(1),
+            Instr::Jmp(2),
+            li(R0, 5),
+            li(R2, 1),
+            Instr::Ret,
+        ]
+    );
+
+    // the second branch still needs the comparison
+    let code = vec![
+        li(R2, 1),
+        cmp(Src::Imm(2)),
+        jcc(Cond::Lt, 1),
+        jcc(Cond::Eq, 2),
+    ];
+    assert_eq!(apply("constant-compare", code.clone()), (code, 0));
+}
+
+#[test]
+fn push_pop_pairs() {
+    let (code, n) = apply(
+        "push-pop",
+        vec![
+            Instr::Push(R2),
+            Instr::Pop(R2),
+            Instr::Push(R2),
+            Instr::Pop(R0),
+        ],
+    );
+    assert_eq!(n, 2);
+    assert_eq!(code, [Instr::Mov { dst: R0, src: R2 }]);
+}
+
+#[test]
+fn rules_have_distinct_names() {
+    for (i, rule) in RULES.iter().enumerate() {
+        assert!(RULES[..i].iter().all(|x| x.name != rule.name));
+        assert!(!rule.help.is_empty());
+    }
+}
+
+const SRC: &str = "program p
+var integer i, s, t;
+    char c;
+procedure show(integer x)
+begin
+  if x < 3 then write(x) else write('.') fi
+end
+begin
+  read(i, c);
+  s := 0;
+  while i < 10 do
+    t := s;
+    s := t + i;
+    show(i);
+    i := i + 1
+  endwh;
+  writeln(c, s)
+end.
+";
+
+#[test]
+fn peephole_preserves_behavior_and_shrinks_code() {
+    let (prog, _) = interp::compile(SRC, &Options::default()).unwrap();
+    let mut expected = Vec::new();
+    let limits = Limits::default();
+    interp::run(
+        &prog,
+        &Config::default(),
+        &b"0 z"[..],
+        &mut expected,
+        &limits,
+    )
+    .unwrap();
+
+    let module = ir::lower(&prog);
+    for allocator in [Allocator::Spill, Allocator::LinearScan] {
+        let before = compile(&module, allocator);
+        let mut after = before.clone();
+        let applied = optimize(&mut after);
+        assert!(applied.contains(&"jump-to-next"), "{applied:?}");
+        assert!(applied.contains(&"branch-over-jump"), "{applied:?}");
+        if allocator == Allocator::Spill {
+            assert!(applied.contains(&"load-after-store"), "{applied:?}");
+        }
+        assert!(after.len() < before.len());
+
+        let mut output = Vec::new();
+        exec::run(
+            &after,
+            &Config::default(),
+            &b"0 z"[..],
+            &mut output,
+            &limits,
+        )
+        .unwrap();
+        assert_eq!(output, expected, "{after}");
+    }
+}
+
+#[test]
+fn constant_compares_shrink_code() {
+    let src = "program p
+var integer i;
+begin
+  read(i);
+  if 1 < 2 then write(i) else write(0) fi;
+  while 0 < i do
+    i := i - 1
+  endwh;
+  writeln(i)
+end.
+";
+    let (prog, _) = interp::compile(src, &Options::default()).unwrap();
+    let before = compile(&ir::lower(&prog), Allocator::LinearScan);
+    let mut after = before.clone();
+    let rule = find_rule("constant-compare").unwrap();
+    let applied = optimize_function(&mut after.main, std::slice::from_ref(rule));
+    assert_eq!(applied.len(), 1);
+    assert_eq!(after.len(), before.len() - 1);
+
+    let run = |prog| {
+        let mut output = Vec::new();
+        let limits = Limits::default();
+        exec::run(prog, &Config::default(), &b"3"[..], &mut output, &limits).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+    assert_eq!(run(&before), "30\n");
+    assert_eq!(run(&after), "30\n");
+}
@@ -0,0 +1,65 @@
+use quote::ToTokens;
+use snlc::frontend::parse_program;
+use snlc::ll1::Ll1Parser;
+use snlc::runtime::{Input, ReadError};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[test]
+fn tokens_span_lines() {
+    let mut input = Input::new("  12 -3\n\n\tx yz\n7".as_bytes());
+    assert_eq!(input.read::<i32>(), Ok(12));
+    assert_eq!(input.read::<i32>(), Ok(-3));
+    assert_eq!(input.read::<char>(), Ok('x'));
+    assert_eq!(input.read::<char>(), Ok('y'));
+    assert_eq!(input.read::<char>(), Ok('z'));
+    assert_eq!(input.read::<i64>(), Ok(7));
+    assert_eq!(input.read::<char>(), Err(ReadError::Eof));
+    assert_eq!(input.read::<i32>(), Err(ReadError::Eof));
+}
+
+#[test]
+fn bad_integers() {
+    let mut input = Input::new("1x 40000 -40000 40000".as_bytes());
+    let err = input.read::<i16>().unwrap_err();
+    assert_eq!(err, ReadError::InvalidInteger("1x".to_string()));
+    assert_eq!(err.to_string(), "invalid integer input `1x`");
+    let err = input.read::<i16>().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "integer input `40000` does not fit in a 16-bit integer"
+    );
+    assert!(input.read::<i16>().is_err());
+    assert_eq!(input.read::<i32>(), Ok(40000));
+}
+
+#[test]
+fn read_takes_a_variable_list() {
+    let src = "program p\nvar integer a, b;\n    char c;\nbegin\n  read(a, b,\n       c)\nend.\n";
+    let rd = parse_program(src).unwrap();
+    let ll1 = Ll1Parser::snl().parse_program(src).unwrap();
+    assert_eq!(rd.body[0].to_string(), "read(a, b, c)");
+    assert_eq!(ll1.body[0].to_string(), "read(a, b, c)");
+
+    let code = rd.to_token_stream().to_string();
+    assert_eq!(code.matches("= :: snlc :: runtime :: read (").count(), 3);
+}
+
+#[test]
+fn bad_input_names_the_file_and_line() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_snlc"))
+        .args(["run", "tests/read/echo.snl"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"x\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "0\n");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "tests/read/echo.snl:5: runtime error: invalid integer input `x`\n"
+    );
+}
@@ -0,0 +1,186 @@
+use snlc::interp::{self, Limits};
+use snlc::ir::passes::PassManager;
+use snlc::ir::{self, Module};
+use snlc::sema::Options;
+use snlc::vm::codegen::compile;
+use snlc::vm::exec::{self, Stats};
+use snlc::vm::regalloc::{intervals, linear_scan, Allocator, Location};
+use snlc::vm::{Program, CALLEE_SAVED};
+use snlc_ast::codegen::Config;
+
+/// Values live across calls and `read`, more arguments than argument
+/// registers and a procedure touching globals.
+const CALLS: &str = "program p
+var integer n, i, s, g;
+    char c;
+procedure six(integer a, integer b, integer c, integer d, integer e, integer f)
+var integer t;
+begin
+  t := a - b + c * d;
+  g := g + t - e + f;
+  write(a, ' ', f, ' ')
+end
+begin
+  read(n, c);
+  i := 0;
+  s := 0;
+  while i < n do
+    six(i, n, s, 2, i + 1, 7);
+    s := s + i;
+    write(c);
+    i := i + 1
+  endwh;
+  writeln(s, ' ', g)
+end.
+";
+
+/// More values live at once than there are registers.
+const PRESSURE: &str = "program p
+var integer a, b, c, d, e, f, g, h, i, j, k, n;
+begin
+  read(n);
+  a := n + 1; b := n + 2; c := n + 3; d := n + 4; e := n + 5; f := n + 6;
+  g := n + 7; h := n + 8; i := n + 9; j := n + 10; k := n + 11;
+  while 0 < n do
+    a := a + b; b := b + c; c := c + d; d := d + e; e := e + f; f := f + g;
+    g := g + h; h := h + i; i := i + j; j := j + k; k := k + a;
+    n := n - 1
+  endwh;
+  writeln(a, ' ', b, ' ', c, ' ', d, ' ', e, ' ', f, ' ', g, ' ', h, ' ', i, ' ', j, ' ', k)
+end.
+";
+
+/// The loop of `hello.snl` summing doubles.
+const LOOP: &str = "program p
+var integer i, s;
+begin
+  read(i);
+  s := 0;
+  while i < 100 do
+    s := s + i * 2;
+    i := i + 1
+  endwh;
+  writeln(s)
+end.
+";
+
+fn lower(src: &str, passes: &str) -> Module {
+    let (prog, _) = interp::compile(src, &Options::default()).unwrap();
+    let mut module = ir::lower(&prog);
+    PassManager::new(passes, "")
+        .unwrap()
+        .run(&mut module, &Config::default(), &mut Vec::new())
+        .unwrap();
+    module
+}
+
+fn interpret(src: &str, input: &str) -> String {
+    let (prog, _) = interp::compile(src, &Options::default()).unwrap();
+    let mut output = Vec::new();
+    let limits = Limits::default();
+    interp::run(
+        &prog,
+        &Config::default(),
+        input.as_bytes(),
+        &mut output,
+        &limits,
+    )
+    .unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+fn execute(prog: &Program, input: &str) -> (String, Stats) {
+    let mut output = Vec::new();
+    let limits = Limits::default();
+    let stats = exec::run(
+        prog,
+        &Config::default(),
+        input.as_bytes(),
+        &mut output,
+        &limits,
+    )
+    .unwrap();
+    (String::from_utf8(output).unwrap(), stats)
+}
+
+#[test]
+fn allocation_preserves_behavior() {
+    for (src, input) in [(CALLS, "4 x"), (PRESSURE, "5"), (LOOP, "7")] {
+        let expected = interpret(src, input);
+        for passes in ["", "ssa,gvn,copy-prop,dce"] {
+            for allocator in [Allocator::Spill, Allocator::LinearScan] {
+                let prog = compile(&lower(src, passes), allocator);
+                assert_eq!(execute(&prog, input).0, expected, "{passes}\n{prog}");
+            }
+        }
+    }
+}
+
+#[test]
+fn fewer_instructions_than_spilling_everything() {
+    for (src, input) in [(CALLS, "4 x"), (PRESSURE, "5"), (LOOP, "7")] {
+        let module = lower(src, "");
+        let spill = compile(&module, Allocator::Spill);
+        let scan = compile(&module, Allocator::LinearScan);
+        assert!(
+            scan.len() < spill.len(),
+            "{} >= {}",
+            scan.len(),
+            spill.len()
+        );
+        let (_, spill) = execute(&spill, input);
+        let (_, scan) = execute(&scan, input);
+        assert!(scan.instructions < spill.instructions, "{scan:?} {spill:?}");
+        assert!(scan.memory < spill.memory, "{scan:?} {spill:?}");
+    }
+
+    // the loop runs without touching memory
+    let prog = compile(&lower(LOOP, ""), Allocator::LinearScan);
+    let (_, stats) = execute(&prog, "7");
+    assert_eq!(stats.memory, 0, "{prog}");
+}
+
+#[test]
+fn values_live_across_calls_get_callee_saved_registers() {
+    let module = lower(CALLS, "");
+    let alloc = linear_scan(&module.main);
+    for interval in intervals(&module.main) {
+        match alloc.locations[interval.var] {
+            Some(Location::Reg(reg)) if interval.crosses_call => {
+                assert!(CALLEE_SAVED.contains(&reg), "{interval:?} in {reg}")
+            }
+            _ => {}
+        }
+    }
+    let crossing = intervals(&module.main)
+        .iter()
+        .filter(|x| x.crosses_call)
+        .count();
+    assert!(crossing > 0);
+
+    // `six` saves what it uses and takes its last two arguments from the
+    // stack
+    let prog = compile(&module, Allocator::LinearScan);
+    let six = prog.procs[0]
+        .code
+        .iter()
+        .map(|x| format!("{x:?}"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        six.iter().filter(|x| x.starts_with("Pop(Reg(0))")).count(),
+        2
+    );
+}
+
+#[test]
+fn pressure_spills_some_values() {
+    let module = lower(PRESSURE, "");
+    let alloc = linear_scan(&module.main);
+    let spilled = alloc
+        .locations
+        .iter()
+        .filter(|x| matches!(x, Some(Location::Slot(_))))
+        .count();
+    assert!(spilled > 0);
+    assert_eq!(alloc.frame, spilled);
+}
@@ -0,0 +1,161 @@
+use snlc::interp::{self, Limits};
+use snlc::ir::exec;
+use snlc::ir::passes::PassManager;
+use snlc::ir::{self, Inst, Module};
+use snlc::sema::Options;
+use snlc_ast::codegen::Config;
+
+const SRC: &str = "program p
+var integer n, a, b, t, g;
+    char c;
+procedure addg(integer x)
+var integer l;
+begin
+  l := x * 2;
+  g := g + l;
+  g := g + x * 2
+end
+begin
+  read(n, c);
+  a := 1;
+  b := 2;
+  while 0 < n do
+    t := a;
+    a := b;
+    b := t;
+    addg(n);
+    if a < b then write(c) else write(a + b, ' ') fi;
+    n := n - 1
+  endwh;
+  writeln(a, b, g)
+end.
+";
+
+fn lower(src: &str) -> Module {
+    let (prog, _) = interp::compile(src, &Options::default()).unwrap();
+    ir::lower(&prog)
+}
+
+/// The module after `passes` and what running it prints.
+fn run_passes(src: &str, passes: &str, input: &str) -> (Module, String) {
+    let mut module = lower(src);
+    let config = Config::default();
+    PassManager::new(passes, "")
+        .unwrap()
+        .run(&mut module, &config, &mut Vec::new())
+        .unwrap();
+    let mut output = Vec::new();
+    exec::run(
+        &module,
+        &config,
+        input.as_bytes(),
+        &mut output,
+        &Limits::default(),
+    )
+    .unwrap();
+    (module, String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn passes_preserve_behavior() {
+    let (prog, _) = interp::compile(SRC, &Options::default()).unwrap();
+    for input in ["0 x", "3 y", "4 z"] {
+        let mut expected = Vec::new();
+        interp::run(
+            &prog,
+            &Config::default(),
+            input.as_bytes(),
+            &mut expected,
+            &Limits::default(),
+        )
+        .unwrap();
+        let expected = String::from_utf8(expected).unwrap();
+        for passes in [
+            "",
+            "ssa,out-of-ssa",
+            "ssa,copy-prop,out-of-ssa",
+            "ssa,gvn,copy-prop,dce,out-of-ssa,dce",
+        ] {
+            assert_eq!(run_passes(SRC, passes, input).1, expected, "{passes}");
+        }
+    }
+}
+
+#[test]
+fn phis_go_at_the_loop_header() {
+    let (module, _) = run_passes(SRC, "ssa", "0 x");
+    let main = &module.main;
+    let preds = main.predecessors();
+    for (id, block) in main.blocks.iter().enumerate() {
+        for inst in &block.insts {
+            if let Inst::Phi { args, .. } = inst {
+                assert!(preds[id].len() > 1, "bb{id} has one predecessor");
+                assert_eq!(args.len(), preds[id].len());
+            }
+        }
+    }
+    let text = module.to_string();
+    assert!(text.contains("%a.2 = phi [bb0: %a.1], [bb"), "{text}");
+    // `t` is assigned before every read, so it needs no phi
+    assert!(!text.contains("%t.2 = phi"), "{text}");
+}
+
+#[test]
+fn gvn_reuses_dominating_values() {
+    let (module, _) = run_passes(SRC, "ssa,gvn,dce", "0 x");
+    let muls = module.procs[0]
+        .blocks
+        .iter()
+        .flat_map(|x| &x.insts)
+        .filter(|x| matches!(x, Inst::Binary { .. }))
+        .count();
+    // `x * 2` once, then the two additions
+    assert_eq!(muls, 3, "{module}");
+    assert!(module.procs[0].len() < lower(SRC).procs[0].len());
+}
+
+#[test]
+fn swapping_phis_copy_through_a_temporary() {
+    let (module, output) = run_passes(SRC, "ssa,copy-prop,out-of-ssa", "3 y");
+    assert_eq!(output, "3 y3 2124\n");
+    assert!(module
+        .main
+        .blocks
+        .iter()
+        .all(|x| !x.insts.iter().any(|x| matches!(x, Inst::Phi { .. }))));
+}
+
+#[test]
+fn pipelines_are_checked_before_running() {
+    let err = |passes: &str, print_after: &str| PassManager::new(passes, print_after).err();
+    assert_eq!(
+        err("gvn", "").as_deref(),
+        Some("pass `gvn` needs SSA form, run `ssa` before it")
+    );
+    assert_eq!(
+        err("ssa,ssa", "").as_deref(),
+        Some("pass `ssa` can't run on SSA form, run `out-of-ssa` before it")
+    );
+    assert_eq!(
+        err("ssa,licm", "").as_deref(),
+        Some("unknown pass `licm`, expected one of ssa, out-of-ssa, copy-prop, gvn, dce")
+    );
+    assert!(err("dce", "all").is_none());
+    assert!(err("ssa,dce,copy-prop", "copy-prop").is_none());
+}
+
+#[test]
+fn print_after_shows_the_named_passes() {
+    let mut module = lower(SRC);
+    let mut out = Vec::new();
+    PassManager::new("ssa,dce,out-of-ssa", "ssa,out-of-ssa")
+        .unwrap()
+        .run(&mut module, &Config::default(), &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let headers = out
+        .lines()
+        .filter(|x| x.starts_with("; after"))
+        .collect::<Vec<_>>();
+    assert_eq!(headers, ["; after ssa", "; after out-of-ssa"]);
+}
@@ -0,0 +1,99 @@
+use snlc::interp::{self, Limits, Observer};
+use snlc::sema::Options;
+use snlc::trace::{Profiler, Tracer};
+use snlc_ast::codegen::Config;
+
+const SRC: &str = "program p
+var integer n, s;
+procedure add(integer x)
+var integer t;
+begin
+  t := x * 2;
+  s := s + t
+end
+begin
+  read(n);
+  s := 1;
+  while 0 < n do
+    add(n);
+    n := n - 1
+  endwh;
+  writeln(s)
+end.
+";
+
+fn run(input: &str, observer: &mut dyn Observer) -> String {
+    let (prog, _) = interp::compile(SRC, &Options::default()).unwrap();
+    let mut output = Vec::new();
+    interp::run_observed(
+        &prog,
+        &Config::default(),
+        input.as_bytes(),
+        &mut output,
+        &Limits::default(),
+        observer,
+    )
+    .unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn trace_shows_statements_and_changes() {
+    let mut trace = Vec::new();
+    assert_eq!(run("1", &mut Tracer::new(SRC, &mut trace)), "3\n");
+    assert_eq!(
+        String::from_utf8(trace).unwrap(),
+        "p:10: read(n);
+    n = 1
+p:11: s := 1;
+    s = 1
+p:12: while 0 < n do
+p:13: add(n);
+call add(x = 1)
+add:6: t := x * 2;
+    t = 2
+add:7: s := s + t
+    s = 3
+return from add
+p:14: n := n - 1
+    n = 0
+p:12: while 0 < n do
+p:16: writeln(s)
+"
+    );
+}
+
+#[test]
+fn profile_counts_lines_and_calls() {
+    let mut profiler = Profiler::new();
+    assert_eq!(run("3", &mut profiler), "13\n");
+    let counts = profiler
+        .lines()
+        .iter()
+        .map(|(line, x)| (*line, x.count))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        counts,
+        [
+            (6, 3),
+            (7, 3),
+            (10, 1),
+            (11, 1),
+            (12, 4),
+            (13, 3),
+            (14, 3),
+            (16, 1)
+        ]
+    );
+    assert_eq!(profiler.procedures()["add"].count, 3);
+    assert_eq!(profiler.procedures()["p"].count, 1);
+    // the program's time includes its procedures'
+    assert!(profiler.procedures()["p"].time >= profiler.procedures()["add"].time);
+
+    let report = profiler.report(SRC);
+    assert!(
+        report.lines().any(|x| x.starts_with("   12         4")),
+        "{report}"
+    );
+    assert!(report.contains("\nprocedure"), "{report}");
+}
@@ -0,0 +1,108 @@
+use quote::ToTokens;
+use snlc::formatter::format_source;
+use snlc::frontend::parse_program;
+use snlc::ll1::Ll1Parser;
+use snlc::sema::{analyze, Options};
+use snlc_ast::ast::Program;
+
+const SRC: &str = "program p
+var integer x;
+    char c;
+begin
+  write('x = ', x, ', c = ', c);
+  writeln;
+  writeln('it''s ', x + 1, '\\t{ not a comment }');
+  write('')
+end.
+";
+
+fn diagnostics(src: &str) -> Vec<String> {
+    match analyze(src, &Options::default()) {
+        Ok(model) => model.diagnostics,
+        Err(diagnostics) => diagnostics,
+    }
+    .iter()
+    .map(|x| x.render("p.snl", src))
+    .collect()
+}
+
+fn body(prog: &Program) -> String {
+    let stms = prog.body.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    stms.join("; ")
+}
+
+#[test]
+fn parsers_agree_on_write_statements() {
+    let rd = body(&parse_program(SRC).unwrap());
+    let ll1 = body(&Ll1Parser::snl().parse_program(SRC).unwrap());
+    assert_eq!(rd, ll1);
+    assert!(rd.contains("writeln('it\\'s ', x + 1, '\\t{ not a comment }')"));
+    assert!(rd.starts_with("write('x = ', x, ', c = ', c); writeln; "));
+    assert!(rd.ends_with("; write('')"));
+}
+
+#[test]
+fn write_generates_display_formatting() {
+    let code = parse_program(SRC).unwrap().to_token_stream().to_string();
+    assert!(code.contains(r#"print ! ("{}{}{}{}" , "x = " , x , ", c = " , c)"#));
+    assert!(code.contains(r#"println ! ("")"#));
+    assert!(!code.contains("{:?}"));
+}
+
+#[test]
+fn strings_are_only_written() {
+    assert_eq!(diagnostics(SRC), Vec::<String>::new());
+    let src = "program p
+var char c;
+begin
+  c := 'ab';
+  write('a' + 1, 'ab' + 1);
+  if 'ab' = 'ab' then writeln fi
+end.
+";
+    assert_eq!(
+        diagnostics(src),
+        [
+            "p.snl:4:8: error: character literal may only contain one character",
+            "p.snl:5:9: error: `+` cannot be applied to char",
+            "p.snl:5:18: error: `+` cannot be applied to string",
+            "p.snl:6:6: error: a string can only be used as an argument of `write`",
+        ]
+    );
+}
+
+#[test]
+fn strings_where_a_char_is_required() {
+    let src = "program p
+var char c;
+    integer x;
+begin
+  c := '';
+  c := 'ab';
+  if c = '' then writeln fi;
+  while 'it''s' < c do c := 'a' endwh;
+  x := '';
+  c := ('ab')
+end.
+";
+    assert_eq!(
+        diagnostics(src),
+        [
+            "p.snl:5:8: error: empty character literal",
+            "p.snl:6:8: error: character literal may only contain one character",
+            "p.snl:7:10: error: empty character literal",
+            "p.snl:8:9: error: character literal may only contain one character",
+            "p.snl:9:8: error: a string can only be used as an argument of `write`",
+            "p.snl:10:9: error: a string can only be used as an argument of `write`",
+        ]
+    );
+}
+
+#[test]
+fn format_keeps_string_literals() {
+    let src = "program p begin writeln('a,  b',1);writeln end.";
+    assert_eq!(
+        format_source(src).unwrap(),
+        "program p\nbegin\n  writeln('a,  b', 1);\n  writeln\nend.\n"
+    );
+}